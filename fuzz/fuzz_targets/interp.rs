@@ -0,0 +1,32 @@
+//! Small random programs through every stage. Most of them don't parse, the ones that do are run
+//! with limits so every input finishes quickly.
+
+#![no_main]
+
+use std::io;
+
+use libfuzzer_sys::fuzz_target;
+use loxi::{Limits, Lox, Options, RunMode};
+
+mod tokens;
+
+/// Number of statements a program can run before it's stopped.
+const FUEL: u64 = 10_000;
+
+fuzz_target!(|tokens: Vec<tokens::Token>| {
+    let options = Options {
+        max_call_depth: 64,
+        max_steps: Some(FUEL),
+        limits: Limits {
+            max_heap: Some(1 << 20),
+            max_string_len: Some(1 << 12),
+            max_collection_len: Some(1 << 12),
+        },
+        ..Options::default()
+    };
+
+    let mut lox = Lox::new(&options);
+    lox.set_stdout(io::sink());
+    lox.set_stderr(io::sink());
+    let _ = lox.run(&tokens::source(&tokens), RunMode::Normal);
+});
@@ -0,0 +1,15 @@
+//! Arbitrary text through the lexer, dumping the tokens so every one of them is displayed.
+
+#![no_main]
+
+use std::io;
+
+use libfuzzer_sys::fuzz_target;
+use loxi::{Lox, Options, RunMode};
+
+fuzz_target!(|source: &str| {
+    let mut lox = Lox::new(&Options::default());
+    lox.set_stdout(io::sink());
+    lox.set_stderr(io::sink());
+    let _ = lox.run(source, RunMode::DumpLex);
+});
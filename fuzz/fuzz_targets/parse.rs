@@ -0,0 +1,18 @@
+//! Arbitrary sequences of valid tokens through the parser, which random bytes rarely get past the
+//! lexer to produce.
+
+#![no_main]
+
+use std::io;
+
+use libfuzzer_sys::fuzz_target;
+use loxi::{Lox, Options, RunMode};
+
+mod tokens;
+
+fuzz_target!(|tokens: Vec<tokens::Token>| {
+    let mut lox = Lox::new(&Options::default());
+    lox.set_stdout(io::sink());
+    lox.set_stderr(io::sink());
+    let _ = lox.run(&tokens::source(&tokens), RunMode::DumpParse);
+});
@@ -0,0 +1,167 @@
+//! Tokens of Lox that are joined into a source, shared by the targets.
+
+use arbitrary::Arbitrary;
+
+#[derive(Arbitrary, Debug)]
+pub enum Token {
+    Punctuation(Punctuation),
+    Keyword(Keyword),
+    Identifier(Identifier),
+    Number(u16),
+    String(u8),
+}
+
+#[derive(Arbitrary, Debug)]
+pub enum Punctuation {
+    ParenLeft,
+    ParenRight,
+    BraceLeft,
+    BraceRight,
+    BracketLeft,
+    BracketRight,
+    Comma,
+    Dot,
+    Semicolon,
+    Colon,
+    Minus,
+    Plus,
+    Star,
+    Slash,
+    Bang,
+    BangEqual,
+    Equal,
+    EqualEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Ampersand,
+    Pipe,
+    Caret,
+    LessLess,
+    GreaterGreater,
+}
+
+#[derive(Arbitrary, Debug)]
+pub enum Keyword {
+    And,
+    Class,
+    Else,
+    False,
+    For,
+    Fun,
+    If,
+    In,
+    Nil,
+    Or,
+    Print,
+    Return,
+    Super,
+    Switch,
+    Case,
+    This,
+    True,
+    Var,
+    While,
+}
+
+/// A few names, so the same variables are used more than once.
+#[derive(Arbitrary, Debug)]
+pub enum Identifier {
+    A,
+    B,
+    C,
+    Init,
+    Print,
+    Clock,
+}
+
+pub fn source(tokens: &[Token]) -> String {
+    let tokens = tokens.iter().map(Token::as_str).collect::<Vec<_>>();
+    tokens.join(" ")
+}
+
+impl Token {
+    fn as_str(&self) -> String {
+        match self {
+            Token::Punctuation(punct) => punct.as_str().to_owned(),
+            Token::Keyword(keyword) => keyword.as_str().to_owned(),
+            Token::Identifier(ident) => ident.as_str().to_owned(),
+            Token::Number(num) => num.to_string(),
+            Token::String(len) => format!("\"{}\"", "s".repeat(*len as usize % 8)),
+        }
+    }
+}
+
+impl Punctuation {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Punctuation::ParenLeft => "(",
+            Punctuation::ParenRight => ")",
+            Punctuation::BraceLeft => "{",
+            Punctuation::BraceRight => "}",
+            Punctuation::BracketLeft => "[",
+            Punctuation::BracketRight => "]",
+            Punctuation::Comma => ",",
+            Punctuation::Dot => ".",
+            Punctuation::Semicolon => ";",
+            Punctuation::Colon => ":",
+            Punctuation::Minus => "-",
+            Punctuation::Plus => "+",
+            Punctuation::Star => "*",
+            Punctuation::Slash => "/",
+            Punctuation::Bang => "!",
+            Punctuation::BangEqual => "!=",
+            Punctuation::Equal => "=",
+            Punctuation::EqualEqual => "==",
+            Punctuation::Less => "<",
+            Punctuation::LessEqual => "<=",
+            Punctuation::Greater => ">",
+            Punctuation::GreaterEqual => ">=",
+            Punctuation::Ampersand => "&",
+            Punctuation::Pipe => "|",
+            Punctuation::Caret => "^",
+            Punctuation::LessLess => "<<",
+            Punctuation::GreaterGreater => ">>",
+        }
+    }
+}
+
+impl Keyword {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Keyword::And => "and",
+            Keyword::Class => "class",
+            Keyword::Else => "else",
+            Keyword::False => "false",
+            Keyword::For => "for",
+            Keyword::Fun => "fun",
+            Keyword::If => "if",
+            Keyword::In => "in",
+            Keyword::Nil => "nil",
+            Keyword::Or => "or",
+            Keyword::Print => "print",
+            Keyword::Return => "return",
+            Keyword::Super => "super",
+            Keyword::Switch => "switch",
+            Keyword::Case => "case",
+            Keyword::This => "this",
+            Keyword::True => "true",
+            Keyword::Var => "var",
+            Keyword::While => "while",
+        }
+    }
+}
+
+impl Identifier {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Identifier::A => "a",
+            Identifier::B => "b",
+            Identifier::C => "c",
+            Identifier::Init => "init",
+            Identifier::Print => "eprint",
+            Identifier::Clock => "clock",
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! Pauses and resumes the program being debugged. The controller is an observer of the
+//! interpreter, so it runs on the thread of the program and pausing simply blocks that thread
+//! until the server tells it to resume.
+
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+use loxi::{Location, Observer, Scope, Step, Stepper, Value};
+use serde_json::{json, Value as Json};
+
+use crate::protocol::Client;
+use crate::THREAD_ID;
+
+/// State shared by the server and the controller.
+#[derive(Default)]
+pub struct Control {
+    /// Lines to stop at, the program is a single file so the path is not needed.
+    pub breakpoints: Mutex<BTreeSet<usize>>,
+
+    /// Set by the server to stop at the next statement.
+    pub pause: AtomicBool,
+
+    /// Whether the program is stopped and waiting for commands.
+    pub paused: AtomicBool,
+}
+
+/// Sent by the server while the program is paused.
+pub enum Command {
+    Resume(Step),
+    StackTrace(Sender<Json>),
+    Scopes(u64, Sender<Json>),
+    Variables(u64, Sender<Json>),
+}
+
+pub struct Controller {
+    client: Client,
+    control: Arc<Control>,
+    commands: Mutex<Receiver<Command>>,
+    program: String,
+    stepper: Stepper,
+}
+
+impl Controller {
+    pub fn new(
+        client: Client,
+        control: Arc<Control>,
+        commands: Receiver<Command>,
+        program: String,
+        stop_on_entry: bool,
+    ) -> Self {
+        Self {
+            client,
+            control,
+            commands: Mutex::new(commands),
+            program,
+            stepper: Stepper::new(stop_on_entry),
+        }
+    }
+
+    /// Block until the server resumes the program, answering its requests in the meantime.
+    fn pause(&mut self, loc: Location, scope: &Scope<'_>, reason: &str) {
+        let chain = scope.chain();
+
+        self.control.paused.store(true, Ordering::SeqCst);
+        self.client.event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        );
+
+        let commands = self.commands.lock().unwrap();
+        loop {
+            // the server is gone, there's nothing left to wait for
+            let Ok(command) = commands.recv() else {
+                self.stepper.resume(Step::Continue, loc);
+                return;
+            };
+
+            let (reply, body) = match command {
+                Command::Resume(step) => {
+                    self.stepper.resume(step, loc);
+                    return;
+                }
+                Command::StackTrace(reply) => (reply, self.stack_trace(loc)),
+                Command::Scopes(frame, reply) => (reply, scopes(&chain, frame)),
+                Command::Variables(reference, reply) => {
+                    (reply, variables(&chain, reference, scope))
+                }
+            };
+            let _ = reply.send(body);
+        }
+    }
+
+    fn stack_trace(&self, loc: Location) -> Json {
+        let frames = self.stepper.backtrace(loc).into_iter().enumerate();
+        let frames = frames.map(|(id, frame)| {
+            json!({
+                "id": id,
+                "name": frame.name,
+                "line": frame.loc.line,
+                "column": frame.loc.column,
+                "source": { "path": self.program },
+            })
+        });
+        let frames = frames.collect::<Vec<_>>();
+        json!({ "stackFrames": frames, "totalFrames": frames.len() })
+    }
+}
+
+impl Observer for Controller {
+    fn on_statement(&mut self, loc: Location, scope: &Scope<'_>) {
+        let breakpoints = self.control.breakpoints.lock().unwrap();
+        let reason = self.stepper.should_pause(loc, &breakpoints);
+        drop(breakpoints);
+
+        if self.control.pause.swap(false, Ordering::SeqCst) {
+            self.pause(loc, scope, "pause");
+        } else if let Some(reason) = reason {
+            self.pause(loc, scope, reason.as_str());
+        }
+    }
+
+    fn on_call(&mut self, name: &str, loc: Location) {
+        self.stepper.enter(name, loc);
+    }
+
+    fn on_return(&mut self, _name: &str, _loc: Location) {
+        self.stepper.leave();
+    }
+}
+
+/// Every environment of the chain is a scope, the reference of the scope at `index` is `index + 1`
+/// since zero means no reference. Only the innermost frame can see its locals, the others only
+/// see the globals.
+fn scopes(chain: &[Vec<(String, Value)>], frame: u64) -> Json {
+    let globals = chain.len() - 1;
+    let first = if frame == 0 { 0 } else { globals };
+    let scopes = (first..chain.len()).map(|index| {
+        let name = match index {
+            index if index == globals => "Globals".to_owned(),
+            0 => "Locals".to_owned(),
+            index => format!("Enclosing #{index}"),
+        };
+        json!({ "name": name, "variablesReference": index + 1, "expensive": false })
+    });
+    json!({ "scopes": scopes.collect::<Vec<_>>() })
+}
+
+fn variables(chain: &[Vec<(String, Value)>], reference: u64, scope: &Scope<'_>) -> Json {
+    let variables = reference
+        .checked_sub(1)
+        .and_then(|index| chain.get(index as usize))
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .map(|(name, value)| {
+            json!({ "name": name, "value": scope.display(value), "variablesReference": 0 })
+        });
+    json!({ "variables": variables.collect::<Vec<_>>() })
+}
@@ -0,0 +1,219 @@
+//! A Debug Adapter Protocol server for Lox programs, so editors like VS Code can set breakpoints,
+//! step through a program, and inspect its variables and call stack.
+//!
+//! The server talks to the editor through stdin and stdout, while the program runs on its own
+//! thread under a `Controller` that pauses it when needed.
+
+use std::io::{self, BufReader};
+use std::process::ExitCode;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::thread;
+
+use loxi::{Capabilities, Lox, LoxError, Options, Outcome, RunMode, Step};
+use loxi_protocol::read_message;
+use serde_json::{json, Value as Json};
+
+use self::controller::{Command, Control, Controller};
+use self::protocol::{Client, Output};
+
+mod controller;
+mod protocol;
+
+/// The program runs on a single thread, which is the only one reported to the client.
+const THREAD_ID: u64 = 1;
+
+/// Every Lox call recurses on the native stack, same as the `loxi` binary.
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+/// The arguments of the `launch` request.
+struct Launch {
+    program: String,
+    stop_on_entry: bool,
+    capabilities: Capabilities,
+}
+
+fn main() -> ExitCode {
+    let client = Client::new();
+    let control = Arc::new(Control::default());
+    let (commands, receiver) = mpsc::channel();
+    let mut receiver = Some(receiver);
+    let mut launch = None;
+
+    let mut stdin = BufReader::new(io::stdin());
+    loop {
+        let request = match read_message(&mut stdin) {
+            Ok(Some(request)) => request,
+            Ok(None) => return ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("Unable to read a message: {err}");
+                return ExitCode::FAILURE;
+            }
+        };
+        let args = &request["arguments"];
+
+        match request["command"].as_str().unwrap_or_default() {
+            "initialize" => {
+                client.respond(
+                    &request,
+                    json!({ "supportsConfigurationDoneRequest": true }),
+                );
+                client.event("initialized", json!({}));
+            }
+            "launch" => match args["program"].as_str() {
+                Some(program) => {
+                    launch = Some(Launch {
+                        program: program.to_owned(),
+                        stop_on_entry: args["stopOnEntry"].as_bool().unwrap_or(false),
+                        capabilities: Capabilities {
+                            fs: args["allowFs"].as_bool().unwrap_or(false),
+                            env: args["allowEnv"].as_bool().unwrap_or(false),
+                            run: args["allowRun"].as_bool().unwrap_or(false),
+                        },
+                    });
+                    client.respond(&request, json!({}));
+                }
+                None => client.fail(&request, "missing the path of the program"),
+            },
+            "setBreakpoints" => {
+                let lines = args["breakpoints"]
+                    .as_array()
+                    .map(Vec::as_slice)
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|breakpoint| breakpoint["line"].as_u64())
+                    .map(|line| line as usize)
+                    .collect::<Vec<_>>();
+                *control.breakpoints.lock().unwrap() = lines.iter().copied().collect();
+
+                let breakpoints = lines
+                    .iter()
+                    .map(|line| json!({ "verified": true, "line": line }));
+                let breakpoints = breakpoints.collect::<Vec<_>>();
+                client.respond(&request, json!({ "breakpoints": breakpoints }));
+            }
+            "configurationDone" => match (launch.take(), receiver.take()) {
+                (Some(launch), Some(receiver)) => {
+                    client.respond(&request, json!({}));
+                    let controller = Controller::new(
+                        client.clone(),
+                        Arc::clone(&control),
+                        receiver,
+                        launch.program.clone(),
+                        launch.stop_on_entry,
+                    );
+                    spawn_program(client.clone(), launch, controller);
+                }
+                _ => client.fail(&request, "the program is not launched or already running"),
+            },
+            "threads" => {
+                let threads = json!([{ "id": THREAD_ID, "name": "main" }]);
+                client.respond(&request, json!({ "threads": threads }));
+            }
+            "continue" => resume(&client, &control, &commands, &request, Step::Continue),
+            "next" => resume(&client, &control, &commands, &request, Step::Over),
+            "stepIn" => resume(&client, &control, &commands, &request, Step::In),
+            "stepOut" => resume(&client, &control, &commands, &request, Step::Out),
+            "pause" => {
+                control.pause.store(true, Ordering::SeqCst);
+                client.respond(&request, json!({}));
+            }
+            "stackTrace" => inspect(&client, &control, &commands, &request, Command::StackTrace),
+            "scopes" => {
+                let frame = args["frameId"].as_u64().unwrap_or(0);
+                let command = |reply| Command::Scopes(frame, reply);
+                inspect(&client, &control, &commands, &request, command);
+            }
+            "variables" => {
+                let reference = args["variablesReference"].as_u64().unwrap_or(0);
+                let command = |reply| Command::Variables(reference, reply);
+                inspect(&client, &control, &commands, &request, command);
+            }
+            "disconnect" | "terminate" => {
+                client.respond(&request, json!({}));
+                return ExitCode::SUCCESS;
+            }
+            _ => client.fail(&request, "unsupported request"),
+        }
+    }
+}
+
+/// Run the program on its own thread, then tell the client it's finished.
+fn spawn_program(client: Client, launch: Launch, controller: Controller) {
+    let options = Options {
+        capabilities: launch.capabilities,
+        ..Options::default()
+    };
+
+    let run = move || {
+        let mut lox = Lox::new(&options);
+        lox.set_stdout(Output::new(client.clone(), "stdout"));
+        lox.set_stderr(Output::new(client.clone(), "stderr"));
+        lox.set_stdin(io::empty()); // stdin is used by the protocol
+        lox.set_observer(controller);
+
+        let code = match lox.run_file(&launch.program, RunMode::Normal) {
+            Ok(Outcome::Completed) => 0,
+            Ok(Outcome::Exit(code)) => code,
+            Err(err) => {
+                let output = format!("{err}\n");
+                client.event("output", json!({ "category": "stderr", "output": output }));
+                match err {
+                    LoxError::EmptyError => 0,
+                    LoxError::IoError(_) => 1,
+                    LoxError::LexError { .. } => 65,
+                    LoxError::ParseError { .. } => 65,
+                    LoxError::ResolveError { .. } => 65,
+                    LoxError::LintError { .. } => 65,
+                    LoxError::RuntimeError { .. } => 70,
+                    LoxError::Exit(code) => code,
+                }
+            }
+        };
+        client.event("exited", json!({ "exitCode": code }));
+        client.event("terminated", json!({}));
+    };
+
+    thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run)
+        .expect("Unable to spawn the interpreter thread");
+}
+
+fn resume(
+    client: &Client,
+    control: &Control,
+    commands: &Sender<Command>,
+    request: &Json,
+    step: Step,
+) {
+    match control.paused.swap(false, Ordering::SeqCst) {
+        true => {
+            // respond first, the program may stop again before the response would be sent
+            client.respond(request, json!({ "allThreadsContinued": true }));
+            let _ = commands.send(Command::Resume(step));
+        }
+        false => client.fail(request, "the program is not paused"),
+    }
+}
+
+/// Ask the paused program about its state, only the program's thread can look into it.
+fn inspect(
+    client: &Client,
+    control: &Control,
+    commands: &Sender<Command>,
+    request: &Json,
+    command: impl FnOnce(Sender<Json>) -> Command,
+) {
+    if !control.paused.load(Ordering::SeqCst) {
+        return client.fail(request, "the program is not paused");
+    }
+
+    let (reply, body) = mpsc::channel();
+    let _ = commands.send(command(reply));
+    match body.recv() {
+        Ok(body) => client.respond(request, body),
+        Err(_) => client.fail(request, "the program is not running"),
+    }
+}
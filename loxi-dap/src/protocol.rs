@@ -0,0 +1,88 @@
+//! Messages of the Debug Adapter Protocol, read and written by `loxi_protocol`.
+
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use loxi_protocol::write_message;
+use serde_json::{json, Value};
+
+/// Sends messages to the client through stdout. Clones share the same sequence numbers, so
+/// messages can be sent from both the server and the program being debugged.
+#[derive(Clone)]
+pub struct Client {
+    seq: Arc<Mutex<u64>>,
+}
+
+impl Client {
+    pub fn new() -> Self {
+        Self {
+            seq: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    pub fn respond(&self, request: &Value, body: Value) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": true,
+            "body": body,
+        }));
+    }
+
+    pub fn fail(&self, request: &Value, message: &str) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": false,
+            "message": message,
+        }));
+    }
+
+    pub fn event(&self, event: &str, body: Value) {
+        self.send(json!({
+            "type": "event",
+            "event": event,
+            "body": body,
+        }));
+    }
+
+    /// Send `message`. Errors are ignored, since they mean the client is gone and there's no one
+    /// to report them to.
+    fn send(&self, mut message: Value) {
+        let mut seq = self.seq.lock().expect("lock should not be poisoned");
+        *seq += 1;
+        message["seq"] = json!(*seq);
+        let _ = write_message(&mut io::stdout().lock(), &message);
+    }
+}
+
+/// Sends the output of the program to the client as `output` events, so it shows up in the debug
+/// console instead of corrupting the messages on stdout.
+pub struct Output {
+    client: Client,
+    category: &'static str,
+}
+
+impl Output {
+    /// `category` is either `"stdout"` or `"stderr"`.
+    pub fn new(client: Client, category: &'static str) -> Self {
+        Self { client, category }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let output = String::from_utf8_lossy(buf);
+        self.client.event(
+            "output",
+            json!({ "category": self.category, "output": output }),
+        );
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+#ifndef LOXI_H
+#define LOXI_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* An interpreter with the output it captured. The state persists between `lox_eval` calls. */
+typedef struct LoxHandle LoxHandle;
+
+/* A value of the interpreter given to the host. The host owns every value it gets and frees it
+ * using `lox_value_free`. */
+typedef struct LoxValue LoxValue;
+
+typedef enum {
+    LOX_NIL,
+    LOX_BOOL,
+    LOX_NUMBER,
+    LOX_STRING,
+    /* Any other value, e.g. a function or a list. */
+    LOX_OTHER,
+} LoxValueType;
+
+/* A native function implemented by the host. `args` are only valid during the call. Returning
+ * NULL reports a runtime error at the call site. */
+typedef LoxValue *(*LoxNativeFn)(void *data, const LoxValue *const *args, size_t argc);
+
+/* Create an interpreter with the default options. Free it using `lox_free`. */
+LoxHandle *lox_new(void);
+
+/* Run `source` and capture its output. Returns 0 on success, 65 for errors found before running,
+ * 70 for runtime errors, the code given to `exit(code)`, or 1 if `source` is not valid UTF-8. */
+int lox_eval(LoxHandle *lox, const char *source);
+
+/* The output of the last `lox_eval`, including its diagnostics. The string is owned by `lox` and
+ * stays valid until the next `lox_eval` or `lox_free`. */
+const char *lox_get_output(const LoxHandle *lox);
+
+/* The value of the last expression statement run by the last `lox_eval`, nil if it failed or ended
+ * with another statement. */
+LoxValue *lox_get_value(const LoxHandle *lox);
+
+/* Define the global native function `name` taking `arity` arguments, implemented by `body`. `data`
+ * is given to every call of `body` as is. Returns 0, or 1 if `name` is not valid UTF-8. */
+int lox_register_native(LoxHandle *lox, const char *name, size_t arity, LoxNativeFn body,
+                        void *data);
+
+LoxValue *lox_value_nil(void);
+LoxValue *lox_value_bool(int value);
+LoxValue *lox_value_number(double value);
+
+/* A string with a copy of `value`, or NULL if `value` is not valid UTF-8. */
+LoxValue *lox_value_string(const char *value);
+
+LoxValueType lox_value_type(const LoxValue *value);
+
+/* Write the number to `out`. Returns 1, or 0 without writing if `value` is not a number. */
+int lox_value_as_number(const LoxValue *value, double *out);
+
+/* Write the bool to `out` as 0 or 1. Returns 1, or 0 without writing if `value` is not a bool. */
+int lox_value_as_bool(const LoxValue *value, int *out);
+
+/* The text of a string, or NULL if `value` is not a string. The string is owned by `value` and
+ * stays valid until it's freed. */
+const char *lox_value_as_string(const LoxValue *value);
+
+/* Free a value. Does nothing if `value` is NULL. */
+void lox_value_free(LoxValue *value);
+
+/* Free an interpreter created by `lox_new`. Does nothing if `lox` is NULL. */
+void lox_free(LoxHandle *lox);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,316 @@
+//! C ABI of the interpreter, to embed it from C, C++, or any language that can load a shared
+//! library, e.g. Python using `ctypes`. The declarations are in `include/loxi.h`.
+//!
+//! The output of the program, including its diagnostics, is captured instead of written to the
+//! standard streams so the host can show it however it wants.
+//!
+//! Values cross the boundary as `LoxValue` handles owned by the host, which frees each of them
+//! using `lox_value_free`. Only nil, bools, numbers, and strings can be read by the host, other
+//! values can only be passed back to the interpreter.
+
+use std::ffi::{c_char, c_double, c_int, c_void, CStr, CString};
+use std::io::Write;
+
+use loxi::{Lox, LoxError, NativeError, Options, OutputBuffer, Value};
+
+/// An interpreter with the output it captured. The state persists between `lox_eval` calls.
+pub struct LoxHandle {
+    lox: Lox,
+    buffer: OutputBuffer,
+    output: CString,
+
+    /// The value of the last `lox_eval`.
+    value: Value,
+}
+
+/// A value of the interpreter given to the host.
+pub struct LoxValue {
+    value: Value,
+
+    /// The text of a string, so it can be read as a C string.
+    text: Option<CString>,
+}
+
+#[repr(C)]
+pub enum LoxValueType {
+    Nil,
+    Bool,
+    Number,
+    String,
+
+    /// Any other value, e.g. a function or a list.
+    Other,
+}
+
+/// A native function implemented by the host. `args` are only valid during the call. Returning
+/// NULL reports a runtime error at the call site.
+pub type LoxNativeFn = unsafe extern "C" fn(
+    data: *mut c_void,
+    args: *const *const LoxValue,
+    argc: usize,
+) -> *mut LoxValue;
+
+/// Create an interpreter with the default options. Free it using `lox_free`.
+#[no_mangle]
+pub extern "C" fn lox_new() -> *mut LoxHandle {
+    let buffer = OutputBuffer::default();
+    let mut lox = Lox::new(&Options::default());
+    lox.set_stdout(buffer.clone());
+    lox.set_stderr(buffer.clone());
+
+    let handle = LoxHandle {
+        lox,
+        buffer,
+        output: CString::default(),
+        value: Value::Nil,
+    };
+    Box::into_raw(Box::new(handle))
+}
+
+/// Run `source` and capture its output. Returns the same status as the `loxi` binary: 0 on
+/// success, 65 for errors found before running, 70 for runtime errors, the code given to
+/// `exit(code)`, or 1 if `source` is not valid UTF-8.
+///
+/// # Safety
+///
+/// `lox` must come from `lox_new` and not be freed yet, and `source` must be a valid pointer to
+/// a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn lox_eval(lox: *mut LoxHandle, source: *const c_char) -> c_int {
+    let handle = &mut *lox;
+    handle.buffer.clear();
+    handle.value = Value::Nil;
+
+    let status = match CStr::from_ptr(source).to_str() {
+        Ok(source) => match handle.lox.eval(source) {
+            Ok(value) => {
+                handle.value = value;
+                0
+            }
+            Err(err) => {
+                let _ = writeln!(handle.buffer, "{err}");
+                match err {
+                    LoxError::EmptyError => 0,
+                    LoxError::IoError(_) => 1,
+                    LoxError::LexError { .. } => 65,
+                    LoxError::ParseError { .. } => 65,
+                    LoxError::ResolveError { .. } => 65,
+                    LoxError::LintError { .. } => 65,
+                    LoxError::RuntimeError { .. } => 70,
+                    LoxError::Exit(code) => code,
+                }
+            }
+        },
+        Err(err) => {
+            let _ = writeln!(
+                handle.buffer,
+                "--[ LoxError ]-- Source is not valid UTF-8: {err}"
+            );
+            1
+        }
+    };
+
+    // a NUL in the output would cut it short when read as a C string
+    let output = handle.buffer.text().replace('\0', "");
+    handle.output = CString::new(output).expect("NUL should have been removed");
+    status
+}
+
+/// The output of the last `lox_eval`, including its diagnostics. The string is owned by `lox` and
+/// stays valid until the next `lox_eval` or `lox_free`.
+///
+/// # Safety
+///
+/// `lox` must come from `lox_new` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn lox_get_output(lox: *const LoxHandle) -> *const c_char {
+    (*lox).output.as_ptr()
+}
+
+/// The value of the last expression statement run by the last `lox_eval`, nil if it failed or
+/// ended with another statement.
+///
+/// # Safety
+///
+/// `lox` must come from `lox_new` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn lox_get_value(lox: *const LoxHandle) -> *mut LoxValue {
+    LoxValue::new((*lox).value.clone()).into_raw()
+}
+
+/// Define the global native function `name` taking `arity` arguments, implemented by `body`.
+/// `data` is given to every call of `body` as is. Returns 0, or 1 if `name` is not valid UTF-8.
+///
+/// # Safety
+///
+/// `lox` must come from `lox_new` and not be freed yet, and `name` must be a valid pointer to a
+/// NUL terminated string. `data` must stay valid as long as `lox`.
+#[no_mangle]
+pub unsafe extern "C" fn lox_register_native(
+    lox: *mut LoxHandle,
+    name: *const c_char,
+    arity: usize,
+    body: LoxNativeFn,
+    data: *mut c_void,
+) -> c_int {
+    let Ok(name) = CStr::from_ptr(name).to_str() else {
+        return 1;
+    };
+    let callback = Callback { body, data };
+    (*lox).lox.register_native(name, arity, move |args| {
+        let args = args.iter().cloned().map(LoxValue::new).collect::<Vec<_>>();
+        let pointers = args.iter().map(|arg| arg as *const _).collect::<Vec<_>>();
+        let result = callback.call(&pointers);
+        match result.is_null() {
+            true => Err(NativeError::Custom("the host returned NULL".to_owned())),
+            false => Ok(Box::from_raw(result).value),
+        }
+    });
+    0
+}
+
+#[no_mangle]
+pub extern "C" fn lox_value_nil() -> *mut LoxValue {
+    LoxValue::new(Value::Nil).into_raw()
+}
+
+#[no_mangle]
+pub extern "C" fn lox_value_bool(value: c_int) -> *mut LoxValue {
+    LoxValue::new(Value::Bool(value != 0)).into_raw()
+}
+
+#[no_mangle]
+pub extern "C" fn lox_value_number(value: c_double) -> *mut LoxValue {
+    LoxValue::new(Value::Number(value)).into_raw()
+}
+
+/// A string with a copy of `value`, or NULL if `value` is not valid UTF-8.
+///
+/// # Safety
+///
+/// `value` must be a valid pointer to a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn lox_value_string(value: *const c_char) -> *mut LoxValue {
+    match CStr::from_ptr(value).to_str() {
+        Ok(value) => LoxValue::new(Value::string(value.to_owned())).into_raw(),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// # Safety
+///
+/// `value` must come from one of the `lox_value_*` functions and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn lox_value_type(value: *const LoxValue) -> LoxValueType {
+    match (*value).value {
+        Value::Nil => LoxValueType::Nil,
+        Value::Bool(_) => LoxValueType::Bool,
+        Value::Number(_) | Value::Int(_) => LoxValueType::Number,
+        Value::String(_) => LoxValueType::String,
+        _ => LoxValueType::Other,
+    }
+}
+
+/// Write the number to `out`. Returns 1, or 0 without writing if `value` is not a number.
+///
+/// # Safety
+///
+/// `value` must come from one of the `lox_value_*` functions and not be freed yet, and `out` must
+/// be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn lox_value_as_number(value: *const LoxValue, out: *mut c_double) -> c_int {
+    match (*value).value.as_number() {
+        Some(number) => {
+            *out = number;
+            1
+        }
+        None => 0,
+    }
+}
+
+/// Write the bool to `out` as 0 or 1. Returns 1, or 0 without writing if `value` is not a bool.
+///
+/// # Safety
+///
+/// `value` must come from one of the `lox_value_*` functions and not be freed yet, and `out` must
+/// be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn lox_value_as_bool(value: *const LoxValue, out: *mut c_int) -> c_int {
+    match (*value).value {
+        Value::Bool(bool) => {
+            *out = bool as c_int;
+            1
+        }
+        _ => 0,
+    }
+}
+
+/// The text of a string, or NULL if `value` is not a string. The string is owned by `value` and
+/// stays valid until it's freed.
+///
+/// # Safety
+///
+/// `value` must come from one of the `lox_value_*` functions and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn lox_value_as_string(value: *const LoxValue) -> *const c_char {
+    match &(*value).text {
+        Some(text) => text.as_ptr(),
+        None => std::ptr::null(),
+    }
+}
+
+/// Free a value. Does nothing if `value` is NULL.
+///
+/// # Safety
+///
+/// `value` must be NULL or come from one of the `lox_value_*` functions, and must not be used
+/// again after this call.
+#[no_mangle]
+pub unsafe extern "C" fn lox_value_free(value: *mut LoxValue) {
+    if !value.is_null() {
+        drop(Box::from_raw(value));
+    }
+}
+
+/// Free an interpreter created by `lox_new`. Does nothing if `lox` is NULL.
+///
+/// # Safety
+///
+/// `lox` must be NULL or come from `lox_new`, and must not be used again after this call.
+#[no_mangle]
+pub unsafe extern "C" fn lox_free(lox: *mut LoxHandle) {
+    if !lox.is_null() {
+        drop(Box::from_raw(lox));
+    }
+}
+
+impl LoxValue {
+    fn new(value: Value) -> Self {
+        // a NUL in the string would cut it short when read as a C string
+        let text = match &value {
+            Value::String(str) => Some(CString::new(str.replace('\0', "")).expect("NUL removed")),
+            _ => None,
+        };
+        Self { value, text }
+    }
+
+    fn into_raw(self) -> *mut Self {
+        Box::into_raw(Box::new(self))
+    }
+}
+
+/// A native function of the host with its data.
+struct Callback {
+    body: LoxNativeFn,
+    data: *mut c_void,
+}
+
+impl Callback {
+    unsafe fn call(&self, args: &[*const LoxValue]) -> *mut LoxValue {
+        (self.body)(self.data, args.as_ptr(), args.len())
+    }
+}
+
+// the host is responsible for `data` being usable from the thread the interpreter runs on
+unsafe impl Send for Callback {}
+unsafe impl Sync for Callback {}
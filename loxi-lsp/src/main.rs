@@ -0,0 +1,264 @@
+//! A Language Server Protocol server for Lox programs, so editors like VS Code can show the errors
+//! of a program as it's edited, go to the definition of a variable, show what a name is on hover,
+//! list the declarations of a program, and highlight the names by what they refer to.
+//!
+//! The server talks to the editor through stdin and stdout. Every request checks the whole
+//! document again, using a new `Lox` each time so the documents don't share any state. Columns are
+//! counted in characters, which is the same as the UTF-16 code units the protocol uses for most
+//! programs.
+
+use std::collections::HashMap;
+use std::io::{self, BufReader};
+use std::process::ExitCode;
+
+use loxi::{
+    Diagnostic, ErrorKind, LintConfig, Location, Lox, Options, SemanticToken, Span, Symbol,
+    SymbolKind,
+};
+use loxi_protocol::read_message;
+use serde_json::{json, Value as Json};
+
+use self::protocol::{method_not_found, notify, respond};
+
+mod protocol;
+
+fn main() -> ExitCode {
+    let mut documents = HashMap::<String, String>::new();
+    let mut shutdown = false;
+
+    let mut stdin = BufReader::new(io::stdin());
+    loop {
+        let message = match read_message(&mut stdin) {
+            Ok(Some(message)) => message,
+            Ok(None) => return ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("Unable to read a message: {err}");
+                return ExitCode::FAILURE;
+            }
+        };
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+        match message["method"].as_str().unwrap_or_default() {
+            "initialize" => {
+                let capabilities = json!({
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "hoverProvider": true,
+                    "documentSymbolProvider": true,
+                    "semanticTokensProvider": {
+                        "legend": {
+                            "tokenTypes": ["variable", "parameter", "function", "class", "method"],
+                            "tokenModifiers": ["declaration"],
+                        },
+                        "full": true,
+                    },
+                });
+                let info = json!({ "name": "loxi-lsp", "version": env!("CARGO_PKG_VERSION") });
+                respond(
+                    &message,
+                    json!({ "capabilities": capabilities, "serverInfo": info }),
+                );
+            }
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                documents.insert(uri.to_owned(), text.to_owned());
+                publish_diagnostics(uri, text);
+            }
+            "textDocument/didChange" => {
+                // the whole document is sent on every change since the sync kind is full
+                let changes = params["contentChanges"].as_array();
+                let text = changes.and_then(|changes| changes.last());
+                if let Some(text) = text.and_then(|change| change["text"].as_str()) {
+                    documents.insert(uri.to_owned(), text.to_owned());
+                    publish_diagnostics(uri, text);
+                }
+            }
+            "textDocument/didClose" => {
+                documents.remove(uri);
+                notify(
+                    "textDocument/publishDiagnostics",
+                    json!({ "uri": uri, "diagnostics": [] }),
+                );
+            }
+            "textDocument/definition" => {
+                let text = documents.get(uri).map(String::as_str).unwrap_or_default();
+                let position = location(&params["position"]);
+                let result = match definition(text, position) {
+                    Some(span) => json!({ "uri": uri, "range": range(span) }),
+                    None => Json::Null,
+                };
+                respond(&message, result);
+            }
+            "textDocument/hover" => {
+                let text = documents.get(uri).map(String::as_str).unwrap_or_default();
+                let position = location(&params["position"]);
+                let result = match hover(text, position) {
+                    Some((symbol, span)) => json!({
+                        "contents": {
+                            "kind": "markdown",
+                            "value": format!("```lox\n{}\n```\n{}", symbol.detail, symbol.kind),
+                        },
+                        "range": range(span),
+                    }),
+                    None => Json::Null,
+                };
+                respond(&message, result);
+            }
+            "textDocument/documentSymbol" => {
+                let text = documents.get(uri).map(String::as_str).unwrap_or_default();
+                let symbols = new_lox().symbols(text).unwrap_or_default();
+                respond(&message, document_symbols(&symbols));
+            }
+            "textDocument/semanticTokens/full" => {
+                let text = documents.get(uri).map(String::as_str).unwrap_or_default();
+                let tokens = new_lox().semantic_tokens(text).unwrap_or_default();
+                respond(&message, json!({ "data": semantic_tokens(&tokens) }));
+            }
+            "shutdown" => {
+                shutdown = true;
+                respond(&message, Json::Null);
+            }
+            "exit" => {
+                return match shutdown {
+                    true => ExitCode::SUCCESS,
+                    false => ExitCode::FAILURE,
+                };
+            }
+            // notifications have no id and need no response, e.g. `initialized`
+            _ if message.get("id").is_some() => method_not_found(&message),
+            _ => (),
+        }
+    }
+}
+
+/// The output is not needed, the diagnostics are sent to the client instead.
+fn new_lox() -> Lox {
+    let mut lox = Lox::new(&Options::default());
+    lox.set_stdout(io::sink());
+    lox.set_stderr(io::sink());
+    lox
+}
+
+/// Send the errors of `text` to the client, or the lint warnings if there are none. The resolver
+/// doesn't run when linting, so its errors are looked for separately.
+fn publish_diagnostics(uri: &str, text: &str) {
+    let mut lox = new_lox();
+    let diagnostics = match lox.lint(text, &LintConfig::default()) {
+        Ok(mut warnings) => {
+            if let Err(err) = new_lox().bindings(text) {
+                warnings.extend(err.diagnostics());
+            }
+            warnings
+        }
+        Err(err) => err.diagnostics(),
+    };
+
+    let diagnostics = diagnostics.iter().map(diagnostic).collect::<Vec<_>>();
+    notify(
+        "textDocument/publishDiagnostics",
+        json!({ "uri": uri, "diagnostics": diagnostics }),
+    );
+}
+
+fn diagnostic(diagnostic: &Diagnostic) -> Json {
+    // 1 is an error and 2 is a warning
+    let severity = match diagnostic.kind {
+        ErrorKind::Lint => 2,
+        _ => 1,
+    };
+    // an empty range is easy to miss in an editor, so it's widened to the character at its start
+    let Span { start, mut end } = diagnostic.span;
+    if end == start {
+        end.column += 1;
+    }
+    json!({
+        "range": range(Span { start, end }),
+        "severity": severity,
+        "source": "loxi",
+        "message": diagnostic.message,
+    })
+}
+
+fn definition(text: &str, position: Location) -> Option<Span> {
+    new_lox().bindings(text).ok()?.find_definition(position)
+}
+
+/// The declaration of the name at `position`, along with the span of the name.
+fn hover(text: &str, position: Location) -> Option<(Symbol, Span)> {
+    let mut lox = new_lox();
+    let bindings = lox.bindings(text).ok()?;
+    let definition = bindings.find_definition(position)?;
+    let symbols = lox.symbols(text).ok()?;
+    let symbol = Symbol::find(&symbols, definition)?.clone();
+
+    let references = bindings.find_references(&symbol.name);
+    let reference = references.iter().find(|r| r.span.contains(position))?;
+    Some((symbol, reference.span))
+}
+
+fn document_symbols(symbols: &[Symbol]) -> Json {
+    let symbols = symbols.iter().map(|symbol| {
+        // the numbers of the `SymbolKind` enumeration of the protocol
+        let kind = match symbol.kind {
+            SymbolKind::Variable | SymbolKind::Parameter => 13,
+            SymbolKind::Function => 12,
+            SymbolKind::Class => 5,
+            SymbolKind::Method => 6,
+        };
+        json!({
+            "name": symbol.name,
+            "detail": symbol.detail,
+            "kind": kind,
+            "range": range(symbol.span),
+            "selectionRange": range(symbol.span),
+            "children": document_symbols(&symbol.children),
+        })
+    });
+    Json::Array(symbols.collect())
+}
+
+/// The tokens encoded the way the protocol wants them: five numbers for each token, with its
+/// position relative to the previous token.
+fn semantic_tokens(tokens: &[SemanticToken]) -> Vec<u32> {
+    let mut data = Vec::with_capacity(tokens.len() * 5);
+    let mut previous = Location::new(1, 1);
+    for token in tokens {
+        let Span { start, end } = token.span;
+        let line = start.line - previous.line;
+        let column = match line {
+            0 => start.column - previous.column,
+            _ => start.column - 1,
+        };
+        // the index into the token types of the legend
+        let kind = match token.kind {
+            SymbolKind::Variable => 0,
+            SymbolKind::Parameter => 1,
+            SymbolKind::Function => 2,
+            SymbolKind::Class => 3,
+            SymbolKind::Method => 4,
+        };
+        let length = end.column - start.column;
+        let modifiers = token.declaration as usize;
+        data.extend([line, column, length, kind, modifiers].map(|n| n as u32));
+        previous = start;
+    }
+    data
+}
+
+/// A position of the protocol, which counts from 0 instead of 1.
+fn location(position: &Json) -> Location {
+    let line = position["line"].as_u64().unwrap_or_default() as usize;
+    let column = position["character"].as_u64().unwrap_or_default() as usize;
+    Location::new(line + 1, column + 1)
+}
+
+fn range(span: Span) -> Json {
+    let position = |loc: Location| {
+        json!({
+            "line": loc.line.saturating_sub(1),
+            "character": loc.column.saturating_sub(1),
+        })
+    };
+    json!({ "start": position(span.start), "end": position(span.end) })
+}
@@ -0,0 +1,40 @@
+//! Messages of the Language Server Protocol, which are JSON-RPC messages read and written by
+//! `loxi_protocol`.
+
+use std::io;
+
+use loxi_protocol::write_message;
+use serde_json::{json, Value};
+
+/// JSON-RPC error code of a request for a method the server doesn't have.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+pub fn respond(request: &Value, result: Value) {
+    send(json!({
+        "jsonrpc": "2.0",
+        "id": request["id"],
+        "result": result,
+    }));
+}
+
+pub fn method_not_found(request: &Value) {
+    send(json!({
+        "jsonrpc": "2.0",
+        "id": request["id"],
+        "error": { "code": METHOD_NOT_FOUND, "message": "unsupported method" },
+    }));
+}
+
+pub fn notify(method: &str, params: Value) {
+    send(json!({
+        "jsonrpc": "2.0",
+        "method": method,
+        "params": params,
+    }));
+}
+
+/// Send `message` through stdout. Errors are ignored, since they mean the client is gone and
+/// there's no one to report them to.
+fn send(message: Value) {
+    let _ = write_message(&mut io::stdout().lock(), &message);
+}
@@ -0,0 +1,53 @@
+//! The base protocol shared by `loxi-lsp` and `loxi-dap`: every message is a JSON object preceded
+//! by a header with its length, like HTTP.
+
+use std::io::{self, BufRead, Write};
+
+use serde_json::Value;
+
+#[cfg(test)]
+mod test;
+
+/// The longest message accepted. A longer `Content-Length` is an error instead of an allocation of
+/// that size, since it comes from the client.
+pub const MAX_LENGTH: usize = 64 * 1024 * 1024;
+
+/// Read the next message, `None` if the client closed the connection.
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header")
+    })?;
+    if length > MAX_LENGTH {
+        let message = format!("message of {length} bytes is longer than {MAX_LENGTH} bytes");
+        return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(io::Error::from)
+}
+
+/// Write `message` with its header and flush it.
+pub fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
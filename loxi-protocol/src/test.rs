@@ -0,0 +1,29 @@
+use std::io::BufReader;
+
+use serde_json::json;
+
+use super::{read_message, write_message, MAX_LENGTH};
+
+#[test]
+fn messages_round_trip() {
+    let mut out = Vec::new();
+    write_message(&mut out, &json!({ "id": 1, "text": "é" })).unwrap();
+    write_message(&mut out, &json!([])).unwrap();
+    assert!(out.starts_with(b"Content-Length: 20\r\n\r\n{"));
+
+    let mut reader = BufReader::new(&out[..]);
+    let first = read_message(&mut reader).unwrap();
+    assert_eq!(first, Some(json!({ "id": 1, "text": "é" })));
+    assert_eq!(read_message(&mut reader).unwrap(), Some(json!([])));
+    assert_eq!(read_message(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn invalid_headers_are_errors() {
+    let too_long = format!("Content-Length: {}\r\n\r\n{{}}", MAX_LENGTH + 1);
+    for input in ["Content-Type: json\r\n\r\n{}", too_long.as_str()] {
+        let mut reader = BufReader::new(input.as_bytes());
+        let err = read_message(&mut reader).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
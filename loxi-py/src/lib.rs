@@ -0,0 +1,179 @@
+//! Python bindings of the interpreter, e.g. to check the output of another implementation of Lox
+//! against this one from a Python test suite.
+//!
+//! Build with `maturin develop` in this directory, then from Python:
+//!
+//! ```python
+//! import pyloxi
+//!
+//! lox = pyloxi.Lox()
+//! lox.eval("fun add(a, b) { return a + b; }")
+//! assert lox.call("add", [1, 2]) == 3
+//! ```
+//!
+//! Numbers are converted to `float`, which compares equal to an `int` of the same value. Values
+//! without a Python equivalent, e.g. functions, are given as `pyloxi.Value` objects that can only
+//! be passed back to the interpreter.
+
+use std::collections::HashSet;
+
+use loxi::{Options, Value};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PySystemExit, PyTypeError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyInt, PyList, PyString, PyTuple};
+
+#[cfg(test)]
+mod test;
+
+create_exception!(
+    pyloxi,
+    LoxError,
+    PyException,
+    "An error found in or raised by a program."
+);
+
+/// An interpreter. The state persists between calls, so globals defined by one `eval` can be
+/// used by the next one.
+#[pyclass(unsendable)]
+struct Lox {
+    lox: loxi::Lox,
+}
+
+/// A value of the interpreter that has no Python equivalent.
+#[pyclass(unsendable, name = "Value")]
+struct Opaque {
+    value: Value,
+    repr: String,
+}
+
+/// `#[pymethods]` of pyo3 0.22 converts the `PyErr` of each `PyResult` into a `PyErr`, which clippy
+/// sees as a useless conversion in the generated wrappers. They are in the same module as the
+/// impl, so the lint is only allowed here.
+#[allow(clippy::useless_conversion)]
+mod methods {
+    use super::*;
+
+    #[pymethods]
+    impl Lox {
+        #[new]
+        fn new() -> Self {
+            Self {
+                lox: loxi::Lox::new(&Options::default()),
+            }
+        }
+
+        /// Run `source` and return the value of its last statement if it's an expression statement,
+        /// otherwise `None`. Calling `exit(code)` raises `SystemExit`.
+        fn eval(&mut self, py: Python<'_>, source: &str) -> PyResult<PyObject> {
+            match self.lox.eval(source) {
+                Ok(value) => Ok(self.to_python(py, &value, &mut HashSet::new())),
+                Err(loxi::LoxError::EmptyError) => Ok(py.None()),
+                Err(loxi::LoxError::Exit(code)) => Err(PySystemExit::new_err(code)),
+                Err(err) => {
+                    let diagnostics = err.diagnostics();
+                    let messages = diagnostics
+                        .iter()
+                        .map(|d| format!("{} {}", d.span.start, d.message));
+                    let message = match diagnostics.is_empty() {
+                        true => err.to_string(),
+                        false => messages.collect::<Vec<_>>().join("\n"),
+                    };
+                    Err(LoxError::new_err(message))
+                }
+            }
+        }
+
+        /// Call the global function or class `name` with `args`.
+        fn call(
+            &self,
+            py: Python<'_>,
+            name: &str,
+            args: Vec<Bound<'_, PyAny>>,
+        ) -> PyResult<PyObject> {
+            let args = args.iter().map(to_value).collect::<PyResult<Vec<_>>>()?;
+            match self.lox.call(name, &args) {
+                Ok(value) => Ok(self.to_python(py, &value, &mut HashSet::new())),
+                Err(err) => Err(LoxError::new_err(err.to_string())),
+            }
+        }
+    }
+}
+
+impl Lox {
+    /// `lists` are the lists being converted, a list containing itself is given as a `Value`.
+    fn to_python(&self, py: Python<'_>, value: &Value, lists: &mut HashSet<usize>) -> PyObject {
+        match value {
+            Value::Nil => py.None(),
+            Value::Bool(bool) => bool.into_py(py),
+            Value::Number(number) => number.into_py(py),
+            Value::Int(int) => int.into_py(py),
+            Value::String(str) => str.as_ref().into_py(py),
+            Value::StringLiteral(_) => self.lox.display(value).to_string().into_py(py),
+            Value::List(list) => {
+                let ptr = &**list as *const _ as usize;
+                if !lists.insert(ptr) {
+                    return self.opaque(py, value);
+                }
+                let items = list.borrow().clone();
+                let items = items.iter().map(|item| self.to_python(py, item, lists));
+                let items = items.collect::<Vec<_>>();
+                lists.remove(&ptr);
+                PyList::new_bound(py, items).into_py(py)
+            }
+            value => self.opaque(py, value),
+        }
+    }
+
+    fn opaque(&self, py: Python<'_>, value: &Value) -> PyObject {
+        let repr = self.lox.display(value).to_string();
+        let value = value.clone();
+        Opaque { value, repr }.into_py(py)
+    }
+}
+
+#[pymethods]
+impl Opaque {
+    fn __repr__(&self) -> String {
+        format!("<lox {}>", self.repr)
+    }
+}
+
+fn to_value(object: &Bound<'_, PyAny>) -> PyResult<Value> {
+    // `bool` is a subclass of `int`, so it's checked first
+    if object.is_none() {
+        Ok(Value::nil())
+    } else if let Ok(bool) = object.downcast::<PyBool>() {
+        Ok(Value::bool(bool.is_true()))
+    } else if let Ok(str) = object.downcast::<PyString>() {
+        Ok(Value::string(str.to_str()?.to_owned()))
+    } else if let Ok(list) = object.downcast::<PyList>() {
+        let items = list.iter().map(|item| to_value(&item));
+        Ok(Value::list(items.collect::<PyResult<_>>()?))
+    } else if let Ok(tuple) = object.downcast::<PyTuple>() {
+        let items = tuple.iter().map(|item| to_value(&item));
+        Ok(Value::list(items.collect::<PyResult<_>>()?))
+    } else if let Ok(opaque) = object.downcast::<Opaque>() {
+        Ok(opaque.borrow().value.clone())
+    } else if let Ok(int) = object.downcast::<PyInt>() {
+        match int.extract::<i64>() {
+            Ok(int) => Ok(Value::int(int)),
+            Err(_) => Ok(Value::number(int.extract::<f64>()?)),
+        }
+    } else if let Ok(number) = object.extract::<f64>() {
+        Ok(Value::number(number))
+    } else {
+        let name = object.get_type().name()?;
+        Err(PyTypeError::new_err(format!(
+            "{name} can't be converted to a Lox value"
+        )))
+    }
+}
+
+#[pymodule]
+fn pyloxi(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Lox>()?;
+    m.add_class::<Opaque>()?;
+    m.add("LoxError", m.py().get_type_bound::<LoxError>())?;
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use pyo3::exceptions::PySystemExit;
+use pyo3::prelude::*;
+
+use crate::{Lox, LoxError};
+
+#[test]
+fn eval_and_call_convert_values() {
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let lox = py.get_type_bound::<Lox>().call0().unwrap();
+        let eval = |source: &str| lox.call_method1("eval", (source,));
+        let call = |name: &str, args: Vec<PyObject>| lox.call_method1("call", (name, args));
+        let repr = |value: Bound<'_, PyAny>| value.repr().unwrap().to_string();
+
+        let value = eval("fun add(a, b) { return a + b; } add(1, 2);").unwrap();
+        assert_eq!(value.extract::<i64>().unwrap(), 3);
+        assert!(eval("fun list(a, b, c) { return [a, b, c]; }")
+            .unwrap()
+            .is_none());
+
+        let args = vec![1.5.into_py(py), "a".into_py(py), [true].into_py(py)];
+        assert_eq!(repr(call("list", args).unwrap()), "[1.5, 'a', [True]]");
+
+        // values without a Python equivalent can be passed back
+        let add = eval("add;").unwrap();
+        assert_eq!(repr(add.clone()), "<lox <fun add>>");
+        let args = vec![add.unbind(), 1.into_py(py), 2.into_py(py)];
+        assert_eq!(repr(call("list", args).unwrap()), "[<lox <fun add>>, 1, 2]");
+
+        let err = eval("print 1 + nil;").unwrap_err();
+        assert!(err.is_instance_of::<LoxError>(py));
+        assert!(err.to_string().contains("[1:9] RuntimeError:"), "{err}");
+        let err = call("missing", vec![]).unwrap_err();
+        assert!(err.is_instance_of::<LoxError>(py));
+        let err = eval("exit(2);").unwrap_err();
+        assert!(err.is_instance_of::<PySystemExit>(py));
+    });
+}
@@ -0,0 +1,90 @@
+//! JavaScript bindings of the interpreter, to build a playground that runs Lox in the browser.
+//!
+//! Build with `wasm-pack build loxi-wasm --target web`, then from JavaScript:
+//!
+//! ```js
+//! import init, { run } from "./pkg/loxi_wasm.js";
+//!
+//! await init();
+//! const { output, diagnostics } = run('print "Hello, World!";');
+//! const echoed = run_with_input("print readLine();", "typed by the user\n");
+//! ```
+
+use std::io::{self, Write};
+
+use loxi::{Clock, Lox, Options, OutputBuffer, RunMode};
+use wasm_bindgen::prelude::*;
+
+/// The result of running a program.
+#[wasm_bindgen(getter_with_clone)]
+pub struct RunResult {
+    /// Everything the program printed.
+    pub output: String,
+
+    /// Errors found in the program and the output of `eprint`, without terminal colors.
+    pub diagnostics: String,
+}
+
+/// Run `source` in a fresh interpreter. There is no file system, environment, or processes in
+/// the browser, so the natives using them are not available.
+#[wasm_bindgen]
+pub fn run(source: &str) -> RunResult {
+    run_with_input(source, "")
+}
+
+/// Like `run`, with `input` read by `readLine()` as if it was typed in, since there is no stdin in
+/// the browser.
+#[wasm_bindgen]
+pub fn run_with_input(source: &str, input: &str) -> RunResult {
+    let stdout = OutputBuffer::default();
+    let stderr = OutputBuffer::default();
+
+    let mut lox = Lox::new(&Options::default());
+    lox.set_stdout(stdout.clone());
+    lox.set_stderr(stderr.clone());
+    lox.set_stdin(io::Cursor::new(input.to_owned()));
+    lox.set_clock(JsClock);
+
+    if let Err(err) = lox.run(source, RunMode::Normal) {
+        let _ = writeln!(stderr.clone(), "{err}");
+    }
+
+    RunResult {
+        output: stdout.text(),
+        diagnostics: strip_colors(&stderr.text()),
+    }
+}
+
+/// `std::time` is not available on `wasm32-unknown-unknown`, so the time is read from JavaScript.
+struct JsClock;
+
+impl Clock for JsClock {
+    fn now(&self) -> f64 {
+        js_sys::Date::now()
+    }
+
+    // `performance.now()` would need `web-sys`, the wall clock is good enough for a playground
+    fn monotonic(&self) -> f64 {
+        js_sys::Date::now()
+    }
+}
+
+/// Remove the ANSI escape sequences used to color the diagnostics in a terminal.
+fn strip_colors(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\x1b' => {
+                // sequences used are all of the form `ESC [ <params> m`
+                for ch in chars.by_ref() {
+                    if ch == 'm' {
+                        break;
+                    }
+                }
+            }
+            ch => result.push(ch),
+        }
+    }
+    result
+}
@@ -0,0 +1,147 @@
+//! End-to-end and per-stage benchmarks. The stages are measured through the dump modes, so the
+//! numbers of lexing and parsing include displaying the result into a sink. `loxi bench internal`
+//! runs a few of the same workloads for a quicker check.
+//!
+//! ```sh
+//! cargo bench -p loxi
+//! ```
+
+use std::io;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use loxi::{Lox, Options, RunMode};
+
+const FIB: &str = r#"
+fun fib(n) {
+    if (n < 2) return n;
+    return fib(n - 1) + fib(n - 2);
+}
+fib(20);
+"#;
+
+const LOOP: &str = r#"
+var sum = 0;
+for (var i = 0; i < 100000; i = i + 1) {
+    sum = sum + i;
+}
+"#;
+
+const STRING_CONCAT: &str = r#"
+var s = "";
+for (var i = 0; i < 1000; i = i + 1) {
+    s = s + "lox";
+}
+"#;
+
+const METHOD_CALL: &str = r#"
+class Counter {
+    init() {
+        this.count = 0;
+    }
+
+    increment() {
+        this.count = this.count + 1;
+    }
+}
+
+var counter = Counter();
+for (var i = 0; i < 10000; i = i + 1) {
+    counter.increment();
+}
+"#;
+
+/// The statements in each chunk of `long_program`, nested ones included.
+const CHUNK_STATEMENTS: u64 = 5;
+
+/// A program of about 10k lines using every kind of token, and the number of its chunks.
+fn long_program() -> (String, u64) {
+    let chunk = r#"
+var greeting = "hello";
+fun add(a, b) {
+    // the sum of both
+    return a + b * 2 - 1 / 4;
+}
+if (greeting != nil and !false or 1 <= 2) {
+    print add(1.5, 2) >= 3;
+}
+"#;
+    let chunks = 10_000 / chunk.lines().count();
+    (chunk.repeat(chunks), chunks as u64)
+}
+
+/// `depth` nested groups around a chain of binary operations.
+fn deep_expression(depth: usize) -> String {
+    let open = "(1 + ".repeat(depth);
+    let close = ")".repeat(depth);
+    format!("print {open}1{close};\n")
+}
+
+fn new_lox() -> Lox {
+    let mut lox = Lox::new(&Options::default());
+    lox.set_stdout(io::sink());
+    lox.set_stderr(io::sink());
+    lox
+}
+
+fn run(c: &mut Criterion, name: &str, source: &str, mode: RunMode) {
+    c.bench_function(name, |b| {
+        b.iter_batched(
+            new_lox,
+            |mut lox| lox.run(source, mode).expect("benchmark should run"),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn stages(c: &mut Criterion) {
+    let (program, _) = long_program();
+    run(c, "lex 10k lines", &program, RunMode::DumpLex);
+    run(c, "parse 10k lines", &program, RunMode::DumpParse);
+    run(
+        c,
+        "parse deep expression",
+        &deep_expression(200),
+        RunMode::DumpParse,
+    );
+}
+
+/// The same stages as `stages`, reported as lexed bytes and parsed statements per second.
+fn throughput(c: &mut Criterion) {
+    let (program, chunks) = long_program();
+    let mut group = c.benchmark_group("throughput");
+
+    group.throughput(Throughput::Bytes(program.len() as u64));
+    group.bench_function("lex", |b| {
+        b.iter_batched(
+            new_lox,
+            |mut lox| {
+                lox.run(&program, RunMode::DumpLex)
+                    .expect("benchmark should run")
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.throughput(Throughput::Elements(chunks * CHUNK_STATEMENTS));
+    group.bench_function("parse", |b| {
+        b.iter_batched(
+            new_lox,
+            |mut lox| {
+                lox.run(&program, RunMode::DumpParse)
+                    .expect("benchmark should run")
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+fn programs(c: &mut Criterion) {
+    run(c, "fib", FIB, RunMode::Normal);
+    run(c, "loop", LOOP, RunMode::Normal);
+    run(c, "string concat", STRING_CONCAT, RunMode::Normal);
+    run(c, "method call", METHOD_CALL, RunMode::Normal);
+}
+
+criterion_group!(benches, stages, throughput, programs);
+criterion_main!(benches);
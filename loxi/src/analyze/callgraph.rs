@@ -0,0 +1,388 @@
+use std::collections::BTreeSet;
+
+use rustc_hash::FxHashMap;
+
+use crate::interp::interner::{Interner, Key};
+use crate::lex::token::{Keyword, Special};
+use crate::parse::expr::{Expr, ExprId, RefExpr, ValExpr};
+use crate::parse::stmt::{Stmt, StmtFunction};
+use crate::parse::Program;
+use crate::resolve::ResolveMap;
+use crate::util::Location;
+
+/// Which functions, methods, and classes reference which, found without running the program.
+///
+/// A reference to a variable is followed to its declaration using the resolver, and `this.name`
+/// to the method of the enclosing class. The class of any other object is unknown, so `obj.name`
+/// references the methods called `name` of every class.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CallGraph {
+    nodes: Vec<CallNode>,
+    edges: BTreeSet<(usize, usize)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CallNode {
+    /// The name of a function or class, `Class.name` for a method.
+    pub name: String,
+    pub kind: NodeKind,
+    pub loc: Location,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeKind {
+    /// The top level code of the program, always the first node.
+    Script,
+    Function,
+    Method,
+
+    /// Calling a class calls its initializer, which the class node references.
+    Class,
+}
+
+impl CallGraph {
+    pub fn nodes(&self) -> &[CallNode] {
+        &self.nodes
+    }
+
+    /// The pairs of the index of the node that references and the one referenced.
+    pub fn edges(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.edges.iter().copied()
+    }
+
+    /// The graph in the format of Graphviz.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph callgraph {\n");
+        for (i, node) in self.nodes.iter().enumerate() {
+            let shape = match node.kind {
+                NodeKind::Script | NodeKind::Class => ", shape=box",
+                NodeKind::Function | NodeKind::Method => "",
+            };
+            out.push_str(&format!("    n{i} [label={:?}{shape}];\n", node.name));
+        }
+        for (from, to) in self.edges() {
+            out.push_str(&format!("    n{from} -> n{to};\n"));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let nodes = self.nodes.iter().enumerate().map(|(i, node)| {
+            let kind = match node.kind {
+                NodeKind::Script => "script",
+                NodeKind::Function => "function",
+                NodeKind::Method => "method",
+                NodeKind::Class => "class",
+            };
+            let (line, column) = (node.loc.line, node.loc.column);
+            format!(
+                r#"{{"id":{i},"name":{:?},"kind":"{kind}","line":{line},"column":{column}}}"#,
+                node.name
+            )
+        });
+        let edges = self
+            .edges()
+            .map(|(from, to)| format!(r#"{{"from":{from},"to":{to}}}"#));
+
+        let nodes = nodes.collect::<Vec<_>>().join(",");
+        let edges = edges.collect::<Vec<_>>().join(",");
+        format!("{{\"nodes\":[{nodes}],\"edges\":[{edges}]}}\n")
+    }
+}
+
+/// A reference that can only be followed once the whole program is seen, since the globals and
+/// methods can be declared after it.
+enum Pending {
+    Global(Key),
+    Method(Key),
+    ClassMethod(usize, Key),
+}
+
+pub(super) struct Builder<'a> {
+    interner: &'a Interner,
+    resolve_map: &'a ResolveMap,
+    graph: CallGraph,
+
+    /// The node of each local that is a function or class, in the scopes created by `Resolver`.
+    scopes: Vec<FxHashMap<Key, Option<usize>>>,
+    globals: FxHashMap<Key, Option<usize>>,
+
+    /// The methods of every class, and of each class by its node.
+    methods: FxHashMap<Key, Vec<usize>>,
+    class_methods: FxHashMap<usize, FxHashMap<Key, usize>>,
+
+    /// The node whose body is walked, and the class node of the methods.
+    current: usize,
+    class: Option<usize>,
+    pending: Vec<(usize, Pending)>,
+}
+
+impl<'a> Builder<'a> {
+    pub(super) fn new(interner: &'a Interner, resolve_map: &'a ResolveMap) -> Self {
+        let script = CallNode {
+            name: "<script>".to_owned(),
+            kind: NodeKind::Script,
+            loc: Location::new(1, 1),
+        };
+        Self {
+            interner,
+            resolve_map,
+            graph: CallGraph {
+                nodes: vec![script],
+                edges: BTreeSet::new(),
+            },
+            scopes: Vec::new(),
+            globals: FxHashMap::default(),
+            methods: FxHashMap::default(),
+            class_methods: FxHashMap::default(),
+            current: 0,
+            class: None,
+            pending: Vec::new(),
+        }
+    }
+
+    pub(super) fn build(mut self, program: &Program) -> CallGraph {
+        for stmt in program.statements.iter() {
+            self.stmt(stmt);
+        }
+
+        for (from, pending) in std::mem::take(&mut self.pending) {
+            let targets = match pending {
+                Pending::Global(name) => self
+                    .globals
+                    .get(&name)
+                    .copied()
+                    .flatten()
+                    .into_iter()
+                    .collect(),
+                Pending::Method(name) => self.methods.get(&name).cloned().unwrap_or_default(),
+                Pending::ClassMethod(class, name) => {
+                    let methods = self.class_methods.get(&class);
+                    methods
+                        .and_then(|methods| methods.get(&name))
+                        .copied()
+                        .into_iter()
+                        .collect()
+                }
+            };
+            for to in targets {
+                self.graph.edges.insert((from, to));
+            }
+        }
+        self.graph
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expr { expr } | Stmt::Print { expr, .. } => self.expr(expr),
+            Stmt::Var { name, init, .. } => {
+                if let Some(init) = init {
+                    self.expr(init);
+                }
+                self.declare(*name, None);
+            }
+            Stmt::Block { statements, .. } => {
+                self.scoped(|builder| statements.iter().for_each(|stmt| builder.stmt(stmt)))
+            }
+            Stmt::If {
+                condition,
+                then,
+                otherwise,
+                ..
+            } => {
+                self.expr(condition);
+                self.stmt(then);
+                if let Some(otherwise) = otherwise {
+                    self.stmt(otherwise);
+                }
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                self.expr(condition);
+                self.stmt(body);
+            }
+            Stmt::ForIn {
+                name,
+                iterable,
+                body,
+                ..
+            } => {
+                self.expr(iterable);
+                self.scoped(|builder| {
+                    builder.declare(*name, None);
+                    builder.stmt(body);
+                });
+            }
+            Stmt::Switch {
+                subject,
+                cases,
+                otherwise,
+                ..
+            } => {
+                self.expr(subject);
+                for (value, body) in cases {
+                    self.expr(value);
+                    self.stmt(body);
+                }
+                if let Some(otherwise) = otherwise {
+                    self.stmt(otherwise);
+                }
+            }
+            Stmt::Function { func } => {
+                let name = self.interner.resolve(func.name).to_owned();
+                let node = self.node(name, NodeKind::Function, func.loc);
+                self.declare(func.name, Some(node));
+                self.function(func, node);
+            }
+            Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            }
+            Stmt::Class {
+                loc, name, methods, ..
+            } => {
+                let class_name = self.interner.resolve(*name).to_owned();
+                let class = self.node(class_name.clone(), NodeKind::Class, *loc);
+                self.declare(*name, Some(class));
+
+                let enclosing = self.class.replace(class);
+                self.scoped(|builder| {
+                    let this = builder.interner.keyword(Keyword::This);
+                    builder.declare(this, None);
+
+                    let init = builder.interner.special(Special::Init);
+                    for method in methods.iter() {
+                        let name = builder.interner.resolve(method.name);
+                        let name = format!("{class_name}.{name}");
+                        let node = builder.node(name, NodeKind::Method, method.loc);
+                        builder
+                            .class_methods
+                            .entry(class)
+                            .or_default()
+                            .insert(method.name, node);
+                        match method.name == init {
+                            true => {
+                                builder.graph.edges.insert((class, node));
+                            }
+                            false => builder.methods.entry(method.name).or_default().push(node),
+                        }
+                        builder.function(method, node);
+                    }
+                });
+                self.class = enclosing;
+            }
+        }
+    }
+
+    fn function(&mut self, func: &StmtFunction, node: usize) {
+        let enclosing = std::mem::replace(&mut self.current, node);
+        self.scoped(|builder| {
+            for param in func.params.iter() {
+                builder.declare(*param, None);
+            }
+            func.body.iter().for_each(|stmt| builder.stmt(stmt));
+        });
+        self.current = enclosing;
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::ValExpr(expr, _) => self.val_expr(expr),
+            Expr::RefExpr(expr, id) => self.ref_expr(expr, *id),
+        }
+    }
+
+    fn val_expr(&mut self, expr: &ValExpr) {
+        match expr {
+            ValExpr::Literal { .. } => (),
+            ValExpr::Unary { right, .. } => self.expr(right),
+            ValExpr::Binary { left, right, .. } | ValExpr::Logical { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            ValExpr::Grouping { expr, .. } => self.val_expr(expr),
+            ValExpr::Call { callee, args, .. } => {
+                self.expr(callee);
+                args.iter().for_each(|arg| self.expr(arg));
+            }
+            ValExpr::List { elements, .. } => elements.iter().for_each(|elem| self.expr(elem)),
+        }
+    }
+
+    /// A grouped reference has the id of the outermost grouping, like in `Resolver`.
+    fn ref_expr(&mut self, expr: &RefExpr, id: ExprId) {
+        match expr {
+            RefExpr::Variable { var } => self.variable(var.tok.name, id),
+            RefExpr::Grouping { expr, .. } => self.ref_expr(expr, id),
+            RefExpr::Assignment { value, .. } => self.expr(value),
+            RefExpr::Get { object, prop } => {
+                self.property(object, prop.tok.name);
+                self.expr(object);
+            }
+            RefExpr::Set { object, value, .. } => {
+                self.expr(object);
+                self.expr(value);
+            }
+            RefExpr::This { .. } => (),
+            RefExpr::Index { object, index, .. } => {
+                self.expr(object);
+                self.expr(index);
+            }
+            RefExpr::IndexSet {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.expr(object);
+                self.expr(index);
+                self.expr(value);
+            }
+        }
+    }
+
+    fn variable(&mut self, name: Key, id: ExprId) {
+        let scope = self
+            .resolve_map
+            .distance(id)
+            .and_then(|distance| self.scopes.len().checked_sub(distance + 1));
+        match scope {
+            Some(scope) => {
+                if let Some(Some(node)) = self.scopes[scope].get(&name) {
+                    self.graph.edges.insert((self.current, *node));
+                }
+            }
+            None => self.pending.push((self.current, Pending::Global(name))),
+        }
+    }
+
+    fn property(&mut self, object: &Expr, name: Key) {
+        let is_this = matches!(object, Expr::RefExpr(RefExpr::This { .. }, _));
+        let pending = match (is_this, self.class) {
+            (true, Some(class)) => Pending::ClassMethod(class, name),
+            _ => Pending::Method(name),
+        };
+        self.pending.push((self.current, pending));
+    }
+
+    fn node(&mut self, name: String, kind: NodeKind, loc: Location) -> usize {
+        self.graph.nodes.push(CallNode { name, kind, loc });
+        self.graph.nodes.len() - 1
+    }
+
+    fn declare(&mut self, name: Key, node: Option<usize>) {
+        match self.scopes.last_mut() {
+            Some(scope) => scope.insert(name, node),
+            None => self.globals.insert(name, node),
+        };
+    }
+
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(FxHashMap::default());
+        f(self);
+        self.scopes.pop();
+    }
+}
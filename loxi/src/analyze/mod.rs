@@ -0,0 +1,43 @@
+//! Analyses of a program that only look at its source, to help navigate it.
+
+use crate::interp::interner::Interner;
+use crate::parse::Program;
+use crate::resolve::ResolveMap;
+
+pub use self::callgraph::{CallGraph, CallNode, NodeKind};
+pub use self::refs::{Bindings, Reference, ReferenceKind, Span};
+pub use self::semantic::SemanticToken;
+pub use self::symbols::{Symbol, SymbolKind};
+
+mod callgraph;
+mod refs;
+mod semantic;
+mod symbols;
+
+#[cfg(test)]
+mod test;
+
+pub fn call_graph(program: &Program, resolve_map: &ResolveMap, interner: &Interner) -> CallGraph {
+    callgraph::Builder::new(interner, resolve_map).build(program)
+}
+
+/// The declaration of every variable used in `program`, to find the definition of a use or the
+/// references to a name.
+pub fn bindings(program: &Program, resolve_map: &ResolveMap, interner: &Interner) -> Bindings {
+    refs::Builder::new(interner, resolve_map).build(program)
+}
+
+/// The declarations of `program`, nested in the function or class they are declared in.
+pub fn symbols(program: &Program, interner: &Interner) -> Vec<Symbol> {
+    symbols::Builder::new(interner).build(program)
+}
+
+/// The names in `program` classified by what they refer to, in the order of the source.
+pub fn semantic_tokens(
+    program: &Program,
+    resolve_map: &ResolveMap,
+    interner: &Interner,
+) -> Vec<SemanticToken> {
+    let bindings = bindings(program, resolve_map, interner);
+    semantic::tokens(&bindings, &symbols(program, interner))
+}
@@ -0,0 +1,354 @@
+use std::fmt::Display;
+
+use rustc_hash::FxHashMap;
+use unicode_width::UnicodeWidthStr;
+
+use crate::interp::interner::{Interner, Key};
+use crate::lex::token::Keyword;
+use crate::parse::expr::{Expr, ExprId, RefExpr, ValExpr};
+use crate::parse::stmt::{Stmt, StmtFunction};
+use crate::parse::Program;
+use crate::resolve::ResolveMap;
+use crate::util::Location;
+
+/// A range in the source, from its first column to the column after its last one. A name spans
+/// its width, an error that is only known by where it starts spans nothing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Span {
+    pub start: Location,
+    pub end: Location,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReferenceKind {
+    Definition,
+    Read,
+    Write,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reference {
+    pub name: String,
+    pub span: Span,
+    pub kind: ReferenceKind,
+}
+
+/// Every use of the variables of a program, including functions, classes, and parameters, with
+/// the declaration each one refers to. Properties and methods are looked up when the program runs,
+/// so they are not included.
+#[derive(Clone, Debug, Default)]
+pub struct Bindings {
+    references: Vec<Reference>,
+
+    /// The binding of each reference, and the definition of each binding. A global that is used
+    /// but never declared, like a native, has no definition.
+    binding: Vec<usize>,
+    definitions: Vec<Option<Span>>,
+}
+
+impl Span {
+    pub(crate) fn new(start: Location, name: &str) -> Self {
+        let end = Location {
+            column: start.column + name.width(),
+            ..start
+        };
+        Self { start, end }
+    }
+
+    /// An empty span at `loc`.
+    pub(crate) fn at(loc: Location) -> Self {
+        Self {
+            start: loc,
+            end: loc,
+        }
+    }
+
+    pub fn contains(&self, loc: Location) -> bool {
+        self.start <= loc && loc < self.end
+    }
+}
+
+impl Display for ReferenceKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ReferenceKind::Definition => "definition",
+            ReferenceKind::Read => "read",
+            ReferenceKind::Write => "write",
+        })
+    }
+}
+
+impl Display for Reference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Location { line, column, .. } = self.span.start;
+        write!(f, "{line}:{column} {}", self.kind)
+    }
+}
+
+impl Bindings {
+    /// The definition of the variable used at `position`, if there is a use there.
+    pub fn find_definition(&self, position: Location) -> Option<Span> {
+        let i = self
+            .references
+            .iter()
+            .position(|r| r.span.contains(position))?;
+        self.definitions[self.binding[i]]
+    }
+
+    /// Every reference along with the definition of the variable it refers to.
+    pub(super) fn resolved(&self) -> impl Iterator<Item = (&Reference, Option<Span>)> {
+        let definitions = self
+            .binding
+            .iter()
+            .map(|binding| self.definitions[*binding]);
+        self.references.iter().zip(definitions)
+    }
+
+    /// Every reference to the variables called `name`, in the order of the source.
+    pub fn find_references(&self, name: &str) -> Vec<Reference> {
+        let mut references = self
+            .references
+            .iter()
+            .filter(|reference| reference.name == name)
+            .cloned()
+            .collect::<Vec<_>>();
+        references.sort_by_key(|reference| reference.span);
+        references
+    }
+}
+
+pub(super) struct Builder<'a> {
+    interner: &'a Interner,
+    resolve_map: &'a ResolveMap,
+    bindings: Bindings,
+
+    /// The binding of each local in the scopes created by `Resolver`, and of each global.
+    scopes: Vec<FxHashMap<Key, usize>>,
+    globals: FxHashMap<Key, usize>,
+}
+
+impl<'a> Builder<'a> {
+    pub(super) fn new(interner: &'a Interner, resolve_map: &'a ResolveMap) -> Self {
+        Self {
+            interner,
+            resolve_map,
+            bindings: Bindings::default(),
+            scopes: Vec::new(),
+            globals: FxHashMap::default(),
+        }
+    }
+
+    pub(super) fn build(mut self, program: &Program) -> Bindings {
+        for stmt in program.statements.iter() {
+            self.stmt(stmt);
+        }
+        self.bindings
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expr { expr } | Stmt::Print { expr, .. } => self.expr(expr),
+            Stmt::Var { loc, name, init } => {
+                self.declare(*name, *loc);
+                if let Some(init) = init {
+                    self.expr(init);
+                }
+            }
+            Stmt::Block { statements, .. } => {
+                self.scoped(|builder| statements.iter().for_each(|stmt| builder.stmt(stmt)))
+            }
+            Stmt::If {
+                condition,
+                then,
+                otherwise,
+                ..
+            } => {
+                self.expr(condition);
+                self.stmt(then);
+                if let Some(otherwise) = otherwise {
+                    self.stmt(otherwise);
+                }
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                self.expr(condition);
+                self.stmt(body);
+            }
+            Stmt::ForIn {
+                name,
+                name_loc,
+                iterable,
+                body,
+                ..
+            } => {
+                self.expr(iterable);
+                self.scoped(|builder| {
+                    builder.declare(*name, *name_loc);
+                    builder.stmt(body);
+                });
+            }
+            Stmt::Switch {
+                subject,
+                cases,
+                otherwise,
+                ..
+            } => {
+                self.expr(subject);
+                for (value, body) in cases {
+                    self.expr(value);
+                    self.stmt(body);
+                }
+                if let Some(otherwise) = otherwise {
+                    self.stmt(otherwise);
+                }
+            }
+            Stmt::Function { func } => {
+                self.declare(func.name, func.name_loc);
+                self.function(func);
+            }
+            Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            }
+            Stmt::Class {
+                name,
+                name_loc,
+                methods,
+                ..
+            } => {
+                self.declare(*name, *name_loc);
+                self.scoped(|builder| {
+                    // never referenced by name, it only keeps the scopes in line with `Resolver`
+                    let this = builder.interner.keyword(Keyword::This);
+                    builder.scopes.last_mut().unwrap().insert(this, usize::MAX);
+                    methods.iter().for_each(|method| builder.function(method));
+                });
+            }
+        }
+    }
+
+    fn function(&mut self, func: &StmtFunction) {
+        self.scoped(|builder| {
+            for (param, loc) in func.params.iter().zip(func.param_locs.iter()) {
+                builder.declare(*param, *loc);
+            }
+            func.body.iter().for_each(|stmt| builder.stmt(stmt));
+        });
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::ValExpr(expr, _) => self.val_expr(expr),
+            Expr::RefExpr(expr, id) => self.ref_expr(expr, *id),
+        }
+    }
+
+    fn val_expr(&mut self, expr: &ValExpr) {
+        match expr {
+            ValExpr::Literal { .. } => (),
+            ValExpr::Unary { right, .. } => self.expr(right),
+            ValExpr::Binary { left, right, .. } | ValExpr::Logical { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            ValExpr::Grouping { expr, .. } => self.val_expr(expr),
+            ValExpr::Call { callee, args, .. } => {
+                self.expr(callee);
+                args.iter().for_each(|arg| self.expr(arg));
+            }
+            ValExpr::List { elements, .. } => elements.iter().for_each(|elem| self.expr(elem)),
+        }
+    }
+
+    /// A grouped reference has the id of the outermost grouping, like in `Resolver`.
+    fn ref_expr(&mut self, expr: &RefExpr, id: ExprId) {
+        match expr {
+            RefExpr::Variable { var } => {
+                self.reference(var.tok.name, var.loc, id, ReferenceKind::Read)
+            }
+            RefExpr::Grouping { expr, .. } => self.ref_expr(expr, id),
+            RefExpr::Assignment { var, value } => {
+                self.expr(value);
+                self.reference(var.tok.name, var.loc, id, ReferenceKind::Write);
+            }
+            RefExpr::Get { object, .. } => self.expr(object),
+            RefExpr::Set { object, value, .. } => {
+                self.expr(object);
+                self.expr(value);
+            }
+            RefExpr::This { .. } => (),
+            RefExpr::Index { object, index, .. } => {
+                self.expr(object);
+                self.expr(index);
+            }
+            RefExpr::IndexSet {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.expr(object);
+                self.expr(index);
+                self.expr(value);
+            }
+        }
+    }
+
+    fn reference(&mut self, name: Key, loc: Location, id: ExprId, kind: ReferenceKind) {
+        let scope = self
+            .resolve_map
+            .distance(id)
+            .and_then(|distance| self.scopes.len().checked_sub(distance + 1));
+        let binding = match scope.and_then(|scope| self.scopes[scope].get(&name)) {
+            Some(binding) => *binding,
+            None => self.global(name),
+        };
+        self.push(name, loc, binding, kind);
+    }
+
+    /// Declare `name` in the current scope. A global can be declared more than once, in which
+    /// case its definition is the first one.
+    fn declare(&mut self, name: Key, loc: Location) {
+        let span = Span::new(loc, self.interner.resolve(name));
+        let binding = match self.scopes.last_mut() {
+            Some(scope) => {
+                let binding = self.bindings.definitions.len();
+                self.bindings.definitions.push(Some(span));
+                scope.insert(name, binding);
+                binding
+            }
+            None => {
+                let binding = self.global(name);
+                self.bindings.definitions[binding].get_or_insert(span);
+                binding
+            }
+        };
+        self.push(name, loc, binding, ReferenceKind::Definition);
+    }
+
+    fn global(&mut self, name: Key) -> usize {
+        let definitions = &mut self.bindings.definitions;
+        *self.globals.entry(name).or_insert_with(|| {
+            definitions.push(None);
+            definitions.len() - 1
+        })
+    }
+
+    fn push(&mut self, name: Key, loc: Location, binding: usize, kind: ReferenceKind) {
+        let name = self.interner.resolve(name);
+        self.bindings.references.push(Reference {
+            name: name.to_owned(),
+            span: Span::new(loc, name),
+            kind,
+        });
+        self.bindings.binding.push(binding);
+    }
+
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(FxHashMap::default());
+        f(self);
+        self.scopes.pop();
+    }
+}
@@ -0,0 +1,58 @@
+use rustc_hash::FxHashMap;
+
+use super::refs::{Bindings, ReferenceKind, Span};
+use super::symbols::{Symbol, SymbolKind};
+
+/// A name in the source classified by what it refers to, for highlighting it in an editor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SemanticToken {
+    pub span: Span,
+    pub kind: SymbolKind,
+
+    /// Whether the name is where it's declared, rather than a use of it.
+    pub declaration: bool,
+}
+
+/// The uses are classified by the kind of their declaration, and the uses of globals that are not
+/// declared in the program, e.g. natives, are left out. Methods are only looked up when the
+/// program runs, so only their declarations are included.
+pub(super) fn tokens(bindings: &Bindings, symbols: &[Symbol]) -> Vec<SemanticToken> {
+    let mut kinds = FxHashMap::default();
+    let mut methods = Vec::new();
+    collect(symbols, &mut kinds, &mut methods);
+
+    let mut tokens = bindings
+        .resolved()
+        .filter_map(|(reference, definition)| {
+            let kind = *kinds.get(&definition?)?;
+            Some(SemanticToken {
+                span: reference.span,
+                kind,
+                declaration: reference.kind == ReferenceKind::Definition,
+            })
+        })
+        .chain(methods)
+        .collect::<Vec<_>>();
+    tokens.sort_by_key(|token| token.span);
+    tokens
+}
+
+fn collect(
+    symbols: &[Symbol],
+    kinds: &mut FxHashMap<Span, SymbolKind>,
+    methods: &mut Vec<SemanticToken>,
+) {
+    for symbol in symbols {
+        match symbol.kind {
+            SymbolKind::Method => methods.push(SemanticToken {
+                span: symbol.span,
+                kind: SymbolKind::Method,
+                declaration: true,
+            }),
+            kind => {
+                kinds.insert(symbol.span, kind);
+            }
+        }
+        collect(&symbol.children, kinds, methods);
+    }
+}
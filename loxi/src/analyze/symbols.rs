@@ -0,0 +1,170 @@
+use std::fmt::Display;
+
+use crate::interp::interner::Interner;
+use crate::parse::stmt::{Stmt, StmtFunction};
+use crate::parse::Program;
+
+use super::refs::Span;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolKind {
+    Variable,
+    Parameter,
+    Function,
+    Class,
+    Method,
+}
+
+/// A declaration in a program, e.g. for the outline of an editor.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+
+    /// The name where it's declared.
+    pub span: Span,
+
+    /// How it's declared, e.g. `fun add(a, b)`.
+    pub detail: String,
+
+    /// The declarations inside of it: the methods of a class, or the parameters and locals of a
+    /// function.
+    pub children: Vec<Symbol>,
+}
+
+impl Display for SymbolKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SymbolKind::Variable => "variable",
+            SymbolKind::Parameter => "parameter",
+            SymbolKind::Function => "function",
+            SymbolKind::Class => "class",
+            SymbolKind::Method => "method",
+        })
+    }
+}
+
+impl Symbol {
+    fn new(name: &str, kind: SymbolKind, span: Span) -> Self {
+        let detail = match kind {
+            SymbolKind::Variable => format!("var {name}"),
+            SymbolKind::Class => format!("class {name}"),
+            _ => name.to_owned(),
+        };
+        Self {
+            name: name.to_owned(),
+            kind,
+            span,
+            detail,
+            children: Vec::new(),
+        }
+    }
+
+    /// The symbol declared at `span`, looking into the children.
+    pub fn find(symbols: &[Symbol], span: Span) -> Option<&Symbol> {
+        symbols.iter().find_map(|symbol| match symbol.span == span {
+            true => Some(symbol),
+            false => Symbol::find(&symbol.children, span),
+        })
+    }
+}
+
+pub(super) struct Builder<'a> {
+    interner: &'a Interner,
+}
+
+impl<'a> Builder<'a> {
+    pub(super) fn new(interner: &'a Interner) -> Self {
+        Self { interner }
+    }
+
+    pub(super) fn build(&self, program: &Program) -> Vec<Symbol> {
+        let mut symbols = Vec::new();
+        self.stmts(&program.statements, &mut symbols);
+        symbols
+    }
+
+    fn stmts(&self, stmts: &[Stmt], symbols: &mut Vec<Symbol>) {
+        stmts.iter().for_each(|stmt| self.stmt(stmt, symbols));
+    }
+
+    fn stmt(&self, stmt: &Stmt, symbols: &mut Vec<Symbol>) {
+        match stmt {
+            Stmt::Var { loc, name, .. } => {
+                let name = self.interner.resolve(*name);
+                symbols.push(Symbol::new(
+                    name,
+                    SymbolKind::Variable,
+                    Span::new(*loc, name),
+                ));
+            }
+            Stmt::Block { statements, .. } => self.stmts(statements, symbols),
+            Stmt::If {
+                then, otherwise, ..
+            } => {
+                self.stmt(then, symbols);
+                if let Some(otherwise) = otherwise {
+                    self.stmt(otherwise, symbols);
+                }
+            }
+            Stmt::While { body, .. } => self.stmt(body, symbols),
+            Stmt::ForIn {
+                name,
+                name_loc,
+                body,
+                ..
+            } => {
+                let name = self.interner.resolve(*name);
+                let span = Span::new(*name_loc, name);
+                symbols.push(Symbol::new(name, SymbolKind::Variable, span));
+                self.stmt(body, symbols);
+            }
+            Stmt::Switch {
+                cases, otherwise, ..
+            } => {
+                cases.iter().for_each(|(_, body)| self.stmt(body, symbols));
+                if let Some(otherwise) = otherwise {
+                    self.stmt(otherwise, symbols);
+                }
+            }
+            Stmt::Function { func } => symbols.push(self.function(func, SymbolKind::Function)),
+            Stmt::Class {
+                name,
+                name_loc,
+                methods,
+                ..
+            } => {
+                let name = self.interner.resolve(*name);
+                let mut class = Symbol::new(name, SymbolKind::Class, Span::new(*name_loc, name));
+                class.children = methods
+                    .iter()
+                    .map(|method| self.function(method, SymbolKind::Method))
+                    .collect();
+                symbols.push(class);
+            }
+            Stmt::Expr { .. } | Stmt::Print { .. } | Stmt::Return { .. } => (),
+        }
+    }
+
+    fn function(&self, func: &StmtFunction, kind: SymbolKind) -> Symbol {
+        let name = self.interner.resolve(func.name);
+        let mut symbol = Symbol::new(name, kind, Span::new(func.name_loc, name));
+
+        let params = func
+            .params
+            .iter()
+            .map(|param| self.interner.resolve(*param));
+        let params = params.collect::<Vec<_>>();
+        symbol.detail = match kind {
+            SymbolKind::Method => format!("{name}({})", params.join(", ")),
+            _ => format!("fun {name}({})", params.join(", ")),
+        };
+
+        for (param, loc) in params.iter().zip(func.param_locs.iter()) {
+            let param = Symbol::new(param, SymbolKind::Parameter, Span::new(*loc, param));
+            symbol.children.push(param);
+        }
+        self.stmts(&func.body, &mut symbol.children);
+        symbol
+    }
+}
@@ -0,0 +1,171 @@
+use std::io;
+
+use pretty_assertions::assert_eq;
+
+use crate::analyze::{ReferenceKind, Span, Symbol, SymbolKind};
+use crate::util::Location;
+use crate::{Lox, Options};
+
+/// The edges by the names of their nodes.
+fn edges(source: &str) -> Vec<(String, String)> {
+    let mut lox = Lox::new(&Options::default());
+    lox.set_stderr(io::sink());
+
+    let graph = lox.call_graph(source).expect("source should compile");
+    let name = |i: usize| graph.nodes()[i].name.clone();
+    graph
+        .edges()
+        .map(|(from, to)| (name(from), name(to)))
+        .collect()
+}
+
+#[test]
+fn references_are_followed_to_their_declarations() {
+    let source = r#"
+fun helper() { return later(); }
+class Point {
+    init(x) { this.x = x; this.check(); }
+    check() { return helper; }
+    sum() { return 0; }
+}
+fun later() {
+    fun helper() { return 1; }
+    return helper() + Point(1).sum();
+}
+later();
+"#;
+    let edge = |from: &str, to: &str| (from.to_owned(), to.to_owned());
+    let expected = vec![
+        edge("<script>", "later"),
+        edge("helper", "later"),
+        edge("Point", "Point.init"),
+        edge("Point.init", "Point.check"),
+        edge("Point.check", "helper"),
+        edge("later", "Point"),
+        edge("later", "Point.sum"),
+        edge("later", "helper"),
+    ];
+    assert_eq!(edges(source), expected);
+}
+
+#[test]
+fn references_are_bound_to_their_declarations() {
+    let source = r#"var x = 1;
+fun f(x) {
+  x = x + 1;
+  return x;
+}
+print f(x);
+"#;
+    let mut lox = Lox::new(&Options::default());
+    lox.set_stderr(io::sink());
+    let bindings = lox.bindings(source).expect("source should compile");
+
+    let span = |line, column| Span {
+        start: Location::new(line, column),
+        end: Location::new(line, column + 1),
+    };
+    assert_eq!(
+        bindings.find_definition(Location::new(4, 10)),
+        Some(span(2, 7))
+    );
+    assert_eq!(
+        bindings.find_definition(Location::new(6, 9)),
+        Some(span(1, 5))
+    );
+    assert_eq!(bindings.find_definition(Location::new(6, 5)), None);
+    assert_eq!(
+        bindings.find_definition(Location::new(6, 7)),
+        Some(span(2, 5))
+    );
+
+    let references = bindings.find_references("x");
+    let kinds = references
+        .iter()
+        .map(|reference| (reference.span.start, reference.kind))
+        .collect::<Vec<_>>();
+    let expected = vec![
+        (Location::new(1, 5), ReferenceKind::Definition),
+        (Location::new(2, 7), ReferenceKind::Definition),
+        (Location::new(3, 3), ReferenceKind::Write),
+        (Location::new(3, 7), ReferenceKind::Read),
+        (Location::new(4, 10), ReferenceKind::Read),
+        (Location::new(6, 9), ReferenceKind::Read),
+    ];
+    assert_eq!(kinds, expected);
+}
+
+#[test]
+fn symbols_nest_in_their_declarations() {
+    let source = r#"var x = 1;
+class Point {
+  init(x) { var y = x; }
+}
+"#;
+    let mut lox = Lox::new(&Options::default());
+    lox.set_stderr(io::sink());
+    let symbols = lox.symbols(source).expect("source should compile");
+
+    fn outline(symbols: &[Symbol], depth: usize, out: &mut Vec<String>) {
+        for symbol in symbols {
+            let Location { line, column, .. } = symbol.span.start;
+            let indent = " ".repeat(depth * 2);
+            out.push(format!(
+                "{indent}{} {line}:{column} {}",
+                symbol.kind, symbol.detail
+            ));
+            outline(&symbol.children, depth + 1, out);
+        }
+    }
+    let mut lines = Vec::new();
+    outline(&symbols, 0, &mut lines);
+    assert_eq!(
+        lines,
+        vec![
+            "variable 1:5 var x",
+            "class 2:7 class Point",
+            "  method 3:3 init(x)",
+            "    parameter 3:8 x",
+            "    variable 3:17 var y",
+        ]
+    );
+
+    let init = Symbol::find(&symbols, symbols[1].children[0].span).unwrap();
+    assert_eq!(init.kind, SymbolKind::Method);
+}
+
+#[test]
+fn semantic_tokens_are_classified_by_their_declaration() {
+    let source = r#"fun add(a, b) { return a + b; }
+class Pair { sum() { return add(1, 2); } }
+var pair = Pair();
+print clock();
+"#;
+    let mut lox = Lox::new(&Options::default());
+    lox.set_stderr(io::sink());
+    let tokens = lox.semantic_tokens(source).expect("source should compile");
+
+    let tokens = tokens
+        .iter()
+        .map(|token| {
+            let Location { line, column, .. } = token.span.start;
+            let declaration = if token.declaration { "*" } else { "" };
+            format!("{line}:{column} {}{declaration}", token.kind)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tokens,
+        vec![
+            "1:5 function*",
+            "1:9 parameter*",
+            "1:12 parameter*",
+            "1:24 parameter",
+            "1:28 parameter",
+            "2:7 class*",
+            "2:14 method*",
+            "2:29 function",
+            "3:5 variable*",
+            "3:12 class",
+        ]
+    );
+}
@@ -0,0 +1,114 @@
+//! `loxi bench internal`: a quick check for performance regressions, without building the criterion
+//! benchmarks. Each workload runs a few times in a fresh instance and the fastest run is shown,
+//! which is noisy but enough to notice a stage getting much slower. Like the benchmarks, lexing and
+//! parsing are measured through the dump modes, so they include displaying the result into a sink.
+
+use std::io;
+use std::time::{Duration, Instant};
+
+use loxi::{Lox, LoxError, Options, RunMode};
+
+const FIB: &str = r#"
+fun fib(n) {
+    if (n < 2) return n;
+    return fib(n - 1) + fib(n - 2);
+}
+fib(20);
+"#;
+
+const LOOP: &str = r#"
+var sum = 0;
+for (var i = 0; i < 100000; i = i + 1) {
+    sum = sum + i;
+}
+"#;
+
+/// A chunk of the program lexed and parsed, using every kind of token.
+const CHUNK: &str = r#"
+var greeting = "hello";
+fun add(a, b) {
+    // the sum of both
+    return a + b * 2 - 1 / 4;
+}
+if (greeting != nil and !false or 1 <= 2) {
+    print add(1.5, 2) >= 3;
+}
+"#;
+
+/// The statements in `CHUNK`, nested ones included.
+const CHUNK_STATEMENTS: usize = 5;
+
+/// What a workload is measured per, besides its time.
+enum Rate {
+    Bytes(usize),
+    Statements(usize),
+    None,
+}
+
+struct Workload {
+    name: &'static str,
+    source: String,
+    mode: RunMode,
+    rate: Rate,
+}
+
+pub fn run(iterations: usize, options: &Options) -> Result<(), LoxError> {
+    let chunks = 10_000 / CHUNK.lines().count();
+    let program = CHUNK.repeat(chunks);
+    let workloads = [
+        Workload {
+            name: "lex 10k lines",
+            rate: Rate::Bytes(program.len()),
+            source: program.clone(),
+            mode: RunMode::DumpLex,
+        },
+        Workload {
+            name: "parse 10k lines",
+            rate: Rate::Statements(chunks * CHUNK_STATEMENTS),
+            source: program,
+            mode: RunMode::DumpParse,
+        },
+        Workload {
+            name: "fib(20)",
+            source: FIB.to_owned(),
+            mode: RunMode::Normal,
+            rate: Rate::None,
+        },
+        Workload {
+            name: "loop 100k",
+            source: LOOP.to_owned(),
+            mode: RunMode::Normal,
+            rate: Rate::None,
+        },
+    ];
+
+    println!("fastest of {iterations} runs");
+    for workload in &workloads {
+        let time = measure(workload, iterations.max(1), options)?;
+        let secs = time.as_secs_f64();
+        let rate = match workload.rate {
+            Rate::Bytes(bytes) => format!("{:>10.1} MB/s", bytes as f64 / secs / 1e6),
+            Rate::Statements(count) => format!("{:>10.3} µs/stmt", secs * 1e6 / count as f64),
+            Rate::None => String::new(),
+        };
+        println!("{:<16}{:>10.3} ms{rate}", workload.name, secs * 1e3);
+    }
+    Ok(())
+}
+
+fn measure(
+    workload: &Workload,
+    iterations: usize,
+    options: &Options,
+) -> Result<Duration, LoxError> {
+    let mut fastest = Duration::MAX;
+    for _ in 0..iterations {
+        let mut lox = Lox::new(options);
+        lox.set_stdout(io::sink());
+
+        let start = Instant::now();
+        lox.run(&workload.source, workload.mode)?;
+        fastest = fastest.min(start.elapsed());
+    }
+    Ok(fastest)
+}
@@ -0,0 +1,159 @@
+//! `loxi debug`: an interactive debugger in the terminal. The program pauses before its first
+//! statement, then the commands are read from stdin whenever it pauses.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use loxi::{
+    Location, Lox, LoxError, Observer, Options, Outcome, RunMode, Scope, Step, Stepper, StopReason,
+};
+
+const HELP: &str = "\
+break [file:]line    pause before the line runs, e.g. `break main.lox:12`
+step                 run until the next line, entering calls
+next                 run until the next line of the current function
+finish               run until the current function returns
+continue             run until the next breakpoint
+print expr           evaluate an expression in the current scope
+backtrace            show the call stack
+quit                 stop the program and exit";
+
+struct Debugger {
+    path: PathBuf,
+    lines: Vec<String>,
+    stepper: Stepper,
+    breakpoints: BTreeSet<usize>,
+}
+
+pub fn run(path: PathBuf, options: &Options) -> Result<Outcome, LoxError> {
+    let source = fs::read_to_string(&path)?;
+    let debugger = Debugger {
+        lines: source.lines().map(str::to_owned).collect(),
+        path: path.clone(),
+        stepper: Stepper::new(true),
+        breakpoints: BTreeSet::new(),
+    };
+    println!(
+        "Debugging {}, type 'help' for the list of commands",
+        path.display()
+    );
+
+    let mut lox = Lox::new(options);
+    lox.set_observer(debugger);
+    lox.run_file(path, RunMode::Normal)
+}
+
+impl Debugger {
+    /// Read commands until one of them resumes the program.
+    fn prompt(&mut self, loc: Location, scope: &Scope<'_>) {
+        loop {
+            print!("(loxi) ");
+            io::stdout().flush().expect("Unable to flush stdout");
+
+            let mut line = String::new();
+            match io::stdin().read_line(&mut line) {
+                Ok(0) | Err(_) => process::exit(0),
+                Ok(_) => (),
+            }
+            let (command, arg) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+            let arg = arg.trim();
+
+            let step = match command {
+                "" => continue,
+                "b" | "break" => {
+                    self.add_breakpoint(arg);
+                    continue;
+                }
+                "s" | "step" => Step::In,
+                "n" | "next" => Step::Over,
+                "f" | "finish" => Step::Out,
+                "c" | "continue" => Step::Continue,
+                "p" | "print" => {
+                    match scope.eval(arg) {
+                        Ok(value) => println!("{}", scope.display(&value)),
+                        Err(err) => println!("{err}"),
+                    }
+                    continue;
+                }
+                "bt" | "backtrace" => {
+                    for (i, frame) in self.stepper.backtrace(loc).iter().enumerate() {
+                        println!("#{i} {} at {}", frame.name, self.location(frame.loc.line));
+                    }
+                    continue;
+                }
+                "h" | "help" => {
+                    println!("{HELP}");
+                    continue;
+                }
+                "q" | "quit" => process::exit(0),
+                command => {
+                    println!("Unknown command '{command}', type 'help' for the list of commands");
+                    continue;
+                }
+            };
+            return self.stepper.resume(step, loc);
+        }
+    }
+
+    /// `arg` is either `file:line` or `line`. The program is a single file, so only the lines of
+    /// that file can have a breakpoint.
+    fn add_breakpoint(&mut self, arg: &str) {
+        let (file, line) = match arg.rsplit_once(':') {
+            Some((file, line)) => (Some(file), line),
+            None => (None, arg),
+        };
+        if let Some(file) = file {
+            if !self.path.ends_with(file) {
+                println!("Only breakpoints in {} are supported", self.path.display());
+                return;
+            }
+        }
+
+        match line.parse::<usize>() {
+            Ok(line) if (1..=self.lines.len()).contains(&line) => {
+                self.breakpoints.insert(line);
+                println!("Breakpoint at {}", self.location(line));
+            }
+            _ => println!("Invalid line '{line}'"),
+        }
+    }
+
+    fn location(&self, line: usize) -> String {
+        format!("{}:{line}", file_name(&self.path))
+    }
+
+    fn show_line(&self, line: usize) {
+        let source = self.lines.get(line - 1).map_or("", String::as_str);
+        println!("{line:>4} | {source}");
+    }
+}
+
+impl Observer for Debugger {
+    fn on_statement(&mut self, loc: Location, scope: &Scope<'_>) {
+        if let Some(reason) = self.stepper.should_pause(loc, &self.breakpoints) {
+            if reason == StopReason::Breakpoint {
+                println!("Breakpoint hit at {}", self.location(loc.line));
+            }
+            self.show_line(loc.line);
+            self.prompt(loc, scope);
+        }
+    }
+
+    fn on_call(&mut self, name: &str, loc: Location) {
+        self.stepper.enter(name, loc);
+    }
+
+    fn on_return(&mut self, _name: &str, _loc: Location) {
+        self.stepper.leave();
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map_or_else(
+        || path.display().to_string(),
+        |name| name.to_string_lossy().into(),
+    )
+}
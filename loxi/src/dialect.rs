@@ -0,0 +1,78 @@
+//! The extensions to the language of the book that a program may use.
+
+use crate::lex::token::Keyword;
+
+/// Which extensions to the book's Lox are enabled, checked by the lexer for the literals and the
+/// keywords, by the parser for the syntax, and by the interpreter for the operators and the
+/// natives. The keywords of a disabled extension are names like any other, e.g. `var case = 1;`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Dialect {
+    /// Raw strings like `r"..."`, hexadecimal and binary integers like `0xFF`, exponents like
+    /// `1e3`, and digits separated by `_` like `1_000`.
+    pub literals: bool,
+
+    /// List literals like `[1, 2]` and indexing like `list[0]`.
+    pub lists: bool,
+
+    /// `for (var item in iterable)` loops.
+    pub for_in: bool,
+
+    /// `switch` statements.
+    pub switch: bool,
+
+    /// The bitwise operators `&`, `|`, `^`, `<<`, and `>>`.
+    pub bitwise: bool,
+
+    /// Comparing two strings lexicographically with `<`, `<=`, `>`, and `>=`.
+    pub string_ordering: bool,
+
+    /// The `deepEquals(a, b)` native, which compares lists, maps, and instances by their contents.
+    pub deep_equals: bool,
+}
+
+impl Dialect {
+    /// The language of the book without any extension, e.g. to run its test suite.
+    pub fn book() -> Self {
+        Self {
+            literals: false,
+            lists: false,
+            for_in: false,
+            switch: false,
+            bitwise: false,
+            string_ordering: false,
+            deep_equals: false,
+        }
+    }
+
+    /// Every extension enabled.
+    pub fn extended() -> Self {
+        Self {
+            literals: true,
+            lists: true,
+            for_in: true,
+            switch: true,
+            bitwise: true,
+            string_ordering: true,
+            deep_equals: true,
+        }
+    }
+
+    /// Whether `keyword` is reserved, i.e. it's not a keyword of a disabled extension.
+    pub fn reserves(&self, keyword: &Keyword) -> bool {
+        match keyword {
+            Keyword::Switch | Keyword::Case => self.switch,
+            _ => true,
+        }
+    }
+}
+
+/// The extensions to the syntax, but only the operators and natives of the book.
+impl Default for Dialect {
+    fn default() -> Self {
+        Self {
+            string_ordering: false,
+            deep_equals: false,
+            ..Self::extended()
+        }
+    }
+}
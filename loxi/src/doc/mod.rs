@@ -0,0 +1,53 @@
+//! Documentation of the declarations at the top level of a program, from their `///` comments.
+
+use std::collections::BTreeMap;
+
+use crate::interp::interner::Interner;
+use crate::parse::stmt::{Stmt, StmtFunction};
+use crate::parse::Program;
+use crate::util::Location;
+
+#[cfg(test)]
+mod test;
+
+/// The declarations of `program` as Markdown, each one as a heading with its signature followed by
+/// its documentation from `docs`. The methods of a class are under the heading of the class.
+pub fn markdown(
+    program: &Program,
+    docs: &BTreeMap<Location, String>,
+    interner: &Interner,
+    title: &str,
+) -> String {
+    let mut out = format!("# {title}\n");
+    let mut section = |heading: String, loc: Location| {
+        out.push_str(&format!("\n{heading}\n"));
+        if let Some(doc) = docs.get(&loc) {
+            out.push_str(&format!("\n{doc}\n"));
+        }
+    };
+    let signature = |func: &StmtFunction| {
+        let params = func.params.iter().map(|param| interner.resolve(*param));
+        let params = params.collect::<Vec<_>>().join(", ");
+        format!("{}({params})", interner.resolve(func.name))
+    };
+
+    for stmt in program.statements.iter() {
+        match stmt {
+            Stmt::Var { loc, name, .. } => {
+                section(format!("## `var {}`", interner.resolve(*name)), *loc)
+            }
+            Stmt::Function { func } => section(format!("## `fun {}`", signature(func)), func.loc),
+            Stmt::Class {
+                loc, name, methods, ..
+            } => {
+                let name = interner.resolve(*name);
+                section(format!("## `class {name}`"), *loc);
+                for method in methods.iter() {
+                    section(format!("### `{name}.{}`", signature(method)), method.loc);
+                }
+            }
+            _ => (),
+        }
+    }
+    out
+}
@@ -0,0 +1,59 @@
+use std::io;
+
+use indoc::indoc;
+use pretty_assertions::assert_eq;
+
+use crate::{Lox, Options};
+
+#[test]
+fn doc_comments_document_the_declaration_below_them() {
+    let source = indoc! {r#"
+        /// Not attached to anything.
+
+        /// The origin.
+        var origin = 0;
+
+        // a regular comment
+        /// Adds `a` and `b`.
+        ///
+        /// Both are numbers.
+        fun add(a, b) {
+            /// Inside of a function.
+            var sum = a + b;
+            return sum;
+        }
+
+        class Point {
+            /// Make a point.
+            init(x, y) {}
+            len() { return 0; }
+        }
+        print add(1, 2);
+    "#};
+    let expected = indoc! {"
+        # script.lox
+
+        ## `var origin`
+
+        The origin.
+
+        ## `fun add(a, b)`
+
+        Adds `a` and `b`.
+
+        Both are numbers.
+
+        ## `class Point`
+
+        ### `Point.init(x, y)`
+
+        Make a point.
+
+        ### `Point.len()`
+    "};
+
+    let mut lox = Lox::new(&Options::default());
+    lox.set_stderr(io::sink());
+    let doc = lox.doc(source, "script.lox").expect("source should parse");
+    assert_eq!(doc, expected);
+}
@@ -0,0 +1,181 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io;
+
+use crate::analyze::Span;
+use crate::interp::RuntimeError;
+use crate::lex::LexError;
+use crate::lint::{LintLevel, LintWarning};
+use crate::message;
+use crate::parse::SyntaxError;
+use crate::resolve::ResolveError;
+
+/// Error from running a program. The errors found in the program are kept so the host can inspect
+/// them using `LoxError::diagnostics`, or walk them using `Error::source`.
+#[derive(Debug)]
+pub enum LoxError {
+    IoError(io::Error),
+
+    LexError {
+        source_name: Option<String>,
+        errors: Vec<LexError>,
+    },
+
+    ParseError {
+        source_name: Option<String>,
+        errors: Vec<SyntaxError>,
+    },
+
+    ResolveError {
+        source_name: Option<String>,
+        error: ResolveError,
+    },
+
+    RuntimeError {
+        source_name: Option<String>,
+        error: RuntimeError,
+    },
+
+    /// The program has warnings that are denied, see `Options::lint`. Every warning is kept, not
+    /// only the denied ones.
+    LintError {
+        source_name: Option<String>,
+        warnings: Vec<LintWarning>,
+    },
+
+    EmptyError,
+
+    /// The program called `exit(code)`.
+    Exit(i32),
+}
+
+/// The stage a `LoxError` comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    Io,
+    Lex,
+    Parse,
+    Resolve,
+    Runtime,
+    Empty,
+    Exit,
+
+    /// A warning of the linter, which only stops a program if it's denied.
+    Lint,
+}
+
+/// A single error found in a program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub kind: ErrorKind,
+
+    /// Name of the program the error is found in, the file path for programs run from a file.
+    pub source_name: Option<String>,
+
+    /// Where the error is, empty if only its start is known.
+    pub span: Span,
+
+    /// The error message without the location.
+    pub message: String,
+}
+
+impl LoxError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            LoxError::IoError(_) => ErrorKind::Io,
+            LoxError::LexError { .. } => ErrorKind::Lex,
+            LoxError::ParseError { .. } => ErrorKind::Parse,
+            LoxError::ResolveError { .. } => ErrorKind::Resolve,
+            LoxError::RuntimeError { .. } => ErrorKind::Runtime,
+            LoxError::LintError { .. } => ErrorKind::Lint,
+            LoxError::EmptyError => ErrorKind::Empty,
+            LoxError::Exit(_) => ErrorKind::Exit,
+        }
+    }
+
+    pub fn source_name(&self) -> Option<&str> {
+        match self {
+            LoxError::LexError { source_name, .. }
+            | LoxError::ParseError { source_name, .. }
+            | LoxError::ResolveError { source_name, .. }
+            | LoxError::RuntimeError { source_name, .. }
+            | LoxError::LintError { source_name, .. } => source_name.as_deref(),
+            LoxError::IoError(_) | LoxError::EmptyError | LoxError::Exit(_) => None,
+        }
+    }
+
+    /// The errors found in the program, empty if the error is not caused by the program itself.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let diagnostic = |span: Span, message: String| Diagnostic {
+            kind: self.kind(),
+            source_name: self.source_name().map(str::to_owned),
+            span,
+            message,
+        };
+
+        match self {
+            LoxError::LexError { errors, .. } => errors
+                .iter()
+                .map(|e| diagnostic(e.span(), e.message()))
+                .collect(),
+            LoxError::ParseError { errors, .. } => errors
+                .iter()
+                .map(|e| diagnostic(e.span(), e.message()))
+                .collect(),
+            LoxError::ResolveError { error, .. } => vec![diagnostic(error.span(), error.message())],
+            LoxError::RuntimeError { error, .. } => {
+                vec![diagnostic(error.span(), error.message())]
+            }
+            LoxError::LintError { warnings, .. } => warnings
+                .iter()
+                .map(|w| diagnostic(w.span(), w.labelled_message()))
+                .collect(),
+            LoxError::IoError(_) | LoxError::EmptyError | LoxError::Exit(_) => vec![],
+        }
+    }
+}
+
+impl Display for LoxError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "--[ LoxError ]-- ")?;
+        match self {
+            LoxError::IoError(err) => message::write(f, "error.io", &[("error", err)]),
+            LoxError::LexError { errors, .. } => {
+                message::write(f, "error.lex", &[("count", &errors.len())])
+            }
+            LoxError::ParseError { .. } => message::write(f, "error.parse", &[]),
+            LoxError::ResolveError { .. } => message::write(f, "error.resolve", &[]),
+            LoxError::RuntimeError { .. } => message::write(f, "error.runtime", &[]),
+            LoxError::LintError { warnings, .. } => {
+                let count = warnings
+                    .iter()
+                    .filter(|w| w.level == LintLevel::Deny)
+                    .count();
+                message::write(f, "error.lint", &[("count", &count)])
+            }
+            LoxError::EmptyError => message::write(f, "error.empty", &[]),
+            LoxError::Exit(code) => message::write(f, "error.exit", &[("code", code)]),
+        }
+    }
+}
+
+/// Only the first error is the source when there are multiple errors, the rest can be accessed
+/// through the variant fields or `LoxError::diagnostics`.
+impl Error for LoxError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoxError::IoError(err) => Some(err),
+            LoxError::LexError { errors, .. } => errors.first().map(|e| e as _),
+            LoxError::ParseError { errors, .. } => errors.first().map(|e| e as _),
+            LoxError::ResolveError { error, .. } => Some(error),
+            LoxError::RuntimeError { error, .. } => Some(error),
+            LoxError::LintError { .. } | LoxError::EmptyError | LoxError::Exit(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoxError {
+    fn from(err: io::Error) -> Self {
+        LoxError::IoError(err)
+    }
+}
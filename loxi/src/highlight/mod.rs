@@ -0,0 +1,73 @@
+//! Render a Lox source as a standalone HTML page with syntax highlighting. The source is split by
+//! the lexer with its trivia kept, so comments and the original layout are shown as written, even
+//! if the source has errors.
+
+use crate::interp::interner::Interner;
+use crate::lex::{Lexer, Span, SpanKind};
+
+#[cfg(test)]
+mod test;
+
+const STYLE: &str = "\
+body { margin: 0; background: #fafafa; }
+pre.lox { margin: 0; padding: 1em; color: #383a42; font: 14px/1.5 monospace; }
+.lox .keyword { color: #a626a4; font-weight: bold; }
+.lox .string { color: #50a14f; }
+.lox .number { color: #986801; }
+.lox .comment { color: #a0a1a7; font-style: italic; }
+.lox .operator { color: #0184bc; }
+.lox .unknown { color: #e45649; text-decoration: underline wavy; }
+";
+
+/// A complete HTML page showing `source`, with `title` as its title.
+pub fn to_html(source: &str, title: &str, interner: &mut Interner) -> String {
+    let spans = Lexer::new(source, interner).with_trivia().scan().spans;
+
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str(&format!("<title>{}</title>\n", escape(title)));
+    out.push_str(&format!("<style>\n{STYLE}</style>\n</head>\n<body>\n"));
+    out.push_str("<pre class=\"lox\"><code>");
+    out.push_str(&code(source, &spans));
+    out.push_str("</code></pre>\n</body>\n</html>\n");
+    out
+}
+
+/// The highlighted source, each span that has a color wrapped in a `<span>` of its kind.
+fn code(source: &str, spans: &[Span]) -> String {
+    let mut out = String::with_capacity(source.len() * 2);
+    for Span { kind, range } in spans {
+        let text = escape(&source[range.clone()]);
+        match class(*kind) {
+            Some(class) => out.push_str(&format!("<span class=\"{class}\">{text}</span>")),
+            None => out.push_str(&text),
+        }
+    }
+    out
+}
+
+fn class(kind: SpanKind) -> Option<&'static str> {
+    match kind {
+        SpanKind::Keyword => Some("keyword"),
+        SpanKind::String => Some("string"),
+        SpanKind::Number => Some("number"),
+        SpanKind::Comment => Some("comment"),
+        SpanKind::Operator => Some("operator"),
+        SpanKind::Unknown => Some("unknown"),
+        SpanKind::Identifier | SpanKind::Punctuation | SpanKind::Whitespace => None,
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            ch => out.push(ch),
+        }
+    }
+    out
+}
@@ -0,0 +1,21 @@
+use pretty_assertions::assert_eq;
+
+use crate::lex::Lexer;
+
+use super::*;
+
+#[test]
+fn spans_are_wrapped_by_their_kind_and_escaped() {
+    let source = "if (a < 1) print \"<b>\"; // & done\n";
+    let mut interner = Interner::default();
+    let spans = Lexer::new(source, &mut interner).with_trivia().scan().spans;
+
+    let expected = concat!(
+        r#"<span class="keyword">if</span> (a <span class="operator">&lt;</span> "#,
+        r#"<span class="number">1</span>) <span class="keyword">print</span> "#,
+        r#"<span class="string">&quot;&lt;b&gt;&quot;</span>; "#,
+        r#"<span class="comment">// &amp; done</span>"#,
+        "\n",
+    );
+    assert_eq!(code(source, &spans), expected);
+}
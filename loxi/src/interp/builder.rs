@@ -0,0 +1,200 @@
+use std::cell::{Cell, RefCell};
+use std::io;
+use std::time::Duration;
+
+use rustc_hash::FxHashMap;
+
+use crate::dialect::Dialect;
+use crate::resolve::ResolveMap;
+use crate::util::Location;
+
+use super::cancel::CancelToken;
+use super::clock::{Clock, SystemClock};
+use super::env::DynamicEnv;
+use super::input::{self, LineSource};
+use super::interner::Interner;
+use super::limits::{Limits, Memory};
+use super::observer::Observer;
+use super::value::NumberStyle;
+use super::{stdlib, Capabilities, Interpreter, Sink};
+
+/// Default limit of nested calls. Each call uses the native stack, so the limit must be low enough
+/// for the stack of the thread running the interpreter.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1024;
+
+/// Collects the configuration of an `Interpreter`. Every option has a default, so only the ones
+/// that differ need to be set.
+pub struct InterpreterBuilder {
+    capabilities: Capabilities,
+    cancel: CancelToken,
+    stdout: Box<dyn Sink>,
+    stderr: Box<dyn Sink>,
+    stdin: Box<dyn LineSource>,
+    clock: Box<dyn Clock>,
+    seed: Option<u64>,
+    interner: Interner,
+    observer: Option<Box<dyn Observer>>,
+    max_call_depth: usize,
+    max_steps: Option<u64>,
+    timeout: Option<Duration>,
+    limits: Limits,
+    strict_division: bool,
+    dialect: Dialect,
+    number_style: NumberStyle,
+}
+
+impl InterpreterBuilder {
+    pub fn new() -> Self {
+        Self {
+            capabilities: Capabilities::default(),
+            cancel: CancelToken::new(),
+            stdout: Box::new(io::stdout()),
+            stderr: Box::new(io::stderr()),
+            stdin: Box::new(input::Stdin),
+            clock: Box::new(SystemClock::new()),
+            seed: None,
+            interner: Interner::new(),
+            observer: None,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            max_steps: None,
+            timeout: None,
+            limits: Limits::default(),
+            strict_division: false,
+            dialect: Dialect::default(),
+            number_style: NumberStyle::default(),
+        }
+    }
+
+    pub fn capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    pub fn cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    pub fn stdout(mut self, stdout: Box<dyn Sink>) -> Self {
+        self.stdout = stdout;
+        self
+    }
+
+    pub fn stderr(mut self, stderr: Box<dyn Sink>) -> Self {
+        self.stderr = stderr;
+        self
+    }
+
+    pub fn stdin(mut self, stdin: Box<dyn LineSource>) -> Self {
+        self.stdin = stdin;
+        self
+    }
+
+    pub fn clock(mut self, clock: Box<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Seed the generator of `random` instead of seeding it from the clock, e.g. for reproducible
+    /// runs.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Start with `interner` instead of a new one, e.g. one layered on top of a prelude.
+    pub(crate) fn interner(mut self, interner: Interner) -> Self {
+        self.interner = interner;
+        self
+    }
+
+    pub fn observer(mut self, observer: Box<dyn Observer>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Calls nested deeper than this fail with a stack overflow error.
+    pub fn max_call_depth(mut self, depth: usize) -> Self {
+        self.max_call_depth = depth;
+        self
+    }
+
+    /// Limit the number of statements each run can execute, see `RuntimeError::BudgetExceeded`.
+    pub fn max_steps(mut self, steps: Option<u64>) -> Self {
+        self.max_steps = steps;
+        self
+    }
+
+    /// Limit the time each run can take, measured by the clock, see `RuntimeError::Timeout`.
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Make dividing a number by zero fail with `RuntimeError::DivisionByZero` instead of giving
+    /// `inf` or `nan` as IEEE 754 does.
+    pub fn strict_division(mut self, strict: bool) -> Self {
+        self.strict_division = strict;
+        self
+    }
+
+    /// The extensions to the operators of the book's Lox that are enabled, e.g. whether `<`
+    /// between two strings compares them instead of failing.
+    pub fn dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// How the numbers that are not integers are printed.
+    pub fn number_style(mut self, number_style: NumberStyle) -> Self {
+        self.number_style = number_style;
+        self
+    }
+
+    pub fn build(self) -> Interpreter {
+        let mut interp = Interpreter {
+            dyn_env: DynamicEnv::new_with_global(),
+            interner: self.interner,
+            resolve_map: ResolveMap::default(),
+            stdout: RefCell::new(self.stdout),
+            stderr: RefCell::new(self.stderr),
+            stdin: RefCell::new(self.stdin),
+            clock: self.clock,
+            rng: Cell::new(self.seed),
+            methods: stdlib::Methods::default(),
+            host_classes: FxHashMap::default(),
+            observed: self.observer.is_some(),
+            observer: RefCell::new(self.observer),
+            tests: RefCell::default(),
+            #[cfg(feature = "regex")]
+            regexes: RefCell::default(),
+            scope_eval: Cell::new(false),
+            memory: Memory::new(self.limits),
+            call_site: Cell::new(Location::default()),
+            call_depth: Cell::new(0),
+            max_call_depth: self.max_call_depth,
+            steps: Cell::new(0),
+            max_steps: self.max_steps.unwrap_or(u64::MAX),
+            timeout: self.timeout,
+            deadline: Cell::new(f64::INFINITY),
+            strict_division: self.strict_division,
+            dialect: self.dialect,
+            number_style: self.number_style,
+            capabilities: self.capabilities,
+            cancel: self.cancel,
+        };
+        stdlib::install(&mut interp);
+        interp
+    }
+}
+
+impl Default for InterpreterBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
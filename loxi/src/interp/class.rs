@@ -1,11 +1,11 @@
 use std::borrow::Borrow;
-use std::cell::RefCell;
-use std::rc::Rc;
 
-use rustc_hash::FxHashMap;
+use indexmap::IndexMap;
+use rustc_hash::{FxBuildHasher, FxHashMap};
 
 use crate::lex::token::Special;
 use crate::parse::stmt::{Stmt, Unwind};
+use crate::util::shared::{Lock, Shared};
 use crate::util::Location;
 
 use super::env::DynamicEnv;
@@ -18,30 +18,32 @@ use super::RuntimeError;
 #[derive(Clone, Debug, PartialEq)]
 pub struct Class {
     pub name: Key,
-    pub constructor: Option<Rc<Function>>,
-    pub methods: FxHashMap<Key, Rc<Function>>,
+    pub constructor: Option<Shared<Function>>,
+    pub methods: FxHashMap<Key, Shared<Function>>,
     pub loc: Location,
-    counter: RefCell<usize>,
+    counter: Lock<usize>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Instance {
     pub id: usize,
-    pub class: Rc<Class>,
-    pub fields: RefCell<FxHashMap<Key, Value>>,
+    pub class: Shared<Class>,
+
+    /// In the order they are first set, which is the order they are converted in.
+    pub fields: Lock<IndexMap<Key, Value, FxBuildHasher>>,
 }
 
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
 pub enum Property {
     Field(Value),
-    Method(Rc<Function>),
+    Method(Shared<Function>),
 }
 
 impl Class {
     pub fn new(
         name: Key,
-        constructor: Option<Rc<Function>>,
-        methods: FxHashMap<Key, Rc<Function>>,
+        constructor: Option<Shared<Function>>,
+        methods: FxHashMap<Key, Shared<Function>>,
         loc: Location,
     ) -> Self {
         Self {
@@ -54,13 +56,13 @@ impl Class {
     }
 
     pub fn construct<F>(
-        self: &Rc<Self>,
+        self: &Shared<Self>,
         args: Box<[Value]>,
         interner: &Interner,
         env: &DynamicEnv,
         loc: Location,
         exec: F,
-    ) -> Result<Rc<Instance>, RuntimeError>
+    ) -> Result<Shared<Instance>, RuntimeError>
     where
         F: FnMut(&Stmt) -> Result<Unwind, RuntimeError>,
     {
@@ -71,7 +73,7 @@ impl Class {
                 0 => Ok(instance),
                 len => Err(FunctionError::MismatchedArgument {
                     loc,
-                    expect: 0,
+                    expect: 0.into(),
                     got: len,
                 }
                 .into()),
@@ -79,12 +81,12 @@ impl Class {
         }
 
         let ctor = self.constructor.as_ref().unwrap().as_user_defined();
-        let func = ctor.bind(Rc::clone(&instance), interner);
+        let func = ctor.bind(Shared::clone(&instance), interner);
         let value = func.call(args, interner, env, exec)?;
 
         match value {
             Value::Nil => (),
-            Value::Instance(ret) => match Rc::ptr_eq(&instance, &ret) {
+            Value::Instance(ret) => match Shared::ptr_eq(&instance, &ret) {
                 true => (),
                 false => unreachable!("constructor can't return a different instance!"),
             },
@@ -102,17 +104,17 @@ impl PartialOrd for Class {
 }
 
 impl Instance {
-    fn new(class: &Rc<Class>) -> Rc<Self> {
+    fn new(class: &Shared<Class>) -> Shared<Self> {
         let id = *class.counter.borrow();
         *class.counter.borrow_mut() += 1;
-        Rc::new(Instance {
+        Shared::new(Instance {
             id,
-            class: Rc::clone(class),
-            fields: FxHashMap::default().into(),
+            class: Shared::clone(class),
+            fields: IndexMap::default().into(),
         })
     }
 
-    pub fn get(self: &Rc<Instance>, name: Key, interner: &Interner) -> Option<Property> {
+    pub fn get(self: &Shared<Instance>, name: Key, interner: &Interner) -> Option<Property> {
         if let Some(value) = self.fields.borrow().get(&name) {
             return Some(Property::Field(value.clone()));
         }
@@ -123,15 +125,15 @@ impl Instance {
         // If I were him, I will disallow calling init or define a property named init.
         if name == interner.special(Special::Init) && self.class.constructor.is_some() {
             let ctor = self.class.constructor.as_ref().unwrap().as_user_defined();
-            let bound = Function::UserDefined(ctor.bind(Rc::clone(self), interner));
-            return Some(Property::Method(Rc::new(bound)));
+            let bound = Function::UserDefined(ctor.bind(Shared::clone(self), interner));
+            return Some(Property::Method(Shared::new(bound)));
         }
 
         if let Some(func) = self.class.methods.get(&name) {
             match func.as_ref() {
                 Function::UserDefined(func) => {
-                    let bound = Function::UserDefined(func.bind(Rc::clone(self), interner));
-                    return Some(Property::Method(Rc::new(bound)));
+                    let bound = Function::UserDefined(func.bind(Shared::clone(self), interner));
+                    return Some(Property::Method(Shared::new(bound)));
                 }
                 Function::Native(_) => panic!("native function should not be inside an instance!"),
             }
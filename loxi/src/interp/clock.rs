@@ -0,0 +1,44 @@
+//! Time sources used by natives that read the time, e.g. `Time.now()`.
+
+use std::sync::OnceLock;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::util::shared::ThreadSafe;
+
+/// A source of time. The interpreter reads the time only through this trait so tests can use a
+/// fixed clock.
+pub trait Clock: ThreadSafe {
+    /// Milliseconds since the unix epoch.
+    fn now(&self) -> f64;
+
+    /// Milliseconds since an arbitrary point in time that never goes backward.
+    fn monotonic(&self) -> f64;
+}
+
+/// The clock of the operating system. The clock is only read when a native asks for the time, so
+/// creating one is fine on targets without a system clock, e.g. `wasm32-unknown-unknown`, as long
+/// as it is replaced before running a program that reads the time.
+pub struct SystemClock {
+    start: OnceLock<Instant>,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            start: OnceLock::new(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> f64 {
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system time should be after the unix epoch");
+        since_epoch.as_secs_f64() * 1000.0
+    }
+
+    fn monotonic(&self) -> f64 {
+        self.start.get_or_init(Instant::now).elapsed().as_secs_f64() * 1000.0
+    }
+}
@@ -0,0 +1,245 @@
+use std::vec;
+
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+
+use crate::interp::interner::Interner;
+use crate::interp::value::{float_to_int, Value};
+
+use super::ConvertError;
+
+type Result<T> = std::result::Result<T, ConvertError>;
+
+/// Deserializer that reads a `Value`. The interner is needed for string literals, which can be
+/// nested inside lists, maps, and instances even if the value itself is resolved.
+pub struct Deserializer<'a> {
+    value: Value,
+    interner: &'a Interner,
+}
+
+impl<'a> Deserializer<'a> {
+    pub fn new(value: Value, interner: &'a Interner) -> Self {
+        Self { value, interner }
+    }
+
+    fn string(&self) -> Option<String> {
+        self.value.as_str(self.interner).map(str::to_owned)
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'_> {
+    type Error = ConvertError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        // integral numbers are visited as integers so they can be read into integer types
+        let interner = self.interner;
+        match self.value {
+            Value::Nil => visitor.visit_unit(),
+            Value::Bool(b) => visitor.visit_bool(b),
+            Value::Int(int) => visitor.visit_i64(int),
+            Value::Number(num) => match float_to_int(num) {
+                Some(int) => visitor.visit_i64(int),
+                None => visitor.visit_f64(num),
+            },
+            Value::String(str) => visitor.visit_str(&str),
+            Value::StringLiteral(key) => visitor.visit_str(interner.resolve(key)),
+            Value::List(list) => {
+                let values = list.borrow().clone();
+                visitor.visit_seq(SeqAccess {
+                    values: values.into_iter(),
+                    interner,
+                })
+            }
+            Value::Map(map) => {
+                let entries = map
+                    .borrow()
+                    .iter()
+                    .map(|(key, value)| (key.to_value(), value.clone()))
+                    .collect::<Vec<_>>();
+                visitor.visit_map(MapAccess::new(entries, interner))
+            }
+            Value::Instance(instance) => {
+                let entries = instance
+                    .fields
+                    .borrow()
+                    .iter()
+                    .map(|(key, value)| {
+                        let key = Value::string(interner.resolve(*key).to_owned());
+                        (key, value.clone())
+                    })
+                    .collect::<Vec<_>>();
+                visitor.visit_map(MapAccess::new(entries, interner))
+            }
+            value => Err(ConvertError(format!(
+                "Can't convert a value of type '{}'",
+                value.name()
+            ))),
+        }
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value.as_number() {
+            Some(num) => visitor.visit_f64(num),
+            None => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            Value::Nil => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        if let Some(variant) = self.string() {
+            return visitor.visit_enum(variant.into_deserializer());
+        }
+
+        let entry = match &self.value {
+            Value::Map(map) if map.borrow().len() == 1 => {
+                let map = map.borrow();
+                let (key, value) = map.iter().next().expect("map should have an entry");
+                Some((key.to_value(), value.clone()))
+            }
+            _ => None,
+        };
+        match entry {
+            Some((Value::String(variant), value)) => visitor.visit_enum(EnumAccess {
+                variant: variant.to_string(),
+                value,
+                interner: self.interner,
+            }),
+            _ => Err(ConvertError(format!(
+                "Expect a string or a map with a single string key for an enum, got '{}'",
+                self.value.name()
+            ))),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 char str string bytes byte_buf unit
+        unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct SeqAccess<'a> {
+    values: vec::IntoIter<Value>,
+    interner: &'a Interner,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess<'_> {
+    type Error = ConvertError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        self.values
+            .next()
+            .map(|value| seed.deserialize(Deserializer::new(value, self.interner)))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.values.len())
+    }
+}
+
+struct MapAccess<'a> {
+    entries: vec::IntoIter<(Value, Value)>,
+    value: Option<Value>,
+    interner: &'a Interner,
+}
+
+impl<'a> MapAccess<'a> {
+    fn new(entries: Vec<(Value, Value)>, interner: &'a Interner) -> Self {
+        Self {
+            entries: entries.into_iter(),
+            value: None,
+            interner,
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'_> {
+    type Error = ConvertError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(Deserializer::new(key, self.interner))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let value = self
+            .value
+            .take()
+            .expect("next_key_seed should be called before next_value_seed");
+        seed.deserialize(Deserializer::new(value, self.interner))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+/// A tuple, newtype, or struct variant, read from a map with the variant name as the only key.
+struct EnumAccess<'a> {
+    variant: String,
+    value: Value,
+    interner: &'a Interner,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for EnumAccess<'a> {
+    type Error = ConvertError;
+    type Variant = Deserializer<'a>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self::Variant)> {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+        Ok((variant, Deserializer::new(self.value, self.interner)))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Deserializer<'_> {
+    type Error = ConvertError;
+
+    fn unit_variant(self) -> Result<()> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
@@ -0,0 +1,48 @@
+//! Conversion between `Value` and Rust data structures using serde, so the host can exchange data
+//! with programs without building values by hand.
+//!
+//! Rust data is converted to the value a program would use for it: structs and maps become Lox
+//! maps, sequences and tuples become lists, `None` and unit become `nil`, and every integer and
+//! float becomes a number. Enums use the same representation as `serde_json`: a unit variant is
+//! its name as a string, other variants are a map with the variant name as the only key.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use thiserror::Error;
+
+use super::interner::Interner;
+use super::value::Value;
+
+mod de;
+mod ser;
+
+#[derive(Debug, Error)]
+#[error("ConvertError: {0}")]
+pub struct ConvertError(String);
+
+impl serde::ser::Error for ConvertError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        ConvertError(msg.to_string())
+    }
+}
+
+impl serde::de::Error for ConvertError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        ConvertError(msg.to_string())
+    }
+}
+
+/// Convert `value` into a `Value`. Numbers outside of the range `f64` can represent exactly lose
+/// precision, and map keys must be valid `Map` keys.
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value, ConvertError> {
+    value.serialize(ser::Serializer)
+}
+
+/// Convert `value` into `T`. Instances can be converted the same way as maps, with their fields as
+/// the keys. Classes, functions, and namespaces can't be converted.
+pub fn from_value<T: DeserializeOwned>(
+    value: Value,
+    interner: &Interner,
+) -> Result<T, ConvertError> {
+    T::deserialize(de::Deserializer::new(value, interner))
+}
@@ -0,0 +1,271 @@
+//! Records which statements of a program ran, and how often, for coverage reports.
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Write};
+use std::sync::{Arc, Mutex};
+
+use crate::lex::{Lexer, ScanResult};
+use crate::parse::stmt::Stmt;
+use crate::parse::Parser;
+use crate::util::Location;
+use crate::LoxError;
+
+use super::interner::Interner;
+use super::observer::{Observer, Scope};
+
+/// Counts how many times each statement runs. Clones share the counts, so one can be given to
+/// `Lox::set_observer` while another is kept to build the report after the program finishes.
+#[derive(Clone, Debug, Default)]
+pub struct Coverage {
+    hits: Arc<Mutex<BTreeMap<Location, usize>>>,
+}
+
+/// The counts of `Coverage` matched with the statements of the program that ran.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CoverageReport {
+    /// Every statement, except blocks, along with the number of times it ran.
+    pub statements: Vec<(Location, usize)>,
+
+    /// Every `if` statement whose branches can be told apart.
+    pub branches: Vec<Branch>,
+}
+
+/// The number of times each side of an `if` statement was taken.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Branch {
+    pub loc: Location,
+    pub then: usize,
+    pub otherwise: usize,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Match the counts with the statements of `source`, which should be the program that ran.
+    pub fn report(&self, source: &str) -> Result<CoverageReport, LoxError> {
+        let mut interner = Interner::new();
+        let ScanResult { tokens, errors, .. } = Lexer::new(source, &mut interner).scan();
+        if !errors.is_empty() {
+            return Err(LoxError::LexError {
+                source_name: None,
+                errors,
+            });
+        }
+
+        let program = Parser::new()
+            .parse(tokens)
+            .map_err(|errors| LoxError::ParseError {
+                source_name: None,
+                errors,
+            })?;
+
+        let hits = self.hits.lock().expect("lock should not be poisoned");
+        let mut report = CoverageReport {
+            statements: Vec::new(),
+            branches: Vec::new(),
+        };
+        report.collect(&hits, &program.statements);
+        report.statements.sort();
+        report.branches.sort_by_key(|branch| branch.loc);
+
+        Ok(report)
+    }
+}
+
+impl Observer for Coverage {
+    fn on_statement(&mut self, loc: Location, _scope: &Scope<'_>) {
+        let mut hits = self.hits.lock().expect("lock should not be poisoned");
+        *hits.entry(loc).or_default() += 1;
+    }
+}
+
+impl CoverageReport {
+    /// The number of statements that ran at least once.
+    pub fn covered(&self) -> usize {
+        self.statements.iter().filter(|(_, hits)| *hits > 0).count()
+    }
+
+    /// The report in the lcov tracefile format, e.g. for `genhtml` or an editor extension.
+    pub fn lcov(&self, source_name: &str) -> String {
+        let lines = self.lines();
+        let mut out = String::new();
+        let mut write = || -> std::fmt::Result {
+            writeln!(out, "TN:")?;
+            writeln!(out, "SF:{source_name}")?;
+
+            for (line, hits) in lines.iter() {
+                writeln!(out, "DA:{line},{hits}")?;
+            }
+            let hit = lines.values().filter(|hits| **hits > 0).count();
+            writeln!(out, "LF:{}", lines.len())?;
+            writeln!(out, "LH:{hit}")?;
+
+            let mut hit = 0;
+            for (block, branch) in self.branches.iter().enumerate() {
+                let line = branch.loc.line;
+                for (index, taken) in [branch.then, branch.otherwise].into_iter().enumerate() {
+                    // a branch of an `if` that never ran is marked as not executed at all
+                    match branch.then + branch.otherwise {
+                        0 => writeln!(out, "BRDA:{line},{block},{index},-")?,
+                        _ => writeln!(out, "BRDA:{line},{block},{index},{taken}")?,
+                    }
+                    hit += (taken > 0) as usize;
+                }
+            }
+            writeln!(out, "BRF:{}", self.branches.len() * 2)?;
+            writeln!(out, "BRH:{hit}")?;
+
+            writeln!(out, "end_of_record")
+        };
+        write().expect("writing to a string never fails");
+        out
+    }
+
+    /// The source with the number of times each line ran before it, like `gcov`. Lines without a
+    /// statement are marked with `-`, and lines that never ran with `#####`.
+    pub fn annotate(&self, source: &str) -> String {
+        let lines = self.lines();
+        let mut out = String::new();
+        for (number, line) in source.lines().enumerate() {
+            let hits = match lines.get(&(number + 1)) {
+                None => "-".to_owned(),
+                Some(0) => "#####".to_owned(),
+                Some(hits) => hits.to_string(),
+            };
+            writeln!(out, "{hits:>9}:{:>5}: {line}", number + 1)
+                .expect("writing to a string never fails");
+        }
+        out
+    }
+
+    /// The number of times each line with a statement ran, merging the statements on the same line.
+    fn lines(&self) -> BTreeMap<usize, usize> {
+        let mut lines = BTreeMap::<usize, usize>::new();
+        for (loc, hits) in self.statements.iter() {
+            let line = lines.entry(loc.line).or_default();
+            *line = (*line).max(*hits);
+        }
+        lines
+    }
+
+    fn collect(&mut self, hits: &BTreeMap<Location, usize>, statements: &[Stmt]) {
+        let count = |stmt: &Stmt| first_loc(stmt).map(|loc| hits.get(&loc).copied().unwrap_or(0));
+
+        for stmt in statements {
+            if let Some(loc) = stmt.loc() {
+                self.statements
+                    .push((loc, hits.get(&loc).copied().unwrap_or(0)));
+            }
+
+            match stmt {
+                Stmt::Block { statements, .. } => self.collect(hits, statements),
+                Stmt::If {
+                    loc,
+                    then,
+                    otherwise,
+                    ..
+                } => {
+                    let total = hits.get(loc).copied().unwrap_or(0);
+                    let then_count = count(then);
+                    let otherwise_count = otherwise.as_deref().and_then(count);
+
+                    // without its own statement, a side was taken whenever the other one wasn't
+                    let branch = match (then_count, otherwise_count) {
+                        (None, None) => None,
+                        (Some(then), None) => Some((then, total.saturating_sub(then))),
+                        (None, Some(otherwise)) => {
+                            Some((total.saturating_sub(otherwise), otherwise))
+                        }
+                        (Some(then), Some(otherwise)) => Some((then, otherwise)),
+                    };
+                    if let Some((then, otherwise)) = branch {
+                        self.branches.push(Branch {
+                            loc: *loc,
+                            then,
+                            otherwise,
+                        });
+                    }
+
+                    self.collect(hits, std::slice::from_ref(then));
+                    if let Some(otherwise) = otherwise {
+                        self.collect(hits, std::slice::from_ref(otherwise));
+                    }
+                }
+                Stmt::While { body, .. } | Stmt::ForIn { body, .. } => {
+                    self.collect(hits, std::slice::from_ref(body))
+                }
+                Stmt::Switch {
+                    cases, otherwise, ..
+                } => {
+                    for (_, body) in cases.iter() {
+                        self.collect(hits, std::slice::from_ref(body));
+                    }
+                    if let Some(otherwise) = otherwise {
+                        self.collect(hits, std::slice::from_ref(otherwise));
+                    }
+                }
+                Stmt::Function { func } => self.collect(hits, &func.body),
+                Stmt::Class { methods, .. } => {
+                    for method in methods.iter() {
+                        self.collect(hits, &method.body);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+/// A summary with the lines of the statements that never ran.
+impl Display for CoverageReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total = self.statements.len();
+        let covered = self.covered();
+        let percent = match total {
+            0 => 100.0,
+            _ => covered as f64 / total as f64 * 100.0,
+        };
+        writeln!(f, "Statements: {covered}/{total} ({percent:.1}%)")?;
+
+        let sides = self.branches.len() * 2;
+        let taken = self
+            .branches
+            .iter()
+            .map(|branch| (branch.then > 0) as usize + (branch.otherwise > 0) as usize)
+            .sum::<usize>();
+        writeln!(f, "Branches: {taken}/{sides}")?;
+
+        let mut missed = self
+            .statements
+            .iter()
+            .filter(|(_, hits)| *hits == 0)
+            .map(|(loc, _)| loc.line)
+            .collect::<Vec<_>>();
+        missed.dedup();
+        if !missed.is_empty() {
+            let missed = missed.iter().map(usize::to_string).collect::<Vec<_>>();
+            writeln!(f, "Not run: lines {}", missed.join(", "))?;
+        }
+
+        for branch in self.branches.iter() {
+            let side = match (branch.then, branch.otherwise) {
+                (0, 0) => continue,
+                (0, _) => "then",
+                (_, 0) => "else",
+                _ => continue,
+            };
+            writeln!(f, "Never taken: {side} branch of the if at {}", branch.loc)?;
+        }
+        Ok(())
+    }
+}
+
+/// The location of the first statement that runs whenever `stmt` runs, looking inside blocks.
+fn first_loc(stmt: &Stmt) -> Option<Location> {
+    match stmt {
+        Stmt::Block { statements, .. } => statements.first().and_then(first_loc),
+        stmt => stmt.loc(),
+    }
+}
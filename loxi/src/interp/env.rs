@@ -1,21 +1,30 @@
-use std::cell::RefCell;
-use std::rc::Rc;
-
 use rustc_hash::FxHashMap;
 
+use crate::util::shared::{Lock, Shared};
+
+use crate::resolve::Slot;
+
 use super::interner::Key;
 use super::value::Value;
 
+/// The variables of a scope. The global scope, the one without an enclosing scope, looks them up
+/// by name since they can be declared at any time, e.g. by the REPL or the host. A local scope
+/// stores them in the order they are declared, which is the slot the resolver gives to the
+/// expressions that use them, along with their names for the debugger.
+///
+/// Scopes are shared and link to the scope enclosing them, so a closure keeps the scopes it's
+/// declared in alive after the block or call that made them is done.
 #[derive(Debug)]
 pub struct Env {
-    pub values: RefCell<FxHashMap<Key, Value>>,
-    pub parent: Option<Rc<Env>>,
+    pub values: Lock<FxHashMap<Key, Value>>,
+    pub locals: Lock<Vec<(Key, Value)>>,
+    pub enclosing: Option<Shared<Env>>,
 }
 
 #[derive(Debug)]
 pub struct DynamicEnv {
-    global: Rc<Env>,
-    current: RefCell<Rc<Env>>,
+    global: Shared<Env>,
+    current: Lock<Shared<Env>>,
 }
 
 #[must_use]
@@ -26,33 +35,49 @@ pub struct EnvGuard<'a> {
 #[must_use]
 pub struct EnvBindGuard<'a> {
     env: &'a DynamicEnv,
-    previous: Rc<Env>,
+    previous: Shared<Env>,
 }
 
 impl Env {
     pub fn new() -> Self {
         Self {
-            values: RefCell::new(FxHashMap::default()),
-            parent: None,
+            values: Lock::new(FxHashMap::default()),
+            locals: Lock::new(Vec::new()),
+            enclosing: None,
         }
     }
 
-    pub fn new_with_parent(parent: Rc<Env>) -> Self {
-        Self {
-            values: RefCell::new(FxHashMap::default()),
-            parent: Some(parent),
-        }
+    /// A new local scope enclosed by this one.
+    pub fn child(self: &Shared<Self>) -> Shared<Env> {
+        Shared::new(Self {
+            values: Lock::new(FxHashMap::default()),
+            locals: Lock::new(Vec::new()),
+            enclosing: Some(Shared::clone(self)),
+        })
     }
 
+    /// Declare a variable. In a local scope it takes the next slot, so the variables of a scope
+    /// must be defined in the same order as the resolver declares them.
     pub fn define(&self, key: Key, value: Value) {
-        self.values.borrow_mut().insert(key, value);
+        match self.enclosing {
+            None => _ = self.values.borrow_mut().insert(key, value),
+            Some(_) => self.locals.borrow_mut().push((key, value)),
+        }
     }
 
+    /// Look `key` up by name in this scope and then in the ones enclosing it, for the variables
+    /// that are not resolved.
     pub fn get(&self, key: Key) -> Option<Value> {
+        let locals = self.locals.borrow();
+        if let Some((_, value)) = locals.iter().rev().find(|(name, _)| *name == key) {
+            return Some(value.clone());
+        }
+        drop(locals);
+
         match self.values.borrow().get(&key) {
             Some(value) => Some(value.clone()),
-            _ => match &self.parent {
-                Some(parent) => parent.get(key),
+            _ => match &self.enclosing {
+                Some(enclosing) => enclosing.get(key),
                 _ => None,
             },
         }
@@ -62,42 +87,91 @@ impl Env {
     where
         F: FnOnce(&mut Value) -> R,
     {
+        let mut locals = self.locals.borrow_mut();
+        if let Some((_, value)) = locals.iter_mut().rev().find(|(name, _)| *name == key) {
+            return Some(f(value));
+        }
+        drop(locals);
+
         match self.values.borrow_mut().get_mut(&key) {
             Some(value) => Some(f(value)),
-            _ => match &self.parent {
-                Some(parent) => parent.modify(key, f),
+            _ => match &self.enclosing {
+                Some(enclosing) => enclosing.modify(key, f),
                 _ => None,
             },
         }
     }
+
+    /// The variables of this scope, locals in the order they are declared.
+    pub fn variables(&self) -> Vec<(Key, Value)> {
+        let globals = self.values.borrow();
+        let globals = globals.iter().map(|(key, value)| (*key, value.clone()));
+        globals
+            .chain(self.locals.borrow().iter().cloned())
+            .collect()
+    }
+
+    /// The value of the local variable in `slot`, counted from this scope.
+    pub fn get_at(self: &Shared<Self>, slot: Slot) -> Option<Value> {
+        let env = self.ancestor(slot.distance)?;
+        let locals = env.locals.borrow();
+        locals.get(slot.index).map(|(_, value)| value.clone())
+    }
+
+    pub fn modify_at<F, R>(self: &Shared<Self>, slot: Slot, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut Value) -> R,
+    {
+        let env = self.ancestor(slot.distance)?;
+        let mut locals = env.locals.borrow_mut();
+        locals.get_mut(slot.index).map(|(_, value)| f(value))
+    }
+
+    /// Set the local variable in `slot`, returning `false` if there's no such variable.
+    pub fn assign_at(self: &Shared<Self>, slot: Slot, value: Value) -> bool {
+        self.modify_at(slot, |current| *current = value).is_some()
+    }
+
+    /// The scope `distance` scopes out from this one.
+    fn ancestor(self: &Shared<Self>, mut distance: usize) -> Option<Shared<Env>> {
+        let mut current = Shared::clone(self);
+        while distance > 0 {
+            current = Shared::clone(current.enclosing.as_ref()?);
+            distance -= 1;
+        }
+        Some(current)
+    }
 }
 
 impl DynamicEnv {
     pub fn new_with_global() -> Self {
-        let env = Rc::new(Env::new());
+        let env = Shared::new(Env::new());
         Self {
-            current: Rc::clone(&env).into(),
+            current: Shared::clone(&env).into(),
             global: env,
         }
     }
 
-    pub fn current(&self) -> Rc<Env> {
-        Rc::clone(&self.current.borrow())
+    pub fn global(&self) -> &Shared<Env> {
+        &self.global
+    }
+
+    pub fn current(&self) -> Shared<Env> {
+        Shared::clone(&self.current.borrow())
     }
 
     pub fn create_scope(&self) -> EnvGuard<'_> {
-        let parent = Rc::clone(&self.current.borrow());
-        let env = Rc::new(Env::new_with_parent(parent));
+        let env = self.current.borrow().child();
         *self.current.borrow_mut() = env;
         EnvGuard { env: self }
     }
 
     fn destroy_scope(&self) {
-        let parent = Rc::clone(self.current.borrow().parent.as_ref().unwrap());
-        *self.current.borrow_mut() = parent;
+        let enclosing = Shared::clone(self.current.borrow().enclosing.as_ref().unwrap());
+        *self.current.borrow_mut() = enclosing;
     }
 
-    pub fn bind_scope(&self, env: Rc<Env>) -> EnvBindGuard<'_> {
+    pub fn bind_scope(&self, env: Shared<Env>) -> EnvBindGuard<'_> {
         let previous = std::mem::replace(&mut *self.current.borrow_mut(), env);
         EnvBindGuard {
             env: self,
@@ -105,7 +179,7 @@ impl DynamicEnv {
         }
     }
 
-    fn rebind_scope(&self, env: Rc<Env>) {
+    fn rebind_scope(&self, env: Shared<Env>) {
         *self.current.borrow_mut() = env;
     }
 
@@ -113,17 +187,17 @@ impl DynamicEnv {
         self.current.borrow_mut().define(key, value);
     }
 
+    pub fn define_global(&self, key: Key, value: Value) {
+        self.global.define(key, value);
+    }
+
     pub fn get_global(&self, key: Key) -> Option<Value> {
         self.global.get(key)
     }
 
-    pub fn get_at(&self, key: Key, mut distance: usize) -> Option<Value> {
-        let mut current = Rc::clone(&self.current.borrow());
-        while distance > 0 {
-            current = Rc::clone(current.parent.as_ref()?);
-            distance -= 1;
-        }
-        current.get(key)
+    /// The value of a local variable in the slot given by the resolver.
+    pub fn get_at(&self, slot: Slot) -> Option<Value> {
+        self.current().get_at(slot)
     }
 
     pub fn modify_global<F, R>(&self, key: Key, f: F) -> Option<R>
@@ -133,16 +207,11 @@ impl DynamicEnv {
         self.global.modify(key, f)
     }
 
-    pub fn modify_at<F, R>(&self, key: Key, mut distance: usize, f: F) -> Option<R>
+    pub fn modify_at<F, R>(&self, slot: Slot, f: F) -> Option<R>
     where
         F: FnOnce(&mut Value) -> R,
     {
-        let mut current = Rc::clone(&self.current.borrow());
-        while distance > 0 {
-            current = Rc::clone(current.parent.as_ref()?);
-            distance -= 1;
-        }
-        current.modify(key, f)
+        self.current().modify_at(slot, f)
     }
 }
 
@@ -154,6 +223,6 @@ impl Drop for EnvGuard<'_> {
 
 impl Drop for EnvBindGuard<'_> {
     fn drop(&mut self) {
-        self.env.rebind_scope(Rc::clone(&self.previous));
+        self.env.rebind_scope(Shared::clone(&self.previous));
     }
 }
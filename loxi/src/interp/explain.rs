@@ -0,0 +1,88 @@
+//! Shows how a program is evaluated one operation at a time, for learning the evaluation order.
+
+use std::io::Write;
+
+use crate::util::Location;
+
+use super::observer::{Observer, Scope};
+use super::value::Value;
+use super::Sink;
+
+/// Writes the line of each statement as it's executed, followed by the operations it evaluates in
+/// order, each reduced to its result, e.g. `1 + 2 * 3` becomes `2 * 3 = 6` then `1 + 6 = 7`. Calls
+/// are shown with the operations inside of them indented. Failing to write is ignored like `Tracer`
+/// does.
+pub struct Explainer {
+    out: Box<dyn Sink>,
+    lines: Vec<String>,
+    depth: usize,
+}
+
+impl Explainer {
+    /// `source` is the program being run, to show the statements as written.
+    pub fn new(out: impl Sink + 'static, source: &str) -> Self {
+        Self {
+            out: Box::new(out),
+            lines: source.lines().map(str::to_owned).collect(),
+            depth: 0,
+        }
+    }
+
+    /// Write a line nested `level` times below the statements of the current call.
+    fn line(&mut self, level: usize, loc: Location, event: std::fmt::Arguments<'_>) {
+        let indent = (self.depth + level) * 2;
+        let _ = writeln!(self.out, "{:indent$}{loc} {event}", "");
+    }
+}
+
+impl Observer for Explainer {
+    fn on_statement(&mut self, loc: Location, _scope: &Scope<'_>) {
+        // the whole line, since the location of some statements is not their start, e.g. the name
+        // of a `var`
+        let line = self.lines.get(loc.line.saturating_sub(1));
+        let text = line.map_or("", |line| line.trim()).to_owned();
+        self.line(0, loc, format_args!("{text}"));
+    }
+
+    // a call happens in the middle of an operation, and the statements of the callee are nested
+    // below it
+    fn on_call(&mut self, name: &str, loc: Location) {
+        self.line(1, loc, format_args!("call {name}"));
+        self.depth += 2;
+    }
+
+    fn on_return(&mut self, name: &str, loc: Location) {
+        self.depth = self.depth.saturating_sub(2);
+        self.line(1, loc, format_args!("return {name}"));
+    }
+
+    fn on_operation(
+        &mut self,
+        loc: Location,
+        operator: &str,
+        operands: &[Value],
+        result: &Value,
+        scope: &Scope<'_>,
+    ) {
+        let show = |value: &Value| match value {
+            Value::String(_) | Value::StringLiteral(_) => format!("{:?}", scope.display(value)),
+            value => scope.display(value),
+        };
+        let result = show(result);
+        match operands {
+            [operand] if operator == "and" || operator == "or" => {
+                let lhs = show(operand);
+                self.line(1, loc, format_args!("{lhs} {operator} ... = {result}"));
+            }
+            [operand] => {
+                let operand = show(operand);
+                self.line(1, loc, format_args!("{operator}({operand}) = {result}"));
+            }
+            [lhs, rhs] => {
+                let (lhs, rhs) = (show(lhs), show(rhs));
+                self.line(1, loc, format_args!("{lhs} {operator} {rhs} = {result}"));
+            }
+            _ => (),
+        }
+    }
+}
@@ -1,17 +1,18 @@
-use std::fmt::Debug;
-use std::rc::Rc;
+use std::fmt::{Debug, Display};
 
 use thiserror::Error;
 
 use crate::lex::token::Keyword;
+use crate::message;
 use crate::parse::stmt::{Stmt, Unwind};
+use crate::util::shared::{Shared, ThreadSafe};
 use crate::util::Location;
 
 use super::class::Instance;
 use super::env::DynamicEnv;
 use super::interner::{Interner, Key};
-use super::RuntimeError;
 use super::{env::Env, value::Value};
+use super::{Interpreter, RuntimeError};
 
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
 pub enum Kind {
@@ -25,13 +26,39 @@ pub enum Function {
     UserDefined(UserDefined),
 }
 
-type NativeFn = fn(args: Box<[Value]>) -> Result<Value, RuntimeError>;
+/// Number of arguments a function accepts.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Arity {
+    Exact(usize),
+
+    /// Inclusive range, for native functions with optional trailing arguments.
+    Range(usize, usize),
+
+    /// For native functions with any number of trailing arguments.
+    AtLeast(usize),
+}
+
+pub type NativeFn = fn(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError>;
+
+/// Native function defined by the host, see `Lox::register_native`.
+pub trait HostFn: Fn(&[Value]) -> Result<Value, NativeError> + ThreadSafe {}
+
+impl<F> HostFn for F where F: Fn(&[Value]) -> Result<Value, NativeError> + ThreadSafe {}
 
-#[derive(Clone, Debug, PartialEq, PartialOrd)]
+#[derive(Clone)]
+pub enum NativeBody {
+    Builtin(NativeFn),
+    Host(Shared<dyn HostFn>),
+}
+
+#[derive(Clone, Debug)]
 pub struct Native {
     pub name: Key,
-    pub params: Box<[Key]>,
-    pub body: NativeFn,
+    pub arity: Arity,
+    pub body: NativeBody,
+
+    /// The value a native method is bound to. It's passed as the first argument to `body`.
+    pub receiver: Option<Value>,
 }
 
 #[derive(Clone, Debug)]
@@ -40,18 +67,119 @@ pub struct UserDefined {
     pub params: Box<[Key]>,
     pub body: Box<[Stmt]>,
     pub loc: Location,
-    pub capture: Rc<Env>,
+    pub capture: Shared<Env>,
     pub kind: Kind,
 }
 
 #[derive(Debug, Error)]
 pub enum FunctionError {
-    #[error("{loc} RuntimeError: Mismatched number of arguments. Expected {expect} argument(s) got {got} instead")]
     MismatchedArgument {
         loc: Location,
+        expect: Arity,
+        got: usize,
+    },
+}
+
+/// Error raised from inside a native function. Native functions don't know where they are called
+/// from, so the location is attached by `Native::call` when it's converted into `RuntimeError`.
+#[derive(Debug, Error)]
+pub enum NativeError {
+    InvalidArgument {
+        expect: &'static str,
+        got: &'static str,
+    },
+    IndexOutOfRange {
+        index: usize,
+        len: usize,
+    },
+    InvalidRange {
+        start: usize,
+        end: usize,
+    },
+    EmptyList,
+    InvalidFormat {
+        index: usize,
+    },
+    FormatArguments {
         expect: usize,
         got: usize,
     },
+    NotANumber(String),
+    InvalidJson {
+        index: usize,
+        expect: &'static str,
+    },
+    JsonDepth(usize),
+    NotJson(&'static str),
+    InvalidKey(#[from] super::map::InvalidKey),
+
+    #[cfg(feature = "regex")]
+    Regex(#[from] regex::Error),
+
+    Io(#[from] std::io::Error),
+    Limit(#[from] super::limits::LimitError),
+
+    /// Error with a custom message, for native functions defined by the host.
+    Custom(String),
+
+    /// Error that comes from evaluating Lox code inside a native function, e.g. a callback. It
+    /// already has its own location so it's passed through as is.
+    #[error(transparent)]
+    Runtime(Box<RuntimeError>),
+}
+
+impl Display for FunctionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} RuntimeError: {}", self.loc(), self.message())
+    }
+}
+
+impl Display for NativeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NativeError::InvalidArgument { expect, got } => message::write(
+                f,
+                "native.invalid-argument",
+                &[("expect", expect), ("got", got)],
+            ),
+            NativeError::IndexOutOfRange { index, len } => message::write(
+                f,
+                "native.index-out-of-range",
+                &[("index", index), ("len", len)],
+            ),
+            NativeError::InvalidRange { start, end } => {
+                message::write(f, "native.invalid-range", &[("start", start), ("end", end)])
+            }
+            NativeError::EmptyList => message::write(f, "native.empty-list", &[]),
+            NativeError::InvalidFormat { index } => {
+                message::write(f, "native.invalid-format", &[("index", index)])
+            }
+            NativeError::FormatArguments { expect, got } => message::write(
+                f,
+                "native.format-arguments",
+                &[("expect", expect), ("got", got)],
+            ),
+            NativeError::NotANumber(text) => {
+                message::write(f, "native.not-a-number", &[("text", text)])
+            }
+            NativeError::InvalidJson { index, expect } => message::write(
+                f,
+                "native.invalid-json",
+                &[("index", index), ("expect", expect)],
+            ),
+            NativeError::JsonDepth(limit) => {
+                message::write(f, "native.json-depth", &[("limit", limit)])
+            }
+            NativeError::NotJson(got) => message::write(f, "native.not-json", &[("got", got)]),
+            NativeError::InvalidKey(err) => write!(f, "{err}"),
+            #[cfg(feature = "regex")]
+            NativeError::Regex(err) => write!(f, "{err}"),
+            NativeError::Io(err) => write!(f, "{err}"),
+            NativeError::Limit(err) => write!(f, "{err}"),
+            NativeError::Custom(message) => f.write_str(message),
+            NativeError::Runtime(err) => write!(f, "{err}"),
+        }
+    }
 }
 
 impl Function {
@@ -66,26 +194,116 @@ impl Function {
     }
 }
 
+impl Arity {
+    pub fn accepts(&self, count: usize) -> bool {
+        match *self {
+            Arity::Exact(n) => count == n,
+            Arity::Range(min, max) => (min..=max).contains(&count),
+            Arity::AtLeast(min) => count >= min,
+        }
+    }
+}
+
+impl From<usize> for Arity {
+    fn from(value: usize) -> Self {
+        Arity::Exact(value)
+    }
+}
+
+impl Display for Arity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Arity::Exact(n) => write!(f, "{n}"),
+            Arity::Range(min, max) => write!(f, "{min} to {max}"),
+            Arity::AtLeast(min) => write!(f, "at least {min}"),
+        }
+    }
+}
+
 impl Native {
-    pub fn new(name: Key, params: Box<[Key]>, body: NativeFn) -> Self {
-        Self { name, params, body }
+    pub fn new(name: Key, arity: impl Into<Arity>, body: NativeFn) -> Self {
+        Self {
+            name,
+            arity: arity.into(),
+            body: NativeBody::Builtin(body),
+            receiver: None,
+        }
     }
 
-    pub fn arity(&self) -> usize {
-        self.params.len()
+    pub fn host(name: Key, arity: impl Into<Arity>, body: Shared<dyn HostFn>) -> Self {
+        Self {
+            name,
+            arity: arity.into(),
+            body: NativeBody::Host(body),
+            receiver: None,
+        }
     }
 
-    pub fn call(&self, args: Box<[Value]>) -> Result<Value, RuntimeError> {
-        if args.len() != self.arity() {
+    /// Create a copy of self that is bound to `receiver`, making it a method of that value.
+    pub fn bind(&self, receiver: Value) -> Native {
+        Native {
+            receiver: Some(receiver),
+            ..self.clone()
+        }
+    }
+
+    pub fn arity(&self) -> Arity {
+        self.arity
+    }
+
+    /// Call the native function. The call site location is recorded in the interpreter for the
+    /// duration of the call so the native can pass it along when it calls back into Lox code.
+    pub fn call(
+        &self,
+        interp: &Interpreter,
+        args: Box<[Value]>,
+        loc: Location,
+    ) -> Result<Value, RuntimeError> {
+        if !self.arity.accepts(args.len()) {
             return Err(FunctionError::MismatchedArgument {
-                loc: Location::new(0, 0),
+                loc,
                 expect: self.arity(),
                 got: args.len(),
             }
             .into());
         }
 
-        (self.body)(args)
+        let args = match &self.receiver {
+            None => args,
+            Some(receiver) => std::iter::once(receiver.clone())
+                .chain(args.into_vec())
+                .collect(),
+        };
+
+        let prev_site = interp.call_site.replace(loc);
+        let result = match &self.body {
+            NativeBody::Builtin(body) => body(interp, &args),
+            NativeBody::Host(body) => {
+                // the host has no access to the interner, so string literals are passed as strings
+                let args = args
+                    .into_vec()
+                    .into_iter()
+                    .map(|arg| arg.resolved(&interp.interner))
+                    .collect::<Vec<_>>();
+                body(&args)
+            }
+        };
+        interp.call_site.set(prev_site);
+
+        result.map_err(|err| match err {
+            NativeError::Runtime(err) => *err,
+            NativeError::Limit(err) => RuntimeError::LimitExceeded(loc, err),
+            err => {
+                let name = interp.interner.resolve(self.name).to_owned();
+                RuntimeError::NativeError(loc, name, err)
+            }
+        })
+    }
+}
+
+impl From<RuntimeError> for NativeError {
+    fn from(err: RuntimeError) -> Self {
+        NativeError::Runtime(Box::new(err))
     }
 }
 
@@ -95,6 +313,16 @@ impl FunctionError {
             FunctionError::MismatchedArgument { loc, .. } => *loc,
         }
     }
+
+    /// The message without the location and the `RuntimeError` label.
+    pub fn message(&self) -> String {
+        match self {
+            FunctionError::MismatchedArgument { expect, got, .. } => {
+                let args: [(&str, &dyn Display); 2] = [("expect", expect), ("got", got)];
+                message::format("runtime.mismatched-arguments", &args)
+            }
+        }
+    }
 }
 
 impl UserDefined {
@@ -103,7 +331,7 @@ impl UserDefined {
         params: Box<[Key]>,
         body: Box<[Stmt]>,
         loc: Location,
-        capture: Rc<Env>,
+        capture: Shared<Env>,
         kind: Kind,
     ) -> Self {
         Self {
@@ -133,13 +361,13 @@ impl UserDefined {
         if args.len() != self.arity() {
             return Err(FunctionError::MismatchedArgument {
                 loc: self.loc,
-                expect: self.arity(),
+                expect: self.arity().into(),
                 got: args.len(),
             }
             .into());
         }
 
-        let _guard = env.bind_scope(Rc::new(Env::new_with_parent(Rc::clone(&self.capture))));
+        let _guard = env.bind_scope(self.capture.child());
 
         // https://github.com/rust-lang/rust/issues/59878
         for (i, arg) in args.into_vec().into_iter().enumerate() {
@@ -162,16 +390,17 @@ impl UserDefined {
             Kind::Function => Ok(Value::nil()),
             Kind::Constructor => {
                 let this = interner.keyword(Keyword::This);
-                Ok(env
-                    .get_at(this, 0)
+                Ok(self
+                    .capture
+                    .get(this)
                     .expect("this must exist if function is an initializer/constructor"))
             }
         }
     }
 
     // NOTE: this function creates a copy of self that binds an instance into its new capture
-    pub fn bind(&self, instance: Rc<Instance>, interner: &Interner) -> UserDefined {
-        let new_capture = Env::new_with_parent(Rc::clone(&self.capture));
+    pub fn bind(&self, instance: Shared<Instance>, interner: &Interner) -> UserDefined {
+        let new_capture = self.capture.child();
         let this = interner.keyword(Keyword::This);
         new_capture.define(this, Value::Instance(instance));
         UserDefined::new(
@@ -179,16 +408,49 @@ impl UserDefined {
             self.params.clone(),
             self.body.clone(),
             self.loc,
-            new_capture.into(),
+            new_capture,
             self.kind,
         )
     }
 }
 
+impl Debug for NativeBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NativeBody::Builtin(body) => write!(f, "Builtin({:p})", *body),
+            NativeBody::Host(body) => write!(f, "Host({:p})", Shared::as_ptr(body)),
+        }
+    }
+}
+
+impl PartialEq for NativeBody {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (NativeBody::Builtin(lhs), NativeBody::Builtin(rhs)) => {
+                std::ptr::fn_addr_eq(*lhs, *rhs)
+            }
+            (NativeBody::Host(lhs), NativeBody::Host(rhs)) => Shared::ptr_eq(lhs, rhs),
+            _ => false,
+        }
+    }
+}
+
+impl PartialEq for Native {
+    fn eq(&self, other: &Self) -> bool {
+        (self.name, self.arity) == (other.name, other.arity) && self.body == other.body
+    }
+}
+
+impl PartialOrd for Native {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        (self.name, self.arity).partial_cmp(&(other.name, other.arity))
+    }
+}
+
 impl PartialEq for UserDefined {
     fn eq(&self, other: &Self) -> bool {
         (self.name, self.arity(), self.loc) == (other.name, other.arity(), other.loc)
-            && Rc::ptr_eq(&self.capture, &other.capture)
+            && Shared::ptr_eq(&self.capture, &other.capture)
     }
 }
 
@@ -200,8 +462,8 @@ impl PartialOrd for UserDefined {
         if lhs != rhs {
             lhs.partial_cmp(&rhs)
         } else {
-            let lhs = Rc::as_ptr(&self.capture) as usize;
-            let rhs = Rc::as_ptr(&other.capture) as usize;
+            let lhs = Shared::as_ptr(&self.capture) as usize;
+            let rhs = Shared::as_ptr(&other.capture) as usize;
             lhs.partial_cmp(&rhs)
         }
     }
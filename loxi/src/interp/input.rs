@@ -0,0 +1,28 @@
+//! Input sources used by natives that read from the user, e.g. `readLine()`.
+
+use std::io::{self, BufRead};
+
+use crate::util::shared::ThreadSafe;
+
+/// A source of input lines. Implemented for every `BufRead` type, plus `Stdin`.
+pub trait LineSource: ThreadSafe {
+    /// Read a line including its line terminator into `buf`, returning the number of bytes read.
+    /// Zero bytes read means the end of input is reached.
+    fn read_line(&mut self, buf: &mut String) -> io::Result<usize>;
+}
+
+/// The standard input. Unlike `io::StdinLock`, it only locks stdin for the duration of a single
+/// read so the REPL can still read from stdin while an interpreter is alive.
+pub struct Stdin;
+
+impl<T: BufRead + ThreadSafe> LineSource for T {
+    fn read_line(&mut self, buf: &mut String) -> io::Result<usize> {
+        BufRead::read_line(self, buf)
+    }
+}
+
+impl LineSource for Stdin {
+    fn read_line(&mut self, buf: &mut String) -> io::Result<usize> {
+        io::stdin().read_line(buf)
+    }
+}
@@ -1,19 +1,32 @@
-use lasso::{Rodeo, Spur};
+use lasso::{Key as _, Rodeo, RodeoReader, Spur};
 use strum::IntoEnumIterator;
 
 use crate::lex::token::{Keyword, Special};
+use crate::util::shared::Shared;
 use crate::util::LoxToken;
 
 pub type Key = Spur;
 
+/// Interned strings that can no longer change, shared by every interner created from them using
+/// `Interner::with_base`.
 #[derive(Debug)]
+pub struct FrozenInterner {
+    rodeo: RodeoReader,
+}
+
+/// Interns strings into `Key`s. An interner can be layered on top of a `FrozenInterner`, in which
+/// case the strings of the base are shared and only new strings are stored in this interner. Keys
+/// of new strings start after the keys of the base so they never collide.
+#[derive(Debug, Clone)]
 pub struct Interner {
+    base: Option<Shared<FrozenInterner>>,
     rodeo: Rodeo,
 }
 
 impl Interner {
     pub fn new() -> Self {
         let mut interner = Self {
+            base: None,
             rodeo: Rodeo::default(),
         };
         for keyword in Keyword::iter() {
@@ -25,37 +38,115 @@ impl Interner {
         interner
     }
 
+    /// Create an interner that shares the strings of `base`. The keywords are already in `base`,
+    /// since it's created from an interner.
+    pub fn with_base(base: Shared<FrozenInterner>) -> Self {
+        Self {
+            base: Some(base),
+            rodeo: Rodeo::default(),
+        }
+    }
+
+    /// Turn this interner into a base for other interners. Only an interner without a base can be
+    /// frozen, so the bases never nest.
+    pub fn freeze(self) -> FrozenInterner {
+        assert!(self.base.is_none(), "a layered interner can't be frozen");
+        FrozenInterner {
+            rodeo: self.rodeo.into_reader(),
+        }
+    }
+
+    /// Number of interned strings, including the ones of the base.
+    pub fn len(&self) -> usize {
+        self.base_len() + self.rodeo.len()
+    }
+
     pub fn contains(&self, str: &str) -> bool {
-        self.rodeo.contains(str)
+        self.base_get(str).is_some() || self.rodeo.contains(str)
     }
 
     pub fn resolve(&self, key: Key) -> &str {
-        self.rodeo.resolve(&key)
+        let index = key.into_usize();
+        match &self.base {
+            Some(base) if index < base.rodeo.len() => base.rodeo.resolve(&key),
+            _ => self.rodeo.resolve(&self.local_key(index)),
+        }
     }
 
     pub fn get_or_intern<T>(&mut self, string: T) -> Key
     where
         T: AsRef<str>,
     {
-        self.rodeo.get_or_intern(string)
+        if let Some(key) = self.base_get(string.as_ref()) {
+            return key;
+        }
+        let key = self.rodeo.get_or_intern(string);
+        self.global_key(key)
     }
 
     pub fn get<T>(&self, string: T) -> Key
     where
         T: AsRef<str>,
     {
-        self.rodeo
-            .get(string)
+        self.try_get(string)
             .expect("the key should be interned beforehand")
     }
 
+    /// The key of `string`, `None` if it's not interned, e.g. to look up a name given by the host
+    /// without interning it.
+    pub fn try_get<T>(&self, string: T) -> Option<Key>
+    where
+        T: AsRef<str>,
+    {
+        let string = string.as_ref();
+        self.base_get(string)
+            .or_else(|| self.rodeo.get(string).map(|key| self.global_key(key)))
+    }
+
     pub fn keyword(&self, keyword: Keyword) -> Key {
         self.get(keyword.as_str())
     }
 
+    /// The key of a keyword in every interner, since they are the first strings interned, e.g. for
+    /// the parser which has no interner.
+    pub fn keyword_key(keyword: Keyword) -> Key {
+        Key::try_from_usize(keyword as usize).expect("keywords should be interned first")
+    }
+
+    /// The key of a special name in every interner, since they are interned right after the
+    /// keywords, e.g. for the parser which has no interner.
+    pub fn special_key(special: Special) -> Key {
+        let index = Special::iter()
+            .filter(|special| Keyword::try_from(special.as_str()).is_err())
+            .position(|other| other.as_str() == special.as_str())
+            .expect("a special name that is also a keyword has the key of the keyword");
+        Key::try_from_usize(Keyword::iter().count() + index)
+            .expect("special names should be interned right after the keywords")
+    }
+
     pub fn special(&self, special: Special) -> Key {
         self.get(special.as_str())
     }
+
+    fn base_get(&self, str: &str) -> Option<Key> {
+        self.base.as_ref().and_then(|base| base.rodeo.get(str))
+    }
+
+    fn base_len(&self) -> usize {
+        self.base.as_ref().map_or(0, |base| base.rodeo.len())
+    }
+
+    /// Convert a key of `rodeo` into a key that is unique across the base and `rodeo`.
+    fn global_key(&self, key: Key) -> Key {
+        match self.base_len() {
+            0 => key,
+            len => Key::try_from_usize(key.into_usize() + len).expect("too many interned strings"),
+        }
+    }
+
+    fn local_key(&self, index: usize) -> Key {
+        Key::try_from_usize(index - self.base_len()).expect("key should be from this interner")
+    }
 }
 
 impl Default for Interner {
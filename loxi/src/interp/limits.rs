@@ -0,0 +1,96 @@
+//! Limits on the memory a program can use, for hosts running untrusted programs.
+
+use std::cell::Cell;
+use std::fmt::Display;
+use std::mem::size_of;
+
+use thiserror::Error;
+
+use crate::message;
+
+use super::value::Value;
+
+/// Memory limits of a program. Every limit is disabled by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum number of bytes the program can allocate in total. This is a budget rather than a
+    /// cap on the heap: memory that is freed again is not given back, so a long loop runs out of it
+    /// even if it only keeps a little memory at a time.
+    pub max_allocation: Option<usize>,
+
+    /// Maximum length of a single string in bytes.
+    pub max_string_len: Option<usize>,
+
+    /// Maximum number of elements of a single list or entries of a single map.
+    pub max_collection_len: Option<usize>,
+}
+
+#[derive(Debug, Error)]
+pub enum LimitError {
+    Allocation(usize),
+    StringLen(usize),
+    CollectionLen(usize),
+}
+
+impl Display for LimitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LimitError::Allocation(limit) => {
+                message::write(f, "limit.allocation", &[("limit", limit)])
+            }
+            LimitError::StringLen(limit) => {
+                message::write(f, "limit.string-length", &[("limit", limit)])
+            }
+            LimitError::CollectionLen(limit) => {
+                message::write(f, "limit.collection-size", &[("limit", limit)])
+            }
+        }
+    }
+}
+
+/// Approximate count of the bytes allocated by a program, checked against `Limits`. Only the
+/// allocations that grow with the program are counted: strings, list elements, map entries, and
+/// instances.
+#[derive(Debug, Default)]
+pub struct Memory {
+    limits: Limits,
+    allocated: Cell<usize>,
+}
+
+impl Memory {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            allocated: Cell::new(0),
+        }
+    }
+
+    pub fn allocated(&self) -> usize {
+        self.allocated.get()
+    }
+
+    pub fn alloc(&self, bytes: usize) -> Result<(), LimitError> {
+        let allocated = self.allocated.get().saturating_add(bytes);
+        self.allocated.set(allocated);
+        match self.limits.max_allocation {
+            Some(max) if allocated > max => Err(LimitError::Allocation(max)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Count a new string of `len` bytes.
+    pub fn alloc_string(&self, len: usize) -> Result<(), LimitError> {
+        match self.limits.max_string_len {
+            Some(max) if len > max => Err(LimitError::StringLen(max)),
+            _ => self.alloc(size_of::<String>() + len),
+        }
+    }
+
+    /// Count `added` new elements of a list or entries of a map that has `len` of them after.
+    pub fn alloc_elements(&self, len: usize, added: usize) -> Result<(), LimitError> {
+        match self.limits.max_collection_len {
+            Some(max) if len > max => Err(LimitError::CollectionLen(max)),
+            _ => self.alloc(size_of::<Value>() * added),
+        }
+    }
+}
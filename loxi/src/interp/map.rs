@@ -0,0 +1,124 @@
+use std::fmt::Display;
+
+use indexmap::IndexMap;
+use rustc_hash::FxBuildHasher;
+use thiserror::Error;
+
+use crate::message;
+
+use crate::util::shared::Shared;
+
+use super::interner::Interner;
+use super::value::{float_to_int, Value};
+
+/// A hash map that remembers the insertion order of its keys. Only values that are compared by
+/// value can be used as keys, see `MapKey`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Map {
+    entries: IndexMap<MapKey, Value, FxBuildHasher>,
+}
+
+/// Hashable form of a `Value` that is used as a key of a `Map`.
+///
+/// Keys follow `==` semantics: `"a"` is the same key whether it's a string literal or a produced
+/// string, and `0`, `0.0`, and `-0` are the same key. Different types are never the same key, i.e.
+/// `1` and `"1"` are different keys. `nan` can't be a key since it's not equal to itself.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    Nil,
+    Bool(bool),
+    Number(u64),
+
+    /// Numbers equal to an integer, whatever their type, so `1` and `1.0` are the same key.
+    Int(i64),
+    String(Shared<str>),
+}
+
+#[derive(Debug, Error)]
+pub struct InvalidKey(pub &'static str);
+
+impl Display for InvalidKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        message::write(f, "native.invalid-key", &[("type", &self.0)])
+    }
+}
+
+impl MapKey {
+    pub fn new(value: &Value, interner: &Interner) -> Result<Self, InvalidKey> {
+        match value {
+            Value::StringLiteral(key) => Ok(MapKey::String(Shared::from(interner.resolve(*key)))),
+            value => Self::from_resolved(value),
+        }
+    }
+
+    /// Same as `new`, for values that are known to not be a `StringLiteral`.
+    pub fn from_resolved(value: &Value) -> Result<Self, InvalidKey> {
+        match value {
+            Value::Nil => Ok(MapKey::Nil),
+            Value::Bool(b) => Ok(MapKey::Bool(*b)),
+            Value::Number(num) if num.is_nan() => Err(InvalidKey(value.name())),
+            Value::Number(num) => match float_to_int(*num) {
+                Some(int) => Ok(MapKey::Int(int)),
+                None => Ok(MapKey::Number(num.to_bits())),
+            },
+            Value::Int(int) => Ok(MapKey::Int(*int)),
+            Value::String(str) => Ok(MapKey::String(Shared::clone(str))),
+            _ => Err(InvalidKey(value.name())),
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            MapKey::Nil => Value::nil(),
+            MapKey::Bool(b) => Value::bool(*b),
+            MapKey::Number(bits) => Value::number(f64::from_bits(*bits)),
+            MapKey::Int(int) => Value::int(*int),
+            MapKey::String(str) => Value::String(Shared::clone(str)),
+        }
+    }
+}
+
+impl Map {
+    pub fn get(&self, key: &MapKey) -> Option<Value> {
+        self.entries.get(key).cloned()
+    }
+
+    pub fn insert(&mut self, key: MapKey, value: Value) {
+        self.entries.insert(key, value);
+    }
+
+    /// Remove the entry while keeping the order of the rest of the entries.
+    pub fn remove(&mut self, key: &MapKey) -> Option<Value> {
+        self.entries.shift_remove(key)
+    }
+
+    pub fn contains(&self, key: &MapKey) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = Value> + '_ {
+        self.entries.keys().map(MapKey::to_value)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = Value> + '_ {
+        self.entries.values().cloned()
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut Value> {
+        self.entries.values_mut()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&MapKey, &Value)> {
+        self.entries.iter()
+    }
+}
+
+impl PartialOrd for Map {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        (self == other).then_some(std::cmp::Ordering::Equal)
+    }
+}
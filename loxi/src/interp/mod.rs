@@ -1,49 +1,103 @@
+use std::any::TypeId;
+use std::cell::{Cell, RefCell, RefMut};
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::mem;
 use std::ops::Deref;
-use std::rc::Rc;
+use std::slice;
+use std::time::Duration;
 
 use rustc_hash::FxHashMap;
 use thiserror::Error;
 
+use crate::analyze::Span;
+use crate::dialect::Dialect;
 use crate::lex::token::{Keyword, Special};
+use crate::message;
 use crate::parse::expr::{Expr, ExprId, RefExpr, ValExpr};
 use crate::parse::{stmt::Stmt, stmt::Unwind, token, Program};
 use crate::resolve::ResolveMap;
-use crate::util::{Location, TokLoc};
+use crate::util::shared::{Shared, ThreadSafe};
+use crate::util::{Location, LoxToken, TokLoc};
 
-use self::class::{Class, Property};
+use self::builder::InterpreterBuilder;
+use self::cancel::CancelToken;
+use self::class::{Class, Instance, Property};
+use self::clock::Clock;
 use self::env::DynamicEnv;
-use self::function::{Function, Kind, Native, UserDefined};
+use self::function::{Function, Kind, UserDefined};
+use self::input::LineSource;
 use self::interner::{Interner, Key};
-use self::value::Value;
+use self::limits::Memory;
+use self::map::MapKey;
+use self::object::{ClassBinding, HostClass, HostObject, LoxClass, SetError};
+use self::observer::{Observer, Scope};
+use self::value::{DisplayedValue, NumberStyle, Value};
 
+pub mod builder;
+pub mod cancel;
 pub mod class;
+pub mod clock;
+#[cfg(feature = "serde")]
+pub mod convert;
+pub mod coverage;
 pub mod env;
+pub mod explain;
 pub mod function;
+pub mod input;
 pub mod interner;
+pub mod limits;
+pub mod map;
+pub mod namespace;
+pub mod object;
+pub mod observer;
+pub mod output;
+pub mod profile;
+pub mod snapshot;
+pub mod stdlib;
+pub mod stepper;
+pub mod trace;
 pub mod value;
 
+#[cfg(test)]
+mod test;
+
 #[derive(Debug, Error)]
 pub enum RuntimeError {
-    #[error("{0} RuntimeError: Invalid binary operation '{1}' between '{2}' and '{3}'")]
     InvalidBinaryOp(Location, token::BinaryOp, &'static str, &'static str),
-
-    #[error("{0} RuntimeError: Invalid unary operation '{1}' on '{2}'")]
     InvalidUnaryOp(Location, token::UnaryOp, &'static str),
-
-    #[error("{0} RuntimeError: Trying to access undefined variable: '{1}'")]
     UndefinedVariable(Location, String),
-
-    #[error("{0}")]
     FunctionError(#[from] function::FunctionError),
-
-    #[error("{0} RuntimeError: Trying to access a property on a non-instance object")]
     InvalidPropertyAccess(Location),
-
-    #[error("{0} RuntimeError: Trying to access an undefined property")]
     UndefinedProperty(Location),
-
-    #[error("{0} RuntimeError: Not a function or a callable object")]
+    InvalidProperty(Location, String, #[source] function::NativeError),
     NotCallable(Location),
+    NativeError(Location, String, #[source] function::NativeError),
+    NotIndexable(Location, &'static str),
+    InvalidIndex(Location, String),
+    IndexOutOfRange(Location, usize, usize),
+    InvalidKey(Location, #[source] map::InvalidKey),
+    UndefinedKey(Location, String),
+    NotIterable(Location, &'static str),
+    AssertionFailed(Location, String),
+    Panic(Location, String),
+    LimitExceeded(Location, #[source] limits::LimitError),
+    StackOverflow(Location),
+    Cancelled(Location),
+    BudgetExceeded(Location, u64),
+    Timeout(Location, Duration),
+    DivisionByZero(Location),
+    Io(Location, #[source] io::Error),
+
+    /// Not an actual error, raised by `exit(code)` to unwind the whole program. It's turned into
+    /// `LoxError::Exit` by `Lox::eval`.
+    Exit(Location, i32),
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.loc(), self.message())
+    }
 }
 
 impl RuntimeError {
@@ -56,63 +110,338 @@ impl RuntimeError {
             RuntimeError::NotCallable(loc) => *loc,
             RuntimeError::InvalidPropertyAccess(loc) => *loc,
             RuntimeError::UndefinedProperty(loc) => *loc,
+            RuntimeError::InvalidProperty(loc, ..) => *loc,
+            RuntimeError::NativeError(loc, _, _) => *loc,
+            RuntimeError::NotIndexable(loc, _) => *loc,
+            RuntimeError::InvalidIndex(loc, _) => *loc,
+            RuntimeError::IndexOutOfRange(loc, _, _) => *loc,
+            RuntimeError::InvalidKey(loc, _) => *loc,
+            RuntimeError::UndefinedKey(loc, _) => *loc,
+            RuntimeError::NotIterable(loc, _) => *loc,
+            RuntimeError::AssertionFailed(loc, _) => *loc,
+            RuntimeError::Panic(loc, _) => *loc,
+            RuntimeError::LimitExceeded(loc, _) => *loc,
+            RuntimeError::StackOverflow(loc) => *loc,
+            RuntimeError::Cancelled(loc) => *loc,
+            RuntimeError::BudgetExceeded(loc, _) => *loc,
+            RuntimeError::Timeout(loc, _) => *loc,
+            RuntimeError::DivisionByZero(loc) => *loc,
+            RuntimeError::Io(loc, _) => *loc,
+            RuntimeError::Exit(loc, _) => *loc,
+        }
+    }
+
+    /// An undefined variable spans its name, the other errors are only known by where they
+    /// start, so their span is empty.
+    pub fn span(&self) -> Span {
+        match self {
+            RuntimeError::UndefinedVariable(loc, name) => Span::new(*loc, name),
+            _ => Span::at(self.loc()),
         }
     }
+
+    /// The message without the location, labelled as a `RuntimeError`.
+    pub fn message(&self) -> String {
+        let message = match self {
+            RuntimeError::InvalidBinaryOp(_, op, left, right) => message::format(
+                "runtime.invalid-binary-op",
+                &[("op", op), ("left", left), ("right", right)],
+            ),
+            RuntimeError::InvalidUnaryOp(_, op, operand) => message::format(
+                "runtime.invalid-unary-op",
+                &[("op", op), ("operand", operand)],
+            ),
+            RuntimeError::UndefinedVariable(_, name) => {
+                message::format("runtime.undefined-variable", &[("name", name)])
+            }
+            RuntimeError::FunctionError(err) => err.message(),
+            RuntimeError::InvalidPropertyAccess(_) => {
+                message::format("runtime.invalid-property-access", &[])
+            }
+            RuntimeError::UndefinedProperty(_) => {
+                message::format("runtime.undefined-property", &[])
+            }
+            RuntimeError::InvalidProperty(_, name, err) => message::format(
+                "runtime.invalid-property",
+                &[("name", name), ("error", err)],
+            ),
+            RuntimeError::NotCallable(_) => message::format("runtime.not-callable", &[]),
+            RuntimeError::NativeError(_, name, err) => {
+                message::format("runtime.native-error", &[("name", name), ("error", err)])
+            }
+            RuntimeError::NotIndexable(_, ty) => {
+                message::format("runtime.not-indexable", &[("type", ty)])
+            }
+            RuntimeError::InvalidIndex(_, index) => {
+                message::format("runtime.invalid-index", &[("index", index)])
+            }
+            RuntimeError::IndexOutOfRange(_, index, len) => message::format(
+                "runtime.index-out-of-range",
+                &[("index", index), ("len", len)],
+            ),
+            RuntimeError::InvalidKey(_, err) => err.to_string(),
+            RuntimeError::UndefinedKey(_, key) => {
+                message::format("runtime.undefined-key", &[("key", key)])
+            }
+            RuntimeError::NotIterable(_, ty) => {
+                message::format("runtime.not-iterable", &[("type", ty)])
+            }
+            RuntimeError::AssertionFailed(_, msg) => {
+                message::format("runtime.assertion-failed", &[("message", msg)])
+            }
+            RuntimeError::Panic(_, msg) => message::format("runtime.panic", &[("message", msg)]),
+            RuntimeError::LimitExceeded(_, err) => err.to_string(),
+            RuntimeError::StackOverflow(_) => message::format("runtime.stack-overflow", &[]),
+            RuntimeError::Cancelled(_) => message::format("runtime.cancelled", &[]),
+            RuntimeError::BudgetExceeded(_, limit) => {
+                message::format("runtime.budget-exceeded", &[("limit", limit)])
+            }
+            RuntimeError::Timeout(_, limit) => {
+                let limit = limit.as_millis();
+                message::format("runtime.timeout", &[("limit", &limit)])
+            }
+            RuntimeError::DivisionByZero(_) => message::format("runtime.division-by-zero", &[]),
+            RuntimeError::Io(_, err) => message::format("runtime.io", &[("error", err)]),
+            RuntimeError::Exit(_, code) => message::format("runtime.exit", &[("code", code)]),
+        };
+        format!("RuntimeError: {message}")
+    }
+}
+
+/// Where the output of a program goes, e.g. `io::stdout()`.
+pub trait Sink: Write + ThreadSafe {}
+
+impl<T: Write + ThreadSafe> Sink for T {}
+
+/// Host capabilities that scripts can use. Everything is disabled by default so untrusted scripts
+/// are sandboxed unless the host explicitly allows it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Capabilities {
+    /// Allow reading and writing files, e.g. `readFile` and `writeFile`.
+    pub fs: bool,
+
+    /// Allow reading environment variables using `getenv`.
+    pub env: bool,
+
+    /// Allow running other programs using `exec`.
+    pub run: bool,
 }
 
 pub struct Interpreter {
     dyn_env: DynamicEnv,
     interner: Interner,
     resolve_map: ResolveMap,
+    stdout: RefCell<Box<dyn Sink>>,
+    stderr: RefCell<Box<dyn Sink>>,
+    stdin: RefCell<Box<dyn LineSource>>,
+    clock: Box<dyn Clock>,
+
+    /// State of the generator of `random`, seeded from the clock on first use if not set.
+    rng: Cell<Option<u64>>,
+    methods: stdlib::Methods,
+    host_classes: FxHashMap<TypeId, Shared<HostClass>>,
+    memory: Memory,
+    observer: RefCell<Option<Box<dyn Observer>>>,
+
+    /// Whether there is an observer, checked before every expression without borrowing it.
+    observed: bool,
+
+    /// Tests registered by `test(name, fn)` that are not run yet.
+    tests: RefCell<Vec<stdlib::TestCase>>,
+
+    /// The regexes compiled by the regex natives, by their pattern.
+    #[cfg(feature = "regex")]
+    regexes: RefCell<FxHashMap<Box<str>, regex::Regex>>,
+
+    /// Whether an expression given to `Scope::eval` is evaluated, whose variables are not resolved.
+    scope_eval: Cell<bool>,
+
+    /// Location of the innermost native function call, used when a native calls back into Lox.
+    call_site: Cell<Location>,
+    call_depth: Cell<usize>,
+    max_call_depth: usize,
+
+    /// Statements executed by the current run, and the most it can execute.
+    steps: Cell<u64>,
+    max_steps: u64,
+
+    /// The time a run can take, and the reading of the clock at which the current run times out.
+    timeout: Option<Duration>,
+    deadline: Cell<f64>,
+
+    /// Whether dividing a number by zero fails instead of giving `inf` or `nan`.
+    strict_division: bool,
+
+    /// The extensions to the operators and natives of the book that are enabled, e.g. ordering
+    /// strings or `deepEquals`.
+    dialect: Dialect,
+
+    number_style: NumberStyle,
+    capabilities: Capabilities,
+    cancel: CancelToken,
 }
 
 impl Interpreter {
-    pub fn new() -> Self {
-        let mut interp = Interpreter {
-            dyn_env: DynamicEnv::new_with_global(),
-            interner: Interner::new(),
-            resolve_map: ResolveMap::default(),
-        };
-        interp.populate_env();
-        interp
+    pub fn builder() -> InterpreterBuilder {
+        InterpreterBuilder::new()
+    }
+
+    pub fn interner(&self) -> &Interner {
+        &self.interner
     }
 
-    pub fn interner(&mut self) -> &mut Interner {
+    pub fn interner_mut(&mut self) -> &mut Interner {
         &mut self.interner
     }
 
+    pub(crate) fn into_interner(self) -> Interner {
+        self.interner
+    }
+
+    pub(crate) fn cancel_token(&self) -> &CancelToken {
+        &self.cancel
+    }
+
+    /// Where `print` and `write` output goes, stdout by default.
+    pub fn stdout(&self) -> RefMut<'_, Box<dyn Sink>> {
+        self.stdout.borrow_mut()
+    }
+
+    /// Where `eprint` output and diagnostics go, stderr by default.
+    pub fn stderr(&self) -> RefMut<'_, Box<dyn Sink>> {
+        self.stderr.borrow_mut()
+    }
+
+    pub fn set_stdout(&mut self, stdout: Box<dyn Sink>) {
+        self.stdout = RefCell::new(stdout);
+    }
+
+    pub fn set_stderr(&mut self, stderr: Box<dyn Sink>) {
+        self.stderr = RefCell::new(stderr);
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        // a name that was never interned can't be a global
+        let key = self.interner.try_get(name)?;
+        self.dyn_env.get_global(key)
+    }
+
+    /// Define or overwrite the global variable `name`.
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        let key = self.interner.get_or_intern(name);
+        self.dyn_env.define_global(key, value);
+    }
+
+    /// Define `T` as the global class `name`. Calling the class creates an instance if `T` has a
+    /// constructor.
+    pub fn register_class<T: LoxClass>(&mut self, name: &str) {
+        let mut binding = ClassBinding::<T>::new();
+        T::bind(&mut binding);
+
+        let key = self.interner.get_or_intern(name);
+        let class = Shared::new(binding.build(key, &mut self.interner));
+        if let Some(ctor) = class.constructor() {
+            self.dyn_env
+                .define_global(key, Value::native_function(ctor));
+        }
+        self.host_classes.insert(TypeId::of::<T>(), class);
+    }
+
+    /// Wrap `value` as an instance of its class, `None` if the class is not registered.
+    pub fn new_object<T: LoxClass>(&self, value: T) -> Option<Value> {
+        let class = self.host_classes.get(&TypeId::of::<T>())?;
+        let object = HostObject::new(Shared::clone(class), value);
+        Some(Value::Object(Shared::new(object)))
+    }
+
+    /// Where `readLine` reads from, stdin by default.
+    pub fn stdin(&self) -> RefMut<'_, Box<dyn LineSource>> {
+        self.stdin.borrow_mut()
+    }
+
+    pub fn set_stdin(&mut self, stdin: Box<dyn LineSource>) {
+        self.stdin = RefCell::new(stdin);
+    }
+
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Approximate number of bytes allocated by programs so far, see `Limits::max_allocation`.
+    pub fn allocated(&self) -> usize {
+        self.memory.allocated()
+    }
+
+    /// Display a value the same way `print` does, with the number style of this interpreter.
+    pub fn display<'a>(&'a self, value: &'a Value) -> DisplayedValue<'a, 'a> {
+        value.display(&self.interner).with_style(self.number_style)
+    }
+
+    pub fn number_style(&self) -> NumberStyle {
+        self.number_style
+    }
+
+    pub fn set_observer(&mut self, observer: Option<Box<dyn Observer>>) {
+        self.observed = observer.is_some();
+        self.observer = RefCell::new(observer);
+    }
+
+    /// Remove the registered tests, in the order they are registered.
+    pub fn take_tests(&self) -> Vec<stdlib::TestCase> {
+        self.tests.take()
+    }
+
+    /// Run the program and return the value of its last statement if it's an expression statement,
+    /// otherwise `nil`. The resolve map is merged into the previous ones so functions defined by an
+    /// earlier program keep working.
     pub fn interpret(
         &mut self,
         program: Program,
-        mut resolve_map: ResolveMap,
-    ) -> Result<(), RuntimeError> {
-        std::mem::swap(&mut self.resolve_map, &mut resolve_map);
+        resolve_map: ResolveMap,
+    ) -> Result<Value, RuntimeError> {
+        self.resolve_map.extend(resolve_map);
+        self.start_run();
+
+        let mut last = Value::nil();
         for stmt in program.statements.iter() {
-            match self.execute(stmt)? {
-                Unwind::None => (),
-                Unwind::Return(_, _) => {
-                    unreachable!("stray return detection should have been handled in Resolver!")
+            last = Value::nil();
+            let unwind = match stmt {
+                Stmt::Expr { expr } => {
+                    self.step(stmt)?;
+                    self.observe_statement(stmt);
+                    last = self.eval(expr)?;
+                    Unwind::None
                 }
+                stmt => self.execute(stmt)?,
+            };
+            if let Unwind::Return(_, _) = unwind {
+                unreachable!("stray return detection should have been handled in Resolver!")
             }
         }
-        Ok(())
+        Ok(last)
     }
 
-    fn populate_env(&mut self) {
-        let name = self.interner.get_or_intern("clock");
-        let clock = Native::new(name, Box::new([]), native_functions::clock);
-        self.dyn_env.define(name, Value::native_function(clock));
+    /// Give the next run the whole step budget and time limit.
+    pub(crate) fn start_run(&self) {
+        self.steps.set(0);
+        if let Some(timeout) = self.timeout {
+            let timeout = timeout.as_secs_f64() * 1000.0;
+            self.deadline.set(self.clock.monotonic() + timeout);
+        }
     }
 
     fn execute(&self, stmt: &Stmt) -> Result<Unwind, RuntimeError> {
+        self.step(stmt)?;
+        self.observe_statement(stmt);
         match stmt {
             Stmt::Expr { expr } => {
                 self.eval(expr)?;
                 Ok(Unwind::None)
             }
-            Stmt::Print { expr, .. } => {
+            Stmt::Print { expr, loc } => {
                 let value = self.eval(expr)?;
-                println!("{}", value.display(&self.interner));
+                writeln!(self.stdout(), "{}", self.display(&value))
+                    .map_err(|err| RuntimeError::Io(*loc, err))?;
                 Ok(Unwind::None)
             }
             Stmt::Var { name, init, .. } => {
@@ -125,7 +454,7 @@ impl Interpreter {
                 self.dyn_env.define(*name, value);
                 Ok(Unwind::None)
             }
-            Stmt::Block { statements } => {
+            Stmt::Block { statements, .. } => {
                 let _local = self.dyn_env.create_scope();
                 for stmt in statements {
                     if let Unwind::Return(value, loc) = self.execute(stmt)? {
@@ -156,15 +485,63 @@ impl Interpreter {
                 }
             },
             Stmt::While {
-                condition, body, ..
+                condition,
+                body,
+                loc,
             } => {
                 while self.eval(condition)?.truthiness() {
+                    self.check_cancelled(*loc)?;
                     if let Unwind::Return(value, loc) = self.execute(body)? {
                         return Ok(Unwind::Return(value, loc));
                     }
                 }
                 Ok(Unwind::None)
             }
+            Stmt::ForIn {
+                loc,
+                name,
+                iterable,
+                body,
+                ..
+            } => {
+                // iterate over a snapshot so the body can freely modify the collection
+                let items: Vec<Value> = match self.eval(iterable)? {
+                    Value::Instance(instance) => return self.iterate(instance, *name, body, *loc),
+                    Value::List(list) => list.borrow().clone(),
+                    Value::Map(map) => map.borrow().keys().collect(),
+                    value => match value.as_str(&self.interner) {
+                        Some(str) => str.chars().map(|c| Value::string(c.into())).collect(),
+                        None => return Err(RuntimeError::NotIterable(*loc, value.name())),
+                    },
+                };
+
+                for item in items {
+                    self.check_cancelled(*loc)?;
+                    let _local = self.dyn_env.create_scope();
+                    self.dyn_env.define(*name, item);
+                    if let Unwind::Return(value, loc) = self.execute(body)? {
+                        return Ok(Unwind::Return(value, loc));
+                    }
+                }
+                Ok(Unwind::None)
+            }
+            Stmt::Switch {
+                subject,
+                cases,
+                otherwise,
+                ..
+            } => {
+                let subject = self.eval(subject)?;
+                for (value, body) in cases {
+                    if subject.is_equal(&self.eval(value)?, &self.interner) {
+                        return self.execute(body);
+                    }
+                }
+                match otherwise {
+                    Some(stmt) => self.execute(stmt),
+                    None => Ok(Unwind::None),
+                }
+            }
             Stmt::Function { func } => {
                 self.dyn_env.define(
                     func.name,
@@ -186,7 +563,9 @@ impl Interpreter {
                 };
                 Ok(Unwind::Return(value, *loc))
             }
-            Stmt::Class { loc, name, methods } => {
+            Stmt::Class {
+                loc, name, methods, ..
+            } => {
                 // // is this really necessary?
                 // self.dyn_env.define(*name, Value::Nil);
 
@@ -195,7 +574,7 @@ impl Interpreter {
 
                 for m in methods.into_iter() {
                     let func = |kind| {
-                        Rc::new(Function::UserDefined(UserDefined::new(
+                        Shared::new(Function::UserDefined(UserDefined::new(
                             m.name,
                             m.params.clone(),
                             m.body.clone(),
@@ -226,16 +605,31 @@ impl Interpreter {
     }
 
     pub fn eval(&self, expr: &Expr) -> Result<Value, RuntimeError> {
+        if self.observed {
+            return self.eval_observed(expr);
+        }
         match expr {
             Expr::ValExpr(expr, _id) => self.eval_val(expr),
             Expr::RefExpr(expr, id) => self.eval_ref(expr, *id),
         }
     }
 
+    /// Kept apart from `eval` so that evaluating without an observer stays as fast as before.
+    #[cold]
+    fn eval_observed(&self, expr: &Expr) -> Result<Value, RuntimeError> {
+        let value = match expr {
+            Expr::ValExpr(expr, _id) => self.eval_val(expr)?,
+            Expr::RefExpr(expr, id) => self.eval_ref(expr, *id)?,
+        };
+        self.observe(|observer| observer.on_value(expr.loc(), &value, &Scope::new(self)));
+        Ok(value)
+    }
+
     fn eval_val(&self, expr: &ValExpr) -> Result<Value, RuntimeError> {
         match expr {
             ValExpr::Literal { value } => match &value.tok {
                 token::Literal::Number(num) => Ok(Value::number(*num)),
+                token::Literal::Int(int) => Ok(Value::int(*int)),
                 token::Literal::String(str) => Ok(Value::string_literal(*str)),
                 token::Literal::True => Ok(Value::bool(true)),
                 token::Literal::False => Ok(Value::bool(false)),
@@ -244,7 +638,7 @@ impl Interpreter {
             ValExpr::Grouping { expr, .. } => self.eval_val(expr),
             ValExpr::Unary { operator, right } => {
                 let value = self.eval(right)?;
-                match operator.tok {
+                let result = match operator.tok {
                     token::UnaryOp::Minus => value.minus(),
                     token::UnaryOp::Not => Ok(value.not()),
                 }
@@ -253,7 +647,12 @@ impl Interpreter {
                         RuntimeError::InvalidUnaryOp(operator.loc, operator.tok.clone(), s)
                     }
                     _ => unreachable!("UnaryOp should only return Unary variant of InvalidOp"),
-                })
+                })?;
+
+                if self.observed {
+                    self.observe_operation(operator.loc, operator.tok.as_str(), &[value], &result);
+                }
+                Ok(result)
             }
             ValExpr::Binary {
                 left,
@@ -262,67 +661,255 @@ impl Interpreter {
             } => {
                 let lhs = self.eval(left)?;
                 let rhs = self.eval(right)?;
+                let operands = self.observed.then(|| [lhs.clone(), rhs.clone()]);
+
+                if self.strict_division
+                    && matches!(operator.tok, token::BinaryOp::Div)
+                    && matches!(lhs, Value::Int(_) | Value::Number(_))
+                    && matches!(rhs, Value::Int(0) | Value::Number(0.0))
+                {
+                    return Err(RuntimeError::DivisionByZero(operator.loc));
+                }
+
+                // the book only orders numbers
+                if !self.dialect.string_ordering
+                    && matches!(
+                        operator.tok,
+                        token::BinaryOp::Less
+                            | token::BinaryOp::LessEq
+                            | token::BinaryOp::Greater
+                            | token::BinaryOp::GreaterEq
+                    )
+                    && lhs.as_str(&self.interner).is_some()
+                    && rhs.as_str(&self.interner).is_some()
+                {
+                    let (l, r) = (lhs.name(), rhs.name());
+                    let err =
+                        RuntimeError::InvalidBinaryOp(operator.loc, operator.tok.clone(), l, r);
+                    return Err(err);
+                }
 
-                match operator.tok {
+                let value = match operator.tok {
                     token::BinaryOp::Add => lhs.add(rhs, &self.interner),
                     token::BinaryOp::Sub => lhs.sub(rhs),
                     token::BinaryOp::Mul => lhs.mul(rhs),
                     token::BinaryOp::Div => lhs.div(rhs),
+                    token::BinaryOp::BitAnd => lhs.bit_and(rhs),
+                    token::BinaryOp::BitOr => lhs.bit_or(rhs),
+                    token::BinaryOp::BitXor => lhs.bit_xor(rhs),
+                    token::BinaryOp::ShiftLeft => lhs.shl(rhs),
+                    token::BinaryOp::ShiftRight => lhs.shr(rhs),
                     token::BinaryOp::Equal => Ok(lhs.eq(&rhs, &self.interner)),
                     token::BinaryOp::NotEqual => Ok(lhs.neq(&rhs, &self.interner)),
-                    token::BinaryOp::Less => lhs.lt(&rhs),
-                    token::BinaryOp::LessEq => lhs.le(&rhs),
-                    token::BinaryOp::Greater => lhs.gt(&rhs),
-                    token::BinaryOp::GreaterEq => lhs.ge(&rhs),
+                    token::BinaryOp::Less => lhs.lt(&rhs, &self.interner),
+                    token::BinaryOp::LessEq => lhs.le(&rhs, &self.interner),
+                    token::BinaryOp::Greater => lhs.gt(&rhs, &self.interner),
+                    token::BinaryOp::GreaterEq => lhs.ge(&rhs, &self.interner),
                 }
                 .map_err(|err| match err {
                     value::InvalidOp::Binary(l, r) => {
                         RuntimeError::InvalidBinaryOp(operator.loc, operator.tok.clone(), l, r)
                     }
                     _ => unreachable!("BinaryOp should only return Binary variant of InvalidOp"),
-                })
+                })?;
+
+                // concatenation is the only binary operation that allocates
+                if let Value::String(str) = &value {
+                    self.memory
+                        .alloc_string(str.len())
+                        .map_err(|err| RuntimeError::LimitExceeded(operator.loc, err))?;
+                }
+
+                if let Some(operands) = operands {
+                    self.observe_operation(operator.loc, operator.tok.as_str(), &operands, &value);
+                }
+                Ok(value)
             }
             ValExpr::Logical { left, kind, right } => {
                 let lhs = self.eval(left)?;
-                match (&kind.tok, lhs.truthiness()) {
-                    (token::LogicalOp::And, false) => return Ok(lhs),
-                    (token::LogicalOp::Or, true) => return Ok(lhs),
-                    (_, _) => (),
+                let short_circuit = match kind.tok {
+                    token::LogicalOp::And => !lhs.truthiness(),
+                    token::LogicalOp::Or => lhs.truthiness(),
                 };
-                self.eval(right)
+                if short_circuit {
+                    if self.observed {
+                        let operands = slice::from_ref(&lhs);
+                        self.observe_operation(kind.loc, kind.tok.as_str(), operands, &lhs);
+                    }
+                    return Ok(lhs);
+                }
+
+                let rhs = self.eval(right)?;
+                if self.observed {
+                    let operands = [lhs, rhs.clone()];
+                    self.observe_operation(kind.loc, kind.tok.as_str(), &operands, &rhs);
+                }
+                Ok(rhs)
             }
             ValExpr::Call { callee, loc, args } => {
                 let callee = self.eval(callee)?;
+                if !matches!(callee, Value::Function(_) | Value::Class(_)) {
+                    return Err(RuntimeError::NotCallable(*loc));
+                }
 
-                match callee {
-                    Value::Function(func) => {
-                        let args = args
-                            .into_iter()
-                            .map(|a| self.eval(a))
-                            .collect::<Result<Box<[_]>, _>>()?;
-                        match func.deref() {
-                            function::Function::Native(func) => func.call(args),
-                            function::Function::UserDefined(func) => {
-                                func.call(args, &self.interner, &self.dyn_env, |stmt| {
-                                    self.execute(stmt)
-                                })
-                            }
-                        }
-                    }
-                    Value::Class(class) => {
-                        let args = args
-                            .into_iter()
-                            .map(|a| self.eval(a))
-                            .collect::<Result<Box<[_]>, _>>()?;
-                        let instance =
-                            class.construct(args, &self.interner, &self.dyn_env, *loc, |stmt| {
-                                self.execute(stmt)
-                            })?;
-                        Ok(Value::Instance(instance))
-                    }
-                    _ => Err(RuntimeError::NotCallable(*loc)),
+                let args = args
+                    .into_iter()
+                    .map(|a| self.eval(a))
+                    .collect::<Result<Box<[_]>, _>>()?;
+                self.call_value(&callee, args, *loc)
+            }
+            ValExpr::List { elements, loc } => {
+                let values = elements
+                    .into_iter()
+                    .map(|e| self.eval(e))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.memory
+                    .alloc_elements(values.len(), values.len())
+                    .map_err(|err| RuntimeError::LimitExceeded(*loc, err))?;
+                Ok(Value::list(values))
+            }
+        }
+    }
+
+    /// Iterate over an instance using the iteration protocol: `iter()` returns the iterator, or the
+    /// instance is the iterator itself if it has no `iter` method, then each call to `next()` of
+    /// the iterator returns the next item until it returns nil.
+    fn iterate(
+        &self,
+        instance: Shared<Instance>,
+        name: Key,
+        body: &Stmt,
+        loc: Location,
+    ) -> Result<Unwind, RuntimeError> {
+        let method = |instance: &Shared<Instance>, special| {
+            let property = instance.get(self.interner.special(special), &self.interner);
+            property.map(|property| match property {
+                Property::Field(value) => value,
+                Property::Method(func) => Value::Function(func),
+            })
+        };
+
+        let iterator = match method(&instance, Special::Iter) {
+            Some(iter) => self.call_value(&iter, Box::new([]), loc)?,
+            None => Value::Instance(instance),
+        };
+        let next = match &iterator {
+            Value::Instance(iterator) => method(iterator, Special::Next),
+            _ => None,
+        };
+        let next = next.ok_or(RuntimeError::NotIterable(loc, iterator.name()))?;
+
+        loop {
+            self.check_cancelled(loc)?;
+            let item = self.call_value(&next, Box::new([]), loc)?;
+            if let Value::Nil = item {
+                return Ok(Unwind::None);
+            }
+            let _local = self.dyn_env.create_scope();
+            self.dyn_env.define(name, item);
+            if let Unwind::Return(value, loc) = self.execute(body)? {
+                return Ok(Unwind::Return(value, loc));
+            }
+        }
+    }
+
+    /// Call a function or a class with already evaluated arguments.
+    pub fn call_value(
+        &self,
+        callee: &Value,
+        args: Box<[Value]>,
+        loc: Location,
+    ) -> Result<Value, RuntimeError> {
+        let depth = self.call_depth.get();
+        if depth >= self.max_call_depth {
+            return Err(RuntimeError::StackOverflow(loc));
+        }
+        // a recursive program may run for long without looping
+        self.check_cancelled(loc)?;
+
+        // the name is only looked up when someone is observing
+        let observed = self
+            .observer
+            .try_borrow()
+            .is_ok_and(|observer| observer.is_some());
+        let name = match observed {
+            true => self.callee_name(callee),
+            false => None,
+        };
+        if let Some(name) = name {
+            self.observe(|observer| observer.on_call(name, loc));
+        }
+
+        self.call_depth.set(depth + 1);
+        let result = self.call_value_impl(callee, args, loc);
+        self.call_depth.set(depth);
+
+        if let Some(name) = name {
+            self.observe(|observer| observer.on_return(name, loc));
+        }
+        result
+    }
+
+    fn callee_name(&self, callee: &Value) -> Option<&str> {
+        let name = match callee {
+            Value::Function(func) => match func.deref() {
+                function::Function::Native(func) => func.name,
+                function::Function::UserDefined(func) => func.name,
+            },
+            Value::Class(class) => class.name,
+            _ => return None,
+        };
+        Some(self.interner.resolve(name))
+    }
+
+    fn observe_statement(&self, stmt: &Stmt) {
+        if let Some(loc) = stmt.loc() {
+            let scope = Scope::new(self);
+            self.observe(|observer| observer.on_statement(loc, &scope));
+        }
+    }
+
+    fn observe_operation(&self, loc: Location, operator: &str, operands: &[Value], result: &Value) {
+        let scope = Scope::new(self);
+        self.observe(|observer| observer.on_operation(loc, operator, operands, result, &scope));
+    }
+
+    /// Events caused by the observer itself, e.g. by calling a function using `Scope::eval`, are
+    /// not reported since the observer is already busy.
+    fn observe(&self, f: impl FnOnce(&mut dyn Observer)) {
+        if let Ok(mut observer) = self.observer.try_borrow_mut() {
+            if let Some(observer) = observer.as_mut() {
+                f(observer.as_mut());
+            }
+        }
+    }
+
+    fn call_value_impl(
+        &self,
+        callee: &Value,
+        args: Box<[Value]>,
+        loc: Location,
+    ) -> Result<Value, RuntimeError> {
+        match callee {
+            Value::Function(func) => match func.deref() {
+                function::Function::Native(func) => func.call(self, args, loc),
+                function::Function::UserDefined(func) => {
+                    func.call(args, &self.interner, &self.dyn_env, |stmt| {
+                        self.execute(stmt)
+                    })
                 }
+            },
+            Value::Class(class) => {
+                self.memory
+                    .alloc(mem::size_of::<Instance>())
+                    .map_err(|err| RuntimeError::LimitExceeded(loc, err))?;
+                let instance =
+                    class.construct(args, &self.interner, &self.dyn_env, loc, |stmt| {
+                        self.execute(stmt)
+                    })?;
+                Ok(Value::Instance(instance))
             }
+            _ => Err(RuntimeError::NotCallable(loc)),
         }
     }
 
@@ -353,7 +940,20 @@ impl Interpreter {
                         Property::Method(func) => Ok(Value::Function(func)),
                     },
                 },
-                _ => Err(RuntimeError::InvalidPropertyAccess(prop.loc)),
+                Value::Object(object) => object
+                    .get(prop.tok.name)
+                    .ok_or(RuntimeError::UndefinedProperty(prop.loc)),
+                Value::Namespace(ns) => match ns.get(prop.tok.name) {
+                    Some(value) => Ok(value),
+                    None => Err(RuntimeError::UndefinedProperty(prop.loc)),
+                },
+                value => match self.methods.get(&value, prop.tok.name) {
+                    Some(method) => Ok(Value::native_function(method)),
+                    None if self.methods.supports(&value) => {
+                        Err(RuntimeError::UndefinedProperty(prop.loc))
+                    }
+                    None => Err(RuntimeError::InvalidPropertyAccess(prop.loc)),
+                },
             },
             RefExpr::Set {
                 object,
@@ -362,26 +962,145 @@ impl Interpreter {
             } => match self.eval(object)? {
                 Value::Instance(instance) => {
                     let value = self.eval(value)?;
+                    if !instance.fields.borrow().contains_key(&prop.tok.name) {
+                        self.memory
+                            .alloc(mem::size_of::<(Key, Value)>())
+                            .map_err(|err| RuntimeError::LimitExceeded(prop.loc, err))?;
+                    }
                     instance.set(prop.tok.name, value.clone());
                     Ok(value)
                 }
+                Value::Object(object) => {
+                    let value = self.eval(value)?;
+                    let resolved = value.clone().resolved(&self.interner);
+                    match object.set(prop.tok.name, &resolved) {
+                        Ok(()) => Ok(value),
+                        Err(SetError::Undefined) => Err(RuntimeError::UndefinedProperty(prop.loc)),
+                        Err(SetError::Invalid(err)) => {
+                            let name = self.interner.resolve(prop.tok.name).to_owned();
+                            Err(RuntimeError::InvalidProperty(prop.loc, name, err))
+                        }
+                    }
+                }
                 _ => Err(RuntimeError::InvalidPropertyAccess(prop.loc)),
             },
-            RefExpr::This { .. } => {
+            RefExpr::This { loc } => {
                 let this = self.interner.keyword(Keyword::This);
                 match self.lookup_var(id, this) {
                     Some(value) => Ok(value),
+                    // expressions given to `Scope::eval` are not resolved
+                    None if self.scope_eval.get() => Err(RuntimeError::UndefinedVariable(
+                        *loc,
+                        self.interner.resolve(this).to_owned(),
+                    )),
                     None => unreachable!(
                         "stray this keyword detection should have been handled in Resolver!"
                     ),
                 }
             }
+            RefExpr::Index { object, index, loc } => match self.eval(object)? {
+                Value::List(list) => {
+                    let index = self.eval(index)?;
+                    let list = list.borrow();
+                    let i = self.list_index(&index, list.len(), *loc)?;
+                    Ok(list[i].clone())
+                }
+                Value::Map(map) => {
+                    let index = self.eval(index)?;
+                    let key = self.map_key(&index, *loc)?;
+                    map.borrow().get(&key).ok_or_else(|| {
+                        let key = self.display(&index).to_string();
+                        RuntimeError::UndefinedKey(*loc, key)
+                    })
+                }
+                value => Err(RuntimeError::NotIndexable(*loc, value.name())),
+            },
+            RefExpr::IndexSet {
+                object,
+                index,
+                value,
+                loc,
+            } => match self.eval(object)? {
+                Value::List(list) => {
+                    let index = self.eval(index)?;
+                    let value = self.eval(value)?;
+                    let mut list = list.borrow_mut();
+                    let i = self.list_index(&index, list.len(), *loc)?;
+                    list[i] = value.clone();
+                    Ok(value)
+                }
+                Value::Map(map) => {
+                    let key = self.map_key(&self.eval(index)?, *loc)?;
+                    let value = self.eval(value)?;
+                    let mut map = map.borrow_mut();
+                    if !map.contains(&key) {
+                        self.memory
+                            .alloc_elements(map.len() + 1, 1)
+                            .map_err(|err| RuntimeError::LimitExceeded(*loc, err))?;
+                    }
+                    map.insert(key, value.clone());
+                    Ok(value)
+                }
+                value => Err(RuntimeError::NotIndexable(*loc, value.name())),
+            },
+        }
+    }
+
+    fn list_index(&self, index: &Value, len: usize, loc: Location) -> Result<usize, RuntimeError> {
+        match index.as_int().and_then(|int| usize::try_from(int).ok()) {
+            Some(i) if i < len => Ok(i),
+            Some(i) => Err(RuntimeError::IndexOutOfRange(loc, i, len)),
+            None => Err(RuntimeError::InvalidIndex(
+                loc,
+                self.display(index).to_string(),
+            )),
         }
     }
 
+    /// Count a statement against the step budget and check the time limit.
+    fn step(&self, stmt: &Stmt) -> Result<(), RuntimeError> {
+        let steps = self.steps.get() + 1;
+        self.steps.set(steps);
+
+        let loc = || match stmt {
+            Stmt::Block { loc, .. } => *loc,
+            stmt => stmt.loc().unwrap_or_default(),
+        };
+        if steps > self.max_steps {
+            return Err(RuntimeError::BudgetExceeded(loc(), self.max_steps));
+        }
+        if self.timeout.is_some() {
+            self.check_deadline(loc())?;
+        }
+        Ok(())
+    }
+
+    /// Returns an error if the run is past its time limit.
+    fn check_deadline(&self, loc: Location) -> Result<(), RuntimeError> {
+        match self.timeout {
+            Some(timeout) if self.clock.monotonic() > self.deadline.get() => {
+                Err(RuntimeError::Timeout(loc, timeout))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns an error if the host requested the program to stop.
+    fn check_cancelled(&self, loc: Location) -> Result<(), RuntimeError> {
+        match self.cancel.is_cancelled() {
+            true => Err(RuntimeError::Cancelled(loc)),
+            false => Ok(()),
+        }
+    }
+
+    fn map_key(&self, value: &Value, loc: Location) -> Result<MapKey, RuntimeError> {
+        MapKey::new(value, &self.interner).map_err(|err| RuntimeError::InvalidKey(loc, err))
+    }
+
     fn lookup_var(&self, expr_id: ExprId, key: Key) -> Option<Value> {
-        match self.resolve_map.distance(expr_id) {
-            Some(distance) => self.dyn_env.get_at(key, distance),
+        match self.resolve_map.slot(expr_id) {
+            Some(slot) => self.dyn_env.get_at(slot),
+            None if self.scope_eval.get() => self.dyn_env.current().get(key),
             None => self.dyn_env.get_global(key),
         }
     }
@@ -390,22 +1109,10 @@ impl Interpreter {
     where
         F: FnOnce(&mut Value) -> R,
     {
-        match self.resolve_map.distance(expr_id) {
-            Some(distance) => self.dyn_env.modify_at(key, distance, f),
+        match self.resolve_map.slot(expr_id) {
+            Some(slot) => self.dyn_env.modify_at(slot, f),
+            None if self.scope_eval.get() => self.dyn_env.current().modify(key, f),
             None => self.dyn_env.modify_global(key, f),
         }
     }
 }
-
-mod native_functions {
-    use super::*;
-
-    pub fn clock(_args: Box<[Value]>) -> Result<Value, RuntimeError> {
-        let now = std::time::SystemTime::now();
-        let seconds = now
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs_f64();
-        Ok(Value::number(seconds))
-    }
-}
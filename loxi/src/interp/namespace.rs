@@ -0,0 +1,35 @@
+use rustc_hash::FxHashMap;
+
+use super::interner::Key;
+use super::value::Value;
+
+/// A named collection of values, e.g. `Math`. Its members are accessed like properties but unlike
+/// an instance, they can't be modified from Lox.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Namespace {
+    pub name: Key,
+    members: FxHashMap<Key, Value>,
+}
+
+impl Namespace {
+    pub fn new(name: Key) -> Self {
+        Self {
+            name,
+            members: FxHashMap::default(),
+        }
+    }
+
+    pub fn define(&mut self, name: Key, value: Value) {
+        self.members.insert(name, value);
+    }
+
+    pub fn get(&self, name: Key) -> Option<Value> {
+        self.members.get(&name).cloned()
+    }
+}
+
+impl PartialOrd for Namespace {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.name.partial_cmp(&other.name)
+    }
+}
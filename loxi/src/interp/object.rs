@@ -0,0 +1,448 @@
+//! Rust types exposed to Lox as classes, see `Lox::register_class`.
+//!
+//! An object is a Rust value owned by the interpreter. Its properties and methods are defined by
+//! a `ClassBinding`, which converts the arguments from and the results into `Value` so the
+//! bindings can be written using plain Rust types.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+
+use rustc_hash::FxHashMap;
+
+use crate::util::shared::{Lock, Shared, ThreadSafe};
+
+use super::function::{Arity, HostFn, Native, NativeError};
+use super::interner::{Interner, Key};
+use super::value::Value;
+
+/// A Rust type that can be used as a class in Lox.
+pub trait LoxClass: Sized + ThreadSafe + 'static {
+    /// Define the constructor, properties, and methods that are visible to Lox.
+    fn bind(class: &mut ClassBinding<Self>);
+}
+
+/// The definition of a `LoxClass`, collected by `LoxClass::bind`.
+pub struct ClassBinding<T> {
+    constructor: Option<(Arity, Shared<dyn Constructor>)>,
+    properties: Vec<(String, Property)>,
+    methods: Vec<(String, Method)>,
+    marker: PhantomData<T>,
+}
+
+/// A class defined by the host, see `ClassBinding`.
+pub struct HostClass {
+    pub name: Key,
+    constructor: Option<(Arity, Shared<dyn Constructor>)>,
+    properties: FxHashMap<Key, Property>,
+    methods: FxHashMap<Key, Method>,
+}
+
+/// An instance of a `HostClass`.
+pub struct HostObject {
+    pub class: Shared<HostClass>,
+    data: Lock<Box<dyn AnyObject>>,
+}
+
+/// Error from setting a property of a `HostObject`.
+pub enum SetError {
+    Undefined,
+    Invalid(NativeError),
+}
+
+/// Conversion of a function argument or a property value from `Value`.
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Result<Self, NativeError>;
+}
+
+/// Conversion of a function result or a property value into `Value`.
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+/// Conversion of the arguments of a function, implemented for tuples of `FromValue` types.
+pub trait FromArgs: Sized {
+    const ARITY: usize;
+
+    /// Convert `args`, which has exactly `ARITY` values.
+    fn from_args(args: &[Value]) -> Result<Self, NativeError>;
+}
+
+#[derive(Clone)]
+struct Property {
+    get: Shared<dyn Getter>,
+    set: Option<Shared<dyn Setter>>,
+}
+
+#[derive(Clone)]
+struct Method {
+    arity: Arity,
+    call: Shared<dyn MethodFn>,
+}
+
+/// `Any` that can be stored in the interpreter.
+trait AnyObject: Any + ThreadSafe {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Any + ThreadSafe> AnyObject for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+trait Constructor: Fn(&[Value]) -> Result<Box<dyn AnyObject>, NativeError> + ThreadSafe {}
+trait Getter: Fn(&dyn AnyObject) -> Value + ThreadSafe {}
+trait Setter: Fn(&mut dyn AnyObject, &Value) -> Result<(), NativeError> + ThreadSafe {}
+trait MethodFn: Fn(&mut dyn AnyObject, &[Value]) -> Result<Value, NativeError> + ThreadSafe {}
+
+impl<F> Constructor for F where
+    F: Fn(&[Value]) -> Result<Box<dyn AnyObject>, NativeError> + ThreadSafe
+{
+}
+impl<F> Getter for F where F: Fn(&dyn AnyObject) -> Value + ThreadSafe {}
+impl<F: Fn(&mut dyn AnyObject, &Value) -> Result<(), NativeError> + ThreadSafe> Setter for F {}
+impl<F> MethodFn for F where
+    F: Fn(&mut dyn AnyObject, &[Value]) -> Result<Value, NativeError> + ThreadSafe
+{
+}
+
+fn downcast<T: 'static>(object: &dyn AnyObject) -> &T {
+    object
+        .as_any()
+        .downcast_ref()
+        .expect("object should be an instance of its class")
+}
+
+fn downcast_mut<T: 'static>(object: &mut dyn AnyObject) -> &mut T {
+    object
+        .as_any_mut()
+        .downcast_mut()
+        .expect("object should be an instance of its class")
+}
+
+impl<T: LoxClass> ClassBinding<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            constructor: None,
+            properties: Vec::new(),
+            methods: Vec::new(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Allow creating instances from Lox by calling the class, e.g. `Point(1, 2)`. Without a
+    /// constructor, instances can only be created by the host using `Lox::new_object`.
+    pub fn constructor<A, F>(&mut self, ctor: F) -> &mut Self
+    where
+        A: FromArgs,
+        F: Fn(A) -> Result<T, NativeError> + ThreadSafe + 'static,
+    {
+        let ctor = move |args: &[Value]| -> Result<Box<dyn AnyObject>, NativeError> {
+            Ok(Box::new(ctor(A::from_args(args)?)?))
+        };
+        self.constructor = Some((A::ARITY.into(), Shared::new(ctor)));
+        self
+    }
+
+    /// A property that can be read and assigned to.
+    pub fn property<V, G, S>(&mut self, name: &str, get: G, set: S) -> &mut Self
+    where
+        V: FromValue + IntoValue,
+        G: Fn(&T) -> V + ThreadSafe + 'static,
+        S: Fn(&mut T, V) + ThreadSafe + 'static,
+    {
+        let get = move |object: &dyn AnyObject| get(downcast(object)).into_value();
+        let set = move |object: &mut dyn AnyObject, value: &Value| {
+            set(downcast_mut(object), V::from_value(value)?);
+            Ok(())
+        };
+        let property = Property {
+            get: Shared::new(get),
+            set: Some(Shared::new(set)),
+        };
+        self.properties.push((name.to_owned(), property));
+        self
+    }
+
+    /// A property that can only be read.
+    pub fn getter<V, G>(&mut self, name: &str, get: G) -> &mut Self
+    where
+        V: IntoValue,
+        G: Fn(&T) -> V + ThreadSafe + 'static,
+    {
+        let get = move |object: &dyn AnyObject| get(downcast(object)).into_value();
+        let property = Property {
+            get: Shared::new(get),
+            set: None,
+        };
+        self.properties.push((name.to_owned(), property));
+        self
+    }
+
+    /// A method, called with the arguments converted into `A`.
+    pub fn method<A, R, F>(&mut self, name: &str, method: F) -> &mut Self
+    where
+        A: FromArgs,
+        R: IntoValue,
+        F: Fn(&mut T, A) -> Result<R, NativeError> + ThreadSafe + 'static,
+    {
+        let call = move |object: &mut dyn AnyObject, args: &[Value]| {
+            let result = method(downcast_mut(object), A::from_args(args)?)?;
+            Ok(result.into_value())
+        };
+        let method = Method {
+            arity: A::ARITY.into(),
+            call: Shared::new(call),
+        };
+        self.methods.push((name.to_owned(), method));
+        self
+    }
+
+    pub(crate) fn build(self, name: Key, interner: &mut Interner) -> HostClass {
+        let mut intern = |name: String| interner.get_or_intern(name);
+        HostClass {
+            name,
+            constructor: self.constructor,
+            properties: (self.properties.into_iter())
+                .map(|(name, prop)| (intern(name), prop))
+                .collect(),
+            methods: (self.methods.into_iter())
+                .map(|(name, method)| (intern(name), method))
+                .collect(),
+        }
+    }
+}
+
+impl HostClass {
+    /// The native function that creates an instance of the class, if it has a constructor.
+    pub fn constructor(self: &Shared<Self>) -> Option<Native> {
+        let (arity, ctor) = self.constructor.clone()?;
+        let class = Shared::clone(self);
+        let body = move |args: &[Value]| {
+            let object = HostObject {
+                class: Shared::clone(&class),
+                data: Lock::new(ctor(args)?),
+            };
+            Ok(Value::Object(Shared::new(object)))
+        };
+        Some(Native::host(self.name, arity, Shared::new(body)))
+    }
+}
+
+impl HostObject {
+    pub fn new<T: LoxClass>(class: Shared<HostClass>, value: T) -> Self {
+        let data: Box<dyn AnyObject> = Box::new(value);
+        Self {
+            class,
+            data: Lock::new(data),
+        }
+    }
+
+    /// Access the Rust value of the object, `None` if it's not a `T`.
+    pub fn with<T: LoxClass, R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let mut data = self.data.borrow_mut();
+        (**data).as_any_mut().downcast_mut().map(f)
+    }
+
+    /// Read a property, or get a method bound to this object.
+    pub fn get(self: &Shared<Self>, name: Key) -> Option<Value> {
+        if let Some(prop) = self.class.properties.get(&name) {
+            return Some((prop.get)(&**self.data.borrow()));
+        }
+
+        let Method { arity, call } = self.class.methods.get(&name)?.clone();
+        let object = Shared::clone(self);
+        let body = move |args: &[Value]| call(&mut **object.data.borrow_mut(), args);
+        let body: Shared<dyn HostFn> = Shared::new(body);
+        Some(Value::native_function(Native::host(name, arity, body)))
+    }
+
+    pub fn set(&self, name: Key, value: &Value) -> Result<(), SetError> {
+        match self.class.properties.get(&name) {
+            Some(Property { set: Some(set), .. }) => {
+                set(&mut **self.data.borrow_mut(), value).map_err(SetError::Invalid)
+            }
+            Some(Property { set: None, .. }) => Err(SetError::Invalid(NativeError::Custom(
+                "Property is read-only".to_owned(),
+            ))),
+            None => Err(SetError::Undefined),
+        }
+    }
+}
+
+impl Debug for HostObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HostObject").finish_non_exhaustive()
+    }
+}
+
+/// Objects are only equal to themselves.
+impl PartialEq for HostObject {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl PartialOrd for HostObject {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        (self == other).then_some(std::cmp::Ordering::Equal)
+    }
+}
+
+fn invalid_argument(expect: &'static str, value: &Value) -> NativeError {
+    NativeError::InvalidArgument {
+        expect,
+        got: value.name(),
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Result<Self, NativeError> {
+        Ok(value.clone())
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Result<Self, NativeError> {
+        match value {
+            Value::Bool(b) => Ok(*b),
+            value => Err(invalid_argument("<bool>", value)),
+        }
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Result<Self, NativeError> {
+        match value.as_number() {
+            Some(num) => Ok(num),
+            None => Err(invalid_argument("<number>", value)),
+        }
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: &Value) -> Result<Self, NativeError> {
+        match value.as_int() {
+            Some(int) => Ok(int),
+            None => Err(invalid_argument("<integer>", value)),
+        }
+    }
+}
+
+impl FromValue for usize {
+    fn from_value(value: &Value) -> Result<Self, NativeError> {
+        match value.as_int().and_then(|int| usize::try_from(int).ok()) {
+            Some(int) => Ok(int),
+            None => Err(invalid_argument("<non-negative integer>", value)),
+        }
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Result<Self, NativeError> {
+        match value {
+            Value::String(str) => Ok(str.to_string()),
+            value => Err(invalid_argument("<string>", value)),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> Result<Self, NativeError> {
+        match value {
+            Value::Nil => Ok(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        Value::nil()
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::bool(self)
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::number(self)
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Value {
+        Value::int(self)
+    }
+}
+
+impl IntoValue for usize {
+    fn into_value(self) -> Value {
+        match i64::try_from(self) {
+            Ok(int) => Value::int(int),
+            Err(_) => Value::number(self as f64),
+        }
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::string(self)
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        Value::string(self.to_owned())
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        match self {
+            Some(value) => value.into_value(),
+            None => Value::nil(),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        Value::list(self.into_iter().map(T::into_value).collect())
+    }
+}
+
+macro_rules! impl_from_args {
+    ($len:literal $(, $ty:ident $idx:literal)*) => {
+        impl<$($ty: FromValue),*> FromArgs for ($($ty,)*) {
+            const ARITY: usize = $len;
+
+            #[allow(unused_variables)]
+            fn from_args(args: &[Value]) -> Result<Self, NativeError> {
+                Ok(($($ty::from_value(&args[$idx])?,)*))
+            }
+        }
+    };
+}
+
+impl_from_args!(0);
+impl_from_args!(1, A 0);
+impl_from_args!(2, A 0, B 1);
+impl_from_args!(3, A 0, B 1, C 2);
+impl_from_args!(4, A 0, B 1, C 2, D 3);
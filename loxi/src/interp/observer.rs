@@ -0,0 +1,164 @@
+//! Hooks into the execution of a program, for tools like profilers, debuggers, and coverage.
+
+use std::fmt::Display;
+use std::ops::Deref;
+
+use thiserror::Error;
+
+use crate::lex::{LexError, Lexer, ScanResult};
+use crate::message;
+use crate::parse::stmt::Stmt;
+use crate::parse::{Parser, SyntaxError};
+use crate::util::shared::{Shared, ThreadSafe};
+use crate::util::Location;
+
+use super::env::Env;
+use super::function::Function;
+use super::value::Value;
+use super::{Interpreter, RuntimeError};
+
+/// Receives events as a program runs. Every method does nothing by default, so only the events
+/// that are needed have to be implemented.
+///
+/// The observer is called in the middle of the execution, so it should be cheap.
+pub trait Observer: ThreadSafe {
+    /// Called before a statement is executed, with the location of its start and the variables it
+    /// can see. Blocks are not reported, only the statements inside of them.
+    fn on_statement(&mut self, loc: Location, scope: &Scope<'_>) {
+        let _ = (loc, scope);
+    }
+
+    /// Called before a function, method, or class is called, with the call site.
+    fn on_call(&mut self, name: &str, loc: Location) {
+        let _ = (name, loc);
+    }
+
+    /// Called after a call reported by `on_call` finishes, whether it succeeded or not.
+    fn on_return(&mut self, name: &str, loc: Location) {
+        let _ = (name, loc);
+    }
+
+    /// Called after an expression is evaluated, with the location of its start and its value. The
+    /// parts of an expression are reported before the expression itself.
+    fn on_value(&mut self, loc: Location, value: &Value, scope: &Scope<'_>) {
+        let _ = (loc, value, scope);
+    }
+
+    /// Called after a unary, binary, or logical operator is applied, with the location and text of
+    /// the operator, its operands, and its result. The right operand of `and` and `or` is left out
+    /// when it's not evaluated.
+    fn on_operation(
+        &mut self,
+        loc: Location,
+        operator: &str,
+        operands: &[Value],
+        result: &Value,
+        scope: &Scope<'_>,
+    ) {
+        let _ = (loc, operator, operands, result, scope);
+    }
+}
+
+/// The variables visible to the statement about to be executed, e.g. for a debugger to show them.
+pub struct Scope<'a> {
+    interp: &'a Interpreter,
+}
+
+/// Error of evaluating an expression using `Scope::eval`.
+#[derive(Debug, Error)]
+pub enum EvalError {
+    Lex(LexError),
+    Syntax(SyntaxError),
+    NotExpression,
+    Unknown,
+    Runtime(#[from] RuntimeError),
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalError::Lex(err) => write!(f, "{err}"),
+            EvalError::Syntax(err) => write!(f, "{err}"),
+            EvalError::NotExpression => message::write(f, "eval.not-expression", &[]),
+            EvalError::Unknown => message::write(f, "eval.unknown", &[]),
+            EvalError::Runtime(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl<'a> Scope<'a> {
+    pub(crate) fn new(interp: &'a Interpreter) -> Self {
+        Self { interp }
+    }
+
+    /// The variables of every environment from the innermost one to the globals, each sorted by
+    /// name. Native functions are left out since they are not part of the state of the program.
+    pub fn chain(&self) -> Vec<Vec<(String, Value)>> {
+        let mut chain = Vec::new();
+        let mut env = Some(self.interp.dyn_env.current());
+        while let Some(current) = env {
+            chain.push(self.variables(&current));
+            env = current.enclosing.as_ref().map(Shared::clone);
+        }
+        chain
+    }
+
+    /// Evaluate the expression `source` as if it's written in place of the statement about to be
+    /// executed. The interner can't change while the program is running, so only the names and
+    /// strings that already appear in the program can be used.
+    pub fn eval(&self, source: &str) -> Result<Value, EvalError> {
+        let interp = self.interp;
+        let source = format!("{};", source.trim().trim_end_matches(';'));
+
+        let mut interner = interp.interner.clone();
+        let ScanResult { tokens, errors, .. } = Lexer::new(&source, &mut interner).scan();
+        if let Some(err) = errors.into_iter().next() {
+            return Err(EvalError::Lex(err));
+        }
+        if interner.len() != interp.interner.len() {
+            return Err(EvalError::Unknown);
+        }
+
+        let program = Parser::new().parse(tokens).map_err(|errors| {
+            let err = errors.into_iter().next();
+            EvalError::Syntax(err.expect("there should be at least one error"))
+        })?;
+        let expr = match program.statements.as_slice() {
+            [Stmt::Expr { expr }] => expr,
+            _ => return Err(EvalError::NotExpression),
+        };
+
+        // the expression is not resolved, so its variables are looked up by name
+        interp.scope_eval.set(true);
+        let value = interp.eval(expr);
+        interp.scope_eval.set(false);
+        Ok(value?.resolved(&interp.interner))
+    }
+
+    /// Display a value the same way `print` does.
+    pub fn display(&self, value: &Value) -> String {
+        self.interp.display(value).to_string()
+    }
+
+    fn variables(&self, env: &Env) -> Vec<(String, Value)> {
+        let interner = &self.interp.interner;
+        let mut variables = env
+            .variables()
+            .into_iter()
+            .filter(|(_, value)| !is_native(value))
+            .map(|(key, value)| {
+                let name = interner.resolve(key).to_owned();
+                (name, value.resolved(interner))
+            })
+            .collect::<Vec<_>>();
+        variables.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+        variables
+    }
+}
+
+fn is_native(value: &Value) -> bool {
+    match value {
+        Value::Function(func) => matches!(func.deref(), Function::Native(_)),
+        _ => false,
+    }
+}
@@ -0,0 +1,37 @@
+//! Output sinks that capture what a program writes, e.g. to return it from an embedding API.
+
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// An output sink that can still be read after it's given to the interpreter: every clone writes
+/// to the same buffer. It's `Send` and `Sync`, so it also works with the `arc` feature.
+#[derive(Clone, Debug, Default)]
+pub struct OutputBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl OutputBuffer {
+    /// The output written so far, with invalid UTF-8 replaced by `U+FFFD`.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.bytes()).into_owned()
+    }
+
+    /// Forget the output written so far.
+    pub fn clear(&self) {
+        self.bytes().clear();
+    }
+
+    // a panic can't happen while the lock is held, so the buffer is still valid if it's poisoned
+    fn bytes(&self) -> MutexGuard<'_, Vec<u8>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Write for OutputBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.bytes().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
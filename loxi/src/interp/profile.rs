@@ -0,0 +1,109 @@
+//! Measures how often each function is called and how long it takes, for profiling.
+
+use std::fmt::Display;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rustc_hash::FxHashMap;
+
+use crate::util::Location;
+
+use super::observer::Observer;
+
+/// Counts the calls of each function, method, class, and native, and the time spent in them.
+/// Clones share the counts, so one can be given to `Lox::set_observer` while another is kept to
+/// build the report after the program finishes.
+#[derive(Clone, Debug, Default)]
+pub struct Profiler {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// The calls that haven't returned yet, innermost last.
+    stack: Vec<(String, Instant)>,
+    functions: FxHashMap<String, FunctionProfile>,
+}
+
+/// The calls of every function that was called, the slowest first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProfileReport {
+    pub functions: Vec<FunctionProfile>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionProfile {
+    pub name: String,
+    pub calls: usize,
+
+    /// The time from the call to the return, including the functions it calls. The time of a
+    /// recursive call is only counted once, by the outermost call.
+    pub time: Duration,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn report(&self) -> ProfileReport {
+        let state = self.state.lock().expect("lock should not be poisoned");
+        let mut functions = state.functions.values().cloned().collect::<Vec<_>>();
+        functions.sort_by(|a, b| b.time.cmp(&a.time).then_with(|| a.name.cmp(&b.name)));
+        ProfileReport { functions }
+    }
+}
+
+impl Observer for Profiler {
+    fn on_call(&mut self, name: &str, _loc: Location) {
+        let mut state = self.state.lock().expect("lock should not be poisoned");
+        state.stack.push((name.to_owned(), Instant::now()));
+        state
+            .functions
+            .entry(name.to_owned())
+            .or_insert_with(|| FunctionProfile {
+                name: name.to_owned(),
+                calls: 0,
+                time: Duration::ZERO,
+            })
+            .calls += 1;
+    }
+
+    fn on_return(&mut self, _name: &str, _loc: Location) {
+        let mut state = self.state.lock().expect("lock should not be poisoned");
+        let Some((name, start)) = state.stack.pop() else {
+            return;
+        };
+        if state.stack.iter().any(|(outer, _)| *outer == name) {
+            return;
+        }
+        if let Some(function) = state.functions.get_mut(&name) {
+            function.time += start.elapsed();
+        }
+    }
+}
+
+/// A table with a row for each function, aligned to the longest name.
+impl Display for ProfileReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let width = self
+            .functions
+            .iter()
+            .map(|function| function.name.len())
+            .chain(["Function".len()])
+            .max()
+            .unwrap_or_default();
+
+        writeln!(
+            f,
+            "{:width$}  {:>10}  {:>12}",
+            "Function", "Calls", "Time (ms)"
+        )?;
+        for function in self.functions.iter() {
+            let FunctionProfile { name, calls, time } = function;
+            let time = time.as_secs_f64() * 1000.0;
+            writeln!(f, "{name:width$}  {calls:>10}  {time:>12.3}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,427 @@
+//! Saves the globals of a program to bytes and reads them back, so a session can be continued
+//! later, e.g. after restarting the host.
+//!
+//! The format is `LOXS`, a version byte, the declarations, then the globals. Functions and classes
+//! are saved as their declaration printed back as Lox, which is run again when restored. Every
+//! count and length is a little-endian `u32`, numbers are little-endian `f64`.
+
+use std::fmt::Display;
+use std::ops::Deref;
+
+use rustc_hash::FxHashMap;
+use thiserror::Error;
+
+use crate::message;
+use crate::minify::minify;
+use crate::parse::stmt::{Stmt, StmtFunction};
+use crate::parse::Program;
+use crate::util::shared::Shared;
+
+use super::class::Class;
+use super::function::{Function, Kind, UserDefined};
+use super::interner::Key;
+use super::map::{Map, MapKey};
+use super::value::Value;
+use super::Interpreter;
+
+const MAGIC: &[u8] = b"LOXS";
+const VERSION: u8 = 1;
+
+const NIL: u8 = 0;
+const FALSE: u8 = 1;
+const TRUE: u8 = 2;
+const NUMBER: u8 = 3;
+const STRING: u8 = 4;
+const LIST: u8 = 5;
+const MAP: u8 = 6;
+const DECLARATION: u8 = 7;
+const INT: u8 = 8;
+
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    /// The bytes are not a snapshot, or it's cut short.
+    Invalid,
+
+    /// The snapshot is made by another version of the interpreter.
+    Version(u8),
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotError::Invalid => message::write(f, "snapshot.invalid", &[]),
+            SnapshotError::Version(version) => {
+                message::write(f, "snapshot.version", &[("version", version)])
+            }
+        }
+    }
+}
+
+/// A snapshot read back from bytes. The declarations have to be run, in order, before the globals
+/// can be turned into values.
+pub(crate) struct Snapshot {
+    /// The source of each declaration, along with the name of the global it defines.
+    pub declarations: Vec<(String, String)>,
+    pub globals: Vec<(String, Saved)>,
+}
+
+pub(crate) enum Saved {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    Int(i64),
+    String(String),
+    List(Vec<Saved>),
+    Map(Vec<(Saved, Saved)>),
+
+    /// The value defined by the declaration at this index.
+    Declaration(usize),
+}
+
+/// Save the globals of `interp`, sorted by name. Natives and namespaces are left out since every
+/// interpreter already has them, and so are the values that can't be saved: instances, host
+/// objects, and functions that are not declared at the top level. Lists and maps are saved by
+/// value, so globals that share one get a copy each when restored, and the ones containing
+/// themselves are left out too.
+pub(crate) fn save(interp: &Interpreter) -> Vec<u8> {
+    let mut saver = Saver {
+        interp,
+        out: Vec::new(),
+        declarations: Vec::new(),
+        declared: FxHashMap::default(),
+        visiting: Vec::new(),
+    };
+
+    let mut globals = interp
+        .dyn_env
+        .global()
+        .values
+        .borrow()
+        .iter()
+        .map(|(key, value)| (interp.interner.resolve(*key).to_owned(), value.clone()))
+        .collect::<Vec<_>>();
+    globals.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+
+    let mut saved = Vec::new();
+    for (name, value) in globals {
+        let declarations = saver.declarations.len();
+        match saver.value(&value) {
+            true => saved.push((name, std::mem::take(&mut saver.out))),
+            false => {
+                // the declarations found in a value that can't be saved are not needed
+                saver.out.clear();
+                saver.declarations.truncate(declarations);
+                saver.declared.retain(|_, index| *index < declarations);
+            }
+        }
+    }
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(VERSION);
+    write_len(&mut bytes, saver.declarations.len());
+    for (name, source) in saver.declarations.iter() {
+        write_str(&mut bytes, name);
+        write_str(&mut bytes, source);
+    }
+    write_len(&mut bytes, saved.len());
+    for (name, value) in saved {
+        write_str(&mut bytes, &name);
+        bytes.extend(value);
+    }
+    bytes
+}
+
+/// Read back the bytes made by `save`.
+pub(crate) fn load(bytes: &[u8]) -> Result<Snapshot, SnapshotError> {
+    let mut reader = Reader { bytes };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(SnapshotError::Invalid);
+    }
+    match reader.byte()? {
+        VERSION => (),
+        version => return Err(SnapshotError::Version(version)),
+    }
+
+    let mut declarations = Vec::new();
+    for _ in 0..reader.len()? {
+        declarations.push((reader.string()?, reader.string()?));
+    }
+    let mut globals = Vec::new();
+    for _ in 0..reader.len()? {
+        globals.push((reader.string()?, reader.value(declarations.len())?));
+    }
+
+    match reader.bytes.is_empty() {
+        true => Ok(Snapshot {
+            declarations,
+            globals,
+        }),
+        false => Err(SnapshotError::Invalid),
+    }
+}
+
+impl Saved {
+    pub fn into_value(self, declared: &[Value]) -> Value {
+        match self {
+            Saved::Nil => Value::nil(),
+            Saved::Bool(bool) => Value::bool(bool),
+            Saved::Number(num) => Value::number(num),
+            Saved::Int(int) => Value::int(int),
+            Saved::String(str) => Value::string(str),
+            Saved::List(values) => Value::list(
+                values
+                    .into_iter()
+                    .map(|value| value.into_value(declared))
+                    .collect(),
+            ),
+            Saved::Map(entries) => {
+                let mut map = Map::default();
+                for (key, value) in entries {
+                    let key = MapKey::from_resolved(&key.into_value(declared))
+                        .expect("the keys of a saved map should be valid keys");
+                    map.insert(key, value.into_value(declared));
+                }
+                Value::map(map)
+            }
+            Saved::Declaration(index) => declared[index].clone(),
+        }
+    }
+}
+
+struct Saver<'a> {
+    interp: &'a Interpreter,
+    out: Vec<u8>,
+
+    /// The source of the declarations found so far, and their index by the address of their value.
+    declarations: Vec<(String, String)>,
+    declared: FxHashMap<usize, usize>,
+
+    /// The addresses of the lists and maps being saved, to find the ones containing themselves.
+    visiting: Vec<usize>,
+}
+
+impl Saver<'_> {
+    /// Write `value`, returns false if it can't be saved.
+    fn value(&mut self, value: &Value) -> bool {
+        let interner = &self.interp.interner;
+        match value {
+            Value::Nil => self.out.push(NIL),
+            Value::Bool(false) => self.out.push(FALSE),
+            Value::Bool(true) => self.out.push(TRUE),
+            Value::Number(num) => {
+                self.out.push(NUMBER);
+                self.out.extend(num.to_le_bytes());
+            }
+            Value::Int(int) => {
+                self.out.push(INT);
+                self.out.extend(int.to_le_bytes());
+            }
+            Value::String(str) => {
+                self.out.push(STRING);
+                write_str(&mut self.out, str);
+            }
+            Value::StringLiteral(key) => {
+                self.out.push(STRING);
+                write_str(&mut self.out, interner.resolve(*key));
+            }
+            Value::List(list) => {
+                let address = Shared::as_ptr(list) as usize;
+                if self.visiting.contains(&address) {
+                    return false;
+                }
+                self.visiting.push(address);
+                let list = list.borrow();
+                self.out.push(LIST);
+                write_len(&mut self.out, list.len());
+                let ok = list.iter().all(|value| self.value(value));
+                self.visiting.pop();
+                return ok;
+            }
+            Value::Map(map) => {
+                let address = Shared::as_ptr(map) as usize;
+                if self.visiting.contains(&address) {
+                    return false;
+                }
+                self.visiting.push(address);
+                let map = map.borrow();
+                self.out.push(MAP);
+                write_len(&mut self.out, map.len());
+                let ok = map
+                    .iter()
+                    .all(|(key, value)| self.value(&key.to_value()) && self.value(value));
+                self.visiting.pop();
+                return ok;
+            }
+            Value::Function(func) => {
+                let address = Shared::as_ptr(func) as usize;
+                let declaration = match func.deref() {
+                    Function::UserDefined(func) => self.function(func),
+                    Function::Native(_) => None,
+                };
+                return self.declaration(address, declaration);
+            }
+            Value::Class(class) => {
+                let address = Shared::as_ptr(class) as usize;
+                let declaration = self.class(class);
+                return self.declaration(address, declaration);
+            }
+            Value::Instance(_) | Value::Namespace(_) | Value::Object(_) => return false,
+        }
+        true
+    }
+
+    /// Write a reference to the declaration of the value at `address`, adding the declaration if
+    /// it's not added yet.
+    fn declaration(&mut self, address: usize, declaration: Option<(String, String)>) -> bool {
+        let index = match self.declared.get(&address) {
+            Some(index) => *index,
+            None => {
+                let Some(declaration) = declaration else {
+                    return false;
+                };
+                self.declarations.push(declaration);
+                self.declared.insert(address, self.declarations.len() - 1);
+                self.declarations.len() - 1
+            }
+        };
+        self.out.push(DECLARATION);
+        write_len(&mut self.out, index);
+        true
+    }
+
+    /// A function can only be declared again if it can't see any local, i.e. it's declared at the
+    /// top level.
+    fn function(&self, func: &UserDefined) -> Option<(String, String)> {
+        let global = self.interp.dyn_env.global();
+        if func.kind != Kind::Function || !Shared::ptr_eq(&func.capture, global) {
+            return None;
+        }
+        let stmt = Stmt::Function {
+            func: declaration(func),
+        };
+        Some(self.print(func.name, stmt))
+    }
+
+    fn class(&self, class: &Class) -> Option<(String, String)> {
+        let mut methods = Vec::new();
+        for method in class.constructor.iter().chain(class.methods.values()) {
+            let Function::UserDefined(method) = method.deref() else {
+                return None;
+            };
+            if !Shared::ptr_eq(&method.capture, self.interp.dyn_env.global()) {
+                return None;
+            }
+            methods.push(declaration(method));
+        }
+        methods.sort_by_key(|method| method.loc);
+
+        let stmt = Stmt::Class {
+            loc: class.loc,
+            name: class.name,
+            name_loc: class.loc,
+            methods: methods.into(),
+        };
+        Some(self.print(class.name, stmt))
+    }
+
+    fn print(&self, name: Key, stmt: Stmt) -> (String, String) {
+        let interp = self.interp;
+        let program = Program {
+            statements: vec![stmt],
+        };
+        let source = minify(&program, &interp.resolve_map, &interp.interner, false);
+        (interp.interner.resolve(name).to_owned(), source)
+    }
+}
+
+fn declaration(func: &UserDefined) -> StmtFunction {
+    let params = func.params.iter().map(|param| (*param, func.loc)).collect();
+    StmtFunction::new((func.name, func.loc), params, func.body.clone(), func.loc)
+}
+
+fn write_len(out: &mut Vec<u8>, len: usize) {
+    let len = u32::try_from(len).expect("a snapshot should not have more than u32::MAX items");
+    out.extend(len.to_le_bytes());
+}
+
+fn write_str(out: &mut Vec<u8>, str: &str) {
+    write_len(out, str.len());
+    out.extend(str.as_bytes());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.bytes.len() < len {
+            return Err(SnapshotError::Invalid);
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn len(&mut self) -> Result<usize, SnapshotError> {
+        let bytes = self.take(4)?.try_into().expect("should take 4 bytes");
+        Ok(u32::from_le_bytes(bytes) as usize)
+    }
+
+    fn string(&mut self) -> Result<String, SnapshotError> {
+        let len = self.len()?;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| SnapshotError::Invalid)
+    }
+
+    fn value(&mut self, declarations: usize) -> Result<Saved, SnapshotError> {
+        let saved = match self.byte()? {
+            NIL => Saved::Nil,
+            FALSE => Saved::Bool(false),
+            TRUE => Saved::Bool(true),
+            NUMBER => {
+                let bytes = self.take(8)?.try_into().expect("should take 8 bytes");
+                Saved::Number(f64::from_le_bytes(bytes))
+            }
+            INT => {
+                let bytes = self.take(8)?.try_into().expect("should take 8 bytes");
+                Saved::Int(i64::from_le_bytes(bytes))
+            }
+            STRING => Saved::String(self.string()?),
+            LIST => {
+                let len = self.len()?;
+                let values = (0..len)
+                    .map(|_| self.value(declarations))
+                    .collect::<Result<_, _>>()?;
+                Saved::List(values)
+            }
+            MAP => {
+                let len = self.len()?;
+                let mut entries = Vec::new();
+                for _ in 0..len {
+                    let key = self.value(declarations)?;
+                    let valid = match key {
+                        Saved::Nil | Saved::Bool(_) | Saved::Int(_) | Saved::String(_) => true,
+                        Saved::Number(num) => !num.is_nan(),
+                        _ => false,
+                    };
+                    if !valid {
+                        return Err(SnapshotError::Invalid);
+                    }
+                    entries.push((key, self.value(declarations)?));
+                }
+                Saved::Map(entries)
+            }
+            DECLARATION => match self.len()? {
+                index if index < declarations => Saved::Declaration(index),
+                _ => return Err(SnapshotError::Invalid),
+            },
+            _ => return Err(SnapshotError::Invalid),
+        };
+        Ok(saved)
+    }
+}
@@ -0,0 +1,280 @@
+//! Formatting values into strings with `{}` placeholders, e.g. to print tables.
+
+use std::io::Write;
+use std::iter;
+
+use super::string_arg;
+use crate::interp::function::{Arity, NativeError};
+use crate::interp::value::Value;
+use crate::interp::Interpreter;
+
+/// The largest width or precision of a placeholder, so a typo can't make a huge string.
+const MAX_WIDTH: usize = u16::MAX as usize;
+
+pub(super) fn install(interp: &mut Interpreter) {
+    interp.define_native("format", Arity::AtLeast(1), format);
+    interp.define_native("printf", Arity::AtLeast(1), printf);
+}
+
+enum Piece<'a> {
+    Text(&'a str),
+    Brace(char),
+    Placeholder(Spec),
+}
+
+struct Spec {
+    fill: char,
+    align: Option<Align>,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+    kind: Kind,
+}
+
+#[derive(Clone, Copy)]
+enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Display,
+    Hex,
+    UpperHex,
+    Octal,
+    Binary,
+    Exp,
+}
+
+/// `format(fmt, ...)` replaces each `{}` in `fmt` with the next argument as `print` shows it, and
+/// `{{` and `}}` with a brace. A placeholder may have a spec after a colon, in the form of
+/// `{:[[fill]align][0][width][.precision][type]}`:
+///
+/// - `align` is `<`, `>`, or `^`, numbers are aligned to the right by default and the rest to the
+///   left. `fill` is the character used to pad, a space by default.
+/// - `0` pads numbers with zeros after the sign instead.
+/// - `precision` is the number of decimals of a number, or the maximum length of a string.
+/// - `type` is `x`, `X`, `o`, or `b` to show an integer in another base, or `e` to show a number
+///   in the scientific notation.
+///
+/// The width and the precision are at most 65535.
+///
+/// The number of arguments must match the number of placeholders.
+fn format(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    Ok(Value::string(format_args(interp, args)?))
+}
+
+/// `printf(fmt, ...)` prints the result of `format` followed by a newline, like `print`.
+fn printf(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let str = format_args(interp, args)?;
+    writeln!(interp.stdout(), "{str}")?;
+    Ok(Value::nil())
+}
+
+fn format_args(interp: &Interpreter, args: &[Value]) -> Result<String, NativeError> {
+    let pieces = parse(string_arg(interp, args, 0)?)?;
+
+    let values = &args[1..];
+    let placeholders = pieces
+        .iter()
+        .filter(|piece| matches!(piece, Piece::Placeholder(_)))
+        .count();
+    if placeholders != values.len() {
+        return Err(NativeError::FormatArguments {
+            expect: placeholders,
+            got: values.len(),
+        });
+    }
+
+    let mut out = String::new();
+    let mut values = values.iter();
+    for piece in pieces {
+        match piece {
+            Piece::Text(text) => out.push_str(text),
+            Piece::Brace(brace) => out.push(brace),
+            Piece::Placeholder(spec) => {
+                // counted before rendering since the width or precision can be huge
+                let len = spec.width.saturating_add(spec.precision.unwrap_or(0));
+                interp.memory.alloc_string(len)?;
+
+                let value = values.next().expect("arguments should have been counted");
+                out.push_str(&render(interp, value, &spec)?);
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn parse(fmt: &str) -> Result<Vec<Piece<'_>>, NativeError> {
+    let mut pieces = Vec::new();
+    let mut rest = fmt;
+
+    while let Some(start) = rest.find(['{', '}']) {
+        pieces.push(Piece::Text(&rest[..start]));
+        let index = fmt.len() - rest.len() + start;
+        let invalid = || NativeError::InvalidFormat {
+            index: fmt[..index].chars().count(),
+        };
+
+        let (brace, after) = rest[start..].split_at(1);
+        if after.starts_with(brace) {
+            pieces.push(Piece::Brace(if brace == "{" { '{' } else { '}' }));
+            rest = &after[1..];
+            continue;
+        }
+        if brace == "}" {
+            return Err(invalid());
+        }
+
+        let end = after.find('}').ok_or_else(invalid)?;
+        let spec = parse_spec(&after[..end]).ok_or_else(invalid)?;
+        pieces.push(Piece::Placeholder(spec));
+        rest = &after[end + 1..];
+    }
+
+    pieces.push(Piece::Text(rest));
+    Ok(pieces)
+}
+
+/// Parse what's between the braces of a placeholder.
+fn parse_spec(inner: &str) -> Option<Spec> {
+    let mut spec = Spec {
+        fill: ' ',
+        align: None,
+        zero: false,
+        width: 0,
+        precision: None,
+        kind: Kind::Display,
+    };
+    if inner.is_empty() {
+        return Some(spec);
+    }
+
+    let mut rest = inner.strip_prefix(':')?;
+    let mut chars = rest.chars();
+    match (chars.next(), chars.next().and_then(align)) {
+        (Some(fill), Some(align)) => {
+            spec.fill = fill;
+            spec.align = Some(align);
+            rest = &rest[fill.len_utf8() + 1..];
+        }
+        (first, _) => {
+            if let Some(align) = first.and_then(align) {
+                spec.align = Some(align);
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    if let Some(after) = rest.strip_prefix('0') {
+        spec.zero = true;
+        rest = after;
+    }
+    let (width, after) = digits(rest);
+    spec.width = match width {
+        "" => 0,
+        width => number(width)?,
+    };
+    rest = after;
+    if let Some(after) = rest.strip_prefix('.') {
+        let (precision, after) = digits(after);
+        spec.precision = Some(number(precision)?);
+        rest = after;
+    }
+
+    spec.kind = match rest {
+        "" => Kind::Display,
+        "x" => Kind::Hex,
+        "X" => Kind::UpperHex,
+        "o" => Kind::Octal,
+        "b" => Kind::Binary,
+        "e" => Kind::Exp,
+        _ => return None,
+    };
+    Some(spec)
+}
+
+fn align(ch: char) -> Option<Align> {
+    match ch {
+        '<' => Some(Align::Left),
+        '>' => Some(Align::Right),
+        '^' => Some(Align::Center),
+        _ => None,
+    }
+}
+
+/// Split the leading digits of `text`, if any, from the rest.
+fn digits(text: &str) -> (&str, &str) {
+    let end = text
+        .find(|ch: char| !ch.is_ascii_digit())
+        .unwrap_or(text.len());
+    text.split_at(end)
+}
+
+/// Parse a width or a precision, `None` if there are no digits or it's over `MAX_WIDTH`.
+fn number(digits: &str) -> Option<usize> {
+    digits.parse().ok().filter(|number| *number <= MAX_WIDTH)
+}
+
+fn render(interp: &Interpreter, value: &Value, spec: &Spec) -> Result<String, NativeError> {
+    let number = || {
+        value.as_number().ok_or(NativeError::InvalidArgument {
+            expect: "<number>",
+            got: value.name(),
+        })
+    };
+
+    let body = match spec.kind {
+        Kind::Display => match (value.as_number(), spec.precision) {
+            (Some(num), Some(precision)) => format!("{num:.precision$}"),
+            (None, Some(precision)) => {
+                let str = interp.display(value).to_string();
+                str.chars().take(precision).collect()
+            }
+            (_, None) => interp.display(value).to_string(),
+        },
+        Kind::Exp => match spec.precision {
+            Some(precision) => format!("{:.precision$e}", number()?),
+            None => format!("{:e}", number()?),
+        },
+        Kind::Hex | Kind::UpperHex | Kind::Octal | Kind::Binary => {
+            let int = value.as_int().ok_or(NativeError::InvalidArgument {
+                expect: "<integer>",
+                got: value.name(),
+            })?;
+            let (sign, abs) = (if int < 0 { "-" } else { "" }, int.unsigned_abs());
+            match spec.kind {
+                Kind::Hex => format!("{sign}{abs:x}"),
+                Kind::UpperHex => format!("{sign}{abs:X}"),
+                Kind::Octal => format!("{sign}{abs:o}"),
+                _ => format!("{sign}{abs:b}"),
+            }
+        }
+    };
+
+    let len = body.chars().count();
+    if len >= spec.width {
+        return Ok(body);
+    }
+    let padding = spec.width - len;
+
+    let numeric = value.as_number().is_some();
+    if spec.zero && numeric {
+        let (sign, digits) = match body.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None => ("", body.as_str()),
+        };
+        return Ok(format!("{sign}{}{digits}", "0".repeat(padding)));
+    }
+
+    let default = if numeric { Align::Right } else { Align::Left };
+    let (before, after) = match spec.align.unwrap_or(default) {
+        Align::Left => (0, padding),
+        Align::Right => (padding, 0),
+        Align::Center => (padding / 2, padding - padding / 2),
+    };
+    let fill = |count| iter::repeat_n(spec.fill, count).collect::<String>();
+    Ok(format!("{}{body}{}", fill(before), fill(after)))
+}
@@ -0,0 +1,46 @@
+//! File system natives. They are only installed when the `fs` capability is enabled. Relative paths
+//! are resolved against the working directory of the host process.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use super::string_arg;
+use crate::interp::function::NativeError;
+use crate::interp::value::Value;
+use crate::interp::Interpreter;
+
+pub(super) fn install(interp: &mut Interpreter) {
+    interp.define_native("readFile", 1, read_file);
+    interp.define_native("writeFile", 2, write_file);
+    interp.define_native("appendFile", 2, append_file);
+    interp.define_native("exists", 1, exists);
+}
+
+/// Read the whole file as a string.
+fn read_file(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let path = string_arg(interp, args, 0)?;
+    Ok(Value::string(fs::read_to_string(path)?))
+}
+
+/// Write the string into the file, replacing its content. The file is created if it doesn't exist.
+fn write_file(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let path = string_arg(interp, args, 0)?;
+    let text = string_arg(interp, args, 1)?;
+    fs::write(path, text)?;
+    Ok(Value::nil())
+}
+
+/// Append the string to the end of the file. The file is created if it doesn't exist.
+fn append_file(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let path = string_arg(interp, args, 0)?;
+    let text = string_arg(interp, args, 1)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(text.as_bytes())?;
+    Ok(Value::nil())
+}
+
+fn exists(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let path = string_arg(interp, args, 0)?;
+    Ok(Value::bool(Path::new(path).exists()))
+}
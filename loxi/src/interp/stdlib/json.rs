@@ -0,0 +1,365 @@
+//! Converting between JSON text and values, e.g. to read config files. Objects become maps with
+//! string keys and arrays become lists.
+
+use std::fmt::Write;
+
+use super::string_arg;
+use crate::interp::function::NativeError;
+use crate::interp::map::{Map, MapKey};
+use crate::interp::value::Value;
+use crate::interp::Interpreter;
+
+/// Arrays and objects nested deeper than this are rejected, so deep input can't overflow the stack.
+/// Cyclic lists and maps also end up here when serialized.
+const MAX_DEPTH: usize = 512;
+
+pub(super) fn install(interp: &mut Interpreter) {
+    interp.define_native("jsonParse", 1, json_parse);
+    interp.define_native("jsonStringify", 1, json_stringify);
+}
+
+/// `jsonParse(text)` converts JSON text to a value. `null` becomes `nil`, and numbers without a
+/// fraction or an exponent become integers if they fit.
+fn json_parse(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let text = string_arg(interp, args, 0)?;
+    let mut parser = Parser {
+        interp,
+        text,
+        pos: 0,
+        depth: 0,
+    };
+
+    let value = parser.value()?;
+    parser.whitespace();
+    if parser.pos < text.len() {
+        return Err(parser.expected("<end of input>"));
+    }
+    Ok(value)
+}
+
+/// `jsonStringify(value)` converts a value to compact JSON text. Map keys that are not strings are
+/// converted to strings as `print` shows them. Functions, classes, instances, `nan`, and `inf` have
+/// no JSON representation.
+fn json_stringify(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let mut out = String::new();
+    stringify(interp, &args[0], &mut out, 0)?;
+    interp.memory.alloc_string(out.len())?;
+    Ok(Value::string(out))
+}
+
+struct Parser<'a> {
+    interp: &'a Interpreter,
+    text: &'a str,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn value(&mut self) -> Result<Value, NativeError> {
+        self.whitespace();
+        match self.peek() {
+            Some(b'{') => self.nested(Self::object),
+            Some(b'[') => self.nested(Self::array),
+            Some(b'"') => {
+                let str = self.string()?;
+                self.interp.memory.alloc_string(str.len())?;
+                Ok(Value::string(str))
+            }
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(b't') => self.word("true", Value::bool(true)),
+            Some(b'f') => self.word("false", Value::bool(false)),
+            Some(b'n') => self.word("null", Value::nil()),
+            _ => Err(self.expected("<value>")),
+        }
+    }
+
+    fn nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<Value, NativeError>,
+    ) -> Result<Value, NativeError> {
+        if self.depth == MAX_DEPTH {
+            return Err(NativeError::JsonDepth(MAX_DEPTH));
+        }
+        self.depth += 1;
+        self.pos += 1;
+        let value = parse(self)?;
+        self.depth -= 1;
+        Ok(value)
+    }
+
+    fn object(&mut self) -> Result<Value, NativeError> {
+        let mut map = Map::default();
+        self.whitespace();
+        if self.eat(b'}') {
+            return Ok(Value::map(map));
+        }
+
+        loop {
+            self.whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.expected("<string>"));
+            }
+            let key = MapKey::String(self.string()?.into());
+            self.whitespace();
+            if !self.eat(b':') {
+                return Err(self.expected("':'"));
+            }
+            let value = self.value()?;
+
+            if !map.contains(&key) {
+                self.interp.memory.alloc_elements(map.len() + 1, 1)?;
+            }
+            map.insert(key, value);
+
+            self.whitespace();
+            if self.eat(b'}') {
+                return Ok(Value::map(map));
+            }
+            if !self.eat(b',') {
+                return Err(self.expected("',' or '}'"));
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, NativeError> {
+        let mut list = Vec::new();
+        self.whitespace();
+        if self.eat(b']') {
+            return Ok(Value::list(list));
+        }
+
+        loop {
+            let value = self.value()?;
+            self.interp.memory.alloc_elements(list.len() + 1, 1)?;
+            list.push(value);
+
+            self.whitespace();
+            if self.eat(b']') {
+                return Ok(Value::list(list));
+            }
+            if !self.eat(b',') {
+                return Err(self.expected("',' or ']'"));
+            }
+        }
+    }
+
+    /// Parse a string, starting at its opening quote.
+    fn string(&mut self) -> Result<String, NativeError> {
+        self.pos += 1;
+        let mut str = String::new();
+
+        loop {
+            let rest = &self.text[self.pos..];
+            let end = rest
+                .find(|ch: char| ch == '"' || ch == '\\' || ch < ' ')
+                .ok_or_else(|| self.expected_at(self.text.len(), "'\"'"))?;
+            str.push_str(&rest[..end]);
+            self.pos += end;
+
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(str);
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    str.push(self.escape()?);
+                }
+                _ => return Err(self.expected("'\"'")),
+            }
+        }
+    }
+
+    /// Parse an escape sequence after its backslash.
+    fn escape(&mut self) -> Result<char, NativeError> {
+        let ch = match self.peek() {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{8}',
+            Some(b'f') => '\u{c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+                self.pos += 1;
+                return self.unicode_escape();
+            }
+            _ => return Err(self.expected("<escape>")),
+        };
+        self.pos += 1;
+        Ok(ch)
+    }
+
+    /// Parse the hex digits of a `\u` escape, and the low surrogate after it if it's a high one.
+    fn unicode_escape(&mut self) -> Result<char, NativeError> {
+        let start = self.pos;
+        let high = self.hex4()?;
+        let code = match high {
+            0xd800..=0xdbff => {
+                if !self.text[self.pos..].starts_with("\\u") {
+                    return Err(self.expected_at(start, "<escape>"));
+                }
+                self.pos += 2;
+                match self.hex4()? {
+                    low @ 0xdc00..=0xdfff => 0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00),
+                    _ => return Err(self.expected_at(start, "<escape>")),
+                }
+            }
+            code => code,
+        };
+        char::from_u32(code).ok_or_else(|| self.expected_at(start, "<escape>"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, NativeError> {
+        let digits = self.text.get(self.pos..self.pos + 4).unwrap_or_default();
+        if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(self.expected("<escape>"));
+        }
+        self.pos += 4;
+        Ok(u32::from_str_radix(digits, 16).expect("should be four hex digits"))
+    }
+
+    fn number(&mut self) -> Result<Value, NativeError> {
+        let start = self.pos;
+        self.eat(b'-');
+        if !self.eat(b'0') && self.digits() == 0 {
+            return Err(self.expected("<digit>"));
+        }
+
+        let mut integral = true;
+        if self.eat(b'.') {
+            integral = false;
+            if self.digits() == 0 {
+                return Err(self.expected("<digit>"));
+            }
+        }
+        if self.eat(b'e') || self.eat(b'E') {
+            integral = false;
+            let _ = self.eat(b'+') || self.eat(b'-');
+            if self.digits() == 0 {
+                return Err(self.expected("<digit>"));
+            }
+        }
+
+        let text = &self.text[start..self.pos];
+        match text.parse::<i64>() {
+            Ok(int) if integral => Ok(Value::int(int)),
+            _ => Ok(Value::number(
+                text.parse().expect("should be a valid float"),
+            )),
+        }
+    }
+
+    /// Skip the digits at the current position and return how many there are.
+    fn digits(&mut self) -> usize {
+        let start = self.pos;
+        while self.peek().is_some_and(|b| b.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+
+    fn word(&mut self, word: &'static str, value: Value) -> Result<Value, NativeError> {
+        if !self.text[self.pos..].starts_with(word) {
+            return Err(self.expected("<value>"));
+        }
+        self.pos += word.len();
+        Ok(value)
+    }
+
+    fn whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        let matched = self.peek() == Some(byte);
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    fn expected(&self, expect: &'static str) -> NativeError {
+        self.expected_at(self.pos, expect)
+    }
+
+    fn expected_at(&self, pos: usize, expect: &'static str) -> NativeError {
+        NativeError::InvalidJson {
+            index: self.text[..pos].chars().count(),
+            expect,
+        }
+    }
+}
+
+fn stringify(
+    interp: &Interpreter,
+    value: &Value,
+    out: &mut String,
+    depth: usize,
+) -> Result<(), NativeError> {
+    match value {
+        Value::Nil => out.push_str("null"),
+        Value::Bool(b) => write!(out, "{b}").unwrap(),
+        Value::Int(int) => write!(out, "{int}").unwrap(),
+        Value::Number(num) if num.is_nan() => return Err(NativeError::NotJson("nan")),
+        Value::Number(num) if num.is_infinite() => return Err(NativeError::NotJson("inf")),
+        Value::Number(num) => write!(out, "{num}").unwrap(),
+        Value::String(_) | Value::StringLiteral(_) => {
+            let str = value.as_str(&interp.interner).expect("should be a string");
+            quote(str, out);
+        }
+        Value::List(_) | Value::Map(_) if depth == MAX_DEPTH => {
+            return Err(NativeError::JsonDepth(MAX_DEPTH));
+        }
+        Value::List(list) => {
+            out.push('[');
+            for (i, value) in list.borrow().iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                stringify(interp, value, out, depth + 1)?;
+            }
+            out.push(']');
+        }
+        Value::Map(map) => {
+            out.push('{');
+            for (i, (key, value)) in map.borrow().iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                match key {
+                    MapKey::String(str) => quote(str, out),
+                    key => quote(&interp.display(&key.to_value()).to_string(), out),
+                }
+                out.push(':');
+                stringify(interp, value, out, depth + 1)?;
+            }
+            out.push('}');
+        }
+        value => return Err(NativeError::NotJson(value.name())),
+    }
+    Ok(())
+}
+
+fn quote(str: &str, out: &mut String) {
+    out.push('"');
+    for ch in str.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            ch if ch < ' ' => write!(out, "\\u{:04x}", ch as u32).unwrap(),
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+}
@@ -0,0 +1,199 @@
+//! Methods on list values. Methods that take a callback work on a snapshot of the list, so the
+//! callback is free to modify the list while it's being iterated.
+
+use std::cmp::Ordering;
+
+use super::{index_arg, list_arg, Methods};
+use crate::interp::function::{Arity, NativeError};
+use crate::interp::value::Value;
+use crate::interp::Interpreter;
+
+pub(super) fn install(interp: &mut Interpreter, methods: &mut Methods) {
+    methods.define_list(interp, "push", 1, push);
+    methods.define_list(interp, "pop", 0, pop);
+    methods.define_list(interp, "insert", 2, insert);
+    methods.define_list(interp, "remove", 1, remove);
+    methods.define_list(interp, "len", 0, len);
+    methods.define_list(interp, "contains", 1, contains);
+    methods.define_list(interp, "indexOf", 1, index_of);
+    methods.define_list(interp, "sort", Arity::Range(0, 1), sort);
+    methods.define_list(interp, "map", 1, map);
+    methods.define_list(interp, "filter", 1, filter);
+}
+
+fn push(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let mut list = list_arg(args, 0)?.borrow_mut();
+    interp.memory.alloc_elements(list.len() + 1, 1)?;
+    list.push(args[1].clone());
+    Ok(Value::nil())
+}
+
+/// Remove the last element of the list and return it.
+fn pop(_interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    list_arg(args, 0)?
+        .borrow_mut()
+        .pop()
+        .ok_or(NativeError::EmptyList)
+}
+
+/// `list.insert(index, value)` inserts `value` before `index`. `index` can be the length of the
+/// list, which is the same as `push`.
+fn insert(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let mut list = list_arg(args, 0)?.borrow_mut();
+    let index = index_arg(args, 1)?;
+
+    if index > list.len() {
+        let len = list.len();
+        return Err(NativeError::IndexOutOfRange { index, len });
+    }
+
+    interp.memory.alloc_elements(list.len() + 1, 1)?;
+    list.insert(index, args[2].clone());
+    Ok(Value::nil())
+}
+
+/// Remove the element at `index` and return it.
+fn remove(_interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let mut list = list_arg(args, 0)?.borrow_mut();
+    let index = index_arg(args, 1)?;
+
+    if index >= list.len() {
+        let len = list.len();
+        return Err(NativeError::IndexOutOfRange { index, len });
+    }
+
+    Ok(list.remove(index))
+}
+
+fn len(_interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let list = list_arg(args, 0)?.borrow();
+    Ok(Value::int(list.len() as i64))
+}
+
+/// Whether the list contains the value, compared using `==` semantics.
+fn contains(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let list = list_arg(args, 0)?.borrow();
+    let found = list.iter().any(|v| v.is_equal(&args[1], &interp.interner));
+    Ok(Value::bool(found))
+}
+
+/// Returns the index of the first element equal to the argument, or -1 if not found.
+fn index_of(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let list = list_arg(args, 0)?.borrow();
+    let index = match list
+        .iter()
+        .position(|v| v.is_equal(&args[1], &interp.interner))
+    {
+        Some(index) => index as i64,
+        None => -1,
+    };
+    Ok(Value::int(index))
+}
+
+/// Sort the list in place. Without argument the list must contain only numbers or only strings.
+/// The optional argument is a comparator `fun (a, b)` that returns a negative number if `a` should
+/// come before `b`, a positive number if after, and zero if they are equal. The sort is stable.
+fn sort(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let list = list_arg(args, 0)?;
+    let values = list.borrow().clone();
+
+    let sorted = match args.get(1) {
+        None => merge_sort(values, &mut |a, b| default_order(interp, a, b))?,
+        Some(cmp) => merge_sort(values, &mut |a, b| {
+            let args = Box::new([a.clone(), b.clone()]);
+            let value = interp.call_value(cmp, args, interp.call_site.get())?;
+            match value.as_number() {
+                Some(num) => Ok(num.partial_cmp(&0.0).unwrap_or(Ordering::Equal)),
+                None => Err(NativeError::InvalidArgument {
+                    expect: "<number>",
+                    got: value.name(),
+                }),
+            }
+        })?,
+    };
+
+    *list.borrow_mut() = sorted;
+    Ok(Value::nil())
+}
+
+/// Returns a new list with the function applied to each element.
+fn map(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let values = list_arg(args, 0)?.borrow().clone();
+    let site = interp.call_site.get();
+
+    let mapped = values
+        .into_iter()
+        .map(|v| interp.call_value(&args[1], Box::new([v]), site))
+        .collect::<Result<Vec<_>, _>>()?;
+    interp.memory.alloc_elements(mapped.len(), mapped.len())?;
+    Ok(Value::list(mapped))
+}
+
+/// Returns a new list with only the elements the predicate function returns a truthy value for.
+fn filter(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let values = list_arg(args, 0)?.borrow().clone();
+    let site = interp.call_site.get();
+
+    let mut filtered = Vec::new();
+    for value in values {
+        if interp
+            .call_value(&args[1], Box::new([value.clone()]), site)?
+            .truthiness()
+        {
+            filtered.push(value);
+        }
+    }
+    interp
+        .memory
+        .alloc_elements(filtered.len(), filtered.len())?;
+    Ok(Value::list(filtered))
+}
+
+fn default_order(interp: &Interpreter, lhs: &Value, rhs: &Value) -> Result<Ordering, NativeError> {
+    match (lhs, rhs) {
+        (Value::Int(lhs), Value::Int(rhs)) => Ok(lhs.cmp(rhs)),
+        _ => match (lhs.as_number(), rhs.as_number()) {
+            (Some(lhs), Some(rhs)) => Ok(lhs.total_cmp(&rhs)),
+            (lhs_num, _) => match (lhs.as_str(&interp.interner), rhs.as_str(&interp.interner)) {
+                (Some(lhs), Some(rhs)) => Ok(lhs.cmp(rhs)),
+                _ => Err(NativeError::InvalidArgument {
+                    expect: "<number> or <string>",
+                    got: match lhs_num {
+                        Some(_) => rhs.name(),
+                        None => lhs.name(),
+                    },
+                }),
+            },
+        },
+    }
+}
+
+/// Stable merge sort with a fallible comparator. The standard library sort can't propagate errors
+/// and may panic on a comparator that isn't a total order, which a user defined one may well be.
+fn merge_sort<F>(mut values: Vec<Value>, cmp: &mut F) -> Result<Vec<Value>, NativeError>
+where
+    F: FnMut(&Value, &Value) -> Result<Ordering, NativeError>,
+{
+    if values.len() <= 1 {
+        return Ok(values);
+    }
+
+    let right = values.split_off(values.len() / 2);
+    let left = merge_sort(values, cmp)?;
+    let right = merge_sort(right, cmp)?;
+
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        match cmp(r, l)? {
+            Ordering::Less => merged.extend(right.next()),
+            _ => merged.extend(left.next()),
+        }
+    }
+    merged.extend(left);
+    merged.extend(right);
+
+    Ok(merged)
+}
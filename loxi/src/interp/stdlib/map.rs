@@ -0,0 +1,64 @@
+//! Methods on map values. `keys` and `values` return lists in insertion order.
+
+use super::{key_arg, map_arg, Methods};
+use crate::interp::function::{Arity, NativeError};
+use crate::interp::value::Value;
+use crate::interp::Interpreter;
+
+pub(super) fn install(interp: &mut Interpreter, methods: &mut Methods) {
+    methods.define_map(interp, "get", Arity::Range(1, 2), get);
+    methods.define_map(interp, "set", 2, set);
+    methods.define_map(interp, "remove", 1, remove);
+    methods.define_map(interp, "has", 1, has);
+    methods.define_map(interp, "keys", 0, keys);
+    methods.define_map(interp, "values", 0, values);
+    methods.define_map(interp, "len", 0, len);
+}
+
+/// `map.get(key, default)` returns the value of `key`, or `default` if it's not in the map.
+/// `default` is `nil` if omitted.
+fn get(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let map = map_arg(args, 0)?.borrow();
+    let key = key_arg(interp, args, 1)?;
+    let default = args.get(2).cloned().unwrap_or(Value::nil());
+    Ok(map.get(&key).unwrap_or(default))
+}
+
+fn set(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let key = key_arg(interp, args, 1)?;
+    let mut map = map_arg(args, 0)?.borrow_mut();
+    if !map.contains(&key) {
+        interp.memory.alloc_elements(map.len() + 1, 1)?;
+    }
+    map.insert(key, args[2].clone());
+    Ok(Value::nil())
+}
+
+/// Remove `key` from the map and return its value, or `nil` if it's not in the map.
+fn remove(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let key = key_arg(interp, args, 1)?;
+    let removed = map_arg(args, 0)?.borrow_mut().remove(&key);
+    Ok(removed.unwrap_or(Value::nil()))
+}
+
+fn has(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let key = key_arg(interp, args, 1)?;
+    Ok(Value::bool(map_arg(args, 0)?.borrow().contains(&key)))
+}
+
+fn keys(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let map = map_arg(args, 0)?.borrow();
+    interp.memory.alloc_elements(map.len(), map.len())?;
+    Ok(Value::list(map.keys().collect()))
+}
+
+fn values(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let map = map_arg(args, 0)?.borrow();
+    interp.memory.alloc_elements(map.len(), map.len())?;
+    Ok(Value::list(map.values().collect()))
+}
+
+fn len(_interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let map = map_arg(args, 0)?.borrow();
+    Ok(Value::int(map.len() as i64))
+}
@@ -0,0 +1,128 @@
+//! Math functions and constants, grouped in the `Math` namespace. With the `math-globals` feature
+//! they are also defined as flat globals, e.g. `sqrt(2)` in addition to `Math.sqrt(2)`.
+
+use super::number_arg;
+use crate::interp::function::{Native, NativeError, NativeFn};
+use crate::interp::namespace::Namespace;
+use crate::interp::value::{float_to_int, Value};
+use crate::interp::Interpreter;
+
+use macros::{rounding, unary};
+
+const FUNCTIONS: [(&str, usize, NativeFn); 12] = [
+    ("sqrt", 1, sqrt),
+    ("abs", 1, abs),
+    ("floor", 1, floor),
+    ("ceil", 1, ceil),
+    ("round", 1, round),
+    ("sin", 1, sin),
+    ("cos", 1, cos),
+    ("log", 1, log),
+    ("pow", 2, pow),
+    ("min", 2, min),
+    ("max", 2, max),
+    ("div", 2, div),
+];
+
+const CONSTANTS: [(&str, f64); 2] = [("PI", std::f64::consts::PI), ("E", std::f64::consts::E)];
+
+pub(super) fn install(interp: &mut Interpreter) {
+    let mut members = Vec::new();
+
+    for (name, arity, body) in FUNCTIONS {
+        let key = interp.interner.get_or_intern(name);
+        members.push((key, Value::native_function(Native::new(key, arity, body))));
+    }
+    for (name, value) in CONSTANTS {
+        let key = interp.interner.get_or_intern(name);
+        members.push((key, Value::number(value)));
+    }
+
+    let mut math = Namespace::new(interp.interner.get_or_intern("Math"));
+    for (key, value) in members {
+        if cfg!(feature = "math-globals") {
+            interp.dyn_env.define(key, value.clone());
+        }
+        math.define(key, value);
+    }
+    interp.define_value("Math", Value::namespace(math));
+}
+
+unary!(sqrt, f64::sqrt);
+rounding!(floor, f64::floor);
+rounding!(ceil, f64::ceil);
+rounding!(round, f64::round);
+unary!(sin, f64::sin);
+unary!(cos, f64::cos);
+unary!(log, f64::ln);
+
+fn abs(_interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    match args[0] {
+        Value::Int(int) => Ok(int
+            .checked_abs()
+            .map_or_else(|| Value::number((int as f64).abs()), Value::int)),
+        _ => Ok(Value::number(number_arg(args, 0)?.abs())),
+    }
+}
+
+/// `Math.div(a, b)` divides rounding down like `floor(a / b)`, but exactly for integers. Dividing
+/// by zero gives `inf` or `nan` like `/` does.
+fn div(_interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    if let (Value::Int(lhs), Value::Int(rhs)) = (&args[0], &args[1]) {
+        if let (Some(quot), Some(rem)) = (lhs.checked_div(*rhs), lhs.checked_rem(*rhs)) {
+            let floor = match rem != 0 && (rem < 0) != (*rhs < 0) {
+                true => quot - 1,
+                false => quot,
+            };
+            return Ok(Value::int(floor));
+        }
+    }
+    let quot = (number_arg(args, 0)? / number_arg(args, 1)?).floor();
+    Ok(float_to_int(quot).map_or(Value::number(quot), Value::int))
+}
+
+fn pow(_interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let base = number_arg(args, 0)?;
+    let exp = number_arg(args, 1)?;
+    Ok(Value::number(base.powf(exp)))
+}
+
+fn min(_interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let lhs = number_arg(args, 0)?;
+    let rhs = number_arg(args, 1)?;
+    Ok(Value::number(lhs.min(rhs)))
+}
+
+fn max(_interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let lhs = number_arg(args, 0)?;
+    let rhs = number_arg(args, 1)?;
+    Ok(Value::number(lhs.max(rhs)))
+}
+
+mod macros {
+    /// define a native function that applies `$func` to its only number argument
+    macro_rules! unary {
+        ($name:ident, $func:path) => {
+            fn $name(_interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+                Ok(Value::number($func(number_arg(args, 0)?)))
+            }
+        };
+    }
+
+    /// define a native function that rounds its only number argument with `$func`, giving an
+    /// integer when the result fits in one
+    macro_rules! rounding {
+        ($name:ident, $func:path) => {
+            fn $name(_interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+                if let Value::Int(int) = args[0] {
+                    return Ok(Value::int(int));
+                }
+                let num = $func(number_arg(args, 0)?);
+                Ok(float_to_int(num).map_or(Value::number(num), Value::int))
+            }
+        };
+    }
+
+    pub(crate) use rounding;
+    pub(crate) use unary;
+}
@@ -0,0 +1,385 @@
+//! Native functions and values that are installed into the global environment when an
+//! `Interpreter` is created, and native methods on builtin values.
+
+use std::io::Write;
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
+
+use rustc_hash::FxHashMap;
+
+use crate::util::shared::{Lock, Shared};
+
+use super::function::{Arity, HostFn, Native, NativeError, NativeFn};
+use super::interner::Key;
+use super::map::{Map, MapKey};
+use super::value::Value;
+use super::{Interpreter, RuntimeError};
+
+mod format;
+#[cfg(not(target_arch = "wasm32"))]
+mod fs;
+mod json;
+mod list;
+mod map;
+mod math;
+#[cfg(not(target_arch = "wasm32"))]
+mod process;
+mod random;
+#[cfg(feature = "regex")]
+mod regex;
+mod string;
+mod testing;
+mod time;
+
+pub use self::testing::TestCase;
+
+/// Native methods that can be called on builtin values, e.g. `"lox".toUpper()`.
+#[derive(Default)]
+pub struct Methods {
+    string: FxHashMap<Key, Native>,
+    list: FxHashMap<Key, Native>,
+    map: FxHashMap<Key, Native>,
+}
+
+impl Interpreter {
+    pub(super) fn define_native(&mut self, name: &str, arity: impl Into<Arity>, body: NativeFn) {
+        let key = self.interner.get_or_intern(name);
+        let native = Native::new(key, arity, body);
+        self.dyn_env.define(key, Value::native_function(native));
+    }
+
+    /// Define a global native function whose body is provided by the host.
+    pub fn define_host_native(
+        &mut self,
+        name: &str,
+        arity: impl Into<Arity>,
+        body: Shared<dyn HostFn>,
+    ) {
+        let key = self.interner.get_or_intern(name);
+        let native = Native::host(key, arity, body);
+        self.dyn_env.define(key, Value::native_function(native));
+    }
+
+    pub(super) fn define_value(&mut self, name: &str, value: Value) {
+        let key = self.interner.get_or_intern(name);
+        self.dyn_env.define(key, value);
+    }
+}
+
+impl Methods {
+    /// Get the method `name` of `value` bound to it.
+    pub fn get(&self, value: &Value, name: Key) -> Option<Native> {
+        self.table(value)?.get(&name).map(|m| m.bind(value.clone()))
+    }
+
+    /// Whether `value` has any method at all.
+    pub fn supports(&self, value: &Value) -> bool {
+        self.table(value).is_some()
+    }
+
+    fn table(&self, value: &Value) -> Option<&FxHashMap<Key, Native>> {
+        match value {
+            Value::String(_) | Value::StringLiteral(_) => Some(&self.string),
+            Value::List(_) => Some(&self.list),
+            Value::Map(_) => Some(&self.map),
+            _ => None,
+        }
+    }
+
+    fn define_string(
+        &mut self,
+        interp: &mut Interpreter,
+        name: &str,
+        arity: impl Into<Arity>,
+        body: NativeFn,
+    ) {
+        let key = interp.interner.get_or_intern(name);
+        self.string.insert(key, Native::new(key, arity, body));
+    }
+
+    fn define_list(
+        &mut self,
+        interp: &mut Interpreter,
+        name: &str,
+        arity: impl Into<Arity>,
+        body: NativeFn,
+    ) {
+        let key = interp.interner.get_or_intern(name);
+        self.list.insert(key, Native::new(key, arity, body));
+    }
+
+    fn define_map(
+        &mut self,
+        interp: &mut Interpreter,
+        name: &str,
+        arity: impl Into<Arity>,
+        body: NativeFn,
+    ) {
+        let key = interp.interner.get_or_intern(name);
+        self.map.insert(key, Native::new(key, arity, body));
+    }
+}
+
+pub(super) fn install(interp: &mut Interpreter) {
+    let mut methods = Methods::default();
+    string::install(interp, &mut methods);
+    list::install(interp, &mut methods);
+    map::install(interp, &mut methods);
+    interp.methods = methods;
+
+    math::install(interp);
+    format::install(interp);
+    json::install(interp);
+    random::install(interp);
+    time::install(interp);
+    testing::install(interp);
+
+    #[cfg(feature = "regex")]
+    regex::install(interp);
+
+    // there is no file system, environment, processes, or blocking sleep in the browser
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        if interp.capabilities.fs {
+            fs::install(interp);
+        }
+        if interp.capabilities.env {
+            process::install_env(interp);
+        }
+        if interp.capabilities.run {
+            process::install_run(interp);
+        }
+        interp.define_native("sleep", 1, sleep);
+    }
+
+    interp.define_native("clock", 0, clock);
+    interp.define_native("print", 1, print);
+    interp.define_native("println", 1, print);
+    interp.define_native("write", 1, write);
+    interp.define_native("eprint", 1, eprint);
+    interp.define_native("readLine", 0, read_line);
+    interp.define_native("Map", 0, new_map);
+    interp.define_native("type", 1, type_of);
+    interp.define_native("num", 1, num);
+    interp.define_native("str", 1, str);
+    interp.define_native("exit", Arity::Range(0, 1), exit);
+    interp.define_native("assert", Arity::Range(1, 2), assert);
+    interp.define_native("panic", 1, panic);
+    if interp.dialect.deep_equals {
+        interp.define_native("deepEquals", 2, deep_equals);
+    }
+
+    // these are plain globals instead of keywords so existing programs can still shadow them
+    interp.define_value("nan", Value::number(f64::NAN));
+    interp.define_value("inf", Value::number(f64::INFINITY));
+}
+
+/// Returns the number of seconds since the unix epoch as a double.
+fn clock(interp: &Interpreter, _args: &[Value]) -> Result<Value, NativeError> {
+    Ok(Value::number(interp.clock.now() / 1000.0))
+}
+
+/// `sleep(ms)` suspends the program for the given milliseconds. The sleep is done in small steps so
+/// cancelling the interpreter or running out of time still stops the program promptly. A duration
+/// too long to represent sleeps until the program is stopped.
+#[cfg(not(target_arch = "wasm32"))]
+fn sleep(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+    let ms = match args[0].as_number() {
+        Some(ms) if ms.is_finite() && ms >= 0.0 => ms,
+        _ => Err(NativeError::InvalidArgument {
+            expect: "<non-negative number>",
+            got: args[0].name(),
+        })?,
+    };
+
+    // read from the clock of the interpreter like the time limit, so a test clock also moves it
+    let deadline = interp.clock.monotonic() + ms;
+    loop {
+        interp.check_cancelled(interp.call_site.get())?;
+        interp.check_deadline(interp.call_site.get())?;
+        let remaining = deadline - interp.clock.monotonic();
+        if remaining <= 0.0 {
+            return Ok(Value::nil());
+        }
+        let remaining = Duration::try_from_secs_f64(remaining / 1000.0).unwrap_or(POLL_INTERVAL);
+        thread::sleep(remaining.min(POLL_INTERVAL));
+    }
+}
+
+fn deep_equals(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let [lhs, rhs] = args else {
+        unreachable!("arity should have been checked by Native::call")
+    };
+    Ok(Value::bool(lhs.deep_eq(rhs, &interp.interner)))
+}
+
+/// `print(value)` writes a value to stdout followed by a newline like the `print` statement, which
+/// makes it usable in expressions. `println` is the same.
+fn print(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    writeln!(interp.stdout(), "{}", interp.display(&args[0]))?;
+    Ok(Value::nil())
+}
+
+/// Write a value to stdout without a trailing newline. The output is flushed immediately so it can
+/// be used for progress indicators.
+fn write(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let mut stdout = interp.stdout();
+    write!(stdout, "{}", interp.display(&args[0]))?;
+    stdout.flush()?;
+    Ok(Value::nil())
+}
+
+/// Write a value to stderr followed by a newline.
+fn eprint(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let mut stderr = interp.stderr();
+    writeln!(stderr, "{}", interp.display(&args[0]))?;
+    stderr.flush()?;
+    Ok(Value::nil())
+}
+
+/// Read a single line from the input without its line terminator, or `nil` on end of input.
+fn read_line(interp: &Interpreter, _args: &[Value]) -> Result<Value, NativeError> {
+    let mut line = String::new();
+    if interp.stdin().read_line(&mut line)? == 0 {
+        return Ok(Value::nil());
+    }
+
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    Ok(Value::string(line))
+}
+
+/// `type(value)` returns the name of the type of the value, e.g. `"number"` or `"instance"`.
+fn type_of(_interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    Ok(Value::string(args[0].type_name().to_owned()))
+}
+
+/// `num(value)` converts a string to a number, ignoring the whitespace around it. Numbers are
+/// returned as is.
+fn num(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    if args[0].as_number().is_some() {
+        return Ok(args[0].clone());
+    }
+    let text = string_arg(interp, args, 0)?.trim();
+    match (text.parse::<i64>(), text.parse::<f64>()) {
+        (Ok(int), _) => Ok(Value::int(int)),
+        (_, Ok(num)) => Ok(Value::number(num)),
+        _ => Err(NativeError::NotANumber(text.to_owned())),
+    }
+}
+
+/// `str(value)` converts any value to a string, as `print` shows it.
+fn str(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let str = interp.display(&args[0]).to_string();
+    interp.memory.alloc_string(str.len())?;
+    Ok(Value::string(str))
+}
+
+/// Create an empty map. Maps have no literal syntax since `{` would be ambiguous with a block.
+fn new_map(_interp: &Interpreter, _args: &[Value]) -> Result<Value, NativeError> {
+    Ok(Value::map(Map::default()))
+}
+
+/// `exit(code)` stops the program with the status code, `0` if omitted. It unwinds through the
+/// interpreter instead of exiting the process so the host decides what to do with it.
+fn exit(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let code = match args.first() {
+        None => 0,
+        Some(value) => match value.as_int().and_then(|int| i32::try_from(int).ok()) {
+            Some(code) => code,
+            None => Err(NativeError::InvalidArgument {
+                expect: "<integer>",
+                got: value.name(),
+            })?,
+        },
+    };
+    Err(RuntimeError::Exit(interp.call_site.get(), code).into())
+}
+
+/// `assert(cond, msg)` raises an error at the call site if `cond` is falsy. `msg` is optional.
+fn assert(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    if args[0].truthiness() {
+        return Ok(Value::nil());
+    }
+
+    let msg = match args.get(1) {
+        Some(msg) => interp.display(msg).to_string(),
+        None => "expected a truthy value".to_owned(),
+    };
+    Err(RuntimeError::AssertionFailed(interp.call_site.get(), msg).into())
+}
+
+/// `panic(msg)` always raises an error at the call site.
+fn panic(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let msg = interp.display(&args[0]).to_string();
+    Err(RuntimeError::Panic(interp.call_site.get(), msg).into())
+}
+
+fn string_arg<'a>(
+    interp: &'a Interpreter,
+    args: &'a [Value],
+    index: usize,
+) -> Result<&'a str, NativeError> {
+    args[index]
+        .as_str(&interp.interner)
+        .ok_or(NativeError::InvalidArgument {
+            expect: "<string>",
+            got: args[index].name(),
+        })
+}
+
+fn list_arg(args: &[Value], index: usize) -> Result<&Shared<Lock<Vec<Value>>>, NativeError> {
+    match &args[index] {
+        Value::List(list) => Ok(list),
+        value => Err(NativeError::InvalidArgument {
+            expect: "<list>",
+            got: value.name(),
+        }),
+    }
+}
+
+fn map_arg(args: &[Value], index: usize) -> Result<&Shared<Lock<Map>>, NativeError> {
+    match &args[index] {
+        Value::Map(map) => Ok(map),
+        value => Err(NativeError::InvalidArgument {
+            expect: "<map>",
+            got: value.name(),
+        }),
+    }
+}
+
+fn key_arg(interp: &Interpreter, args: &[Value], index: usize) -> Result<MapKey, NativeError> {
+    Ok(MapKey::new(&args[index], &interp.interner)?)
+}
+
+fn number_arg(args: &[Value], index: usize) -> Result<f64, NativeError> {
+    match args[index].as_number() {
+        Some(num) => Ok(num),
+        None => Err(NativeError::InvalidArgument {
+            expect: "<number>",
+            got: args[index].name(),
+        }),
+    }
+}
+
+/// Get a non-negative integer argument that is used as an index.
+fn index_arg(args: &[Value], index: usize) -> Result<usize, NativeError> {
+    match args[index]
+        .as_int()
+        .and_then(|int| usize::try_from(int).ok())
+    {
+        Some(index) => Ok(index),
+        None => Err(NativeError::InvalidArgument {
+            expect: "<index>",
+            got: args[index].name(),
+        }),
+    }
+}
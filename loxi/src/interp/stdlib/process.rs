@@ -0,0 +1,62 @@
+//! Natives that access the host process. Each group is installed only when its capability is
+//! enabled.
+
+use std::env;
+use std::process::Command;
+
+use super::{list_arg, string_arg};
+use crate::interp::function::NativeError;
+use crate::interp::map::{Map, MapKey};
+use crate::interp::value::Value;
+use crate::interp::Interpreter;
+
+pub(super) fn install_env(interp: &mut Interpreter) {
+    interp.define_native("getenv", 1, getenv);
+}
+
+pub(super) fn install_run(interp: &mut Interpreter) {
+    interp.define_native("exec", 2, exec);
+}
+
+/// Returns the value of the environment variable, or `nil` if it's not set or not valid unicode.
+fn getenv(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let name = string_arg(interp, args, 0)?;
+    match env::var(name) {
+        Ok(value) => Ok(Value::string(value)),
+        Err(_) => Ok(Value::nil()),
+    }
+}
+
+/// `exec(cmd, args)` runs the program `cmd` with the list of string arguments `args` and waits for
+/// it to finish. Returns a map with `status` (`nil` if the program was killed by a signal), and the
+/// captured `stdout` and `stderr`.
+fn exec(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let cmd = string_arg(interp, args, 0)?;
+    let cmd_args = list_arg(args, 1)?
+        .borrow()
+        .iter()
+        .map(|arg| match arg.as_str(&interp.interner) {
+            Some(str) => Ok(str.to_owned()),
+            None => Err(NativeError::InvalidArgument {
+                expect: "<string>",
+                got: arg.name(),
+            }),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let output = Command::new(cmd).args(cmd_args).output()?;
+
+    let status = match output.status.code() {
+        Some(code) => Value::int(code.into()),
+        None => Value::nil(),
+    };
+    let text = |bytes: Vec<u8>| Value::string(String::from_utf8_lossy(&bytes).into_owned());
+
+    let mut map = Map::default();
+    let mut set = |key: &str, value| map.insert(MapKey::String(key.to_owned().into()), value);
+    set("status", status);
+    set("stdout", text(output.stdout));
+    set("stderr", text(output.stderr));
+
+    Ok(Value::map(map))
+}
@@ -0,0 +1,65 @@
+//! Pseudo-random numbers. The generator is stored on the interpreter and seeded from the clock on
+//! first use, unless the script calls `seed(n)` first so its runs are reproducible.
+
+use crate::interp::function::NativeError;
+use crate::interp::value::Value;
+use crate::interp::Interpreter;
+
+pub(super) fn install(interp: &mut Interpreter) {
+    interp.define_native("random", 0, random);
+    interp.define_native("randomRange", 2, random_range);
+    interp.define_native("seed", 1, seed);
+}
+
+/// `random()` returns a number in `[0, 1)`.
+fn random(interp: &Interpreter, _args: &[Value]) -> Result<Value, NativeError> {
+    // the 53 high bits fill the mantissa
+    let bits = next(interp) >> 11;
+    Ok(Value::number(bits as f64 / (1u64 << 53) as f64))
+}
+
+/// `randomRange(a, b)` returns an integer in `[a, b)`.
+fn random_range(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let low = int_arg(args, 0)?;
+    let high = int_arg(args, 1)?;
+    if high <= low {
+        return Err(NativeError::InvalidArgument {
+            expect: "<integer greater than the first>",
+            got: args[1].name(),
+        });
+    }
+
+    let span = (i128::from(high) - i128::from(low)) as u128;
+    let offset = (u128::from(next(interp)) * span) >> 64;
+    Ok(Value::int((i128::from(low) + offset as i128) as i64))
+}
+
+/// `seed(n)` restarts the generator from the integer `n`.
+fn seed(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    interp.rng.set(Some(int_arg(args, 0)? as u64));
+    Ok(Value::nil())
+}
+
+fn int_arg(args: &[Value], index: usize) -> Result<i64, NativeError> {
+    args[index]
+        .as_int()
+        .ok_or_else(|| NativeError::InvalidArgument {
+            expect: "<integer>",
+            got: args[index].name(),
+        })
+}
+
+/// The next output of SplitMix64, which is small and good enough for scripts but not for
+/// cryptography.
+fn next(interp: &Interpreter) -> u64 {
+    let state = interp.rng.get().unwrap_or_else(|| {
+        interp.clock.now().to_bits() ^ interp.clock.monotonic().to_bits().rotate_left(32)
+    });
+    let state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    interp.rng.set(Some(state));
+
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
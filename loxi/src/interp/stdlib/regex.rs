@@ -0,0 +1,107 @@
+//! Regular expression natives, backed by the `regex` crate. A match is returned as a map with these
+//! keys:
+//!
+//! - `text`: the matched text,
+//! - `start` and `end`: character indices of the match in the searched text,
+//! - `groups`: list of the capture groups (starting from group 1), `nil` for a group that didn't
+//!   participate in the match,
+//! - `named`: map of the named capture groups.
+
+use regex::{Captures, Regex};
+
+use super::string_arg;
+use crate::interp::function::NativeError;
+use crate::interp::map::{Map, MapKey};
+use crate::interp::value::Value;
+use crate::interp::Interpreter;
+
+/// The most regexes kept compiled. The cache is emptied when it's full, so a program that makes
+/// new patterns in a loop doesn't grow it without bound.
+const CACHE_SIZE: usize = 64;
+
+pub(super) fn install(interp: &mut Interpreter) {
+    interp.define_native("regexMatch", 2, regex_match);
+    interp.define_native("regexFindAll", 2, regex_find_all);
+    interp.define_native("regexReplace", 3, regex_replace);
+}
+
+/// `regexMatch(pattern, text)` returns the first match, or `nil` if there is none.
+fn regex_match(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let regex = compile(interp, string_arg(interp, args, 0)?)?;
+    let text = string_arg(interp, args, 1)?;
+
+    match regex.captures(text) {
+        Some(caps) => Ok(match_value(&regex, text, &caps)),
+        None => Ok(Value::nil()),
+    }
+}
+
+/// `regexFindAll(pattern, text)` returns a list of all non-overlapping matches.
+fn regex_find_all(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let regex = compile(interp, string_arg(interp, args, 0)?)?;
+    let text = string_arg(interp, args, 1)?;
+
+    let matches = regex
+        .captures_iter(text)
+        .map(|caps| match_value(&regex, text, &caps))
+        .collect();
+    Ok(Value::list(matches))
+}
+
+/// `regexReplace(pattern, text, replacement)` replaces all matches. The replacement can refer to
+/// capture groups using `$1` or `${name}`.
+fn regex_replace(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let regex = compile(interp, string_arg(interp, args, 0)?)?;
+    let text = string_arg(interp, args, 1)?;
+    let replacement = string_arg(interp, args, 2)?;
+
+    let replaced = regex.replace_all(text, replacement);
+    Ok(Value::string(replaced.into_owned()))
+}
+
+/// Compile `pattern`, or reuse the regex compiled for it before, since patterns are usually the
+/// same on every call, e.g. in a loop.
+fn compile(interp: &Interpreter, pattern: &str) -> Result<Regex, NativeError> {
+    let mut cache = interp.regexes.borrow_mut();
+    if let Some(regex) = cache.get(pattern) {
+        return Ok(regex.clone());
+    }
+
+    let regex = Regex::new(pattern)?;
+    if cache.len() >= CACHE_SIZE {
+        cache.clear();
+    }
+    cache.insert(pattern.into(), regex.clone());
+    Ok(regex)
+}
+
+fn match_value(regex: &Regex, text: &str, caps: &Captures) -> Value {
+    let whole = caps.get(0).expect("group 0 is always the whole match");
+    let group = |m: Option<regex::Match>| match m {
+        Some(m) => Value::string(m.as_str().to_owned()),
+        None => Value::nil(),
+    };
+
+    let groups = caps.iter().skip(1).map(group).collect();
+
+    let mut named = Map::default();
+    for name in regex.capture_names().flatten() {
+        named.insert(
+            MapKey::String(name.to_owned().into()),
+            group(caps.name(name)),
+        );
+    }
+
+    let start = text[..whole.start()].chars().count();
+    let end = start + whole.as_str().chars().count();
+
+    let mut map = Map::default();
+    let mut set = |key: &str, value| map.insert(MapKey::String(key.to_owned().into()), value);
+    set("text", Value::string(whole.as_str().to_owned()));
+    set("start", Value::int(start as i64));
+    set("end", Value::int(end as i64));
+    set("groups", Value::list(groups));
+    set("named", Value::map(named));
+
+    Value::map(map)
+}
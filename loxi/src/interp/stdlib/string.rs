@@ -0,0 +1,111 @@
+//! Methods on string values. Indices are counted in characters (unicode scalar values), not bytes.
+
+use super::{index_arg, string_arg, Methods};
+use crate::interp::function::NativeError;
+use crate::interp::value::Value;
+use crate::interp::Interpreter;
+
+pub(super) fn install(interp: &mut Interpreter, methods: &mut Methods) {
+    methods.define_string(interp, "len", 0, len);
+    methods.define_string(interp, "substring", 2, substring);
+    methods.define_string(interp, "indexOf", 1, index_of);
+    methods.define_string(interp, "split", 1, split);
+    methods.define_string(interp, "trim", 0, trim);
+    methods.define_string(interp, "toUpper", 0, to_upper);
+    methods.define_string(interp, "toLower", 0, to_lower);
+    methods.define_string(interp, "replace", 2, replace);
+    methods.define_string(interp, "charAt", 1, char_at);
+}
+
+fn len(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let str = string_arg(interp, args, 0)?;
+    Ok(Value::int(str.chars().count() as i64))
+}
+
+/// `str.substring(start, end)` returns the characters in range `[start, end)`.
+fn substring(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let str = string_arg(interp, args, 0)?;
+    let start = index_arg(args, 1)?;
+    let end = index_arg(args, 2)?;
+
+    let len = str.chars().count();
+    if end > len {
+        return Err(NativeError::IndexOutOfRange { index: end, len });
+    } else if start > end {
+        return Err(NativeError::InvalidRange { start, end });
+    }
+
+    let substr = str.chars().skip(start).take(end - start).collect();
+    new_string(interp, substr)
+}
+
+/// Returns the character index of the first occurrence of the argument, or -1 if not found.
+fn index_of(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let str = string_arg(interp, args, 0)?;
+    let pattern = string_arg(interp, args, 1)?;
+
+    let index = match str.find(pattern) {
+        Some(byte_index) => str[..byte_index].chars().count() as i64,
+        None => -1,
+    };
+    Ok(Value::int(index))
+}
+
+/// Split the string by a separator into a list. An empty separator splits it into characters.
+fn split(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let str = string_arg(interp, args, 0)?;
+    let sep = string_arg(interp, args, 1)?;
+
+    let parts: Vec<_> = match sep.is_empty() {
+        true => str.chars().map(|c| Value::string(c.to_string())).collect(),
+        false => str
+            .split(sep)
+            .map(|s| Value::string(s.to_owned()))
+            .collect(),
+    };
+    interp.memory.alloc_elements(parts.len(), parts.len())?;
+    interp.memory.alloc(str.len())?;
+    Ok(Value::list(parts))
+}
+
+fn trim(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let str = string_arg(interp, args, 0)?;
+    new_string(interp, str.trim().to_owned())
+}
+
+fn to_upper(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let str = string_arg(interp, args, 0)?;
+    new_string(interp, str.to_uppercase())
+}
+
+fn to_lower(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let str = string_arg(interp, args, 0)?;
+    new_string(interp, str.to_lowercase())
+}
+
+/// Replace every occurrence of the first argument with the second one.
+fn replace(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let str = string_arg(interp, args, 0)?;
+    let from = string_arg(interp, args, 1)?;
+    let to = string_arg(interp, args, 2)?;
+    new_string(interp, str.replace(from, to))
+}
+
+fn char_at(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let str = string_arg(interp, args, 0)?;
+    let index = index_arg(args, 1)?;
+
+    match str.chars().nth(index) {
+        Some(ch) => Ok(Value::string(ch.to_string())),
+        None => Err(NativeError::IndexOutOfRange {
+            index,
+            len: str.chars().count(),
+        }),
+    }
+}
+
+/// Create a string value, counting it against the memory limits.
+fn new_string(interp: &Interpreter, str: String) -> Result<Value, NativeError> {
+    interp.memory.alloc_string(str.len())?;
+    Ok(Value::string(str))
+}
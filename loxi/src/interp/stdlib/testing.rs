@@ -0,0 +1,65 @@
+//! Natives for writing tests in Lox. `test(name, fn)` only registers a test, they are run later by
+//! the host, see `Lox::run_tests`.
+
+use super::string_arg;
+use crate::interp::function::NativeError;
+use crate::interp::value::Value;
+use crate::interp::{Interpreter, RuntimeError};
+use crate::util::Location;
+
+/// A test registered by `test(name, fn)`.
+#[derive(Clone)]
+pub struct TestCase {
+    pub name: String,
+    pub func: Value,
+
+    /// Where the test is registered.
+    pub loc: Location,
+}
+
+pub(super) fn install(interp: &mut Interpreter) {
+    interp.define_native("test", 2, test);
+    interp.define_native("assertEqual", 2, assert_equal);
+    interp.define_native("assertTrue", 1, assert_true);
+}
+
+/// `test(name, fn)` registers `fn` as a test. It's called without arguments and passes if it
+/// returns without an error.
+fn test(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let name = string_arg(interp, args, 0)?.to_owned();
+    let func = match &args[1] {
+        func @ (Value::Function(_) | Value::Class(_)) => func.clone(),
+        value => Err(NativeError::InvalidArgument {
+            expect: "<function>",
+            got: value.name(),
+        })?,
+    };
+
+    let loc = interp.call_site.get();
+    interp.tests.borrow_mut().push(TestCase { name, func, loc });
+    Ok(Value::nil())
+}
+
+/// `assertEqual(actual, expected)` fails if the values are not deeply equal, see `deepEquals`.
+fn assert_equal(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let [actual, expected] = args else {
+        unreachable!("arity should have been checked by Native::call")
+    };
+    if actual.deep_eq(expected, &interp.interner) {
+        return Ok(Value::nil());
+    }
+
+    let actual = interp.display(actual);
+    let expected = interp.display(expected);
+    let msg = format!("expected {expected}, got {actual}");
+    Err(RuntimeError::AssertionFailed(interp.call_site.get(), msg).into())
+}
+
+fn assert_true(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    if args[0].truthiness() {
+        return Ok(Value::nil());
+    }
+
+    let msg = format!("expected true, got {}", interp.display(&args[0]));
+    Err(RuntimeError::AssertionFailed(interp.call_site.get(), msg).into())
+}
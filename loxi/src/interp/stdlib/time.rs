@@ -0,0 +1,98 @@
+//! Date and time functions, grouped in the `Time` namespace. Times are represented as milliseconds
+//! since the unix epoch and are always formatted in UTC.
+
+use std::fmt::Write;
+
+use super::{number_arg, string_arg};
+use crate::interp::function::{Native, NativeError, NativeFn};
+use crate::interp::namespace::Namespace;
+use crate::interp::value::Value;
+use crate::interp::Interpreter;
+
+const FUNCTIONS: [(&str, usize, NativeFn); 3] = [
+    ("now", 0, now),
+    ("hrtime", 0, hrtime),
+    ("formatTime", 2, format_time),
+];
+
+const MS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+
+pub(super) fn install(interp: &mut Interpreter) {
+    let mut time = Namespace::new(interp.interner.get_or_intern("Time"));
+    for (name, arity, body) in FUNCTIONS {
+        let key = interp.interner.get_or_intern(name);
+        time.define(key, Value::native_function(Native::new(key, arity, body)));
+    }
+    interp.define_value("Time", Value::namespace(time));
+}
+
+/// Returns the current time in whole milliseconds since the unix epoch.
+fn now(interp: &Interpreter, _args: &[Value]) -> Result<Value, NativeError> {
+    Ok(Value::int(interp.clock.now().floor() as i64))
+}
+
+/// Returns a monotonic time in milliseconds with sub-millisecond precision. Only the difference
+/// between two calls is meaningful, which makes it suitable for benchmarking.
+fn hrtime(interp: &Interpreter, _args: &[Value]) -> Result<Value, NativeError> {
+    Ok(Value::number(interp.clock.monotonic()))
+}
+
+/// `formatTime(ms, fmt)` formats the time in UTC. The supported specifiers are `%Y` (year), `%m`
+/// (month), `%d` (day), `%H` (hour), `%M` (minute), `%S` (second), `%L` (millisecond), and `%%`.
+/// Other characters are copied as is.
+fn format_time(interp: &Interpreter, args: &[Value]) -> Result<Value, NativeError> {
+    let ms = number_arg(args, 0)?;
+    let fmt = string_arg(interp, args, 1)?;
+
+    if !ms.is_finite() {
+        return Err(NativeError::InvalidArgument {
+            expect: "<finite number>",
+            got: args[0].name(),
+        });
+    }
+
+    let ms = ms.floor() as i64;
+    let (year, month, day) = civil_from_days(ms.div_euclid(MS_PER_DAY));
+    let ms_of_day = ms.rem_euclid(MS_PER_DAY);
+    let (hour, minute) = (ms_of_day / 3_600_000, ms_of_day / 60_000 % 60);
+    let (second, milli) = (ms_of_day / 1000 % 60, ms_of_day % 1000);
+
+    let mut out = String::new();
+    let mut chars = fmt.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '%' {
+            out.push(ch);
+            continue;
+        }
+        // writing into a String never fails
+        let _ = match chars.next() {
+            Some('Y') => write!(out, "{year:04}"),
+            Some('m') => write!(out, "{month:02}"),
+            Some('d') => write!(out, "{day:02}"),
+            Some('H') => write!(out, "{hour:02}"),
+            Some('M') => write!(out, "{minute:02}"),
+            Some('S') => write!(out, "{second:02}"),
+            Some('L') => write!(out, "{milli:03}"),
+            Some('%') => write!(out, "%"),
+            Some(other) => write!(out, "%{other}"),
+            None => write!(out, "%"),
+        };
+    }
+
+    Ok(Value::string(out))
+}
+
+/// Convert days since the unix epoch into a (year, month, day) date in the proleptic Gregorian
+/// calendar. See <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
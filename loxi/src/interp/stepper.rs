@@ -0,0 +1,151 @@
+//! Pausing a program at breakpoints and stepping through it, for debuggers built on `Observer`.
+
+use std::collections::BTreeSet;
+
+use crate::util::Location;
+
+/// How to continue a paused program.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// Run until the next breakpoint.
+    Continue,
+
+    /// Pause at the next line, including the lines inside of the functions called by this one.
+    In,
+
+    /// Pause at the next line of the current function.
+    Over,
+
+    /// Pause after the current function returns.
+    Out,
+}
+
+/// Why a program is paused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    Entry,
+    Breakpoint,
+    Step,
+}
+
+/// A function, method, or class that is being called, or the program itself as the outermost
+/// frame.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub name: String,
+
+    /// The statement being executed in the innermost frame, the call site in the others.
+    pub loc: Location,
+}
+
+#[derive(Clone, Copy)]
+enum Mode {
+    Entry,
+    Run,
+    In { line: usize, depth: usize },
+    Over { line: usize, depth: usize },
+    Out { depth: usize },
+}
+
+/// Tracks the calls of a program and decides at which statements it should pause. An observer
+/// forwards its events here, then blocks the program whenever `should_pause` says so.
+pub struct Stepper {
+    mode: Mode,
+
+    /// The calls that haven't returned yet, with their call sites.
+    calls: Vec<Frame>,
+
+    /// Line and call depth of the previous statement, so a breakpoint is hit once per line.
+    last: (usize, usize),
+}
+
+impl StopReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StopReason::Entry => "entry",
+            StopReason::Breakpoint => "breakpoint",
+            StopReason::Step => "step",
+        }
+    }
+}
+
+impl Stepper {
+    /// Start by pausing at the first statement if `stop_on_entry`, otherwise run until a
+    /// breakpoint.
+    pub fn new(stop_on_entry: bool) -> Self {
+        Self {
+            mode: match stop_on_entry {
+                true => Mode::Entry,
+                false => Mode::Run,
+            },
+            calls: Vec::new(),
+            last: (0, 0),
+        }
+    }
+
+    /// Called before each statement with the lines that have a breakpoint.
+    pub fn should_pause(
+        &mut self,
+        loc: Location,
+        breakpoints: &BTreeSet<usize>,
+    ) -> Option<StopReason> {
+        let depth = self.calls.len();
+        let moved = (loc.line, depth) != self.last;
+        self.last = (loc.line, depth);
+
+        if moved && breakpoints.contains(&loc.line) {
+            return Some(StopReason::Breakpoint);
+        }
+
+        let step = match self.mode {
+            Mode::Entry => return Some(StopReason::Entry),
+            Mode::Run => false,
+            Mode::In { line, depth: from } => loc.line != line || depth != from,
+            Mode::Over { line, depth: from } => depth < from || (depth == from && loc.line != line),
+            Mode::Out { depth: from } => depth < from,
+        };
+        step.then_some(StopReason::Step)
+    }
+
+    /// Continue the program paused at `loc`.
+    pub fn resume(&mut self, step: Step, loc: Location) {
+        let (line, depth) = (loc.line, self.calls.len());
+        self.mode = match step {
+            Step::Continue => Mode::Run,
+            Step::In => Mode::In { line, depth },
+            Step::Over => Mode::Over { line, depth },
+            Step::Out => Mode::Out { depth },
+        };
+    }
+
+    pub fn enter(&mut self, name: &str, loc: Location) {
+        self.calls.push(Frame {
+            name: name.to_owned(),
+            loc,
+        });
+    }
+
+    pub fn leave(&mut self) {
+        self.calls.pop();
+    }
+
+    /// The frames from the innermost one, which is paused at `loc`, to the program itself.
+    pub fn backtrace(&self, loc: Location) -> Vec<Frame> {
+        let mut frames = Vec::with_capacity(self.calls.len() + 1);
+        let mut loc = loc;
+        for depth in (0..=self.calls.len()).rev() {
+            let name = match depth {
+                0 => "<script>",
+                depth => &self.calls[depth - 1].name,
+            };
+            frames.push(Frame {
+                name: name.to_owned(),
+                loc,
+            });
+            if depth > 0 {
+                loc = self.calls[depth - 1].loc;
+            }
+        }
+        frames
+    }
+}
@@ -0,0 +1,1111 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use std::{env, fs, process};
+
+use pretty_assertions::assert_eq;
+
+use crate::lex::Lexer;
+use crate::parse::Parser;
+use crate::resolve::{Resolver, Slot};
+use crate::util::shared::Shared;
+use crate::util::Location;
+use crate::{Dialect, Lox, LoxError, NativeError, Options};
+
+use super::clock::Clock;
+use super::env::{DynamicEnv, Env};
+use super::interner::Interner;
+use super::map::{Map, MapKey};
+use super::output::OutputBuffer;
+use super::value::{NumberStyle, Value};
+use super::{Capabilities, Interpreter, RuntimeError};
+
+struct FixedClock {
+    now: f64,
+    monotonic: f64,
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> f64 {
+        self.now
+    }
+
+    fn monotonic(&self) -> f64 {
+        self.monotonic
+    }
+}
+
+/// Run `source` and return what it wrote to stdout.
+fn run(interp: &mut Interpreter, source: &str) -> String {
+    let output = OutputBuffer::default();
+    interp.set_stdout(Box::new(output.clone()));
+
+    let result = Lexer::new(source, interp.interner_mut()).scan();
+    assert!(result.errors.is_empty());
+
+    let program = Parser::new().parse(result.tokens).expect("should parse");
+    let resolve_map = Resolver::new(&interp.interner)
+        .resolve(&program)
+        .expect("should resolve");
+    interp
+        .interpret(program, resolve_map)
+        .expect("should run without error");
+
+    output.text()
+}
+
+/// Run `source`, which should fail, and return the error.
+fn run_error(interp: &mut Interpreter, source: &str) -> RuntimeError {
+    let result = Lexer::new(source, interp.interner_mut()).scan();
+    let program = Parser::new().parse(result.tokens).expect("should parse");
+    let resolve_map = Resolver::new(&interp.interner)
+        .resolve(&program)
+        .expect("should resolve");
+    interp
+        .interpret(program, resolve_map)
+        .expect_err("should fail")
+}
+
+fn interpreter_with_clock(now: f64, monotonic: f64) -> Interpreter {
+    Interpreter::builder()
+        .clock(Box::new(FixedClock { now, monotonic }))
+        .build()
+}
+
+fn key(value: Value, interner: &Interner) -> MapKey {
+    MapKey::new(&value, interner).expect("value should be a valid key")
+}
+
+#[test]
+fn map_key_numbers_and_strings_are_distinct() {
+    let interner = Interner::new();
+
+    let num = key(Value::number(1.0), &interner);
+    let str = key(Value::string("1".to_owned()), &interner);
+    assert_ne!(num, str);
+
+    let mut map = Map::default();
+    map.insert(num.clone(), Value::string("number".to_owned()));
+    map.insert(str.clone(), Value::string("string".to_owned()));
+
+    assert_eq!(map.len(), 2);
+    assert_eq!(map.get(&num), Some(Value::string("number".to_owned())));
+    assert_eq!(map.get(&str), Some(Value::string("string".to_owned())));
+}
+
+#[test]
+fn map_key_follows_equality_semantics() {
+    let mut interner = Interner::new();
+    let literal = Value::string_literal(interner.get_or_intern("key"));
+
+    // string literals and produced strings with the same content are the same key
+    assert_eq!(
+        key(literal, &interner),
+        key(Value::string("key".to_owned()), &interner)
+    );
+
+    // 0 == -0, so they must be the same key
+    assert_eq!(
+        key(Value::number(0.0), &interner),
+        key(Value::number(-0.0), &interner)
+    );
+
+    // different types are never the same key even if they are "similar"
+    assert_ne!(
+        key(Value::bool(true), &interner),
+        key(Value::number(1.0), &interner)
+    );
+    assert_ne!(
+        key(Value::nil(), &interner),
+        key(Value::bool(false), &interner)
+    );
+    assert_ne!(
+        key(Value::number(1.0), &interner),
+        key(Value::number(1.0 + f64::EPSILON), &interner)
+    );
+}
+
+#[test]
+fn map_key_shares_the_text_of_a_produced_string() {
+    let interner = Interner::new();
+    let value = Value::string("key".to_owned());
+
+    // keying a map by a string doesn't copy it, only a literal is copied out of the interner
+    match (&value, key(value.clone(), &interner)) {
+        (Value::String(str), MapKey::String(key)) => assert!(Shared::ptr_eq(str, &key)),
+        (_, key) => panic!("expect a string key, got {key:?}"),
+    }
+}
+
+#[test]
+fn map_key_rejects_invalid_values() {
+    let interner = Interner::new();
+
+    assert!(MapKey::new(&Value::number(f64::NAN), &interner).is_err());
+    assert!(MapKey::new(&Value::list(Vec::new()), &interner).is_err());
+    assert!(MapKey::new(&Value::map(Map::default()), &interner).is_err());
+}
+
+#[test]
+fn map_keeps_insertion_order() {
+    let interner = Interner::new();
+
+    let mut map = Map::default();
+    for num in [3.0, 1.0, 2.0] {
+        map.insert(key(Value::number(num), &interner), Value::nil());
+    }
+    map.remove(&key(Value::number(1.0), &interner));
+    map.insert(key(Value::number(1.0), &interner), Value::nil());
+
+    let keys = map.keys().map(|k| k.display(&interner).to_string());
+    assert_eq!(keys.collect::<Vec<_>>(), ["3", "2", "1"]);
+}
+
+#[test]
+fn instance_fields_keep_insertion_order() {
+    let mut interp = Interpreter::builder().build();
+    run(
+        &mut interp,
+        "class P {} var p = P(); p.z = 1; p.a = 2; p.m = 3; p.z = 4;",
+    );
+
+    let Some(Value::Instance(instance)) = interp.get_global("p") else {
+        panic!("p should be an instance");
+    };
+    let fields = instance.fields.borrow();
+    let names = fields.keys().map(|key| interp.interner().resolve(*key));
+    assert_eq!(names.collect::<Vec<_>>(), ["z", "a", "m"]);
+}
+
+#[test]
+fn for_in_iterates_over_builtin_values() {
+    let source = r#"
+for (var x in [1, "a"]) print x;
+var map = Map();
+map.set("k", 1);
+map.set(2, nil);
+for (var key in map) print key;
+for (var ch in "hé") print ch;
+for (var x in []) print x;
+"#;
+    let mut interp = Interpreter::builder().build();
+    assert_eq!(run(&mut interp, source), "1\na\nk\n2\nh\né\n");
+}
+
+#[test]
+fn map_methods() {
+    let source = r#"
+var map = Map();
+map.set("a", 1);
+map.set("b", 2);
+map.set("a", 3);
+print map.len();
+print map.get("a");
+print map.get("c");
+print map.get("c", 0);
+print map.has("b");
+print map.remove("b");
+print map.remove("b");
+print map.has("b");
+print map.keys();
+print map.values();
+"#;
+    let mut interp = Interpreter::builder().build();
+    assert_eq!(
+        run(&mut interp, source),
+        "2\n3\nnil\n0\ntrue\n2\nnil\nfalse\n[a]\n[3]\n"
+    );
+}
+
+#[test]
+fn list_methods() {
+    let source = r#"
+var list = [3, 1];
+list.push(2);
+print list;
+print list.len();
+print list[0] + list[2];
+list[1] = 5;
+print list.pop();
+print list;
+list.insert(0, 4);
+list.insert(3, 6);
+print list;
+print list.remove(1);
+print list.contains(5);
+print list.contains("5");
+print list.indexOf(6);
+print list.indexOf(7);
+list.sort();
+print list;
+fun descending(a, b) { return b - a; }
+list.sort(descending);
+print list;
+var words = ["b", "c", "a"];
+words.sort();
+print words;
+fun double(x) { return x * 2; }
+fun big(x) { return x > 4; }
+print list.map(double);
+print list.filter(big);
+print list;
+"#;
+    let expect = [
+        "[3, 1, 2]",
+        "3",
+        "5",
+        "2",
+        "[3, 5]",
+        "[4, 3, 5, 6]",
+        "3",
+        "true",
+        "false",
+        "2",
+        "-1",
+        "[4, 5, 6]",
+        "[6, 5, 4]",
+        "[a, b, c]",
+        "[12, 10, 8]",
+        "[6, 5]",
+        "[6, 5, 4]",
+    ];
+    let mut interp = Interpreter::builder().build();
+    assert_eq!(run(&mut interp, source), expect.join("\n") + "\n");
+
+    let errors = [
+        ("[].pop();", "Can't pop from an empty list"),
+        ("[1].insert(2, 0);", "Index 2 is out of range for length 1"),
+        ("[1].remove(1);", "Index 1 is out of range for length 1"),
+        ("[1].remove(-1);", "Invalid argument"),
+        ("[1, 2][2];", "Index 2 is out of range for length 2"),
+        (
+            "var l = [1]; l[1] = 2;",
+            "Index 1 is out of range for length 1",
+        ),
+        ("[1, \"a\"].sort();", "Invalid argument"),
+        ("fun none(a, b) {} [1, 2].sort(none);", "Invalid argument"),
+        ("[1].map(1);", "Not a function"),
+    ];
+    for (source, message) in errors {
+        let err = run_error(&mut interp, source).to_string();
+        assert!(err.contains(message), "{source}: {err}");
+    }
+}
+
+#[test]
+fn string_methods() {
+    let source = r#"
+var s = "  héllo wörld ";
+print s.len();
+print s.trim();
+print s.trim().substring(1, 5);
+print s.trim().substring(3, 3) == "";
+print s.indexOf("wö");
+print s.indexOf("x");
+print s.trim().split(" ");
+print "a,b,,c".split(",");
+print "hé".split("");
+print s.toUpper();
+print "ÀB".toLower();
+print "a-b-c".replace("-", "+");
+print s.charAt(3);
+"#;
+    let expect = [
+        "14",
+        "héllo wörld",
+        "éllo",
+        "true",
+        "8",
+        "-1",
+        "[héllo, wörld]",
+        "[a, b, , c]",
+        "[h, é]",
+        "  HÉLLO WÖRLD ",
+        "àb",
+        "a+b+c",
+        "é",
+    ];
+    let mut interp = Interpreter::builder().build();
+    assert_eq!(run(&mut interp, source), expect.join("\n") + "\n");
+
+    let errors = [
+        (
+            "\"abc\".substring(1, 4);",
+            "Index 4 is out of range for length 3",
+        ),
+        (
+            "\"abc\".substring(2, 1);",
+            "Range start 2 is after its end 1",
+        ),
+        ("\"abc\".charAt(3);", "Index 3 is out of range for length 3"),
+        ("\"abc\".charAt(-1);", "Invalid argument"),
+        ("\"abc\".indexOf(1);", "Invalid argument"),
+    ];
+    for (source, message) in errors {
+        let err = run_error(&mut interp, source).to_string();
+        assert!(err.contains(message), "{source}: {err}");
+    }
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn regex_natives() {
+    let source = r#"
+var m = regexMatch("(?<word>[a-z]+)(\d)?", "é1 hello ab3");
+print m.get("text");
+print m.get("start");
+print m.get("end");
+print m.get("groups");
+print m.get("named").get("word");
+print regexMatch("x", "abc");
+for (var m in regexFindAll("[a-z](\d)", "a1 b c2")) print m.get("groups");
+print regexReplace("(?<d>\d+)", "a1b22", "<$d>");
+for (var i = 0; i < 10; i = i + 1) regexMatch("a+", "aaa");
+"#;
+    let expect = [
+        "hello",
+        "3",
+        "8",
+        "[hello, nil]",
+        "hello",
+        "nil",
+        "[1]",
+        "[2]",
+        "a<1>b<22>",
+    ];
+    let mut interp = Interpreter::builder().build();
+    assert_eq!(run(&mut interp, source), expect.join("\n") + "\n");
+
+    // every pattern is compiled once
+    assert_eq!(interp.regexes.borrow().len(), 5);
+    let source = r#"for (var i = 0; i < 100; i = i + 1) regexMatch("a" + str(i), "a");"#;
+    run(&mut interp, source);
+    assert!(interp.regexes.borrow().len() <= 64);
+
+    let err = run_error(&mut interp, r#"regexMatch("(", "a");"#).to_string();
+    assert!(err.contains("regex parse error"), "{err}");
+}
+
+#[test]
+fn for_in_iterates_over_instances() {
+    let source = r#"
+class Range {
+    init(start, end) { this.start = start; this.end = end; }
+    iter() { return RangeIter(this.start, this.end); }
+}
+class RangeIter {
+    init(i, end) { this.i = i; this.end = end; }
+    next() {
+        if (this.i >= this.end) return nil;
+        this.i = this.i + 1;
+        return this.i - 1;
+    }
+}
+for (var i in Range(1, 4)) print i;
+var it = RangeIter(7, 9);
+for (var i in it) print i;
+for (var i in it) print i;
+"#;
+    let mut interp = Interpreter::builder().build();
+    assert_eq!(run(&mut interp, source), "1\n2\n3\n7\n8\n");
+}
+
+#[test]
+fn locals_are_found_in_their_slots() {
+    let source = r#"
+var a = "global";
+{
+    var a = "outer";
+    var b = "b";
+    fun show() { print a + b; }
+    {
+        var b = "inner";
+        var a = b;
+        show();
+        print a;
+        a = "assigned";
+        print a;
+    }
+    show();
+}
+print a;
+"#;
+    let mut interp = Interpreter::builder().build();
+    assert_eq!(
+        run(&mut interp, source),
+        "outerb\ninner\nassigned\nouterb\nglobal\n"
+    );
+}
+
+#[test]
+fn env_child_outlives_the_scope_that_made_it() {
+    let mut interner = Interner::new();
+    let x = interner.get_or_intern("x");
+    let env = DynamicEnv::new_with_global();
+
+    let captured = {
+        let _guard = env.create_scope();
+        env.define(x, Value::Number(1.0));
+        env.current()
+    };
+    assert!(Shared::ptr_eq(&env.current(), env.global()));
+
+    let closure = captured.child();
+    let slot = Slot {
+        distance: 1,
+        index: 0,
+    };
+    assert_eq!(closure.get_at(slot), Some(Value::Number(1.0)));
+    assert!(closure.assign_at(slot, Value::Number(2.0)));
+    assert_eq!(captured.get(x), Some(Value::Number(2.0)));
+}
+
+#[test]
+fn env_shadowed_variables_keep_their_slots() {
+    let mut interner = Interner::new();
+    let x = interner.get_or_intern("x");
+    let outer = Shared::new(Env::new()).child();
+    outer.define(x, Value::Number(1.0));
+    let inner = outer.child();
+    inner.define(x, Value::Number(2.0));
+    inner.define(x, Value::Number(3.0));
+
+    let at = |distance, index| Slot { distance, index };
+    assert_eq!(inner.get(x), Some(Value::Number(3.0)));
+    assert_eq!(inner.get_at(at(0, 0)), Some(Value::Number(2.0)));
+    assert_eq!(inner.get_at(at(1, 0)), Some(Value::Number(1.0)));
+    assert_eq!(inner.get_at(at(2, 0)), None);
+    assert!(!inner.assign_at(at(0, 2), Value::Nil));
+}
+
+#[test]
+fn switch_runs_the_first_equal_case() {
+    let source = r#"
+fun describe(x) {
+    switch (x) {
+        case 1: return "one";
+        case "a" + "b": return "ab";
+        case nil:
+        else: return "other";
+    }
+    return "nil";
+}
+print describe(1);
+print describe("ab");
+print describe(nil);
+print describe(2);
+"#;
+    let mut interp = Interpreter::builder().build();
+    assert_eq!(run(&mut interp, source), "one\nab\nnil\nother\n");
+}
+
+#[test]
+fn functions_and_classes_are_equal_by_identity() {
+    let mut interp = Interpreter::builder().build();
+    let source = r#"
+        fun make() { class A {} fun f() {} return [A, f]; }
+        var a = make();
+        var b = make();
+        print a[0] == a[0];
+        print a[0] == b[0];
+        print a[1] == b[1];
+        print clock == clock;
+        class C { m() {} }
+        var c = C();
+        var m = c.m;
+        print m == m;
+        print c.m == c.m;
+    "#;
+    assert_eq!(
+        run(&mut interp, source),
+        "true\nfalse\nfalse\ntrue\ntrue\nfalse\n"
+    );
+}
+
+#[test]
+fn instances_and_collections_are_equal_by_identity() {
+    let mut interp = Interpreter::builder().build();
+    let source = r#"
+        class A {}
+        var a = A();
+        print a == a;
+        print a == A();
+        print a != A();
+        var list = [1];
+        print list == list;
+        print [1] == [1];
+        var map = Map();
+        print map == map;
+        print Map() == Map();
+        print Math == Math;
+    "#;
+    assert_eq!(
+        run(&mut interp, source),
+        "true\nfalse\ntrue\ntrue\nfalse\ntrue\nfalse\ntrue\n"
+    );
+}
+
+#[test]
+fn logical_operators_short_circuit_and_return_operands() {
+    let source = r#"
+var calls = 0;
+fun touch(value) { calls = calls + 1; return value; }
+print nil or "default";
+print "first" or touch("second");
+print 0 and touch("right");
+print false and touch("right");
+print touch(nil) or touch(false) or touch("last");
+print calls;
+"#;
+    let mut interp = Interpreter::builder().build();
+    assert_eq!(
+        run(&mut interp, source),
+        "default\nfirst\nright\nfalse\nlast\n4\n"
+    );
+}
+
+#[test]
+fn bitwise_operators_truncate_to_integers() {
+    let source = r#"
+print 6 & 3;
+print 6 | 3 ^ 1;
+print 5.9 & 7;
+print -16 >> 2;
+print 1 + 2 << 3;
+print 1 << 64;
+print 1 | 2 == 3;
+"#;
+    let mut interp = Interpreter::builder().build();
+    assert_eq!(run(&mut interp, source), "2\n6\n5\n-4\n24\n1\ntrue\n");
+}
+
+#[test]
+fn integers_are_exact_until_they_overflow() {
+    let source = r#"
+print 9007199254740993;
+print 9223372036854775807 + 1;
+print 7 / 2;
+print 1 == 1.0;
+print 2 * 0.5;
+print Math.div(-7, 2);
+var map = Map();
+map.set(1, "a");
+map.set(1.0, "b");
+print map.len();
+print [10, 20][1.0];
+"#;
+    let mut interp = Interpreter::builder().build();
+    let output = run(&mut interp, source);
+    assert_eq!(
+        output,
+        "9007199254740993\n9.223372036854776E18\n3.5\ntrue\n1\n-4\n1\n20\n"
+    );
+}
+
+#[test]
+fn integers_and_floats_are_ordered_exactly() {
+    let source = r#"
+var int = 9007199254740993;
+var float = 9007199254740992.0;
+print [int == float, int < float, int > float, float < int, int >= float];
+print [int - 1 == float, int - 1 <= float, int - 1 < float];
+print [-3 < -2.5, -2 > -2.5, 2 < 2.5, 3 > 2.5];
+print 9223372036854775807 < 9223372036854775808.0;
+print -9223372036854775807 - 1 >= -9223372036854775808.0;
+print [1 < 0 / 0, 1 > 0 / 0];
+"#;
+    let mut interp = Interpreter::builder().build();
+    let output = run(&mut interp, source);
+    assert_eq!(
+        output,
+        "[false, false, true, true, true]\n[true, true, false]\n[true, true, true, true]\n\
+         true\ntrue\n[false, false]\n"
+    );
+}
+
+#[test]
+fn format_pads_and_converts_values() {
+    let source = r#"
+printf("|{:<6}|{:>6}|{:^7}|", "ab", 1.5, true);
+printf("{:.2} {:08.3} {:x} {:#>6X} {:b} {:e}", 3.14159, -2.5, 255, 255, 5, 1500);
+print format("{{{}}} {:.3}", nil, "truncated");
+"#;
+    let mut interp = Interpreter::builder().build();
+    assert_eq!(
+        run(&mut interp, source),
+        "|ab    |   1.5| true  |\n3.14 -002.500 ff ####FF 101 1.5e3\n{nil} tru\n"
+    );
+
+    let mut lox = Lox::new(&Options::default());
+    lox.set_stderr(OutputBuffer::default());
+    for source in [
+        r#"format("{}");"#,
+        r#"format("{:q}", 1);"#,
+        r#"format("{:x}", 1.5);"#,
+        r#"format("{:99999999999}", 1);"#,
+        r#"format("{:.65536}", 1);"#,
+    ] {
+        assert!(matches!(
+            lox.eval(source),
+            Err(LoxError::RuntimeError {
+                error: RuntimeError::NativeError(..),
+                ..
+            })
+        ));
+    }
+}
+
+#[test]
+fn math_namespace_wraps_number_functions() {
+    let source = r#"
+print Math.abs(-3);
+print Math.floor(2.7) + Math.ceil(2.2) + Math.round(2.5);
+print Math.sqrt(16) + Math.pow(2, 10);
+print Math.min(1, 2) + Math.max(1, 2);
+print Math.sin(0) + Math.cos(0);
+print Math.PI > 3.14 and Math.PI < 3.15;
+"#;
+    let mut interp = Interpreter::builder().build();
+    assert_eq!(run(&mut interp, source), "3\n8\n1028\n3\n1\ntrue\n");
+
+    let mut lox = Lox::new(&Options::default());
+    lox.set_stderr(OutputBuffer::default());
+    assert!(matches!(
+        lox.eval("Math.pow(2);"),
+        Err(LoxError::RuntimeError {
+            error: RuntimeError::FunctionError(..),
+            ..
+        })
+    ));
+}
+
+#[test]
+fn seeded_random_numbers_are_reproducible() {
+    let source = r#"
+seed(42);
+var first = [random(), randomRange(10, 20)];
+seed(42);
+print deepEquals(first, [random(), randomRange(10, 20)]);
+var ok = true;
+for (var i = 0; i < 100; i = i + 1) {
+  var n = randomRange(-3, 3);
+  var x = random();
+  ok = ok and n >= -3 and n < 3 and Math.floor(n) == n and x >= 0 and x < 1;
+}
+print ok;
+"#;
+    let mut interp = Interpreter::builder()
+        .seed(7)
+        .dialect(Dialect::extended())
+        .build();
+    assert_eq!(run(&mut interp, source), "true\ntrue\n");
+}
+
+#[test]
+fn deep_equals_compares_contents() {
+    let source = r#"
+class Point { init(x, y) { this.x = x; this.y = y; } }
+class Other { init(x, y) { this.x = x; this.y = y; } }
+print deepEquals([1, "a", [nil]], [1, "a", [nil]]);
+print deepEquals([1, 2], [1, 2, 3]);
+print deepEquals(1, 1.0);
+var m1 = Map(); m1.set("k", [1]);
+var m2 = Map(); m2.set("k", [1]);
+print deepEquals(m1, m2);
+m2.set("j", 2);
+print deepEquals(m1, m2);
+print deepEquals(Point(1, [2]), Point(1, [2]));
+print deepEquals(Point(1, 2), Point(1, 3));
+print deepEquals(Point(1, 2), Other(1, 2));
+var a = [1]; a.push(a);
+var b = [1]; b.push(b);
+print deepEquals(a, b);
+var c = [2]; c.push(c);
+print deepEquals(a, c);
+var p = Point(1, nil); p.y = p;
+var q = Point(1, nil); q.y = q;
+print deepEquals(p, q);
+"#;
+    let mut interp = Interpreter::builder().dialect(Dialect::extended()).build();
+    assert_eq!(
+        run(&mut interp, source),
+        "true\nfalse\ntrue\ntrue\nfalse\ntrue\nfalse\nfalse\ntrue\nfalse\ntrue\n"
+    );
+
+    // it's opt-in
+    let mut interp = Interpreter::builder().build();
+    let err = run_error(&mut interp, "deepEquals(1, 1);");
+    assert!(matches!(err, RuntimeError::UndefinedVariable(_, _)));
+}
+
+#[test]
+fn type_and_conversions() {
+    let source = r#"
+class A {}
+print type(1) + " " + type("a") + " " + type(nil) + " " + type([]) + " " + type(A());
+print num(" 42 ") + num("1.5") + num(2);
+print str(1) + str(true) + str([1, "a"]);
+"#;
+    let mut interp = Interpreter::builder().build();
+    assert_eq!(
+        run(&mut interp, source),
+        "number string nil list instance\n45.5\n1true[1, a]\n"
+    );
+
+    let mut lox = Lox::new(&Options::default());
+    lox.set_stderr(OutputBuffer::default());
+    assert!(matches!(
+        lox.eval(r#"num("12abc");"#),
+        Err(LoxError::RuntimeError {
+            error: RuntimeError::NativeError(_, _, NativeError::NotANumber(_)),
+            ..
+        })
+    ));
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[test]
+fn process_natives_need_their_capability() {
+    let source =
+        r#"print type(getenv) + " " + type(exec) + " " + str(getenv("LOXI_UNSET_VARIABLE"));"#;
+    let capabilities = Capabilities {
+        env: true,
+        run: true,
+        ..Capabilities::default()
+    };
+    let mut interp = Interpreter::builder().capabilities(capabilities).build();
+    assert_eq!(run(&mut interp, source), "function function nil\n");
+
+    let mut lox = Lox::new(&Options::default());
+    lox.set_stderr(OutputBuffer::default());
+    for source in ["getenv;", "exec;"] {
+        assert!(matches!(
+            lox.eval(source),
+            Err(LoxError::RuntimeError {
+                error: RuntimeError::UndefinedVariable(..),
+                ..
+            })
+        ));
+    }
+}
+
+#[test]
+fn assert_and_panic_fail_at_their_call() {
+    let mut interp = Interpreter::builder().build();
+    assert_eq!(
+        run(
+            &mut interp,
+            r#"assert(true); assert(1, "unused"); print "ok";"#
+        ),
+        "ok\n"
+    );
+
+    // the location is the one of the parenthesis of the call, like for other call errors
+    let cases = [
+        ("assert(false);", 1, 7, "expected a truthy value"),
+        (
+            "var x = nil;\nassert(x, \"x is \" + str(x));",
+            2,
+            7,
+            "x is nil",
+        ),
+        ("assert(nil, [1, 2]);", 1, 7, "[1, 2]"),
+    ];
+    for (source, line, column, message) in cases {
+        match run_error(&mut interp, source) {
+            RuntimeError::AssertionFailed(loc, msg) => {
+                assert_eq!(loc, Location::new(line, column), "{source}");
+                assert_eq!(msg, message);
+            }
+            err => panic!("{source}: unexpected error {err}"),
+        }
+    }
+
+    let err = run_error(&mut interp, "print 1;\npanic(\"boom\");");
+    assert_eq!(err.loc(), Location::new(2, 6));
+    assert!(matches!(&err, RuntimeError::Panic(_, msg) if msg == "boom"));
+    assert!(err.to_string().ends_with("Panic: boom"), "{err}");
+
+    let err = run_error(&mut interp, "panic(3);");
+    assert!(matches!(err, RuntimeError::Panic(_, msg) if msg == "3"));
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[test]
+fn fs_natives_need_their_capability() {
+    let dir = env::temp_dir().join(format!("loxi-fs-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("out.txt");
+    let path = path.to_str().unwrap();
+
+    let source = format!(
+        r#"
+var path = "{path}";
+print exists(path);
+writeFile(path, "a");
+appendFile(path, "b");
+print exists(path);
+print readFile(path);
+writeFile(path, "c");
+print readFile(path);
+"#
+    );
+    let capabilities = Capabilities {
+        fs: true,
+        ..Capabilities::default()
+    };
+    let mut interp = Interpreter::builder().capabilities(capabilities).build();
+    assert_eq!(run(&mut interp, &source), "false\ntrue\nab\nc\n");
+    assert_eq!(fs::read_to_string(path).unwrap(), "c");
+
+    let missing = dir.join("missing.txt");
+    let source = format!(r#"readFile("{}");"#, missing.to_str().unwrap());
+    let err = run_error(&mut interp, &source);
+    assert!(matches!(
+        err,
+        RuntimeError::NativeError(_, _, NativeError::Io(_))
+    ));
+    fs::remove_dir_all(&dir).unwrap();
+
+    let mut interp = Interpreter::builder().build();
+    for source in ["readFile;", "writeFile;", "appendFile;", "exists;"] {
+        let err = run_error(&mut interp, source);
+        assert!(matches!(err, RuntimeError::UndefinedVariable(..)));
+    }
+}
+
+#[test]
+fn json_round_trips_through_values() {
+    let text = r#" {"a": [1, 2.5, -3e2, true, null], "b": "x\n\u00e9\ud83d\ude00\""} "#;
+    let source = r#"
+        var value = jsonParse(text);
+        print value.get("a");
+        print type(value.get("a")[0]) + " " + value.get("b");
+        print jsonStringify(value);
+        print jsonStringify([nil, "q", 0.5]);
+    "#;
+    let mut interp = Interpreter::builder().build();
+    interp.set_global("text", Value::string(text.to_owned()));
+    assert_eq!(
+        run(&mut interp, source),
+        "[1, 2.5, -300, true, nil]\n\
+         number x\né😀\"\n\
+         {\"a\":[1,2.5,-300,true,null],\"b\":\"x\\né😀\\\"\"}\n\
+         [null,\"q\",0.5]\n"
+    );
+
+    let mut lox = Lox::new(&Options::default());
+    lox.set_stderr(OutputBuffer::default());
+    let errors = [
+        (
+            r#"jsonParse("[1, 2");"#,
+            "Invalid JSON at character 5: expect ',' or ']'",
+        ),
+        (
+            r#"jsonParse("{a: 1}");"#,
+            "Invalid JSON at character 1: expect <string>",
+        ),
+        (
+            r#"jsonParse("[1,]");"#,
+            "Invalid JSON at character 3: expect <value>",
+        ),
+        (
+            r#"jsonParse("01");"#,
+            "Invalid JSON at character 1: expect <end of input>",
+        ),
+        (
+            r#"jsonStringify(clock);"#,
+            "Can't convert '<function>' to JSON",
+        ),
+    ];
+    for (source, message) in errors {
+        match lox.eval(source) {
+            Err(LoxError::RuntimeError {
+                error: RuntimeError::NativeError(_, _, error),
+                ..
+            }) => assert_eq!(error.to_string(), message),
+            result => panic!("unexpected result: {result:?}"),
+        }
+    }
+}
+
+#[test]
+fn numbers_are_printed_like_jlox_or_rust() {
+    // the outputs of jlox, e.g. `test/number/literals.lox` of the book
+    let numbers = [
+        (123.0, "123", "123"),
+        (-0.0, "-0", "-0"),
+        (123.456, "123.456", "123.456"),
+        (-0.001, "-0.001", "-0.001"),
+        (0.0001, "1.0E-4", "0.0001"),
+        (1e7, "1.0E7", "10000000"),
+        (1234567.5, "1234567.5", "1234567.5"),
+        (2.5e-10, "2.5E-10", "0.00000000025"),
+        (
+            -1.5e300 * 10.0,
+            "-1.5E301",
+            &format!("-15{}", "0".repeat(300)),
+        ),
+        (f64::NAN, "NaN", "nan"),
+        (f64::INFINITY, "Infinity", "inf"),
+        (f64::NEG_INFINITY, "-Infinity", "-inf"),
+    ];
+    for (num, jlox, rust) in numbers {
+        assert_eq!(NumberStyle::Jlox.format(num), jlox);
+        assert_eq!(NumberStyle::Rust.format(num), rust);
+    }
+
+    let mut interp = Interpreter::builder().build();
+    assert_eq!(
+        run(&mut interp, "print 10000000; print 1e7;"),
+        "10000000\n1.0E7\n"
+    );
+
+    // each interpreter has its own style
+    let mut rust = Interpreter::builder()
+        .number_style(NumberStyle::Rust)
+        .build();
+    assert_eq!(run(&mut rust, "print 1e7;"), "10000000\n");
+    assert_eq!(run(&mut interp, "print 1e7;"), "1.0E7\n");
+}
+
+#[test]
+fn strings_are_ordered_with_extensions() {
+    let mut lox = Lox::new(&Options::default());
+    lox.set_stderr(OutputBuffer::default());
+    let err = lox.eval("\"a\" < \"b\";").unwrap_err();
+    assert!(matches!(
+        err,
+        LoxError::RuntimeError {
+            error: RuntimeError::InvalidBinaryOp(..),
+            ..
+        }
+    ));
+
+    let options = Options {
+        dialect: Dialect::extended(),
+        ..Options::default()
+    };
+    let mut lox = Lox::new(&options);
+    let stdout = OutputBuffer::default();
+    lox.set_stdout(stdout.clone());
+
+    // a literal and a concatenated string are stored differently
+    let source = r#"
+        var b = "b";
+        print "a" < b + "";
+        print "b" <= b + "";
+        print "ab" > "b";
+        print "abc" >= "ab";
+    "#;
+    lox.eval(source).expect("should compare strings");
+    assert_eq!(stdout.text(), "true\ntrue\nfalse\ntrue\n");
+}
+
+#[test]
+fn division_by_zero_is_ieee_unless_strict() {
+    // like jlox, and `nan` is not equal to itself as in `test/number/nan_equality.lox` of the book
+    let mut interp = Interpreter::builder().build();
+    let source =
+        "var nan = 0 / 0; print 1 / 0; print -1 / 0.0; print nan == nan; print nan != nan;";
+    assert_eq!(
+        run(&mut interp, source),
+        "Infinity\n-Infinity\nfalse\ntrue\n"
+    );
+
+    let options = Options {
+        strict_division: true,
+        ..Options::default()
+    };
+    let mut lox = Lox::new(&options);
+    lox.set_stderr(OutputBuffer::default());
+
+    let result = lox.eval("1 / 2;").expect("should divide by non-zero");
+    assert!(matches!(result, Value::Number(0.5)));
+    for source in ["1 / 0;", "0 / 0;", "1.5 / -0.0;"] {
+        let err = lox.eval(source).unwrap_err();
+        assert!(matches!(
+            err,
+            LoxError::RuntimeError {
+                error: RuntimeError::DivisionByZero(_),
+                ..
+            }
+        ));
+    }
+}
+
+#[test]
+fn print_is_also_a_native() {
+    let source = r#"
+var printed = print("a");
+print printed;
+true and println(1 + 2);
+print (1 + 2) * 3;
+"#;
+    let mut interp = Interpreter::builder().build();
+    assert_eq!(run(&mut interp, source), "a\nnil\n3\n9\n");
+}
+
+#[test]
+fn time_reads_from_injected_clock() {
+    let mut interp = interpreter_with_clock(1_700_000_000_123.4, 42.5);
+
+    assert_eq!(run(&mut interp, "write(Time.now());"), "1700000000123");
+    assert_eq!(run(&mut interp, "write(Time.hrtime());"), "42.5");
+    // seconds are printed like jlox does
+    assert_eq!(run(&mut interp, "write(clock());"), "1.7000000001234E9");
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[test]
+fn sleep_waits_on_injected_clock() {
+    /// Moves a day forward every time it's read.
+    struct DayPerRead(Mutex<f64>);
+
+    impl Clock for DayPerRead {
+        fn now(&self) -> f64 {
+            self.monotonic()
+        }
+
+        fn monotonic(&self) -> f64 {
+            let mut now = self.0.lock().unwrap();
+            *now += 86_400_000.0;
+            *now
+        }
+    }
+
+    let clock = DayPerRead(Mutex::new(0.0));
+    let mut interp = Interpreter::builder().clock(Box::new(clock)).build();
+
+    // a week passes on the clock, but only a few polls in reality
+    let start = Instant::now();
+    assert_eq!(
+        run(&mut interp, "sleep(7 * 86400000); write(Time.hrtime());"),
+        "7.776E8"
+    );
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn time_format() {
+    let mut interp = interpreter_with_clock(1_700_000_000_123.0, 0.0);
+
+    let format = |ms: &str| format!(r#"write(Time.formatTime({ms}, "%Y-%m-%d %H:%M:%S.%L"));"#);
+    assert_eq!(
+        run(&mut interp, &format("Time.now()")),
+        "2023-11-14 22:13:20.123"
+    );
+    assert_eq!(run(&mut interp, &format("0")), "1970-01-01 00:00:00.000");
+    assert_eq!(run(&mut interp, &format("-1")), "1969-12-31 23:59:59.999");
+
+    // leap day
+    assert_eq!(
+        run(&mut interp, &format("951782400000")),
+        "2000-02-29 00:00:00.000"
+    );
+
+    // unknown specifiers and literal percent signs are kept as is
+    assert_eq!(
+        run(&mut interp, r#"write(Time.formatTime(0, "%Y%% %q %"));"#),
+        "1970% %q %"
+    );
+}
@@ -0,0 +1,52 @@
+//! Logs every statement and expression of a program as it runs, for debugging.
+
+use std::io::Write;
+
+use crate::util::Location;
+
+use super::observer::{Observer, Scope};
+use super::value::Value;
+use super::Sink;
+
+/// Writes a line for each statement executed, each expression evaluated along with its value, and
+/// each call and return, indented by the depth of the calls. Failing to write is ignored so that
+/// tracing never stops the program.
+pub struct Tracer {
+    out: Box<dyn Sink>,
+    depth: usize,
+}
+
+impl Tracer {
+    pub fn new(out: impl Sink + 'static) -> Self {
+        Self {
+            out: Box::new(out),
+            depth: 0,
+        }
+    }
+
+    fn line(&mut self, loc: Location, event: std::fmt::Arguments<'_>) {
+        let indent = self.depth * 2;
+        let _ = writeln!(self.out, "{:indent$}{loc} {event}", "");
+    }
+}
+
+impl Observer for Tracer {
+    fn on_statement(&mut self, loc: Location, _scope: &Scope<'_>) {
+        self.line(loc, format_args!("statement"));
+    }
+
+    fn on_call(&mut self, name: &str, loc: Location) {
+        self.line(loc, format_args!("call {name}"));
+        self.depth += 1;
+    }
+
+    fn on_return(&mut self, name: &str, loc: Location) {
+        self.depth = self.depth.saturating_sub(1);
+        self.line(loc, format_args!("return {name}"));
+    }
+
+    fn on_value(&mut self, loc: Location, value: &Value, scope: &Scope<'_>) {
+        let value = scope.display(value);
+        self.line(loc, format_args!("value {value}"));
+    }
+}
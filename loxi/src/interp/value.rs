@@ -1,19 +1,43 @@
-use std::rc::Rc;
+use std::cmp::Ordering;
+use std::str::FromStr;
 use std::{fmt::Display, ops::Deref};
 
+use rustc_hash::FxHashSet;
+use strum::IntoEnumIterator;
+
+use crate::util::shared::{Lock, Shared};
+
 use super::class::{Class, Instance};
 use super::function::{Function, Native, UserDefined};
 use super::interner::{Interner, Key};
+use super::map::Map;
+use super::namespace::Namespace;
+use super::object::HostObject;
 
 #[derive(Debug, PartialEq, PartialOrd)]
 pub enum Value {
     Nil,
     Bool(bool),
+
+    /// Numbers follow IEEE 754 semantics: dividing by zero produces `inf` (or `nan` for `0/0`)
+    /// instead of an error, and `nan` is unordered, i.e. `nan == nan` is `false` and every
+    /// comparison involving `nan` is `false`.
     Number(f64),
-    Class(Rc<Class>),
-    String(Rc<String>),
-    Function(Rc<Function>),
-    Instance(Rc<Instance>),
+
+    /// Integers come from literals without a fractional part. Arithmetic between integers gives an
+    /// integer, unless it overflows or it's a division, while mixing them with other numbers gives
+    /// a number. Both are the same type in Lox, i.e. `1 == 1.0`.
+    Int(i64),
+    Class(Shared<Class>),
+    String(Shared<str>),
+    Function(Shared<Function>),
+    Instance(Shared<Instance>),
+    List(Shared<Lock<Vec<Value>>>),
+    Map(Shared<Lock<Map>>),
+    Namespace(Shared<Namespace>),
+
+    /// Instance of a class defined by the host, see `LoxClass`.
+    Object(Shared<HostObject>),
 
     /// `StringLiteral` is a special case of string, the value is static.
     /// It can only produces real `String` if it was operated on.
@@ -28,6 +52,7 @@ pub enum Value {
 pub struct DisplayedValue<'a, 'b> {
     value: &'a Value,
     interner: &'b Interner,
+    style: NumberStyle,
 }
 
 pub enum InvalidOp {
@@ -37,6 +62,9 @@ pub enum InvalidOp {
 
 type OpResult = Result<Value, InvalidOp>;
 
+/// 2^63, the bound of the floats that fit in `i64`. It's exactly representable, unlike `i64::MAX`.
+const INT_LIMIT: f64 = 9_223_372_036_854_775_808.0;
+
 impl Value {
     pub fn nil() -> Self {
         Value::Nil
@@ -50,30 +78,113 @@ impl Value {
         Value::Number(num)
     }
 
+    pub fn int(int: i64) -> Self {
+        Value::Int(int)
+    }
+
     pub fn class(class: Class) -> Self {
-        Value::Class(Rc::new(class))
+        Value::Class(Shared::new(class))
     }
 
     pub fn string(str: String) -> Self {
-        Value::String(Rc::new(str))
+        Value::String(Shared::from(str))
     }
 
     pub fn function(func: UserDefined) -> Self {
-        Value::Function(Rc::new(Function::UserDefined(func)))
+        Value::Function(Shared::new(Function::UserDefined(func)))
     }
 
     pub fn native_function(func: Native) -> Self {
-        Value::Function(Rc::new(Function::Native(func)))
+        Value::Function(Shared::new(Function::Native(func)))
     }
 
     pub fn instance(instance: Instance) -> Self {
-        Value::Instance(Rc::new(instance))
+        Value::Instance(Shared::new(instance))
     }
 
     pub fn string_literal(key: Key) -> Self {
         Value::StringLiteral(key)
     }
 
+    pub fn list(values: Vec<Value>) -> Self {
+        Value::List(Shared::new(Lock::new(values)))
+    }
+
+    pub fn map(map: Map) -> Self {
+        Value::Map(Shared::new(Lock::new(map)))
+    }
+
+    pub fn namespace(namespace: Namespace) -> Self {
+        Value::Namespace(Shared::new(namespace))
+    }
+
+    /// Get the underlying string if the value is a `String` or a `StringLiteral`.
+    pub fn as_str<'a>(&'a self, interner: &'a Interner) -> Option<&'a str> {
+        match self {
+            Value::String(str) => Some(str.as_ref()),
+            Value::StringLiteral(key) => Some(interner.resolve(*key)),
+            _ => None,
+        }
+    }
+
+    /// Get the value of a `Number` or an `Int`, the latter may be rounded.
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Number(num) => Some(*num),
+            Value::Int(int) => Some(*int as f64),
+            _ => None,
+        }
+    }
+
+    /// Get the value of an `Int`, or of a `Number` without a fractional part that fits in `i64`.
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Value::Number(num) => float_to_int(*num),
+            Value::Int(int) => Some(*int),
+            _ => None,
+        }
+    }
+
+    /// Turn a `StringLiteral` into a `String` so it can be read without the interner, e.g. by the
+    /// host. The literals in lists, maps, and fields of instances are turned in place, which the
+    /// program can't notice since both are the same string. Other values are returned as is.
+    pub fn resolved(self, interner: &Interner) -> Value {
+        match self {
+            Value::StringLiteral(key) => Value::string(interner.resolve(key).to_owned()),
+            value => {
+                value.resolve_contents(interner, &mut FxHashSet::default());
+                value
+            }
+        }
+    }
+
+    /// Resolve the literals in a list, map, or instance. Each one is only visited once since they
+    /// may contain themselves.
+    fn resolve_contents(&self, interner: &Interner, visited: &mut FxHashSet<usize>) {
+        let ptr = match self {
+            Value::List(list) => Shared::as_ptr(list) as usize,
+            Value::Map(map) => Shared::as_ptr(map) as usize,
+            Value::Instance(instance) => Shared::as_ptr(instance) as usize,
+            _ => return,
+        };
+        if !visited.insert(ptr) {
+            return;
+        }
+
+        let resolve = |value: &mut Value| match value {
+            Value::StringLiteral(key) => *value = Value::string(interner.resolve(*key).to_owned()),
+            value => value.resolve_contents(interner, visited),
+        };
+        match self {
+            Value::List(list) => list.borrow_mut().iter_mut().for_each(resolve),
+            Value::Map(map) => map.borrow_mut().values_mut().for_each(resolve),
+            Value::Instance(instance) => {
+                instance.fields.borrow_mut().values_mut().for_each(resolve)
+            }
+            _ => unreachable!("only containers should have been visited"),
+        }
+    }
+
     /// follows Ruby's simple rule: `false` and `nil` are falsy, everything else truthy
     pub fn truthiness(&self) -> bool {
         match self {
@@ -83,103 +194,161 @@ impl Value {
         }
     }
 
-    pub fn not(&self) -> Value {
+    pub(crate) fn not(&self) -> Value {
         Value::Bool(!self.truthiness())
     }
 
-    pub fn minus(&self) -> OpResult {
+    pub(crate) fn minus(&self) -> OpResult {
         match self {
             Value::Number(num) => Ok(Value::number(-num)),
+            // `-0` is kept as a number, integers have no negative zero
+            Value::Int(int) => Ok(match int.checked_neg() {
+                Some(neg) if *int != 0 => Value::int(neg),
+                _ => Value::number(-(*int as f64)),
+            }),
             _ => invalid_unary(self),
         }
     }
 
-    pub fn add(self, other: Self, interner: &Interner) -> OpResult {
+    pub(crate) fn add(self, other: Self, interner: &Interner) -> OpResult {
         match (self, other) {
-            (Value::Number(num1), Value::Number(num2)) => Ok(Value::number(num1 + num2)),
-            (Value::String(str1), Value::String(str2)) => {
-                let mut new_str = str1.deref().clone();
-                new_str.push_str(str2.deref().as_str());
-                Ok(Value::string(new_str))
+            (lhs @ (Value::Number(_) | Value::Int(_)), rhs) => {
+                lhs.arithmetic(rhs, i64::checked_add, |num1, num2| num1 + num2)
             }
-            (Value::String(str1), Value::StringLiteral(str2)) => {
-                let mut new_str = str1.deref().clone();
-                let str2 = interner.resolve(str2);
-                new_str.push_str(str2);
-                Ok(Value::string(new_str))
-            }
-            (Value::StringLiteral(str1), Value::String(str2)) => {
-                let mut new_str = str2.deref().clone();
-                let str1 = interner.resolve(str1);
-                new_str.insert_str(0, str1);
-                Ok(Value::string(new_str))
-            }
-            (Value::StringLiteral(str1), Value::StringLiteral(str2)) => {
-                let mut new_str = String::new();
-                let str1 = interner.resolve(str1);
-                let str2 = interner.resolve(str2);
-                new_str.push_str(str1);
-                new_str.push_str(str2);
-                Ok(Value::string(new_str))
-            }
-            (lhs, rhs) => invalid_binary(&lhs, &rhs),
+            (lhs, rhs) => match (lhs.as_str(interner), rhs.as_str(interner)) {
+                (Some(str1), Some(str2)) => Ok(Value::string([str1, str2].concat())),
+                _ => invalid_binary(&lhs, &rhs),
+            },
         }
     }
 
-    pub fn sub(self, other: Self) -> OpResult {
-        match (self, other) {
-            (Value::Number(num1), Value::Number(num2)) => Ok(Value::number(num1 - num2)),
-            (lhs, rhs) => invalid_binary(&lhs, &rhs),
-        }
+    pub(crate) fn sub(self, other: Self) -> OpResult {
+        self.arithmetic(other, i64::checked_sub, |num1, num2| num1 - num2)
     }
 
-    pub fn mul(self, other: Self) -> OpResult {
-        match (self, other) {
-            (Value::Number(num1), Value::Number(num2)) => Ok(Value::number(num1 * num2)),
-            (lhs, rhs) => invalid_binary(&lhs, &rhs),
+    pub(crate) fn mul(self, other: Self) -> OpResult {
+        self.arithmetic(other, i64::checked_mul, |num1, num2| num1 * num2)
+    }
+
+    /// The result is always a number, e.g. `7 / 2` is `3.5`, see `Math.div` for the integer
+    /// division.
+    pub(crate) fn div(self, other: Self) -> OpResult {
+        self.arithmetic(other, |_, _| None, |num1, num2| num1 / num2)
+    }
+
+    /// Apply `int` to two integers, or `float` to the numbers if either isn't an integer or `int`
+    /// overflows.
+    fn arithmetic(
+        self,
+        other: Self,
+        int: impl Fn(i64, i64) -> Option<i64>,
+        float: impl Fn(f64, f64) -> f64,
+    ) -> OpResult {
+        if let (Value::Int(int1), Value::Int(int2)) = (&self, &other) {
+            if let Some(result) = int(*int1, *int2) {
+                return Ok(Value::int(result));
+            }
+        }
+        match (self.as_number(), other.as_number()) {
+            (Some(num1), Some(num2)) => Ok(Value::number(float(num1, num2))),
+            _ => invalid_binary(&self, &other),
         }
     }
 
-    pub fn div(self, other: Self) -> OpResult {
-        match (self, other) {
-            (Value::Number(num1), Value::Number(num2)) => Ok(Value::number(num1 / num2)),
-            (lhs, rhs) => invalid_binary(&lhs, &rhs),
+    pub(crate) fn bit_and(self, other: Self) -> OpResult {
+        self.integers(other, |int1, int2| int1 & int2)
+    }
+
+    pub(crate) fn bit_or(self, other: Self) -> OpResult {
+        self.integers(other, |int1, int2| int1 | int2)
+    }
+
+    pub(crate) fn bit_xor(self, other: Self) -> OpResult {
+        self.integers(other, |int1, int2| int1 ^ int2)
+    }
+
+    /// Only the low 6 bits of the shift amount are used, like the shifts of `i64`.
+    pub(crate) fn shl(self, other: Self) -> OpResult {
+        self.integers(other, |int1, int2| int1.wrapping_shl(int2 as u32))
+    }
+
+    pub(crate) fn shr(self, other: Self) -> OpResult {
+        self.integers(other, |int1, int2| int1.wrapping_shr(int2 as u32))
+    }
+
+    /// Apply `op` to the numbers truncated to integers, saturating at the bounds of `i64` and with
+    /// nan as 0.
+    fn integers(self, other: Self, op: impl Fn(i64, i64) -> i64) -> OpResult {
+        let truncate = |value: &Value| match value {
+            Value::Number(num) => Some(*num as i64),
+            Value::Int(int) => Some(*int),
+            _ => None,
+        };
+        match (truncate(&self), truncate(&other)) {
+            (Some(int1), Some(int2)) => Ok(Value::int(op(int1, int2))),
+            _ => invalid_binary(&self, &other),
         }
     }
 
-    pub fn eq(&self, other: &Self, interner: &Interner) -> Value {
+    pub(crate) fn eq(&self, other: &Self, interner: &Interner) -> Value {
         Value::Bool(self.is_equal(other, interner))
     }
 
-    pub fn neq(&self, other: &Self, interner: &Interner) -> Value {
+    pub(crate) fn neq(&self, other: &Self, interner: &Interner) -> Value {
         Value::Bool(!self.is_equal(other, interner))
     }
 
-    pub fn gt(&self, other: &Self) -> OpResult {
-        match (self, other) {
-            (Value::Number(num1), Value::Number(num2)) => Ok(Value::bool(*num1 > *num2)),
-            (lhs, rhs) => invalid_binary(lhs, rhs),
-        }
+    /// Structural equality. Unlike `eq`, lists and maps are compared element-by-element and
+    /// instances of the same class are compared field-by-field instead of by identity.
+    /// Self-referencing objects are handled by assuming that a pair that is already being compared
+    /// is equal.
+    pub fn deep_eq(&self, other: &Self, interner: &Interner) -> bool {
+        let mut visited = FxHashSet::default();
+        self.is_deep_equal(other, interner, &mut visited)
     }
 
-    pub fn ge(&self, other: &Self) -> OpResult {
-        match (self, other) {
-            (Value::Number(num1), Value::Number(num2)) => Ok(Value::bool(*num1 >= *num2)),
-            (lhs, rhs) => invalid_binary(lhs, rhs),
-        }
+    pub(crate) fn gt(&self, other: &Self, interner: &Interner) -> OpResult {
+        let ordering = self.compare(other, interner)?;
+        Ok(Value::bool(ordering == Some(Ordering::Greater)))
     }
 
-    pub fn lt(&self, other: &Self) -> OpResult {
-        match (self, other) {
-            (Value::Number(num1), Value::Number(num2)) => Ok(Value::bool(*num1 < *num2)),
-            (lhs, rhs) => invalid_binary(lhs, rhs),
-        }
+    pub(crate) fn ge(&self, other: &Self, interner: &Interner) -> OpResult {
+        let ordering = self.compare(other, interner)?;
+        Ok(Value::bool(matches!(
+            ordering,
+            Some(Ordering::Greater | Ordering::Equal)
+        )))
     }
 
-    pub fn le(&self, other: &Self) -> OpResult {
+    pub(crate) fn lt(&self, other: &Self, interner: &Interner) -> OpResult {
+        let ordering = self.compare(other, interner)?;
+        Ok(Value::bool(ordering == Some(Ordering::Less)))
+    }
+
+    pub(crate) fn le(&self, other: &Self, interner: &Interner) -> OpResult {
+        let ordering = self.compare(other, interner)?;
+        Ok(Value::bool(matches!(
+            ordering,
+            Some(Ordering::Less | Ordering::Equal)
+        )))
+    }
+
+    /// Order two numbers, `None` if either is `nan`, or two strings lexicographically.
+    fn compare(&self, other: &Self, interner: &Interner) -> Result<Option<Ordering>, InvalidOp> {
         match (self, other) {
-            (Value::Number(num1), Value::Number(num2)) => Ok(Value::bool(*num1 <= *num2)),
-            (lhs, rhs) => invalid_binary(lhs, rhs),
+            (Value::Int(int1), Value::Int(int2)) => return Ok(Some(int1.cmp(int2))),
+            (Value::Int(int), Value::Number(num)) => return Ok(cmp_int_float(*int, *num)),
+            (Value::Number(num), Value::Int(int)) => {
+                return Ok(cmp_int_float(*int, *num).map(Ordering::reverse))
+            }
+            _ => (),
+        }
+        if let (Some(str1), Some(str2)) = (self.as_str(interner), other.as_str(interner)) {
+            return Ok(Some(str1.cmp(str2)));
+        }
+        match (self.as_number(), other.as_number()) {
+            (Some(num1), Some(num2)) => Ok(num1.partial_cmp(&num2)),
+            _ => Err(InvalidOp::Binary(self.name(), other.name())),
         }
     }
 
@@ -187,44 +356,142 @@ impl Value {
         match self {
             Value::Nil => "<nil>",
             Value::Bool(_) => "<bool>",
-            Value::Number(_) => "<number>",
+            Value::Number(_) | Value::Int(_) => "<number>",
             Value::String(_) => "<string>",
             Value::Class(_) => "<class>",
             Value::Instance(_) => "<instance>",
             Value::Function(_) => "<function>",
+            Value::List(_) => "<list>",
+            Value::Map(_) => "<map>",
+            Value::Namespace(_) => "<namespace>",
+            Value::Object(_) => "<object>",
             Value::StringLiteral(_) => "<string_literal>",
         }
     }
 
+    /// The name of the type as `type(value)` gives it, e.g. `"number"`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::StringLiteral(_) => "string",
+            value => value.name().trim_matches(['<', '>']),
+        }
+    }
+
     pub fn display<'a, 'b>(&'a self, interner: &'b Interner) -> DisplayedValue<'a, 'b> {
         DisplayedValue {
             value: self,
             interner,
+            style: NumberStyle::default(),
         }
     }
 
-    fn is_equal(&self, other: &Self, interner: &Interner) -> bool {
+    pub fn is_equal(&self, other: &Self, interner: &Interner) -> bool {
         match (self, other) {
             (Value::Nil, Value::Nil) => true,
             (Value::Bool(b1), Value::Bool(b2)) => b1 == b2,
             (Value::Number(num1), Value::Number(num2)) => num1 == num2,
+            (Value::Int(int1), Value::Int(int2)) => int1 == int2,
+            (Value::Int(int), Value::Number(num)) | (Value::Number(num), Value::Int(int)) => {
+                float_to_int(*num) == Some(*int)
+            }
             (Value::String(str1), Value::String(str2)) => str1 == str2,
 
-            // FIXME: not working as expected (failing: test/class/methods_equality.lox)
-            (Value::Function(func1), Value::Function(func2)) => func1 == func2,
-
-            (Value::Class(cls1), Value::Class(cls2)) => cls1 == cls2,
-            (Value::Instance(_), Value::Instance(_)) => todo!(),
+            // every evaluation of a declaration or of a method access makes a new function or
+            // class, even if it looks the same as another one, e.g. `obj.method == obj.method` is
+            // false
+            (Value::Function(func1), Value::Function(func2)) => Shared::ptr_eq(func1, func2),
+            (Value::Class(cls1), Value::Class(cls2)) => Shared::ptr_eq(cls1, cls2),
+            (Value::Instance(inst1), Value::Instance(inst2)) => Shared::ptr_eq(inst1, inst2),
+            (Value::List(list1), Value::List(list2)) => Shared::ptr_eq(list1, list2),
+            (Value::Map(map1), Value::Map(map2)) => Shared::ptr_eq(map1, map2),
+            (Value::Namespace(ns1), Value::Namespace(ns2)) => Shared::ptr_eq(ns1, ns2),
+            (Value::Object(obj1), Value::Object(obj2)) => Shared::ptr_eq(obj1, obj2),
             (Value::String(str1), Value::StringLiteral(str2)) => {
-                str1.as_str() == interner.resolve(*str2)
+                str1.as_ref() == interner.resolve(*str2)
             }
             (Value::StringLiteral(str1), Value::String(str2)) => {
-                interner.resolve(*str1) == str2.as_str()
+                interner.resolve(*str1) == str2.as_ref()
             }
             (Value::StringLiteral(str1), Value::StringLiteral(str2)) => str1 == str2,
             _ => false,
         }
     }
+
+    fn is_deep_equal(
+        &self,
+        other: &Self,
+        interner: &Interner,
+        visited: &mut FxHashSet<(usize, usize)>,
+    ) -> bool {
+        match (self, other) {
+            (Value::Instance(lhs), Value::Instance(rhs)) => {
+                if Shared::ptr_eq(lhs, rhs) {
+                    return true;
+                }
+                if !Shared::ptr_eq(&lhs.class, &rhs.class) {
+                    return false;
+                }
+
+                let pair = (Shared::as_ptr(lhs) as usize, Shared::as_ptr(rhs) as usize);
+                if !visited.insert(pair) {
+                    return true;
+                }
+
+                let lfields = lhs.fields.borrow();
+                let rfields = rhs.fields.borrow();
+                lfields.len() == rfields.len()
+                    && lfields.iter().all(|(key, lvalue)| match rfields.get(key) {
+                        Some(rvalue) => lvalue.is_deep_equal(rvalue, interner, visited),
+                        None => false,
+                    })
+            }
+            (Value::List(lhs), Value::List(rhs)) => {
+                if Shared::ptr_eq(lhs, rhs) {
+                    return true;
+                }
+
+                let pair = (Shared::as_ptr(lhs) as usize, Shared::as_ptr(rhs) as usize);
+                if !visited.insert(pair) {
+                    return true;
+                }
+
+                let lhs = lhs.borrow();
+                let rhs = rhs.borrow();
+                lhs.len() == rhs.len()
+                    && lhs
+                        .iter()
+                        .zip(rhs.iter())
+                        .all(|(l, r)| l.is_deep_equal(r, interner, visited))
+            }
+            (Value::Map(lhs), Value::Map(rhs)) => {
+                if Shared::ptr_eq(lhs, rhs) {
+                    return true;
+                }
+
+                let pair = (Shared::as_ptr(lhs) as usize, Shared::as_ptr(rhs) as usize);
+                if !visited.insert(pair) {
+                    return true;
+                }
+
+                let lhs = lhs.borrow();
+                let rhs = rhs.borrow();
+                lhs.len() == rhs.len()
+                    && lhs.iter().all(|(key, lvalue)| match rhs.get(key) {
+                        Some(rvalue) => lvalue.is_deep_equal(&rvalue, interner, visited),
+                        None => false,
+                    })
+            }
+            _ => self.is_equal(other, interner),
+        }
+    }
+}
+
+impl DisplayedValue<'_, '_> {
+    /// Print the numbers that are not integers in `style`, `NumberStyle::Jlox` if not set.
+    pub fn with_style(mut self, style: NumberStyle) -> Self {
+        self.style = style;
+        self
+    }
 }
 
 impl Display for DisplayedValue<'_, '_> {
@@ -233,8 +500,9 @@ impl Display for DisplayedValue<'_, '_> {
         match self.value {
             Value::Nil => write!(f, "nil"),
             Value::Bool(b) => write!(f, "{b}"),
-            Value::Number(num) => write!(f, "{num}"),
-            Value::String(str) => write!(f, "{}", str.as_str()),
+            Value::Number(num) => f.write_str(&self.style.format(*num)),
+            Value::Int(int) => write!(f, "{int}"),
+            Value::String(str) => write!(f, "{str}"),
             Value::Class(class) => write!(f, "<class {}>", interner.resolve(class.name)),
             Value::Instance(instance) => write!(
                 f,
@@ -252,6 +520,13 @@ impl Display for DisplayedValue<'_, '_> {
                     write!(f, "<native_fun {name}>")
                 }
             },
+            Value::List(_) | Value::Map(_) => {
+                write_nested(f, self.value, interner, self.style, &mut Vec::new())
+            }
+            Value::Namespace(ns) => write!(f, "<namespace {}>", interner.resolve(ns.name)),
+            Value::Object(object) => {
+                write!(f, "<object of:{}>", interner.resolve(object.class.name))
+            }
             Value::StringLiteral(key) => {
                 let name = interner.resolve(*key);
                 write!(f, "{}", name)
@@ -260,21 +535,178 @@ impl Display for DisplayedValue<'_, '_> {
     }
 }
 
+/// How numbers that are not integers are printed. Integers are always printed in full, since they
+/// are exact, while jlox prints every number as a double.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, strum::EnumIter)]
+pub enum NumberStyle {
+    /// Like jlox: `Double.toString` without a trailing `.0`, so numbers from 1e-3 up to 1e7 are
+    /// printed as decimals and the rest in scientific notation like `1.5E10`, and `NaN`,
+    /// `Infinity`, and `-Infinity` for the special values.
+    #[default]
+    Jlox,
+
+    /// Like Rust's `Display`: the shortest decimal that reads back as the same number, without an
+    /// exponent, and `nan`, `inf`, and `-inf` for the special values, which are also their names
+    /// in Lox.
+    Rust,
+}
+
+impl NumberStyle {
+    pub fn name(self) -> &'static str {
+        match self {
+            NumberStyle::Jlox => "jlox",
+            NumberStyle::Rust => "rust",
+        }
+    }
+
+    pub fn format(self, num: f64) -> String {
+        match self {
+            NumberStyle::Rust if num.is_nan() => "nan".to_owned(),
+            NumberStyle::Rust => format!("{num}"),
+            NumberStyle::Jlox if num.is_nan() => "NaN".to_owned(),
+            NumberStyle::Jlox if num.is_infinite() => match num > 0.0 {
+                true => "Infinity".to_owned(),
+                false => "-Infinity".to_owned(),
+            },
+            NumberStyle::Jlox if num == 0.0 || (1e-3..1e7).contains(&num.abs()) => {
+                format!("{num}")
+            }
+            NumberStyle::Jlox => {
+                // the mantissa has at least one decimal, as in `1.0E7`
+                let sci = format!("{num:e}");
+                let (mantissa, exp) = sci.split_once('e').expect("should have an exponent");
+                match mantissa.contains('.') {
+                    true => format!("{mantissa}E{exp}"),
+                    false => format!("{mantissa}.0E{exp}"),
+                }
+            }
+        }
+    }
+}
+
+impl Display for NumberStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for NumberStyle {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        NumberStyle::iter()
+            .find(|style| style.name() == name)
+            .ok_or_else(|| {
+                let styles = NumberStyle::iter()
+                    .map(NumberStyle::name)
+                    .collect::<Vec<_>>();
+                format!(
+                    "unknown number style '{name}', expect one of: {}",
+                    styles.join(", ")
+                )
+            })
+    }
+}
+
+/// Write a list or a map, printing `[...]` or `{...}` for one that (indirectly) contains itself
+/// instead of recursing forever.
+fn write_nested(
+    f: &mut std::fmt::Formatter<'_>,
+    value: &Value,
+    interner: &Interner,
+    style: NumberStyle,
+    parents: &mut Vec<*const ()>,
+) -> std::fmt::Result {
+    let ptr = match value {
+        Value::List(list) => Shared::as_ptr(list) as *const (),
+        Value::Map(map) => Shared::as_ptr(map) as *const (),
+        _ => return write!(f, "{}", value.display(interner).with_style(style)),
+    };
+
+    if parents.contains(&ptr) {
+        return match value {
+            Value::List(_) => write!(f, "[...]"),
+            _ => write!(f, "{{...}}"),
+        };
+    }
+
+    parents.push(ptr);
+    match value {
+        Value::List(list) => {
+            write!(f, "[")?;
+            for (i, value) in list.borrow().iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write_nested(f, value, interner, style, parents)?;
+            }
+            write!(f, "]")?;
+        }
+        Value::Map(map) => {
+            write!(f, "{{")?;
+            for (i, (key, value)) in map.borrow().iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(
+                    f,
+                    "{}: ",
+                    key.to_value().display(interner).with_style(style)
+                )?;
+                write_nested(f, value, interner, style, parents)?;
+            }
+            write!(f, "}}")?;
+        }
+        _ => unreachable!(),
+    }
+    parents.pop();
+
+    Ok(())
+}
+
 impl Clone for Value {
     fn clone(&self) -> Self {
         match self {
             Value::Nil => Value::Nil,
             Value::Bool(b) => Value::Bool(*b),
             Value::Number(num) => Value::Number(*num),
-            Value::Function(fun) => Value::Function(Rc::clone(fun)),
-            Value::String(str) => Value::String(Rc::clone(str)),
-            Value::Class(class) => Value::Class(Rc::clone(class)),
-            Value::Instance(instance) => Value::Instance(Rc::clone(instance)),
+            Value::Int(int) => Value::Int(*int),
+            Value::Function(fun) => Value::Function(Shared::clone(fun)),
+            Value::String(str) => Value::String(Shared::clone(str)),
+            Value::Class(class) => Value::Class(Shared::clone(class)),
+            Value::Instance(instance) => Value::Instance(Shared::clone(instance)),
+            Value::List(list) => Value::List(Shared::clone(list)),
+            Value::Map(map) => Value::Map(Shared::clone(map)),
+            Value::Namespace(ns) => Value::Namespace(Shared::clone(ns)),
+            Value::Object(object) => Value::Object(Shared::clone(object)),
             Value::StringLiteral(key) => Value::StringLiteral(*key),
         }
     }
 }
 
+/// The integer equal to `num`, if any.
+pub(crate) fn float_to_int(num: f64) -> Option<i64> {
+    match num.fract() == 0.0 && (-INT_LIMIT..INT_LIMIT).contains(&num) {
+        true => Some(num as i64),
+        false => None,
+    }
+}
+
+/// Compare without converting `int` to a float, which rounds it past 2^53, so the ordering agrees
+/// with `Value::is_equal`.
+fn cmp_int_float(int: i64, num: f64) -> Option<Ordering> {
+    if num.is_nan() {
+        None
+    } else if num >= INT_LIMIT {
+        Some(Ordering::Less)
+    } else if num < -INT_LIMIT {
+        Some(Ordering::Greater)
+    } else {
+        let ordering = int.cmp(&(num.trunc() as i64));
+        Some(ordering.then(0.0.partial_cmp(&num.fract())?))
+    }
+}
+
 fn invalid_unary(value: &Value) -> OpResult {
     Err(InvalidOp::Unary(value.name()))
 }
@@ -1,12 +1,16 @@
 use std::fmt::{Debug, Display, Formatter};
 use std::iter::Peekable;
+use std::ops::Range;
 use std::str::CharIndices;
 
 use thiserror::Error;
 use unicode_width::UnicodeWidthChar;
 
+use crate::analyze;
+use crate::dialect::Dialect;
 use crate::interp::interner::{Interner, Key};
-use crate::util::{self, Location, LoxToken, TokLoc};
+use crate::message;
+use crate::util::{self, Location, LoxToken, SourceId, TokLoc};
 use macros::tok;
 
 pub mod token;
@@ -87,32 +91,119 @@ impl Display for DisplayedToken<'_, '_> {
 
 #[derive(Debug, Error)]
 pub enum LexError {
-    #[error("{0} Unknown token ({1}) [{2:#x}]")]
     UnknownToken(Location, char, u32),
-
-    #[error("{0} Unterminated string")]
     UnterminatedString(Location),
-
-    #[error("{0} Unable to parse Number '{1}'")]
     UnableToParseNumber(Location, String),
 }
 
+impl Display for LexError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.loc(), self.message())
+    }
+}
+
+impl LexError {
+    pub fn loc(&self) -> Location {
+        match self {
+            LexError::UnknownToken(loc, _, _) => *loc,
+            LexError::UnterminatedString(loc) => *loc,
+            LexError::UnableToParseNumber(loc, _) => *loc,
+        }
+    }
+
+    /// The unknown character or the number, an unterminated string spans nothing.
+    pub fn span(&self) -> analyze::Span {
+        match self {
+            LexError::UnknownToken(loc, char, _) => {
+                analyze::Span::new(*loc, char.encode_utf8(&mut [0; 4]))
+            }
+            LexError::UnterminatedString(loc) => analyze::Span::at(*loc),
+            LexError::UnableToParseNumber(loc, number) => analyze::Span::new(*loc, number),
+        }
+    }
+
+    /// The message without the location.
+    pub fn message(&self) -> String {
+        match self {
+            LexError::UnknownToken(_, char, codepoint) => message::format(
+                "lex.unknown-token",
+                &[("char", char), ("codepoint", &format!("{codepoint:#x}"))],
+            ),
+            LexError::UnterminatedString(_) => message::format("lex.unterminated-string", &[]),
+            LexError::UnableToParseNumber(_, number) => {
+                message::format("lex.invalid-number", &[("number", number)])
+            }
+        }
+    }
+}
+
+/// What a piece of the source is, including the trivia that doesn't become a token.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpanKind {
+    Keyword,
+    Identifier,
+    String,
+    Number,
+    Operator,
+    Punctuation,
+    Comment,
+    Whitespace,
+    Unknown,
+}
+
+/// A piece of the source in bytes, kept by the lexer when created using `with_trivia`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub kind: SpanKind,
+    pub range: Range<usize>,
+}
+
 #[derive(Debug)]
 pub struct Lexer<'a, 'b> {
     source: &'a str,
     chars: Peekable<CharIndices<'a>>,
-    lines: Vec<&'a str>,
     interner: &'b mut Interner,
+    dialect: Dialect,
     tokens: Vec<Token>,
     errors: Vec<LexError>,
     line: LineLocation,
+    spans: Option<Vec<Span>>,
+    docs: Vec<DocComment>,
+    pragmas: Vec<Pragma>,
+    suspicious: Vec<(Location, char)>,
 }
 
 #[derive(Debug)]
-pub struct ScanResult<'a> {
-    pub lines: Vec<&'a str>,
+pub struct ScanResult {
     pub tokens: Vec<Token>,
     pub errors: Vec<LexError>,
+
+    /// Every piece of the source in order, empty unless the lexer keeps the trivia.
+    pub spans: Vec<Span>,
+
+    /// The `///` comments in order, which document the declaration that follows them.
+    pub docs: Vec<DocComment>,
+
+    /// The `// lint:` comments in order, which configure the linter for the lines around them.
+    pub pragmas: Vec<Pragma>,
+
+    /// Whitespace that is not ASCII, e.g. a non-breaking space, which looks like a space but may
+    /// not be one in other tools.
+    pub suspicious: Vec<(Location, char)>,
+}
+
+/// A line of a `///` comment, without the slashes and the space after them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DocComment {
+    pub loc: Location,
+    pub text: String,
+}
+
+/// A `// lint:` comment, with the text after the colon trimmed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pragma {
+    pub loc: Location,
+    pub text: String,
 }
 
 impl<'a, 'b> Lexer<'a, 'b> {
@@ -120,8 +211,8 @@ impl<'a, 'b> Lexer<'a, 'b> {
         Self {
             source: program,
             chars: program.char_indices().peekable(),
-            lines: Vec::new(),
             interner,
+            dialect: Dialect::default(),
             tokens: Vec::new(),
             errors: Vec::new(),
             line: LineLocation {
@@ -129,23 +220,62 @@ impl<'a, 'b> Lexer<'a, 'b> {
                 start: 0,
                 column: 0,
                 char: '\0',
+                source: SourceId::default(),
             },
+            spans: None,
+            docs: Vec::new(),
+            pragmas: Vec::new(),
+            suspicious: Vec::new(),
         }
     }
 
-    pub fn scan(mut self) -> ScanResult<'a> {
+    /// Also keep the spans of the whole source, comments and whitespace included, e.g. to
+    /// highlight it.
+    pub fn with_trivia(mut self) -> Self {
+        self.spans = Some(Vec::new());
+        self
+    }
+
+    /// Only lex the literals and the keywords of the extensions enabled by `dialect`,
+    /// `Dialect::default()` if not set.
+    pub fn with_dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Make the locations of the tokens in `source` of a `SourceMap`.
+    pub fn in_source(mut self, source: SourceId) -> Self {
+        self.line.source = source;
+        self
+    }
+
+    /// Count the lines from `line`, for a source that is a part of a bigger one.
+    pub fn starting_at(mut self, line: usize) -> Self {
+        self.line.index = line;
+        self
+    }
+
+    pub fn scan(mut self) -> ScanResult {
         while let Some((i, ch)) = self.advance() {
+            let tokens = self.tokens.len();
             self.scan_token(i, ch);
+            if self.spans.is_some() {
+                self.add_span(i, ch, tokens);
+            }
 
             // simple debugging to see whether my code is stalling, or just slow :D
             // print!("\r{} chars out of {} scanned", i, self.source.len());
         }
+
         self.add_token(Token::Eof(self.line.to_loc()));
 
         ScanResult {
-            lines: self.lines,
             tokens: self.tokens,
             errors: self.errors,
+            spans: self.spans.unwrap_or_default(),
+            docs: self.docs,
+            pragmas: self.pragmas,
+            suspicious: self.suspicious,
         }
     }
 
@@ -154,15 +284,16 @@ impl<'a, 'b> Lexer<'a, 'b> {
         match single.is_ascii() {
             true => match single {
                 '\n' => self.newline_handler(current),
-                '/' => self.slash_handler(),
-                '"' => self.string_handler(current),
+                '/' => self.slash_handler(current),
+                '"' => self.string_handler(self.line.to_loc(), current),
+                'r' if self.is_raw_string() => self.raw_string_handler(current),
                 c if c.is_ascii_digit() => self.number_handler(current),
-                c if c.is_whitespace() => self.whitespace_handler(),
+                c if c.is_whitespace() => self.whitespace_handler(c),
                 c if is_ascii_identifier(c) => self.ascii_identifier_handler(current, single),
                 _ => self.other_handler(single),
             },
             false => match single {
-                c if c.is_whitespace() => self.whitespace_handler(),
+                c if c.is_whitespace() => self.whitespace_handler(c),
                 _ => self.unicode_identifier_handler(current, single),
             },
         }
@@ -172,42 +303,61 @@ impl<'a, 'b> Lexer<'a, 'b> {
     fn scan_token(&mut self, current: usize, single: char) {
         match single {
             '\n' => self.newline_handler(current),
-            '/' => self.slash_handler(),
-            '"' => self.string_handler(current),
+            '/' => self.slash_handler(current),
+            '"' => self.string_handler(self.line.to_loc(), current),
+            'r' if self.is_raw_string() => self.raw_string_handler(current),
             c if c.is_ascii_digit() => self.number_handler(current),
-            c if c.is_whitespace() => self.whitespace_handler(),
+            c if c.is_whitespace() => self.whitespace_handler(c),
             c if is_ascii_identifier(c) => self.ascii_identifier_handler(current, single),
             _ => self.other_handler(single),
         }
     }
 
     fn newline_handler(&mut self, current: usize) {
-        self.lines.push(&self.source[self.line.start..current]);
-
         self.line.index += 1;
         self.line.start = current + 1;
         self.line.column = 0;
     }
 
-    fn slash_handler(&mut self) {
+    fn slash_handler(&mut self, current: usize) {
         // might be comment
         if self.if_next_is('/') {
+            let loc = self.line.to_loc();
             self.advance();
+            let mut end = self.source.len();
             while let Some((i, ch)) = self.advance() {
                 if ch == '\n' {
                     self.newline_handler(i);
+                    end = i;
                     break;
                 }
             }
+
+            // `///` but not `////`, like in Rust
+            let comment = &self.source[current..end];
+            if let Some(text) = comment.strip_prefix("///") {
+                if !text.starts_with('/') {
+                    let text = text.strip_prefix(' ').unwrap_or(text).trim_end();
+                    self.docs.push(DocComment {
+                        loc,
+                        text: text.to_owned(),
+                    });
+                }
+            } else if let Some(text) = comment[2..].trim_start().strip_prefix("lint:") {
+                self.pragmas.push(Pragma {
+                    loc,
+                    text: text.trim().to_owned(),
+                });
+            }
             return;
         }
 
         self.add_token(tok! { [self.line.to_loc()] -> Operator::Slash });
     }
 
-    fn string_handler(&mut self, current: usize) {
-        let start = self.line.to_loc();
-
+    /// Strings can span many lines, `start` is the location of the token and `quote` is the index
+    /// of the opening quote.
+    fn string_handler(&mut self, start: Location, quote: usize) {
         let mut index = None;
         while let Some((i, ch)) = self.advance() {
             if ch == '"' {
@@ -220,7 +370,7 @@ impl<'a, 'b> Lexer<'a, 'b> {
 
         match index {
             Some(idx) => {
-                let value = &self.source[current + 1..idx];
+                let value = &self.source[quote + 1..idx];
                 let key = self.intern(value);
                 self.add_token(tok!([start] -> Literal::String = key));
             }
@@ -228,34 +378,63 @@ impl<'a, 'b> Lexer<'a, 'b> {
         }
     }
 
+    fn is_raw_string(&mut self) -> bool {
+        self.dialect.literals && matches!(self.peek(), Some((_, '"')))
+    }
+
+    /// `r"..."` is a raw string. Strings have no escapes so every string is taken as written, but
+    /// raw strings are guaranteed to stay that way.
+    fn raw_string_handler(&mut self, current: usize) {
+        let start = self.line.to_loc();
+        let _ = self.advance();
+        self.string_handler(start, current + 1);
+    }
+
+    /// Numbers are decimal, with an optional fraction and exponent (`1.5e-3`), or integers in
+    /// hexadecimal (`0xFF`) or binary (`0b1010`). Digits can be separated by `_`, e.g. `1_000`.
+    /// Letters right after a number are taken as a part of it, so `0xFG` or `1e` are reported as a
+    /// whole instead of being lexed as a number followed by an identifier. Without the literals of
+    /// the extensions, numbers are only digits with an optional fraction like in the book.
     fn number_handler(&mut self, current: usize) {
         let start = self.line.to_loc();
-        let count = self.advance_while(|(_, ch)| ch.is_ascii_digit());
-        let mut index = current + count;
+        let literals = self.dialect.literals;
+        let alphanumeric = move |(_, ch): &(usize, char)| match literals {
+            true => ch.is_ascii_alphanumeric() || *ch == '_',
+            false => ch.is_ascii_digit(),
+        };
+        let mut end = current + 1 + self.advance_while(alphanumeric);
         let mut trailing_dot = false;
 
-        if let Some((_, '.')) = self.peek() {
-            let _ = self.advance();
-            let count = self.advance_while(|(_, ch)| ch.is_ascii_digit());
-            if count > 0 {
-                index += count + 1;
-            } else {
-                trailing_dot = true;
+        let decimal = !matches!(
+            self.source.as_bytes()[current..end],
+            [b'0', b'x' | b'b', ..]
+        );
+        if decimal {
+            if let Some((_, '.')) = self.peek() {
+                let _ = self.advance();
+                match self.peek() {
+                    Some((_, ch)) if ch.is_ascii_digit() => {
+                        end += 1 + self.advance_while(alphanumeric);
+                    }
+                    _ => trailing_dot = true,
+                }
             }
-        }
 
-        match self.source[current..index + 1].parse::<f64>() {
-            Ok(value) => {
-                self.add_token(tok! { [start] -> Literal::Number = value });
-            }
-            Err(_) => {
-                self.add_error(LexError::UnableToParseNumber(
-                    self.line.to_loc(),
-                    self.source[current..index + 1].to_string(),
-                ));
+            // the sign of the exponent is not alphanumeric
+            if literals && self.source[current..end].ends_with(['e', 'E']) {
+                if let Some((_, '+' | '-')) = self.peek() {
+                    let _ = self.advance();
+                    end += 1 + self.advance_while(alphanumeric);
+                }
             }
         }
 
+        let text = &self.source[current..end];
+        match parse_number(text) {
+            Some(literal) => self.add_token(tok! { [start] -> Literal = literal }),
+            None => self.add_error(LexError::UnableToParseNumber(start, text.to_string())),
+        }
+
         // NOTE: I add Dot token here since I can't peek the next next char. I must advance from
         // the dot on the if let block above so on the next iteration the dot is already consumed.
         if trailing_dot {
@@ -263,8 +442,18 @@ impl<'a, 'b> Lexer<'a, 'b> {
         }
     }
 
-    fn whitespace_handler(&mut self) {
-        self.advance_while(|(_, ch)| ch.is_whitespace() && *ch != '\n');
+    fn whitespace_handler(&mut self, single: char) {
+        let mut ch = single;
+        loop {
+            if !ch.is_ascii() {
+                self.suspicious.push((self.line.to_loc(), ch));
+            }
+            match self.peek() {
+                Some((_, next)) if next.is_whitespace() && *next != '\n' => ch = *next,
+                _ => break,
+            }
+            self.advance();
+        }
     }
 
     fn ascii_identifier_handler(&mut self, current: usize, single: char) {
@@ -281,11 +470,11 @@ impl<'a, 'b> Lexer<'a, 'b> {
         });
         let end = current + count + single.len_utf8();
         let value = &self.source[current..end];
-        let key = self.intern(value);
 
+        // keywords are compared by their token, only identifiers need their key
         let token = match token::Keyword::try_from(value) {
-            Ok(keyword) => tok! { [start] -> Keyword = keyword },
-            Err(_) => tok! { [start] -> Literal::Identifier = key },
+            Ok(keyword) if self.dialect.reserves(&keyword) => tok! { [start] -> Keyword = keyword },
+            _ => tok! { [start] -> Literal::Identifier = self.intern(value) },
         };
 
         self.add_token(token);
@@ -347,6 +536,50 @@ impl<'a, 'b> Lexer<'a, 'b> {
     fn add_token(&mut self, token: Token) {
         self.tokens.push(token)
     }
+
+    /// Add the span that started at `start` with `ch`, whose token is the one at `tokens` if any.
+    fn add_span(&mut self, start: usize, ch: char, tokens: usize) {
+        let end = self.chars.peek().map_or(self.source.len(), |(i, _)| *i);
+        let kind = match self.tokens.get(tokens) {
+            Some(Token::Keyword(_)) => SpanKind::Keyword,
+            Some(Token::Literal(tokl)) => match tokl.tok {
+                token::Literal::Identifier(_) => SpanKind::Identifier,
+                token::Literal::String(_) => SpanKind::String,
+                token::Literal::Number(_) | token::Literal::Int(_) => SpanKind::Number,
+            },
+            Some(Token::Operator(_)) => SpanKind::Operator,
+            Some(Token::Punctuation(_)) => SpanKind::Punctuation,
+            Some(Token::Eof(_)) => unreachable!("eof is added after scanning"),
+
+            // the ones that failed are still shown as what they were meant to be
+            None => match ch {
+                '/' => SpanKind::Comment,
+                '"' => SpanKind::String,
+                ch if ch.is_ascii_digit() => SpanKind::Number,
+                ch if ch.is_whitespace() => SpanKind::Whitespace,
+                _ => SpanKind::Unknown,
+            },
+        };
+
+        let spans = self.spans.as_mut().expect("spans should be kept");
+        match kind == SpanKind::Comment && self.source[..end].ends_with('\n') {
+            true => {
+                spans.push(Span {
+                    kind,
+                    range: start..end - 1,
+                });
+                spans.push(Span {
+                    kind: SpanKind::Whitespace,
+                    range: end - 1..end,
+                });
+            }
+            false => spans.push(Span {
+                kind,
+                range: start..end,
+            }),
+        }
+    }
+
     fn add_error(&mut self, err: LexError) {
         self.errors.push(err)
     }
@@ -401,6 +634,7 @@ struct LineLocation {
     pub start: usize,
     pub column: usize, // the displayed column
     pub char: char,
+    pub source: SourceId,
 }
 
 impl LineLocation {
@@ -408,10 +642,46 @@ impl LineLocation {
         Location {
             line: self.index,
             column: self.column - self.char.width().unwrap_or(0) + 1,
+            source: self.source,
         }
     }
 }
 
+/// Parse a number literal, see `Lexer::number_handler`. Integers are kept exact, e.g. `1` but not
+/// `1.0`, unless they don't fit in `i64`.
+fn parse_number(text: &str) -> Option<token::Literal> {
+    let (radix, digits) = match text.get(..2) {
+        Some("0x") => (16, &text[2..]),
+        Some("0b") => (2, &text[2..]),
+        _ => (10, text),
+    };
+
+    // separators go between two digits
+    let bytes = digits.as_bytes();
+    let is_digit = |i: Option<usize>| {
+        i.and_then(|i| bytes.get(i))
+            .is_some_and(|byte| char::from(*byte).is_digit(radix))
+    };
+    let separated = (0..bytes.len())
+        .filter(|i| bytes[*i] == b'_')
+        .all(|i| is_digit(i.checked_sub(1)) && is_digit(Some(i + 1)));
+    if digits.is_empty() || !separated {
+        return None;
+    }
+
+    let digits = digits.replace('_', "");
+    match radix {
+        10 => match (digits.parse::<i64>(), digits.parse::<f64>()) {
+            (Ok(int), _) => Some(token::Literal::Int(int)),
+            (_, Ok(num)) => Some(token::Literal::Number(num)),
+            _ => None,
+        },
+        _ => i64::from_str_radix(&digits, radix)
+            .ok()
+            .map(token::Literal::Int),
+    }
+}
+
 fn is_ascii_identifier(c: char) -> bool {
     c.is_ascii_alphanumeric() || c == '_'
 }
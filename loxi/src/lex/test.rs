@@ -15,10 +15,9 @@ fn hello_test() {
     let result = lexer.scan();
 
     assert_eq!(result.errors.len(), 0);
-    assert_eq!(result.lines.len(), 2);
     assert_eq!(result.tokens.len(), 9);
 
-    let loc = |l, c| Location { line: l, column: c };
+    let loc = Location::new;
     let mut intern = |str| interner.get_or_intern(str);
 
     let tokens = vec![
@@ -30,10 +29,172 @@ fn hello_test() {
         tok! { [loc(2,1)]  -> Keyword::Print },
         tok! { [loc(2,7)]  -> Literal::Identifier = intern("hello") },
         tok! { [loc(2,12)] -> Punctuation::Semicolon },
-        Token::Eof(Location { line: 3, column: 1 }),
+        Token::Eof(Location::new(3, 1)),
     ];
 
     for (t1, t2) in result.tokens.iter().zip(tokens) {
         assert_eq!(*t1, t2);
     }
 }
+
+#[test]
+fn trivia_spans_cover_the_whole_source() {
+    let source = "var x = 1.5; // one\n\"a<b\" @";
+    let mut interner = Interner::default();
+    let result = Lexer::new(source, &mut interner).with_trivia().scan();
+
+    let kinds = result
+        .spans
+        .iter()
+        .map(|span| (span.kind, &source[span.range.clone()]))
+        .collect::<Vec<_>>();
+    let expected = vec![
+        (SpanKind::Keyword, "var"),
+        (SpanKind::Whitespace, " "),
+        (SpanKind::Identifier, "x"),
+        (SpanKind::Whitespace, " "),
+        (SpanKind::Operator, "="),
+        (SpanKind::Whitespace, " "),
+        (SpanKind::Number, "1.5"),
+        (SpanKind::Punctuation, ";"),
+        (SpanKind::Whitespace, " "),
+        (SpanKind::Comment, "// one"),
+        (SpanKind::Whitespace, "\n"),
+        (SpanKind::String, "\"a<b\""),
+        (SpanKind::Whitespace, " "),
+        (SpanKind::Unknown, "@"),
+    ];
+    assert_eq!(kinds, expected);
+}
+
+#[test]
+fn strings_span_lines_and_raw_strings_are_taken_as_written() {
+    let source = "var s = \"one\ntwo\" + r\"a\\n\";\nr;\n";
+    let mut interner = Interner::default();
+    let result = Lexer::new(source, &mut interner).scan();
+    assert_eq!(result.errors.len(), 0);
+
+    let loc = Location::new;
+    let mut intern = |str| interner.get_or_intern(str);
+
+    let tokens = vec![
+        tok! { [loc(1,1)]  -> Keyword::Var },
+        tok! { [loc(1,5)]  -> Literal::Identifier = intern("s") },
+        tok! { [loc(1,7)]  -> Operator::Equal },
+        tok! { [loc(1,9)]  -> Literal::String = intern("one\ntwo") },
+        tok! { [loc(2,6)]  -> Operator::Plus },
+        tok! { [loc(2,8)]  -> Literal::String = intern("a\\n") },
+        tok! { [loc(2,14)] -> Punctuation::Semicolon },
+        tok! { [loc(3,1)]  -> Literal::Identifier = intern("r") },
+        tok! { [loc(3,2)]  -> Punctuation::Semicolon },
+        Token::Eof(Location::new(4, 1)),
+    ];
+    assert_eq!(result.tokens, tokens);
+
+    let result = Lexer::new("r\"open\n", &mut interner).scan();
+    assert!(matches!(
+        result.errors[..],
+        [LexError::UnterminatedString(Location {
+            line: 1,
+            column: 1,
+            ..
+        })]
+    ));
+}
+
+#[test]
+fn number_literals_in_other_forms() {
+    let mut interner = Interner::default();
+    let source = "0xFF 0b1010 1e-3 1_000_000 2.5E+2 1.5 4.foo";
+    let result = Lexer::new(source, &mut interner).scan();
+    assert_eq!(result.errors.len(), 0);
+
+    let literals = result
+        .tokens
+        .iter()
+        .filter_map(|token| match token {
+            Token::Literal(TokLoc { tok, .. }) => Some(tok.display(&interner).to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        literals,
+        ["255", "10", "0.001", "1000000", "250", "1.5", "4", "foo"]
+    );
+
+    let source = "1 0xFG 0b102 1e 1__0 1_ 0x 0xFFFFFFFFFFFFFFFFF";
+    let result = Lexer::new(source, &mut interner).scan();
+    let errors = result
+        .errors
+        .iter()
+        .map(|err| match err {
+            LexError::UnableToParseNumber(loc, number) => (loc.column, number.as_str()),
+            err => panic!("unexpected error {err}"),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        [
+            (3, "0xFG"),
+            (8, "0b102"),
+            (14, "1e"),
+            (17, "1__0"),
+            (22, "1_"),
+            (25, "0x"),
+            (28, "0xFFFFFFFFFFFFFFFFF"),
+        ]
+    );
+}
+
+#[test]
+fn the_book_dialect_only_lexes_the_literals_and_keywords_of_the_book() {
+    let mut interner = Interner::default();
+    let source = r#"r"a" 0x10 1e3 1_0 2.5 case switch"#;
+    let result = Lexer::new(source, &mut interner)
+        .with_dialect(Dialect::book())
+        .scan();
+    assert_eq!(result.errors.len(), 0);
+
+    let tokens = result
+        .tokens
+        .iter()
+        .map(|token| match token {
+            Token::Literal(TokLoc { tok, .. }) => {
+                format!("{}:{}", <&str>::from(tok), tok.display(&interner))
+            }
+            token => token.static_str().to_owned(),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tokens,
+        [
+            "<identifier>:r",
+            "<string>:a",
+            "<number>:0",
+            "<identifier>:x10",
+            "<number>:1",
+            "<identifier>:e3",
+            "<number>:1",
+            "<identifier>:_0",
+            "<number>:2.5",
+            "<identifier>:case",
+            "<identifier>:switch",
+            "<eof>",
+        ]
+    );
+}
+
+#[test]
+fn tokens_only_store_the_text_once() {
+    let mut interner = Interner::default();
+    let _ = Lexer::new(HELLO_WORLD, &mut interner).scan();
+    let len = interner.len();
+
+    // the tokens of a second scan refer to the text stored by the first one
+    let result = Lexer::new(HELLO_WORLD, &mut interner).scan();
+    assert_eq!(interner.len(), len);
+    match (&result.tokens[1], &result.tokens[6]) {
+        (Token::Literal(declared), Token::Literal(used)) => assert_eq!(declared.tok, used.tok),
+        tokens => panic!("expected two identifiers, got {tokens:?}"),
+    }
+}
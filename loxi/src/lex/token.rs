@@ -13,9 +13,12 @@ pub enum Punctuation {
     ParenRight,
     BraceLeft,
     BraceRight,
+    BracketLeft,
+    BracketRight,
     Comma,
     Dot,
     Semicolon,
+    Colon,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
@@ -32,6 +35,11 @@ pub enum Operator {
     Minus,
     Star,
     Slash,
+    Ampersand,
+    Pipe,
+    Caret,
+    LessLess,
+    GreaterGreater,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord, EnumIter)]
@@ -52,6 +60,8 @@ pub enum Keyword {
     Super,
     This,
     Var,
+    Switch,
+    Case,
 }
 
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
@@ -59,6 +69,9 @@ pub enum Literal {
     String(Key),
     Identifier(Key),
     Number(f64),
+
+    /// A number without a fractional part that fits in `i64`.
+    Int(i64),
 }
 
 pub struct DisplayedLiteral<'a, 'b> {
@@ -72,6 +85,14 @@ pub struct DisplayedLiteral<'a, 'b> {
 pub enum Special {
     Init,
     Super,
+
+    /// The methods of the iteration protocol used by `for (x in object)`.
+    Iter,
+    Next,
+
+    /// The `in` of `for (var x in list)`. It's only special right after the name of the loop
+    /// variable, everywhere else it's a name like any other, e.g. `var in = 3;` of the book.
+    In,
 }
 
 impl_token!(Punctuation, Operator, Keyword, Literal);
@@ -92,9 +113,12 @@ impl From<&Punctuation> for char {
             Punctuation::ParenRight => ')',
             Punctuation::BraceLeft => '{',
             Punctuation::BraceRight => '}',
+            Punctuation::BracketLeft => '[',
+            Punctuation::BracketRight => ']',
             Punctuation::Comma => ',',
             Punctuation::Dot => '.',
             Punctuation::Semicolon => ';',
+            Punctuation::Colon => ':',
         }
     }
 }
@@ -106,9 +130,12 @@ impl From<&Punctuation> for &str {
             Punctuation::ParenRight => ")",
             Punctuation::BraceLeft => "{",
             Punctuation::BraceRight => "}",
+            Punctuation::BracketLeft => "[",
+            Punctuation::BracketRight => "]",
             Punctuation::Comma => ",",
             Punctuation::Dot => ".",
             Punctuation::Semicolon => ";",
+            Punctuation::Colon => ":",
         }
     }
 }
@@ -122,9 +149,12 @@ impl TryFrom<char> for Punctuation {
             ')' => Ok(Punctuation::ParenRight),
             '{' => Ok(Punctuation::BraceLeft),
             '}' => Ok(Punctuation::BraceRight),
+            '[' => Ok(Punctuation::BracketLeft),
+            ']' => Ok(Punctuation::BracketRight),
             ',' => Ok(Punctuation::Comma),
             '.' => Ok(Punctuation::Dot),
             ';' => Ok(Punctuation::Semicolon),
+            ':' => Ok(Punctuation::Colon),
             _ => Err(()),
         }
     }
@@ -145,6 +175,11 @@ impl From<&Operator> for &str {
             Operator::Plus => "+",
             Operator::Slash => "/",
             Operator::Star => "*",
+            Operator::Ampersand => "&",
+            Operator::Pipe => "|",
+            Operator::Caret => "^",
+            Operator::LessLess => "<<",
+            Operator::GreaterGreater => ">>",
         }
     }
 }
@@ -166,6 +201,11 @@ impl TryFrom<&str> for Operator {
             "+" => Ok(Operator::Plus),
             "/" => Ok(Operator::Slash),
             "*" => Ok(Operator::Star),
+            "&" => Ok(Operator::Ampersand),
+            "|" => Ok(Operator::Pipe),
+            "^" => Ok(Operator::Caret),
+            "<<" => Ok(Operator::LessLess),
+            ">>" => Ok(Operator::GreaterGreater),
             _ => Err(()),
         }
     }
@@ -190,6 +230,8 @@ impl From<&Keyword> for &str {
             Keyword::Super => "super",
             Keyword::This => "this",
             Keyword::Var => "var",
+            Keyword::Switch => "switch",
+            Keyword::Case => "case",
         }
     }
 }
@@ -215,6 +257,8 @@ impl TryFrom<&str> for Keyword {
             "super" => Ok(Keyword::Super),
             "this" => Ok(Keyword::This),
             "var" => Ok(Keyword::Var),
+            "switch" => Ok(Keyword::Switch),
+            "case" => Ok(Keyword::Case),
             _ => Err(()),
         }
     }
@@ -225,7 +269,7 @@ impl From<&Literal> for &str {
         match value {
             Literal::String(_) => "<string>",
             Literal::Identifier(_) => "<identifier>",
-            Literal::Number(_) => "<number>",
+            Literal::Number(_) | Literal::Int(_) => "<number>",
         }
     }
 }
@@ -237,6 +281,7 @@ impl Display for DisplayedLiteral<'_, '_> {
             Literal::String(key) => write!(f, "{}", interner.resolve(*key)),
             Literal::Identifier(key) => write!(f, "{}", interner.resolve(*key)),
             Literal::Number(num) => write!(f, "{num}"),
+            Literal::Int(int) => write!(f, "{int}"),
         }
     }
 }
@@ -246,6 +291,9 @@ impl Special {
         match self {
             Special::Init => "init",
             Special::Super => "super",
+            Special::Iter => "iter",
+            Special::Next => "next",
+            Special::In => "in",
         }
     }
 }
@@ -1,180 +1,82 @@
-use std::fs::File;
-use std::io::{self, stdin, stdout, Read, Write};
+use std::io::{self, stdout};
 use std::path::PathBuf;
-use thiserror::Error;
-
-use self::interp::Interpreter;
-use self::lex::{Lexer, ScanResult};
-use self::parse::Parser;
-use self::resolve::Resolver;
-use self::util::Location;
 
+pub use self::analyze::{
+    Bindings, CallGraph, CallNode, NodeKind, Reference, ReferenceKind, SemanticToken, Span, Symbol,
+    SymbolKind,
+};
+pub use self::dialect::Dialect;
+pub use self::error::{Diagnostic, ErrorKind, LoxError};
+pub use self::interp::cancel::CancelToken;
+pub use self::interp::clock::Clock;
+#[cfg(feature = "serde")]
+pub use self::interp::convert::{to_value, ConvertError};
+pub use self::interp::coverage::{Branch, Coverage, CoverageReport};
+pub use self::interp::explain::Explainer;
+pub use self::interp::function::{Arity, NativeError};
+pub use self::interp::input::LineSource;
+pub use self::interp::limits::{LimitError, Limits};
+pub use self::interp::object::{
+    ClassBinding, FromArgs, FromValue, HostObject, IntoValue, LoxClass,
+};
+pub use self::interp::observer::{EvalError, Observer, Scope};
+pub use self::interp::output::OutputBuffer;
+pub use self::interp::profile::{FunctionProfile, ProfileReport, Profiler};
+pub use self::interp::snapshot::SnapshotError;
+pub use self::interp::stepper::{Frame, Step, Stepper, StopReason};
+pub use self::interp::trace::Tracer;
+pub use self::interp::value::{NumberStyle, Value};
+pub use self::interp::{Capabilities, RuntimeError, Sink};
+pub use self::lex::LexError;
+pub use self::lint::{LintConfig, LintLevel, LintRule, LintWarning};
+pub use self::lox::{Document, Lox, Options, Outcome, Prelude, TestSummary};
+pub use self::message::{locale, set_locale, Locale, Message};
+pub use self::parse::{SyntaxError, TextEdit};
+pub use self::pool::{InterpreterPool, ScriptResult};
+pub use self::resolve::ResolveError;
+#[cfg(feature = "arc")]
+pub use self::task::RunTask;
+pub use self::transpile::Target;
+pub use self::util::shared::ThreadSafe;
+pub use self::util::{Location, SourceId, SourceMap};
+
+mod analyze;
+mod dialect;
+mod doc;
+mod error;
+mod highlight;
 mod interp;
 mod lex;
+mod lint;
+mod lox;
+mod message;
+mod minify;
 mod parse;
+mod pool;
 mod resolve;
+#[cfg(feature = "arc")]
+mod task;
+mod transpile;
 mod util;
 
-macro_rules! println_red {
-    ($fmt:literal, $($arg:tt)*) => {
-        let str = format!($fmt, $($arg)*);
-        println!("\x1b[1;31m{}\x1b[00m", str)
-    };
-}
-
-#[derive(Debug, Error)]
-pub enum LoxError {
-    #[error("--[ LoxError ]-- Could not read file: '{0}'")]
-    IoError(#[from] io::Error),
-
-    #[error("--[ LoxError ]-- {0} Lexing errors occurred, aborting.")]
-    LexError(usize),
-
-    #[error("--[ LoxError ]-- Parsing error occured, aborting.")]
-    ParseError,
-
-    #[error("--[ LoxError ]-- Resolving error occurred, aborting.")]
-    ResolveError,
-
-    #[error("--[ LoxError ]-- Runtime error occured, aborting.")]
-    RuntimeError,
-
-    #[error("--[ LoxError ]-- Empty file")]
-    EmptyError,
-}
-
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum RunMode {
     Normal,
     DumpLex,
     DumpParse,
+    DumpTree,
+    DumpDot,
 }
 
-pub fn run(program: &str, mode: RunMode) -> Result<(), LoxError> {
-    let mut interpreter = Interpreter::new();
-    let interner = interpreter.interner();
-
-    // lexing
-    let lexer = Lexer::new(program, interner);
-    let ScanResult {
-        lines,
-        tokens,
-        errors,
-    } = lexer.scan();
-
-    if !errors.is_empty() {
-        errors.iter().for_each(|err| {
-            let loc = match err {
-                lex::LexError::UnknownToken(loc, _, _) => loc,
-                lex::LexError::UnterminatedString(loc) => loc,
-                lex::LexError::UnableToParseNumber(loc, _) => loc,
-            };
-            print_context(&lines, *loc);
-            println_red!("{}", err);
-        });
-        return Err(LoxError::LexError(errors.len()));
-    }
-
-    if mode == RunMode::DumpLex {
-        for tok in tokens.iter() {
-            println!("{}", tok.display(interner));
-        }
-        return Ok(());
-    }
-
-    // only <eof> exist
-    if tokens.len() == 1 {
-        return Err(LoxError::EmptyError);
-    }
-
-    // parsing
-    let mut parser = Parser::new();
-    let program = parser.parse(tokens).map_err(|err| {
-        err.iter().for_each(|e| {
-            print_context(&lines, e.loc());
-            println_red!("{}", e);
-        });
-        LoxError::ParseError
-    })?;
-
-    if mode == RunMode::DumpParse {
-        println!("{}", program.display(interner));
-        return Ok(());
-    }
-
-    // resolving
-    let mut resolver = Resolver::new(interner);
-    let resolve_map = resolver.resolve(&program).map_err(|err| {
-        print_context(&lines, err.loc());
-        println_red!("{}", err);
-        LoxError::ResolveError
-    })?;
-
-    // interpreting
-    interpreter.interpret(program, resolve_map).map_err(|err| {
-        print_context(&lines, err.loc());
-        println_red!("{}", err);
-        LoxError::RuntimeError
-    })?;
-
-    Ok(())
+pub fn run(program: &str, mode: RunMode, options: &Options) -> Result<Outcome, LoxError> {
+    Lox::new(options).run(program, mode)
 }
 
-pub fn run_file(path: PathBuf, mode: RunMode) -> Result<(), LoxError> {
-    let contents = {
-        let mut string = String::new();
-        let mut file = File::open(path.clone())?;
-        file.read_to_string(&mut string)?;
-
-        match string.is_empty() {
-            true => return Err(LoxError::EmptyError),
-            false => {
-                // make sure the content of the file ends with newline
-                if !string.ends_with('\n') {
-                    string.push('\n');
-                }
-            }
-        }
-
-        string
-    };
-
-    run(&contents, mode)?;
-    Ok(())
+pub fn run_file(path: PathBuf, mode: RunMode, options: &Options) -> Result<Outcome, LoxError> {
+    Lox::new(options).run_file(path, mode)
 }
 
 // FIXME: currently not working like a REPL
-pub fn run_prompt() -> io::Result<()> {
-    println!("Loxi: a Lox programming language interpreter (currently under construction)");
-
-    let mut line = String::new();
-    loop {
-        print!(">>> ");
-        stdout().flush().expect("Unable to flush stdout");
-
-        if stdin().read_line(&mut line)? == 0 {
-            break;
-        }
-
-        if let Err(err) = run(&line, RunMode::Normal) {
-            println!("{}", err);
-        }
-
-        line.clear();
-    }
-
-    println!("\nExiting loxi...");
-    Ok(())
-}
-
-#[rustfmt::skip]
-fn print_context(lines: &[&str], loc: Location) {
-    let line = match loc.line > lines.len() {
-        true => "",
-        false => lines[loc.line - 1],
-    };
-    println!("{:->width$}", "", width = 80);
-    println!("{:>4} |", "");
-    println!("{:>4} | {}", loc.line, line);
-    println!("{:>4} | \x1b[1m{:>width$}\x1b[1;31m^\x1b[00m", "", "", width = loc.column - 1);
+pub fn run_prompt(options: &Options) -> io::Result<Outcome> {
+    Lox::new(options).repl(&mut stdout())
 }
@@ -0,0 +1,524 @@
+//! Warnings about code that is valid but is likely a mistake. Every rule can be allowed, warned
+//! about, or denied on its own, see `LintConfig`. A `// lint: allow(rule, ...)` comment allows the
+//! rules on its line and on the line after it.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+use std::str::FromStr;
+
+use strum::IntoEnumIterator;
+
+use crate::analyze::Span;
+use crate::interp::interner::{Interner, Key};
+use crate::lex::Pragma;
+use crate::message;
+use crate::parse::expr::{Expr, RefExpr, ValExpr};
+use crate::parse::stmt::{Stmt, StmtFunction};
+use crate::parse::{token, Program};
+use crate::util::Location;
+
+#[cfg(test)]
+mod test;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, strum::EnumIter)]
+pub enum LintRule {
+    /// A local variable that is never read.
+    UnusedVariable,
+
+    /// A local declaration with the same name as a declaration of an enclosing scope.
+    ShadowedName,
+
+    /// A block without statements.
+    EmptyBlock,
+
+    /// An `if` or `while` condition that doesn't depend on anything, except `while (true)`.
+    ConstantCondition,
+
+    /// A statement that doesn't end with `;`. Without this rule it's a syntax error instead.
+    MissingSemicolon,
+
+    /// A `;` on its own, e.g. the second one of `;;`. Without this rule it's a syntax error
+    /// instead.
+    EmptyStatement,
+
+    /// Whitespace that is not ASCII, e.g. a non-breaking space pasted from a web page.
+    SuspiciousCharacter,
+}
+
+/// What happens when a rule finds something.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LintLevel {
+    /// The rule is not checked.
+    Allow,
+    Warn,
+
+    /// Reported as an error, which stops a program from running, see `Options::lint`.
+    Deny,
+}
+
+/// The level of every rule, all of them are warned about by default.
+#[derive(Clone, Debug)]
+pub struct LintConfig {
+    levels: BTreeMap<LintRule, LintLevel>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LintWarning {
+    pub rule: LintRule,
+    pub level: LintLevel,
+    pub loc: Location,
+    pub message: String,
+}
+
+/// What the lexer and the parser found on the way to the syntax tree, for the rules about the text
+/// of the program.
+#[derive(Clone, Copy, Default)]
+pub struct Findings<'a> {
+    pub pragmas: &'a [Pragma],
+    pub suspicious: &'a [(Location, char)],
+    pub missing_semicolons: &'a [Location],
+    pub empty_statements: &'a [Location],
+}
+
+/// Checks a parsed program against the enabled rules.
+pub struct Linter<'a> {
+    interner: &'a Interner,
+    config: &'a LintConfig,
+
+    /// The declarations of every scope, the first one is the global scope.
+    scopes: Vec<Vec<Binding>>,
+    warnings: Vec<LintWarning>,
+
+    /// The rules allowed by a pragma, with the lines they are allowed on.
+    allowed: BTreeSet<(LintRule, usize)>,
+}
+
+struct Binding {
+    name: Key,
+    loc: Location,
+    kind: BindingKind,
+    used: bool,
+}
+
+#[derive(PartialEq, Eq)]
+enum BindingKind {
+    Variable,
+    Other,
+}
+
+impl LintRule {
+    pub fn name(self) -> &'static str {
+        match self {
+            LintRule::UnusedVariable => "unused-variable",
+            LintRule::ShadowedName => "shadowed-name",
+            LintRule::EmptyBlock => "empty-block",
+            LintRule::ConstantCondition => "constant-condition",
+            LintRule::MissingSemicolon => "missing-semicolon",
+            LintRule::EmptyStatement => "empty-statement",
+            LintRule::SuspiciousCharacter => "suspicious-character",
+        }
+    }
+}
+
+impl Display for LintRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for LintRule {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        LintRule::iter()
+            .find(|rule| rule.name() == name)
+            .ok_or_else(|| {
+                let rules = LintRule::iter().map(LintRule::name).collect::<Vec<_>>();
+                format!("unknown rule '{name}', expect one of: {}", rules.join(", "))
+            })
+    }
+}
+
+impl LintConfig {
+    /// Every rule is allowed.
+    pub fn none() -> Self {
+        Self::with_level(LintLevel::Allow)
+    }
+
+    fn with_level(level: LintLevel) -> Self {
+        Self {
+            levels: LintRule::iter().map(|rule| (rule, level)).collect(),
+        }
+    }
+
+    pub fn set_level(&mut self, rule: LintRule, level: LintLevel) -> &mut Self {
+        self.levels.insert(rule, level);
+        self
+    }
+
+    pub fn level(&self, rule: LintRule) -> LintLevel {
+        self.levels.get(&rule).copied().unwrap_or(LintLevel::Allow)
+    }
+
+    /// Warn about `rule`.
+    pub fn enable(&mut self, rule: LintRule) -> &mut Self {
+        self.set_level(rule, LintLevel::Warn)
+    }
+
+    /// Allow `rule`.
+    pub fn disable(&mut self, rule: LintRule) -> &mut Self {
+        self.set_level(rule, LintLevel::Allow)
+    }
+
+    pub fn deny(&mut self, rule: LintRule) -> &mut Self {
+        self.set_level(rule, LintLevel::Deny)
+    }
+
+    /// Deny every rule that is warned about, like `--deny-warnings`.
+    pub fn deny_warnings(&mut self) -> &mut Self {
+        for level in self.levels.values_mut() {
+            if *level == LintLevel::Warn {
+                *level = LintLevel::Deny;
+            }
+        }
+        self
+    }
+
+    /// Whether `rule` is checked, i.e. it's not allowed.
+    pub fn is_enabled(&self, rule: LintRule) -> bool {
+        self.level(rule) != LintLevel::Allow
+    }
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self::with_level(LintLevel::Warn)
+    }
+}
+
+impl LintWarning {
+    /// Warnings are only known by where they start, so the span is empty.
+    pub fn span(&self) -> Span {
+        Span::at(self.loc)
+    }
+
+    /// The message without the location, labelled by its level and followed by its rule.
+    pub fn labelled_message(&self) -> String {
+        let kind = match self.level {
+            LintLevel::Deny => "Error",
+            LintLevel::Allow | LintLevel::Warn => "Warning",
+        };
+        format!("{kind}: {} [{}]", self.message, self.rule)
+    }
+}
+
+impl Display for LintWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.loc, self.labelled_message())
+    }
+}
+
+impl<'a> Linter<'a> {
+    pub fn new(interner: &'a Interner, config: &'a LintConfig) -> Self {
+        Self {
+            interner,
+            config,
+            scopes: vec![Vec::new()],
+            warnings: Vec::new(),
+            allowed: BTreeSet::new(),
+        }
+    }
+
+    /// Check `program`, the warnings are sorted by their location. The missing semicolons and the
+    /// empty statements of `found` come from the parser, see `Parser::with_semicolon_recovery` and
+    /// `Parser::with_empty_statement_recovery`.
+    pub fn lint(mut self, program: &Program, found: Findings<'_>) -> Vec<LintWarning> {
+        for pragma in found.pragmas {
+            self.allow(pragma);
+        }
+        for loc in found.missing_semicolons {
+            let message = message::format("lint.missing-semicolon", &[]);
+            self.warn(LintRule::MissingSemicolon, *loc, message);
+        }
+        for loc in found.empty_statements {
+            let message = message::format("lint.empty-statement", &[]);
+            self.warn(LintRule::EmptyStatement, *loc, message);
+        }
+        for (loc, ch) in found.suspicious {
+            let code = format!("{:04X}", *ch as u32);
+            let message = message::format("lint.suspicious-character", &[("code", &code)]);
+            self.warn(LintRule::SuspiciousCharacter, *loc, message);
+        }
+        for stmt in program.statements.iter() {
+            self.stmt(stmt);
+        }
+
+        self.warnings.sort_by_key(|warning| warning.loc);
+        self.warnings
+    }
+
+    /// Like a block, but an empty case is fine since it's how a case is ignored.
+    fn case_body(&mut self, body: &Stmt) {
+        if let Stmt::Block { statements, .. } = body {
+            self.scoped(|linter| statements.iter().for_each(|stmt| linter.stmt(stmt)));
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expr { expr } => self.expr(expr),
+            Stmt::Print { expr, .. } => self.expr(expr),
+            Stmt::Var { loc, name, init } => {
+                if let Some(init) = init {
+                    self.expr(init);
+                }
+                self.declare(*name, *loc, BindingKind::Variable);
+            }
+            Stmt::Block { loc, statements } => {
+                if statements.is_empty() {
+                    let message = message::format("lint.empty-block", &[]);
+                    self.warn(LintRule::EmptyBlock, *loc, message);
+                }
+                self.scoped(|linter| statements.iter().for_each(|stmt| linter.stmt(stmt)));
+            }
+            Stmt::If {
+                condition,
+                then,
+                otherwise,
+                ..
+            } => {
+                self.condition(condition, false);
+                self.stmt(then);
+                if let Some(otherwise) = otherwise {
+                    self.stmt(otherwise);
+                }
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                self.condition(condition, true);
+                self.stmt(body);
+            }
+            Stmt::ForIn {
+                loc,
+                name,
+                iterable,
+                body,
+                ..
+            } => {
+                self.expr(iterable);
+                self.scoped(|linter| {
+                    linter.declare(*name, *loc, BindingKind::Variable);
+                    linter.stmt(body);
+                });
+            }
+            Stmt::Switch {
+                subject,
+                cases,
+                otherwise,
+                ..
+            } => {
+                self.expr(subject);
+                for (value, body) in cases {
+                    self.expr(value);
+                    self.case_body(body);
+                }
+                if let Some(otherwise) = otherwise {
+                    self.case_body(otherwise);
+                }
+            }
+            Stmt::Function { func } => {
+                self.declare(func.name, func.loc, BindingKind::Other);
+                self.function(func);
+            }
+            Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            }
+            Stmt::Class {
+                loc, name, methods, ..
+            } => {
+                self.declare(*name, *loc, BindingKind::Other);
+                methods.iter().for_each(|method| self.function(method));
+            }
+        }
+    }
+
+    fn function(&mut self, func: &StmtFunction) {
+        self.scoped(|linter| {
+            for param in func.params.iter() {
+                linter.declare(*param, func.loc, BindingKind::Other);
+            }
+            func.body.iter().for_each(|stmt| linter.stmt(stmt));
+        });
+    }
+
+    fn condition(&mut self, condition: &Expr, is_loop: bool) {
+        self.expr(condition);
+
+        let infinite_loop = is_loop && is_literal(condition, &token::Literal::True);
+        if is_constant(condition) && !infinite_loop {
+            let message = message::format("lint.constant-condition", &[]);
+            self.warn(LintRule::ConstantCondition, condition.loc(), message);
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::ValExpr(expr, _) => self.val_expr(expr),
+            Expr::RefExpr(expr, _) => self.ref_expr(expr),
+        }
+    }
+
+    fn val_expr(&mut self, expr: &ValExpr) {
+        match expr {
+            ValExpr::Literal { .. } => (),
+            ValExpr::Unary { right, .. } => self.expr(right),
+            ValExpr::Binary { left, right, .. } | ValExpr::Logical { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            ValExpr::Grouping { expr, .. } => self.val_expr(expr),
+            ValExpr::Call { callee, args, .. } => {
+                self.expr(callee);
+                args.iter().for_each(|arg| self.expr(arg));
+            }
+            ValExpr::List { elements, .. } => elements.iter().for_each(|e| self.expr(e)),
+        }
+    }
+
+    fn ref_expr(&mut self, expr: &RefExpr) {
+        match expr {
+            RefExpr::Variable { var } => self.use_var(var.tok.name),
+            RefExpr::Grouping { expr, .. } => self.ref_expr(expr),
+            // assigning a variable is not reading it
+            RefExpr::Assignment { value, .. } => self.expr(value),
+            RefExpr::Get { object, .. } => self.expr(object),
+            RefExpr::Set { object, value, .. } => {
+                self.expr(object);
+                self.expr(value);
+            }
+            RefExpr::This { .. } => (),
+            RefExpr::Index { object, index, .. } => {
+                self.expr(object);
+                self.expr(index);
+            }
+            RefExpr::IndexSet {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.expr(object);
+                self.expr(index);
+                self.expr(value);
+            }
+        }
+    }
+
+    /// Run `f` in a new scope, then report the variables of the scope that are never used.
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(Vec::new());
+        f(self);
+        let scope = self.scopes.pop().expect("the scope should be pushed above");
+
+        for binding in scope {
+            let name = self.interner.resolve(binding.name);
+            if binding.kind == BindingKind::Variable && !binding.used && !name.starts_with('_') {
+                let message = message::format("lint.unused-variable", &[("name", &name)]);
+                self.warn(LintRule::UnusedVariable, binding.loc, message);
+            }
+        }
+    }
+
+    fn declare(&mut self, name: Key, loc: Location, kind: BindingKind) {
+        let (current, enclosing) = self
+            .scopes
+            .split_last_mut()
+            .expect("there's a global scope");
+
+        // globals are never shadowing anything
+        let shadowed = match enclosing.is_empty() {
+            true => None,
+            false => enclosing.iter().rev().flatten().find(|b| b.name == name),
+        };
+        let shadowed = shadowed.map(|binding| binding.loc);
+
+        current.push(Binding {
+            name,
+            loc,
+            kind,
+            used: false,
+        });
+
+        if let Some(shadowed) = shadowed {
+            let name = self.interner.resolve(name);
+            let args: [(&str, &dyn Display); 2] = [("name", &name), ("previous", &shadowed)];
+            let message = message::format("lint.shadowed-name", &args);
+            self.warn(LintRule::ShadowedName, loc, message);
+        }
+    }
+
+    fn use_var(&mut self, name: Key) {
+        let binding = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .flatten()
+            .find(|b| b.name == name);
+        if let Some(binding) = binding {
+            binding.used = true;
+        }
+    }
+
+    /// Allow the rules of a `// lint: allow(rule, ...)` comment, other comments and unknown rules
+    /// are ignored.
+    fn allow(&mut self, pragma: &Pragma) {
+        let rules = pragma
+            .text
+            .strip_prefix("allow(")
+            .and_then(|rest| rest.strip_suffix(')'));
+        let rules = rules.into_iter().flat_map(|rules| rules.split(','));
+        for rule in rules.filter_map(|rule| rule.trim().parse().ok()) {
+            self.allowed.insert((rule, pragma.loc.line));
+            self.allowed.insert((rule, pragma.loc.line + 1));
+        }
+    }
+
+    fn warn(&mut self, rule: LintRule, loc: Location, message: String) {
+        let level = self.config.level(rule);
+        if level != LintLevel::Allow && !self.allowed.contains(&(rule, loc.line)) {
+            self.warnings.push(LintWarning {
+                rule,
+                level,
+                loc,
+                message,
+            });
+        }
+    }
+}
+
+fn is_literal(expr: &Expr, literal: &token::Literal) -> bool {
+    matches!(expr, Expr::ValExpr(ValExpr::Literal { value }, _) if value.tok == *literal)
+}
+
+/// Whether the value of `expr` only depends on literals.
+fn is_constant(expr: &Expr) -> bool {
+    match expr {
+        Expr::ValExpr(expr, _) => is_constant_val(expr),
+        Expr::RefExpr(..) => false,
+    }
+}
+
+fn is_constant_val(expr: &ValExpr) -> bool {
+    match expr {
+        ValExpr::Literal { .. } => true,
+        ValExpr::Unary { right, .. } => is_constant(right),
+        ValExpr::Binary { left, right, .. } | ValExpr::Logical { left, right, .. } => {
+            is_constant(left) && is_constant(right)
+        }
+        ValExpr::Grouping { expr, .. } => is_constant_val(expr),
+        ValExpr::Call { .. } | ValExpr::List { .. } => false,
+    }
+}
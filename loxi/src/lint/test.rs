@@ -0,0 +1,139 @@
+use std::io;
+
+use pretty_assertions::assert_eq;
+
+use crate::error::LoxError;
+use crate::{Lox, Options};
+
+use super::{LintConfig, LintLevel, LintRule};
+
+/// The rule and line of every warning of `source`.
+fn lint(source: &str, config: &LintConfig) -> Vec<(LintRule, usize)> {
+    let mut lox = Lox::new(&Options::default());
+    lox.set_stderr(io::sink());
+
+    let diagnostics = lox.lint(source, config).expect("source should parse");
+    diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let rule = diagnostic.message.rsplit_once('[').unwrap().1;
+            (
+                rule.trim_end_matches(']').parse().unwrap(),
+                diagnostic.span.start.line,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn every_rule_is_reported() {
+    let source = r#"
+var global = 1;
+fun f(a) {
+    var unused = a;
+    var _ignored = 2;
+    {
+        var a = 3;
+        print a;
+    }
+    {}
+    if (1 < 2) print "always";
+    while (true) { return 1; }
+    print a
+}
+"#;
+
+    let warnings = lint(source, &LintConfig::default());
+    assert_eq!(
+        warnings,
+        vec![
+            (LintRule::UnusedVariable, 4),
+            (LintRule::ShadowedName, 7),
+            (LintRule::EmptyBlock, 10),
+            (LintRule::ConstantCondition, 11),
+            (LintRule::MissingSemicolon, 14),
+        ]
+    );
+}
+
+#[test]
+fn disabled_rules_are_not_reported() {
+    let source = r#"
+fun f() {
+    var unused = 1;
+    if (false) {}
+}
+"#;
+
+    let mut config = LintConfig::default();
+    config
+        .disable(LintRule::UnusedVariable)
+        .disable(LintRule::EmptyBlock);
+    assert_eq!(
+        lint(source, &config),
+        vec![(LintRule::ConstantCondition, 4)]
+    );
+
+    let mut config = LintConfig::none();
+    config.enable(LintRule::EmptyBlock);
+    assert_eq!(lint(source, &config), vec![(LintRule::EmptyBlock, 4)]);
+}
+
+#[test]
+fn missing_semicolon_is_an_error_when_disabled() {
+    let mut lox = Lox::new(&Options::default());
+    lox.set_stderr(io::sink());
+
+    let mut config = LintConfig::default();
+    config.disable(LintRule::MissingSemicolon);
+    assert!(lox.lint("print 1\n", &config).is_err());
+}
+
+#[test]
+fn lexer_and_parser_findings_are_reported() {
+    let source = "var a = 1;;\nprint a;\nvar b\u{a0}= a;\nprint b;\n";
+    assert_eq!(
+        lint(source, &LintConfig::default()),
+        vec![
+            (LintRule::EmptyStatement, 1),
+            (LintRule::SuspiciousCharacter, 3)
+        ]
+    );
+}
+
+#[test]
+fn pragmas_allow_a_rule_on_their_line_and_the_next() {
+    let source = r#"
+// lint: allow(empty-block, constant-condition)
+if (true) {}
+if (true) {} // lint: allow(empty-block)
+{}
+{}
+"#;
+    assert_eq!(
+        lint(source, &LintConfig::default()),
+        vec![(LintRule::ConstantCondition, 4), (LintRule::EmptyBlock, 6)]
+    );
+}
+
+#[test]
+fn denied_warnings_stop_the_program() {
+    let mut config = LintConfig::default();
+    config.deny(LintRule::EmptyBlock);
+    assert_eq!(config.level(LintRule::EmptyBlock), LintLevel::Deny);
+
+    let mut lox = Lox::new(&Options {
+        lint: Some(config),
+        ..Options::default()
+    });
+    lox.set_stderr(io::sink());
+    lox.set_stdout(io::sink());
+    assert!(lox.eval("if (1 < 2) print 1;").is_ok());
+    match lox.eval("{}") {
+        Err(LoxError::LintError { warnings, .. }) => {
+            assert_eq!(warnings.len(), 1);
+            assert_eq!(warnings[0].rule, LintRule::EmptyBlock);
+        }
+        other => panic!("expected a lint error, got {other:?}"),
+    }
+}
@@ -0,0 +1,1116 @@
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::time::Duration;
+
+#[cfg(feature = "serde")]
+use serde::de::DeserializeOwned;
+
+use crate::analyze::{self, Bindings, CallGraph, SemanticToken, Span, Symbol};
+use crate::dialect::Dialect;
+use crate::doc;
+use crate::highlight;
+use crate::interp::builder::{InterpreterBuilder, DEFAULT_MAX_CALL_DEPTH};
+use crate::interp::cancel::CancelToken;
+use crate::interp::clock::Clock;
+#[cfg(feature = "serde")]
+use crate::interp::convert::{self, ConvertError};
+use crate::interp::function::{Arity, NativeError};
+use crate::interp::input::LineSource;
+use crate::interp::interner::{FrozenInterner, Interner};
+use crate::interp::limits::Limits;
+use crate::interp::object::LoxClass;
+use crate::interp::observer::Observer;
+use crate::interp::snapshot::{self, SnapshotError};
+use crate::interp::value::{NumberStyle, Value};
+use crate::interp::{Capabilities, Interpreter, RuntimeError, Sink};
+use crate::lex::{Lexer, Pragma, ScanResult};
+use crate::lint::{Findings, LintConfig, LintLevel, LintRule, LintWarning, Linter};
+use crate::minify;
+use crate::parse::{self, Parser, Program, TextEdit};
+use crate::resolve::{ResolveMap, Resolver};
+#[cfg(feature = "arc")]
+use crate::task::RunTask;
+use crate::transpile::{self, Target};
+use crate::util::shared::{Shared, ThreadSafe};
+use crate::util::{Location, SourceMap};
+use crate::{Diagnostic, ErrorKind, LoxError, RunMode};
+
+#[cfg(test)]
+mod test;
+
+/// Options for running a program.
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub capabilities: Capabilities,
+
+    /// Cancelling this token stops the running program at the next loop iteration, call, or
+    /// `sleep`.
+    pub cancel: CancelToken,
+
+    /// Calls nested deeper than this fail with a stack overflow error. Each call uses the native
+    /// stack, so it must be low enough for the stack of the thread running the program.
+    pub max_call_depth: usize,
+
+    /// The number of statements each run can execute before failing, unlimited by default. Each
+    /// call to `run`, `eval`, and the like, and each test of `run_tests`, is a run.
+    pub max_steps: Option<u64>,
+
+    /// The time each run can take before failing, unlimited by default. It's checked before each
+    /// statement and while sleeping, so only a single slow native call, e.g. `readLine`, can go
+    /// over it.
+    pub timeout: Option<Duration>,
+
+    /// Memory limits, none by default.
+    pub limits: Limits,
+
+    /// Whether dividing a number by zero fails with `RuntimeError::DivisionByZero`. By default it
+    /// gives `inf`, `-inf`, or `nan` like jlox does.
+    pub strict_division: bool,
+
+    /// The extensions to the book's Lox that programs may use, `Dialect::default()` by default.
+    /// `Dialect::book()` runs the language of the book as it is.
+    pub dialect: Dialect,
+
+    /// How the numbers that are not integers are printed, by programs and by `display`, and by the
+    /// programs translated by `transpile`. `NumberStyle::Jlox` by default.
+    pub number_style: NumberStyle,
+
+    /// The lines shown before and after the line of each error reported to the stderr sink, none
+    /// by default.
+    pub context_lines: usize,
+
+    /// Check programs against these rules before running them, not at all by default. Warnings
+    /// are reported to the stderr sink, and a program with denied warnings fails with
+    /// `LoxError::LintError` without running.
+    pub lint: Option<LintConfig>,
+}
+
+/// How a program finished running.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The program ran until its end.
+    Completed,
+
+    /// The program was stopped early by calling `exit(code)`.
+    Exit(i32),
+}
+
+/// The number of tests that passed and failed in `Lox::run_tests`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TestSummary {
+    pub passed: usize,
+    pub failed: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            capabilities: Capabilities::default(),
+            cancel: CancelToken::default(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            max_steps: None,
+            timeout: None,
+            limits: Limits::default(),
+            strict_division: false,
+            dialect: Dialect::default(),
+            number_style: NumberStyle::default(),
+            context_lines: 0,
+            lint: None,
+        }
+    }
+}
+
+/// A program that is compiled once and run at the start of many `Lox` instances, see
+/// `Lox::with_prelude`. The strings interned while compiling it are shared by those instances
+/// instead of being interned again by each of them.
+pub struct Prelude {
+    source: String,
+    interner: Shared<FrozenInterner>,
+    program: Program,
+    resolve_map: ResolveMap,
+}
+
+impl Prelude {
+    /// Compile `source`, errors are reported to stderr.
+    pub fn new(source: &str) -> Result<Self, LoxError> {
+        let mut lox = Lox::new(&Options::default());
+        let compiled = lox
+            .compile(source, RunMode::Normal, &None)?
+            .expect("the whole program should be compiled in normal mode");
+
+        Ok(Self {
+            source: source.to_owned(),
+            interner: Shared::new(lox.interpreter.into_interner().freeze()),
+            program: compiled.program,
+            resolve_map: compiled.resolve_map,
+        })
+    }
+}
+
+/// A source being edited, e.g. in an editor, that is parsed again only where it changes instead of
+/// as a whole on every edit.
+pub struct Document {
+    source: String,
+    interner: Interner,
+
+    /// `None` while the source has errors.
+    program: Option<Program>,
+}
+
+impl Document {
+    pub fn new(source: &str) -> Self {
+        let mut document = Self {
+            source: source.to_owned(),
+            interner: Interner::new(),
+            program: None,
+        };
+        let _ = document.parse();
+        document
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Apply `edit` to the source and parse it again, returning the errors of the new source.
+    pub fn edit(&mut self, edit: &TextEdit) -> Result<(), LoxError> {
+        let reparsed = match &mut self.program {
+            Some(program) => program.reparse(&self.source, edit, &mut self.interner),
+            None => false,
+        };
+        edit.apply(&mut self.source);
+        match reparsed {
+            true => Ok(()),
+            false => self.parse(),
+        }
+    }
+
+    /// The syntax tree like `--dump parse` prints it, `None` while the source has errors.
+    pub fn syntax_tree(&self) -> Option<String> {
+        let program = self.program.as_ref()?;
+        Some(program.display(&self.interner).to_string())
+    }
+
+    fn parse(&mut self) -> Result<(), LoxError> {
+        self.program = None;
+        let ScanResult { tokens, errors, .. } = Lexer::new(&self.source, &mut self.interner).scan();
+        if !errors.is_empty() {
+            return Err(LoxError::LexError {
+                source_name: None,
+                errors,
+            });
+        }
+        let program = Parser::new()
+            .parse(tokens)
+            .map_err(|errors| LoxError::ParseError {
+                source_name: None,
+                errors,
+            })?;
+        self.program = Some(program);
+        Ok(())
+    }
+}
+
+/// A program ready to be interpreted, along with what the lexer found for the linter.
+struct Compiled {
+    program: Program,
+    resolve_map: ResolveMap,
+    pragmas: Vec<Pragma>,
+    suspicious: Vec<(Location, char)>,
+}
+
+/// An interpreter instance for embedding Lox in a Rust program.
+///
+/// The state persists between calls, so globals defined by one `eval` can be used by the next one.
+pub struct Lox {
+    interpreter: Interpreter,
+
+    /// Every source compiled by this instance, to report errors in any of them.
+    sources: SourceMap,
+    context_lines: usize,
+    lint: Option<LintConfig>,
+    dialect: Dialect,
+}
+
+impl Lox {
+    pub fn new(options: &Options) -> Self {
+        Self {
+            interpreter: Self::builder(options).build(),
+            sources: SourceMap::new(),
+            context_lines: options.context_lines,
+            lint: options.lint.clone(),
+            dialect: options.dialect,
+        }
+    }
+
+    /// Create an instance that starts with the globals defined by `prelude`. The prelude runs again
+    /// in every instance, so the instances never share their globals, only the interned strings of
+    /// the prelude are shared.
+    pub fn with_prelude(options: &Options, prelude: &Prelude) -> Result<Self, LoxError> {
+        let interner = Interner::with_base(Shared::clone(&prelude.interner));
+        let mut lox = Self {
+            interpreter: Self::builder(options).interner(interner).build(),
+            sources: SourceMap::new(),
+            context_lines: options.context_lines,
+            lint: options.lint.clone(),
+            dialect: options.dialect,
+        };
+
+        // the prelude is the first source compiled by its own instance too, so its locations are
+        // in the first source of this one
+        lox.sources.add(None, &prelude.source);
+
+        let program = prelude.program.clone();
+        let resolve_map = prelude.resolve_map.clone();
+        lox.interpreter
+            .interpret(program, resolve_map)
+            .map_err(|error| match error {
+                RuntimeError::Exit(_, code) => LoxError::Exit(code),
+                error => {
+                    lox.report(error.loc(), &error);
+                    LoxError::RuntimeError {
+                        source_name: None,
+                        error,
+                    }
+                }
+            })?;
+
+        Ok(lox)
+    }
+
+    /// Run `source` and return the value of its last statement if it's an expression statement,
+    /// otherwise `nil`. Calling `exit(code)` is reported as `LoxError::Exit`.
+    pub fn eval(&mut self, source: &str) -> Result<Value, LoxError> {
+        self.eval_source(source, None)
+    }
+
+    pub fn eval_file(&mut self, path: impl AsRef<Path>) -> Result<Value, LoxError> {
+        let path = path.as_ref();
+        let source = read_source(path)?;
+        self.eval_source(&source, Some(path.display().to_string()))
+    }
+
+    /// Run `source` as a whole program, `exit(code)` is reported as `Outcome::Exit`.
+    pub fn run(&mut self, source: &str, mode: RunMode) -> Result<Outcome, LoxError> {
+        self.run_source(source, mode, None)
+    }
+
+    /// Run `source` as a future that lets it run `statements` statements each time it's polled, so
+    /// a long program doesn't block the executor. The program runs on a thread of its own, see
+    /// `RunTask`. The observer is replaced while it runs.
+    #[cfg(feature = "arc")]
+    pub fn run_async(self, source: &str, statements: usize) -> RunTask {
+        let cancel = self.interpreter.cancel_token().clone();
+        RunTask::spawn(self, source.to_owned(), statements, cancel)
+    }
+
+    pub fn run_file(&mut self, path: impl AsRef<Path>, mode: RunMode) -> Result<Outcome, LoxError> {
+        let path = path.as_ref();
+        let source = read_source(path)?;
+        self.run_source(&source, mode, Some(path.display().to_string()))
+    }
+
+    /// Run the tests registered by `test(name, fn)` in the programs run so far, then forget them.
+    /// The result of each test is written to the stdout sink. Calling `exit(code)` or cancelling
+    /// stops the remaining tests.
+    pub fn run_tests(&mut self) -> Result<TestSummary, LoxError> {
+        let mut summary = TestSummary::default();
+        for test in self.interpreter.take_tests() {
+            self.interpreter.start_run();
+            let result = self
+                .interpreter
+                .call_value(&test.func, Box::new([]), test.loc);
+
+            match result {
+                Ok(_) => {
+                    writeln!(self.interpreter.stdout(), "test {} ... ok", test.name)?;
+                    summary.passed += 1;
+                }
+                Err(RuntimeError::Exit(_, code)) => return Err(LoxError::Exit(code)),
+                Err(error @ RuntimeError::Cancelled(_)) => {
+                    return Err(LoxError::RuntimeError {
+                        source_name: None,
+                        error,
+                    })
+                }
+                Err(error) => {
+                    let mut stdout = self.interpreter.stdout();
+                    writeln!(stdout, "test {} ... FAILED", test.name)?;
+                    writeln!(stdout, "    {error}")?;
+                    summary.failed += 1;
+                }
+            }
+        }
+
+        let TestSummary { passed, failed } = summary;
+        writeln!(
+            self.interpreter.stdout(),
+            "{passed} passed, {failed} failed"
+        )?;
+        Ok(summary)
+    }
+
+    /// Check `source` against the rules enabled in `config` without running it. The warnings are
+    /// reported to the stderr sink like errors, lex and syntax errors stop the check.
+    pub fn lint(&mut self, source: &str, config: &LintConfig) -> Result<Vec<Diagnostic>, LoxError> {
+        self.lint_source(source, config, None)
+    }
+
+    pub fn lint_file(
+        &mut self,
+        path: impl AsRef<Path>,
+        config: &LintConfig,
+    ) -> Result<Vec<Diagnostic>, LoxError> {
+        let path = path.as_ref();
+        let source = read_source(path)?;
+        self.lint_source(&source, config, Some(path.display().to_string()))
+    }
+
+    /// Translate `source` into a standalone program in the language of `target`. The program is
+    /// checked like when running it, and the errors are reported to the stderr sink.
+    pub fn transpile(&mut self, source: &str, target: Target) -> Result<String, LoxError> {
+        self.transpile_source(source, target, None)
+    }
+
+    pub fn transpile_file(
+        &mut self,
+        path: impl AsRef<Path>,
+        target: Target,
+    ) -> Result<String, LoxError> {
+        let path = path.as_ref();
+        let source = read_source(path)?;
+        self.transpile_source(&source, target, Some(path.display().to_string()))
+    }
+
+    /// The documentation of the declarations in `source` as Markdown, titled `title`. Only the
+    /// syntax is checked, the errors are reported to the stderr sink.
+    pub fn doc(&mut self, source: &str, title: &str) -> Result<String, LoxError> {
+        self.doc_source(source, title, None)
+    }
+
+    pub fn doc_file(&mut self, path: impl AsRef<Path>) -> Result<String, LoxError> {
+        let path = path.as_ref();
+        let source = read_source(path)?;
+        let title = path.file_name().unwrap_or(path.as_os_str());
+        let title = title.to_string_lossy();
+        self.doc_source(&source, &title, Some(path.display().to_string()))
+    }
+
+    /// The static call graph of `source`, which is checked like when running it.
+    pub fn call_graph(&mut self, source: &str) -> Result<CallGraph, LoxError> {
+        self.call_graph_source(source, None)
+    }
+
+    pub fn call_graph_file(&mut self, path: impl AsRef<Path>) -> Result<CallGraph, LoxError> {
+        let path = path.as_ref();
+        let source = read_source(path)?;
+        self.call_graph_source(&source, Some(path.display().to_string()))
+    }
+
+    /// The variables of `source` with their declarations, see `Bindings::find_definition` and
+    /// `Bindings::find_references`. The program is checked like when running it.
+    pub fn bindings(&mut self, source: &str) -> Result<Bindings, LoxError> {
+        self.bindings_source(source, None)
+    }
+
+    pub fn bindings_file(&mut self, path: impl AsRef<Path>) -> Result<Bindings, LoxError> {
+        let path = path.as_ref();
+        let source = read_source(path)?;
+        self.bindings_source(&source, Some(path.display().to_string()))
+    }
+
+    /// Render `source` as a standalone HTML page with syntax highlighting, titled `title`. Errors
+    /// in the source are highlighted instead of reported.
+    pub fn highlight(&mut self, source: &str, title: &str) -> String {
+        highlight::to_html(source, title, self.interpreter.interner_mut())
+    }
+
+    pub fn highlight_file(&mut self, path: impl AsRef<Path>) -> Result<String, LoxError> {
+        let path = path.as_ref();
+        let source = read_source(path)?;
+        let title = path.file_name().unwrap_or(path.as_os_str());
+        Ok(self.highlight(&source, &title.to_string_lossy()))
+    }
+
+    /// The declarations of `source`, nested in the function or class they are declared in. The
+    /// program is checked like when running it.
+    pub fn symbols(&mut self, source: &str) -> Result<Vec<Symbol>, LoxError> {
+        self.symbols_source(source, None)
+    }
+
+    pub fn symbols_file(&mut self, path: impl AsRef<Path>) -> Result<Vec<Symbol>, LoxError> {
+        let path = path.as_ref();
+        let source = read_source(path)?;
+        self.symbols_source(&source, Some(path.display().to_string()))
+    }
+
+    /// The names in `source` classified by what they refer to, e.g. for highlighting in an editor.
+    /// The program is checked like when running it.
+    pub fn semantic_tokens(&mut self, source: &str) -> Result<Vec<SemanticToken>, LoxError> {
+        let compiled = self.compile(source, RunMode::Normal, &None)?;
+        let Compiled {
+            program,
+            resolve_map,
+            ..
+        } = compiled.expect("normal mode should always compile");
+        Ok(analyze::semantic_tokens(
+            &program,
+            &resolve_map,
+            self.interpreter.interner(),
+        ))
+    }
+
+    /// Print `source` back as Lox without comments and unneeded whitespace, with shorter names for
+    /// the locals if `rename` is set. The program is checked like when running it.
+    pub fn minify(&mut self, source: &str, rename: bool) -> Result<String, LoxError> {
+        self.minify_source(source, rename, None)
+    }
+
+    pub fn minify_file(
+        &mut self,
+        path: impl AsRef<Path>,
+        rename: bool,
+    ) -> Result<String, LoxError> {
+        let path = path.as_ref();
+        let source = read_source(path)?;
+        self.minify_source(&source, rename, Some(path.display().to_string()))
+    }
+
+    /// Define a global native function implemented by `body`. Calls with a number of arguments that
+    /// doesn't match `arity` are rejected before `body` is called, and an error returned from
+    /// `body` is reported as a runtime error at the call site.
+    pub fn register_native<F>(&mut self, name: &str, arity: impl Into<Arity>, body: F)
+    where
+        F: Fn(&[Value]) -> Result<Value, NativeError> + ThreadSafe + 'static,
+    {
+        self.interpreter
+            .define_host_native(name, arity, Shared::new(body));
+    }
+
+    /// Expose the Rust type `T` to programs as the global class `name`, see `LoxClass`.
+    pub fn register_class<T: LoxClass>(&mut self, name: &str) {
+        self.interpreter.register_class::<T>(name);
+    }
+
+    /// Wrap `value` so it can be given to a program, e.g. using `set_global`. `None` if `T` is not
+    /// registered using `register_class`.
+    pub fn new_object<T: LoxClass>(&self, value: T) -> Option<Value> {
+        self.interpreter.new_object(value)
+    }
+
+    /// Call the global function or class `name` with `args`. Errors are returned as is without
+    /// being reported, and have no location if they don't come from inside the function.
+    pub fn call(&self, name: &str, args: &[Value]) -> Result<Value, RuntimeError> {
+        let callee = self
+            .interpreter
+            .get_global(name)
+            .ok_or_else(|| RuntimeError::UndefinedVariable(Location::default(), name.to_owned()))?;
+        self.call_value(&callee, args)
+    }
+
+    /// Call a function or class value, e.g. a callback the program passed to a native function.
+    pub fn call_value(&self, callee: &Value, args: &[Value]) -> Result<Value, RuntimeError> {
+        self.interpreter
+            .call_value(callee, args.into(), Location::default())
+            .map(|value| self.resolved(value))
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        let value = self.interpreter.get_global(name)?;
+        Some(self.resolved(value))
+    }
+
+    /// Define or overwrite the global variable `name`, e.g. to configure a program before running
+    /// it.
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.interpreter.set_global(name, value);
+    }
+
+    /// Save the globals, so they can be given back to another instance using `restore`, e.g. to
+    /// continue a session after restarting the host. Functions and classes are saved if they are
+    /// declared at the top level, along with the nil, bool, number, string, list, and map values;
+    /// the other globals, e.g. instances, are left out.
+    pub fn snapshot(&self) -> Vec<u8> {
+        snapshot::save(&self.interpreter)
+    }
+
+    /// Define the globals saved by `snapshot`, overwriting the ones with the same name. The
+    /// functions and classes are declared again by running their declarations, which are reported
+    /// to the stderr sink if they fail, e.g. because the snapshot is modified.
+    pub fn restore(&mut self, bytes: &[u8]) -> Result<(), SnapshotError> {
+        let snapshot = snapshot::load(bytes)?;
+
+        let mut declared = Vec::new();
+        for (name, source) in snapshot.declarations {
+            self.eval(&source).map_err(|_| SnapshotError::Invalid)?;
+            let value = self.interpreter.get_global(&name);
+            declared.push(value.ok_or(SnapshotError::Invalid)?);
+        }
+        for (name, saved) in snapshot.globals {
+            let value = saved.into_value(&declared);
+            self.interpreter.set_global(&name, value);
+        }
+        Ok(())
+    }
+
+    /// Redirect the output of `print` and `write` to `stdout`.
+    pub fn set_stdout(&mut self, stdout: impl Sink + 'static) {
+        self.interpreter.set_stdout(Box::new(stdout));
+    }
+
+    /// Redirect the output of `eprint` and the diagnostics of failed runs to `stderr`.
+    pub fn set_stderr(&mut self, stderr: impl Sink + 'static) {
+        self.interpreter.set_stderr(Box::new(stderr));
+    }
+
+    /// Read the input of `readLine` and the REPL from `stdin`.
+    pub fn set_stdin(&mut self, stdin: impl LineSource + 'static) {
+        self.interpreter.set_stdin(Box::new(stdin));
+    }
+
+    /// Read the time of the `clock` and `Time` natives from `clock`, e.g. on targets without a
+    /// system clock.
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.interpreter.set_clock(Box::new(clock));
+    }
+
+    /// The sources compiled so far, which the locations of errors and diagnostics point into.
+    pub fn sources(&self) -> &SourceMap {
+        &self.sources
+    }
+
+    /// Approximate number of bytes allocated by programs so far, see `Limits::max_allocation`.
+    pub fn allocated(&self) -> usize {
+        self.interpreter.allocated()
+    }
+
+    /// Install `observer` to be notified as programs run, replacing the previous one.
+    pub fn set_observer(&mut self, observer: impl Observer + 'static) {
+        self.interpreter.set_observer(Some(Box::new(observer)));
+    }
+
+    pub fn remove_observer(&mut self) {
+        self.interpreter.set_observer(None);
+    }
+
+    /// Read a line from the input source, see `LineSource::read_line`.
+    pub fn read_line(&self, buf: &mut String) -> io::Result<usize> {
+        self.interpreter.stdin().read_line(buf)
+    }
+
+    /// Run each line read from the input source until the end of the input or until a line calls
+    /// `exit(code)`. The prompt and the errors are written to `out`, while the lines write to the
+    /// sinks as usual. Cancelling only stops the line being run.
+    pub fn repl(&mut self, out: &mut dyn Write) -> io::Result<Outcome> {
+        writeln!(
+            out,
+            "Loxi: a Lox programming language interpreter (currently under construction)"
+        )?;
+
+        let mut line = String::new();
+        let outcome = loop {
+            write!(out, ">>> ")?;
+            out.flush()?;
+
+            if self.read_line(&mut line)? == 0 {
+                break Outcome::Completed;
+            }
+
+            match self.run(&line, RunMode::Normal) {
+                Ok(Outcome::Completed) => (),
+                Ok(Outcome::Exit(code)) => break Outcome::Exit(code),
+                Err(err) => writeln!(out, "{err}")?,
+            }
+
+            // a cancelled line shouldn't affect the next one
+            self.interpreter.cancel_token().reset();
+            line.clear();
+        };
+
+        writeln!(out, "\nExiting loxi...")?;
+        Ok(outcome)
+    }
+
+    /// Serve `repl` to the clients connecting to `listener`, one at a time, until a line calls
+    /// `exit(code)`. The state is kept between clients, and each one is also the input source and
+    /// the sinks while it's connected.
+    pub fn serve_repl(&mut self, listener: TcpListener) -> io::Result<Outcome> {
+        for stream in listener.incoming() {
+            if let Outcome::Exit(code) = self.repl_client(stream?) {
+                return Ok(Outcome::Exit(code));
+            }
+        }
+        Ok(Outcome::Completed)
+    }
+
+    /// Like `serve_repl`, for a Unix domain socket.
+    #[cfg(unix)]
+    pub fn serve_repl_unix(&mut self, listener: UnixListener) -> io::Result<Outcome> {
+        for stream in listener.incoming() {
+            if let Outcome::Exit(code) = self.repl_client(stream?) {
+                return Ok(Outcome::Exit(code));
+            }
+        }
+        Ok(Outcome::Completed)
+    }
+
+    /// Convert a value into `T`, see `to_value` for the conversion of the other direction. Unlike
+    /// `to_value` this needs the interpreter, since lists, maps, and instances can contain string
+    /// literals.
+    #[cfg(feature = "serde")]
+    pub fn from_value<T: DeserializeOwned>(&self, value: Value) -> Result<T, ConvertError> {
+        convert::from_value(value, self.interpreter.interner())
+    }
+
+    /// Display a value the same way `print` does.
+    pub fn display<'a>(&'a self, value: &'a Value) -> impl Display + 'a {
+        self.interpreter.display(value)
+    }
+
+    /// Values given to the host never contain string literals, since it can't access the interner.
+    fn resolved(&self, value: Value) -> Value {
+        value.resolved(self.interpreter.interner())
+    }
+
+    fn eval_source(&mut self, source: &str, name: Option<String>) -> Result<Value, LoxError> {
+        let value = self.execute(source, RunMode::Normal, name)?;
+        Ok(self.resolved(value))
+    }
+
+    fn run_source(
+        &mut self,
+        source: &str,
+        mode: RunMode,
+        name: Option<String>,
+    ) -> Result<Outcome, LoxError> {
+        match self.execute(source, mode, name) {
+            Ok(_) => Ok(Outcome::Completed),
+            Err(LoxError::Exit(code)) => Ok(Outcome::Exit(code)),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Run `source` through every stage. Errors are reported to the stderr sink as they are found,
+    /// then returned along with `source_name`.
+    fn execute(
+        &mut self,
+        source: &str,
+        mode: RunMode,
+        source_name: Option<String>,
+    ) -> Result<Value, LoxError> {
+        let Some(Compiled {
+            program,
+            resolve_map,
+            pragmas,
+            suspicious,
+        }) = self.compile(source, mode, &source_name)?
+        else {
+            return Ok(Value::nil());
+        };
+
+        if let Some(config) = &self.lint {
+            let found = Findings {
+                pragmas: &pragmas,
+                suspicious: &suspicious,
+                ..Findings::default()
+            };
+            let warnings = Linter::new(self.interpreter.interner(), config).lint(&program, found);
+            self.report_warnings(&warnings);
+            if warnings.iter().any(|w| w.level == LintLevel::Deny) {
+                return Err(LoxError::LintError {
+                    source_name,
+                    warnings,
+                });
+            }
+        }
+
+        // interpreting
+        let result = self.interpreter.interpret(program, resolve_map);
+        result.map_err(|error| match error {
+            RuntimeError::Exit(_, code) => LoxError::Exit(code),
+            // the error may be in a function defined by another source
+            error => {
+                self.report(error.loc(), &error);
+                let source_name = self.sources.name(error.loc().source).map(str::to_owned);
+                LoxError::RuntimeError { source_name, error }
+            }
+        })
+    }
+
+    /// Lex, parse, and resolve `source`. `None` if `mode` only dumps the result of a stage.
+    fn compile(
+        &mut self,
+        source: &str,
+        mode: RunMode,
+        source_name: &Option<String>,
+    ) -> Result<Option<Compiled>, LoxError> {
+        // lexing
+        let ScanResult {
+            tokens,
+            errors,
+            pragmas,
+            suspicious,
+            ..
+        } = self.scan(source, source_name);
+
+        let interner = self.interpreter.interner();
+
+        if !errors.is_empty() {
+            errors.iter().for_each(|e| self.report(e.loc(), e));
+            return Err(LoxError::LexError {
+                source_name: source_name.clone(),
+                errors,
+            });
+        }
+
+        if mode == RunMode::DumpLex {
+            let mut stdout = self.interpreter.stdout();
+            for tok in tokens.iter() {
+                writeln!(stdout, "{}", tok.display(interner))?;
+            }
+            return Ok(None);
+        }
+
+        // only <eof> exist
+        if tokens.len() == 1 {
+            return Err(LoxError::EmptyError);
+        }
+
+        // parsing
+        let mut parser = Parser::new().with_dialect(self.dialect);
+        let program = parser.parse(tokens).map_err(|errors| {
+            errors.iter().for_each(|e| self.report(e.loc(), e));
+            LoxError::ParseError {
+                source_name: source_name.clone(),
+                errors,
+            }
+        })?;
+
+        if mode == RunMode::DumpParse {
+            writeln!(self.interpreter.stdout(), "{}", program.display(interner))?;
+            return Ok(None);
+        }
+        if mode == RunMode::DumpTree {
+            let tree = parse::render_tree(&program, interner);
+            write!(self.interpreter.stdout(), "{tree}")?;
+            return Ok(None);
+        }
+        if mode == RunMode::DumpDot {
+            write!(self.interpreter.stdout(), "{}", program.to_dot(interner))?;
+            return Ok(None);
+        }
+
+        // resolving
+        let mut resolver = Resolver::new(interner);
+        let resolve_map = resolver.resolve(&program).map_err(|error| {
+            self.report(error.loc(), &error);
+            LoxError::ResolveError {
+                source_name: source_name.clone(),
+                error,
+            }
+        })?;
+
+        Ok(Some(Compiled {
+            program,
+            resolve_map,
+            pragmas,
+            suspicious,
+        }))
+    }
+
+    /// Add `source` to the source map and lex it.
+    fn scan(&mut self, source: &str, source_name: &Option<String>) -> ScanResult {
+        let id = self.sources.add(source_name.clone(), source);
+        Lexer::new(source, self.interpreter.interner_mut())
+            .with_dialect(self.dialect)
+            .in_source(id)
+            .scan()
+    }
+
+    fn transpile_source(
+        &mut self,
+        source: &str,
+        target: Target,
+        source_name: Option<String>,
+    ) -> Result<String, LoxError> {
+        let compiled = self.compile(source, RunMode::Normal, &source_name)?;
+        let Compiled { program, .. } = compiled.expect("normal mode should always compile");
+        Ok(transpile::transpile(
+            &program,
+            self.interpreter.interner(),
+            target,
+            self.interpreter.number_style(),
+            self.dialect,
+        ))
+    }
+
+    fn doc_source(
+        &mut self,
+        source: &str,
+        title: &str,
+        source_name: Option<String>,
+    ) -> Result<String, LoxError> {
+        let ScanResult {
+            tokens,
+            errors,
+            docs,
+            ..
+        } = self.scan(source, &source_name);
+
+        if !errors.is_empty() {
+            errors.iter().for_each(|e| self.report(e.loc(), e));
+            return Err(LoxError::LexError {
+                source_name,
+                errors,
+            });
+        }
+
+        let mut parser = Parser::with_docs(docs).with_dialect(self.dialect);
+        let program = parser.parse(tokens).map_err(|errors| {
+            errors.iter().for_each(|e| self.report(e.loc(), e));
+            LoxError::ParseError {
+                source_name,
+                errors,
+            }
+        })?;
+
+        let interner = self.interpreter.interner();
+        Ok(doc::markdown(&program, parser.docs(), interner, title))
+    }
+
+    fn call_graph_source(
+        &mut self,
+        source: &str,
+        source_name: Option<String>,
+    ) -> Result<CallGraph, LoxError> {
+        let compiled = self.compile(source, RunMode::Normal, &source_name)?;
+        let Compiled {
+            program,
+            resolve_map,
+            ..
+        } = compiled.expect("normal mode should always compile");
+        Ok(analyze::call_graph(
+            &program,
+            &resolve_map,
+            self.interpreter.interner(),
+        ))
+    }
+
+    fn bindings_source(
+        &mut self,
+        source: &str,
+        source_name: Option<String>,
+    ) -> Result<Bindings, LoxError> {
+        let compiled = self.compile(source, RunMode::Normal, &source_name)?;
+        let Compiled {
+            program,
+            resolve_map,
+            ..
+        } = compiled.expect("normal mode should always compile");
+        Ok(analyze::bindings(
+            &program,
+            &resolve_map,
+            self.interpreter.interner(),
+        ))
+    }
+
+    fn symbols_source(
+        &mut self,
+        source: &str,
+        source_name: Option<String>,
+    ) -> Result<Vec<Symbol>, LoxError> {
+        let compiled = self.compile(source, RunMode::Normal, &source_name)?;
+        let Compiled { program, .. } = compiled.expect("normal mode should always compile");
+        Ok(analyze::symbols(&program, self.interpreter.interner()))
+    }
+
+    fn minify_source(
+        &mut self,
+        source: &str,
+        rename: bool,
+        source_name: Option<String>,
+    ) -> Result<String, LoxError> {
+        let compiled = self.compile(source, RunMode::Normal, &source_name)?;
+        let Compiled {
+            program,
+            resolve_map,
+            ..
+        } = compiled.expect("normal mode should always compile");
+        Ok(minify::minify(
+            &program,
+            &resolve_map,
+            self.interpreter.interner(),
+            rename,
+        ))
+    }
+
+    fn lint_source(
+        &mut self,
+        source: &str,
+        config: &LintConfig,
+        source_name: Option<String>,
+    ) -> Result<Vec<Diagnostic>, LoxError> {
+        let ScanResult {
+            tokens,
+            errors,
+            pragmas,
+            suspicious,
+            ..
+        } = self.scan(source, &source_name);
+
+        if !errors.is_empty() {
+            errors.iter().for_each(|e| self.report(e.loc(), e));
+            return Err(LoxError::LexError {
+                source_name,
+                errors,
+            });
+        }
+
+        let mut parser = match config.is_enabled(LintRule::MissingSemicolon) {
+            true => Parser::with_semicolon_recovery(),
+            false => Parser::new(),
+        }
+        .with_dialect(self.dialect);
+        if config.is_enabled(LintRule::EmptyStatement) {
+            parser = parser.with_empty_statement_recovery();
+        }
+        let program = parser.parse(tokens).map_err(|errors| {
+            errors.iter().for_each(|e| self.report(e.loc(), e));
+            LoxError::ParseError {
+                source_name: source_name.clone(),
+                errors,
+            }
+        })?;
+
+        let found = Findings {
+            pragmas: &pragmas,
+            suspicious: &suspicious,
+            missing_semicolons: parser.missing_semicolons(),
+            empty_statements: parser.empty_statements(),
+        };
+        let warnings = Linter::new(self.interpreter.interner(), config).lint(&program, found);
+        self.report_warnings(&warnings);
+
+        let diagnostics = warnings.iter().map(|warning| Diagnostic {
+            kind: ErrorKind::Lint,
+            source_name: source_name.clone(),
+            span: warning.span(),
+            message: warning.labelled_message(),
+        });
+        Ok(diagnostics.collect())
+    }
+
+    /// Write the warnings to the stderr sink, the denied ones as errors.
+    fn report_warnings(&self, warnings: &[LintWarning]) {
+        for warning in warnings {
+            match warning.level {
+                LintLevel::Deny => self.report(warning.loc, warning),
+                LintLevel::Allow | LintLevel::Warn => self.warn(warning.loc, warning),
+            }
+        }
+    }
+
+    /// A client that disconnects or fails only ends its own session.
+    fn repl_client(&mut self, mut client: impl Client) -> Outcome {
+        let mut session = || -> io::Result<Outcome> {
+            self.set_stdin(BufReader::new(client.try_clone()?));
+            self.set_stdout(client.try_clone()?);
+            self.set_stderr(client.try_clone()?);
+            self.repl(&mut client)
+        };
+        let outcome = session().unwrap_or(Outcome::Completed);
+
+        // the sinks still hold the connection until the next client replaces them
+        let _ = client.shutdown();
+        outcome
+    }
+
+    fn builder(options: &Options) -> InterpreterBuilder {
+        Interpreter::builder()
+            .capabilities(options.capabilities)
+            .cancel(options.cancel.clone())
+            .max_call_depth(options.max_call_depth)
+            .max_steps(options.max_steps)
+            .timeout(options.timeout)
+            .limits(options.limits)
+            .strict_division(options.strict_division)
+            .dialect(options.dialect)
+            .number_style(options.number_style)
+    }
+
+    /// Write the error along with the line it occurred at to the stderr sink.
+    fn report(&self, loc: Location, err: &dyn Display) {
+        self.write_diagnostic(loc, err, "\x1b[1;31m");
+    }
+
+    /// Like `report`, but for a warning.
+    fn warn(&self, loc: Location, warning: &dyn Display) {
+        self.write_diagnostic(loc, warning, "\x1b[1;33m");
+    }
+
+    fn write_diagnostic(&self, loc: Location, err: &dyn Display, color: &str) {
+        let snippet = self
+            .sources
+            .render(Span::at(loc), self.context_lines, Some(color));
+
+        let mut out = self.interpreter.stderr();
+        let mut write = || -> io::Result<()> {
+            writeln!(out, "{:->width$}", "", width = 80)?;
+            write!(out, "{snippet}")?;
+            writeln!(out, "{color}{}\x1b[00m", err)?;
+            out.flush()
+        };
+        // the sink may be a client of `serve_repl` that hung up, which shouldn't stop the host
+        let _ = write();
+    }
+}
+
+/// A connection to a client of `Lox::serve_repl`.
+trait Client: Read + Write + ThreadSafe + Sized + 'static {
+    fn try_clone(&self) -> io::Result<Self>;
+    fn shutdown(&self) -> io::Result<()>;
+}
+
+impl Client for TcpStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        TcpStream::try_clone(self)
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        TcpStream::shutdown(self, Shutdown::Both)
+    }
+}
+
+#[cfg(unix)]
+impl Client for UnixStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        UnixStream::try_clone(self)
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        UnixStream::shutdown(self, Shutdown::Both)
+    }
+}
+
+fn read_source(path: &Path) -> Result<String, LoxError> {
+    let mut string = String::new();
+    let mut file = File::open(path)?;
+    file.read_to_string(&mut string)?;
+
+    match string.is_empty() {
+        true => return Err(LoxError::EmptyError),
+        false => {
+            // make sure the content of the file ends with newline
+            if !string.ends_with('\n') {
+                string.push('\n');
+            }
+        }
+    }
+
+    Ok(string)
+}
@@ -0,0 +1,1038 @@
+use std::error::Error;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::{env, fs, process, thread};
+
+use pretty_assertions::assert_eq;
+
+use crate::interp::RuntimeError;
+use crate::util::Location;
+use crate::{
+    Diagnostic, ErrorKind, Lox, LoxError, NativeError, Options, OutputBuffer, Span, Value,
+};
+
+/// A `Lox` with the default options, see `new_lox_with`.
+fn new_lox() -> (Lox, OutputBuffer) {
+    new_lox_with(&Options::default())
+}
+
+/// A `Lox` whose stdout is captured by the returned buffer and whose stderr is discarded, so the
+/// errors of failing programs don't clutter the output of the tests.
+fn new_lox_with(options: &Options) -> (Lox, OutputBuffer) {
+    let mut lox = Lox::new(options);
+    let stdout = OutputBuffer::default();
+    lox.set_stdout(stdout.clone());
+    lox.set_stderr(io::sink());
+    (lox, stdout)
+}
+
+#[test]
+fn lox_keeps_state_between_evals() {
+    let (mut lox, _) = new_lox();
+
+    let result = lox.eval("var count = 1; fun add(n) { count = count + n; return count; }");
+    assert!(matches!(result, Ok(Value::Nil)));
+
+    // closures defined by a previous eval can still resolve their locals
+    let result = lox.eval("add(2); add(3);").expect("should run");
+    assert_eq!(lox.display(&result).to_string(), "6");
+
+    let result = lox.eval("exit(4);");
+    assert!(matches!(result, Err(LoxError::Exit(4))));
+}
+
+#[test]
+fn lox_calls_host_natives() {
+    let (mut lox, _) = new_lox();
+    let calls = Arc::new(Mutex::new(Vec::new()));
+
+    let log = Arc::clone(&calls);
+    lox.register_native("greet", 1, move |args| match &args[0] {
+        Value::String(name) => {
+            log.lock().unwrap().push(name.to_string());
+            Ok(Value::string(format!("hello, {name}")))
+        }
+        _ => Err(NativeError::Custom("expect a name".to_owned())),
+    });
+
+    let result = lox.eval(r#"greet("lox");"#).expect("should run");
+    assert_eq!(lox.display(&result).to_string(), "hello, lox");
+    assert_eq!(*calls.lock().unwrap(), ["lox"]);
+
+    assert!(matches!(
+        lox.eval("greet(1);"),
+        Err(LoxError::RuntimeError { .. })
+    ));
+    assert!(matches!(
+        lox.eval("greet();"),
+        Err(LoxError::RuntimeError { .. })
+    ));
+    assert_eq!(calls.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn lox_writes_to_injected_sinks() {
+    let (mut lox, stdout) = new_lox();
+    let stderr = OutputBuffer::default();
+    lox.set_stderr(stderr.clone());
+
+    lox.eval(r#"print "out"; eprint("err");"#)
+        .expect("should run");
+    assert!(matches!(
+        lox.eval("print nil + 1;"),
+        Err(LoxError::RuntimeError { .. })
+    ));
+
+    assert_eq!(stdout.text(), "out\n");
+    assert!(stderr.text().starts_with("err\n"));
+    assert!(stderr
+        .text()
+        .contains("Invalid binary operation '+' between '<nil>' and '<number>'"));
+}
+
+#[test]
+fn lox_reads_from_injected_input() {
+    let (mut lox, stdout) = new_lox();
+    lox.set_stdin(io::Cursor::new("alice\r\nbob"));
+
+    let source = r#"
+        var name = readLine();
+        while (name != nil) {
+            print "hi " + name;
+            name = readLine();
+        }
+    "#;
+    lox.eval(source).expect("should run");
+
+    assert_eq!(stdout.text(), "hi alice\nhi bob\n");
+}
+
+#[test]
+fn lox_serves_repl_to_clients() {
+    use std::io::Read;
+    use std::net::{Shutdown, TcpListener, TcpStream};
+
+    use crate::Outcome;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = std::thread::spawn(move || {
+        let session = |input: &str| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(input.as_bytes()).unwrap();
+            stream.shutdown(Shutdown::Write).unwrap();
+            let mut output = String::new();
+            stream.read_to_string(&mut output).unwrap();
+            output
+        };
+        (session("var a = 1;\n"), session("print a + 1;\nexit(3);\n"))
+    });
+
+    let (mut lox, _) = new_lox();
+    let outcome = lox.serve_repl(listener).unwrap();
+    assert_eq!(outcome, Outcome::Exit(3));
+
+    let (first, second) = client.join().unwrap();
+    assert!(first.ends_with(">>> >>> \nExiting loxi...\n"));
+    assert!(second.ends_with(">>> 2\n>>> \nExiting loxi...\n"));
+}
+
+#[test]
+fn repl_clients_can_hang_up_before_their_errors_are_reported() {
+    use std::io::{Cursor, Read};
+
+    use crate::Outcome;
+
+    /// A client that sends its input and hangs up, so nothing can be written to it once the input
+    /// is read.
+    #[derive(Clone)]
+    struct HangingUp(Arc<Mutex<Cursor<&'static str>>>);
+
+    impl Read for HangingUp {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().read(buf)
+        }
+    }
+
+    impl Write for HangingUp {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let input = self.0.lock().unwrap();
+            match input.position() < input.get_ref().len() as u64 {
+                true => Ok(buf.len()),
+                false => Err(io::ErrorKind::BrokenPipe.into()),
+            }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl super::Client for HangingUp {
+        fn try_clone(&self) -> io::Result<Self> {
+            Ok(self.clone())
+        }
+
+        fn shutdown(&self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let (mut lox, _) = new_lox();
+    let input = Cursor::new("var a = 1; print a + nil;\n");
+    let client = HangingUp(Arc::new(Mutex::new(input)));
+    assert_eq!(lox.repl_client(client), Outcome::Completed);
+
+    // the state is kept for the next client
+    assert!(matches!(lox.get_global("a"), Some(Value::Int(1))));
+}
+
+#[test]
+fn lox_calls_lox_functions() {
+    let (mut lox, _) = new_lox();
+    let source = r#"
+        var total = 0;
+        fun onUpdate(dt) {
+            total = total + dt;
+            return total;
+        }
+        fun makeCounter() {
+            var count = 0;
+            fun counter() {
+                count = count + 1;
+                return count;
+            }
+            return counter;
+        }
+    "#;
+    lox.eval(source).expect("should run");
+
+    lox.call("onUpdate", &[Value::number(0.5)])
+        .expect("should call");
+    let total = lox
+        .call("onUpdate", &[Value::number(1.0)])
+        .expect("should call");
+    assert!(matches!(total, Value::Number(1.5)));
+
+    let counter = lox.call("makeCounter", &[]).expect("should call");
+    lox.call_value(&counter, &[]).expect("should call");
+    let count = lox.call_value(&counter, &[]).expect("should call");
+    assert!(matches!(count, Value::Int(2)));
+
+    let result = lox.call("missing", &[]);
+    assert!(matches!(result, Err(RuntimeError::UndefinedVariable(_, _))));
+    let result = lox.call("total", &[]);
+    assert!(matches!(result, Err(RuntimeError::NotCallable(_))));
+}
+
+#[test]
+fn lox_resolves_strings_in_collections() {
+    let (mut lox, _) = new_lox();
+    let source = r#"var map = Map(); map.set("k", "b"); var xs = ["a", map]; xs.push(xs);"#;
+    lox.eval(source).unwrap();
+
+    let Some(Value::List(xs)) = lox.get_global("xs") else {
+        panic!("xs should be a list");
+    };
+    let xs = xs.borrow();
+    assert!(matches!(&xs[0], Value::String(str) if &**str == "a"));
+    let Value::Map(map) = &xs[1] else {
+        panic!("the second element should be a map");
+    };
+    let map = map.borrow();
+    assert!(matches!(map.values().next(), Some(Value::String(str)) if &*str == "b"));
+}
+
+#[test]
+fn lox_gets_and_sets_globals() {
+    let (mut lox, _) = new_lox();
+    lox.set_global("width", Value::number(3.0));
+    lox.set_global("name", Value::string("box".to_owned()));
+
+    lox.eval(r#"var area = width * width; var label = name + ": " + "done"; name = "box";"#)
+        .expect("should run");
+    assert!(matches!(lox.get_global("area"), Some(Value::Number(9.0))));
+    assert!(lox.get_global("undefined").is_none());
+
+    for (name, expect) in [("label", "box: done"), ("name", "box")] {
+        match lox.get_global(name) {
+            Some(Value::String(str)) => assert_eq!(&*str, expect),
+            value => panic!("expect a string, got {value:?}"),
+        }
+    }
+
+    // overwrite a variable defined by the program
+    lox.set_global("width", Value::number(4.0));
+    let area = lox.eval("width * width;").expect("should run");
+    assert!(matches!(area, Value::Number(16.0)));
+}
+
+#[test]
+fn lox_errors_are_inspectable() {
+    let (mut lox, _) = new_lox();
+
+    let err = lox.eval("var a = 1;\nprint a + nil;").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Runtime);
+    assert_eq!(
+        err.diagnostics(),
+        [Diagnostic {
+            kind: ErrorKind::Runtime,
+            source_name: None,
+            span: Span {
+                start: Location::new(2, 9),
+                end: Location::new(2, 9),
+            },
+            message: "RuntimeError: Invalid binary operation '+' between '<number>' and '<nil>'"
+                .to_owned(),
+        }]
+    );
+    assert!(err.source().is_some_and(|e| e.is::<RuntimeError>()));
+
+    let err = lox.eval("print 1 @ 2;\nprint 3 # 4;").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Lex);
+    let lines = err
+        .diagnostics()
+        .iter()
+        .map(|d| d.span.start.line)
+        .collect::<Vec<_>>();
+    assert_eq!(lines, [1, 2]);
+}
+
+#[test]
+fn diagnostics_span_the_names_and_tokens_they_are_about() {
+    let (mut lox, _) = new_lox();
+
+    let mut spans = |source: &str| {
+        let err = lox.eval(source).unwrap_err();
+        let diagnostics = err.diagnostics();
+        let spans = diagnostics.iter().map(|d| {
+            let Span { start, end } = d.span;
+            (start.line, start.column, end.column)
+        });
+        spans.collect::<Vec<_>>()
+    };
+
+    assert_eq!(spans("print 1;\nprint missing;"), [(2, 7, 14)]);
+    assert_eq!(spans("print 1 @ 2;"), [(1, 9, 10)]);
+    assert_eq!(spans("print 1 + nil;"), [(1, 9, 9)]);
+    assert_eq!(spans("print (1;"), [(1, 7, 7)]);
+}
+
+#[test]
+fn errors_point_into_the_source_they_are_in() {
+    let path = env::temp_dir().join(format!("loxi-sources-{}.lox", process::id()));
+    fs::write(&path, "fun fail() {\n  return 1 + nil;\n}\n").unwrap();
+    let name = path.display().to_string();
+
+    let (mut lox, _) = new_lox();
+    let stderr = OutputBuffer::default();
+    lox.set_stderr(stderr.clone());
+    let result = lox.eval_file(&path);
+    fs::remove_file(&path).unwrap();
+    result.expect("should define the function");
+
+    // the function fails while it's called from another source
+    let err = lox.eval("print 1;\nfail();").unwrap_err();
+    let LoxError::RuntimeError { source_name, error } = err else {
+        panic!("should be a runtime error: {err:?}");
+    };
+    assert_eq!(source_name.as_deref(), Some(name.as_str()));
+    assert_eq!(error.loc().line, 2);
+    assert_eq!(lox.sources().name(error.loc().source), Some(name.as_str()));
+    assert_eq!(lox.sources().line(error.loc()), Some("  return 1 + nil;"));
+    assert!(stderr.text().contains(&format!("--> {name}:2:12\n")));
+}
+
+#[test]
+fn deep_recursion_is_a_runtime_error() {
+    let options = Options {
+        max_call_depth: 64,
+        ..Options::default()
+    };
+    let (mut lox, _) = new_lox_with(&options);
+
+    let source = "fun depth(n) { if (n == 0) return 0; return depth(n - 1) + 1; }";
+    lox.eval(source).expect("should run");
+
+    let result = lox.eval("depth(63);").expect("should be within the limit");
+    assert!(matches!(result, Value::Int(63)));
+
+    let err = lox.eval("depth(64);").unwrap_err();
+    assert!(matches!(
+        err,
+        LoxError::RuntimeError {
+            error: RuntimeError::StackOverflow(_),
+            ..
+        }
+    ));
+
+    // the depth is restored after the error
+    assert!(lox.eval("depth(10);").is_ok());
+}
+
+#[test]
+fn lox_stops_runs_over_the_step_budget() {
+    let options = Options {
+        max_steps: Some(100),
+        ..Options::default()
+    };
+    let (mut lox, _) = new_lox_with(&options);
+
+    let err = lox.eval("var i = 0; while (true) i = i + 1;").unwrap_err();
+    assert!(matches!(
+        err,
+        LoxError::RuntimeError {
+            error: RuntimeError::BudgetExceeded(
+                Location {
+                    line: 1,
+                    column: 25,
+                    ..
+                },
+                100
+            ),
+            ..
+        }
+    ));
+    assert!(matches!(lox.get_global("i"), Some(Value::Int(98))));
+
+    // every run gets the whole budget
+    assert!(lox.eval("i = 0; while (i < 90) i = i + 1;").is_ok());
+}
+
+#[test]
+fn cancelling_stops_recursion_without_loops() {
+    let options = Options::default();
+    let (mut lox, _) = new_lox_with(&options);
+
+    let cancel = options.cancel.clone();
+    lox.register_native("interrupt", 0, move |_| {
+        cancel.cancel();
+        Ok(Value::nil())
+    });
+
+    let source =
+        "var n = 0;\nfun count() { n = n + 1; if (n == 5) interrupt(); count(); }\ncount();";
+    let err = lox.eval(source).unwrap_err();
+    assert!(matches!(
+        err,
+        LoxError::RuntimeError {
+            error: RuntimeError::Cancelled(Location {
+                line: 2,
+                column: 56,
+                ..
+            }),
+            ..
+        }
+    ));
+    assert!(matches!(lox.get_global("n"), Some(Value::Int(5))));
+}
+
+#[test]
+fn sleep_waits_until_done_or_cancelled() {
+    let options = Options::default();
+    let (mut lox, _) = new_lox_with(&options);
+
+    let start = Instant::now();
+    lox.eval("sleep(20);").unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(20));
+
+    let cancel = options.cancel.clone();
+    let canceller = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        cancel.cancel();
+    });
+    let err = lox.eval("sleep(1e300);").unwrap_err();
+    canceller.join().unwrap();
+    assert!(matches!(
+        err,
+        LoxError::RuntimeError {
+            error: RuntimeError::Cancelled(_),
+            ..
+        }
+    ));
+}
+
+#[test]
+fn sleep_stops_at_the_time_limit() {
+    let options = Options {
+        timeout: Some(Duration::from_millis(20)),
+        ..Options::default()
+    };
+    let (mut lox, _) = new_lox_with(&options);
+
+    let start = Instant::now();
+    let err = lox.eval("sleep(3000);").unwrap_err();
+    assert!(start.elapsed() < Duration::from_millis(1000));
+    assert!(matches!(
+        err,
+        LoxError::RuntimeError {
+            error: RuntimeError::Timeout(..),
+            ..
+        }
+    ));
+}
+
+#[test]
+fn lox_restores_globals_from_a_snapshot() {
+    let (mut lox, _) = new_lox();
+    let source = indoc::indoc! {r#"
+        var list = [1, "two", nil];
+        fun greet(name) { return "hi " + name; }
+        class Counter { init(n) { this.n = n; } next() { this.n = this.n + 1; return this.n; } }
+        var alias = greet;
+        var counter = Counter(1);
+    "#};
+    lox.eval(source).expect("should run");
+    let snapshot = lox.snapshot();
+
+    let (mut restored, _) = new_lox();
+    restored.restore(&snapshot).expect("should restore");
+    let value = restored
+        .eval(r#"alias("lox") + " " + list[1];"#)
+        .expect("should run");
+    assert_eq!(value, Value::string("hi lox two".to_owned()));
+    let value = restored.eval("Counter(41).next();").expect("should run");
+    assert_eq!(value, Value::int(42));
+
+    // instances are left out
+    assert!(restored.get_global("counter").is_none());
+    assert!(restored.restore(&snapshot[..snapshot.len() - 1]).is_err());
+}
+
+#[test]
+fn lox_binds_rust_structs_as_classes() {
+    use crate::{ClassBinding, LoxClass};
+
+    #[derive(Debug, PartialEq)]
+    struct Counter {
+        name: String,
+        count: f64,
+    }
+
+    impl LoxClass for Counter {
+        fn bind(class: &mut ClassBinding<Self>) {
+            class
+                .constructor(|(name,): (String,)| Ok(Counter { name, count: 0.0 }))
+                .property("count", |c| c.count, |c, count| c.count = count)
+                .getter("name", |c| c.name.clone())
+                .method("add", |c, (n,): (f64,)| {
+                    c.count += n;
+                    Ok(c.count)
+                });
+        }
+    }
+
+    let (mut lox, _) = new_lox();
+    lox.register_class::<Counter>("Counter");
+
+    let result =
+        lox.eval(r#"var c = Counter("a"); c.add(2); c.count = c.count * 10; c.name + "!";"#);
+    assert!(matches!(result, Ok(Value::String(str)) if &*str == "a!"));
+
+    let counter = lox.get_global("c").unwrap();
+    let Value::Object(object) = counter else {
+        panic!("should be an object");
+    };
+    assert_eq!(object.with(|c: &mut Counter| c.count), Some(20.0));
+
+    let host = lox.new_object(Counter {
+        name: "b".to_owned(),
+        count: 1.0,
+    });
+    lox.set_global("host", host.unwrap());
+    assert!(matches!(lox.eval("host.add(1);"), Ok(Value::Number(2.0))));
+    let result = lox.eval("c == c and host == host and c != host;");
+    assert!(matches!(result, Ok(Value::Bool(true))));
+
+    for (source, expect) in [
+        (
+            "c.name = \"b\";",
+            "Can't set property 'name': Property is read-only",
+        ),
+        (
+            "c.count = \"b\";",
+            "Can't set property 'count': Invalid argument",
+        ),
+        (
+            "c.add(\"b\");",
+            "Native function 'add' failed: Invalid argument",
+        ),
+        ("c.missing;", "Trying to access an undefined property"),
+    ] {
+        let err = lox.eval(source).unwrap_err();
+        let message = &err.diagnostics()[0].message;
+        assert!(message.contains(expect), "{message}");
+    }
+}
+
+#[test]
+fn lox_notifies_observer() {
+    use crate::{Observer, Scope};
+
+    #[derive(Clone, Default)]
+    struct Events(Arc<Mutex<Vec<String>>>);
+
+    impl Observer for Events {
+        fn on_statement(&mut self, loc: Location, _scope: &Scope<'_>) {
+            self.0.lock().unwrap().push(format!("stmt {}", loc.line));
+        }
+
+        fn on_call(&mut self, name: &str, _loc: Location) {
+            self.0.lock().unwrap().push(format!("call {name}"));
+        }
+
+        fn on_return(&mut self, name: &str, _loc: Location) {
+            self.0.lock().unwrap().push(format!("return {name}"));
+        }
+    }
+
+    let events = Events::default();
+    let (mut lox, _) = new_lox();
+    lox.set_observer(events.clone());
+
+    let source = indoc::indoc! {"
+        fun twice(x) {
+            return x * 2;
+        }
+        print twice(1);
+    "};
+    lox.eval(source).expect("should run");
+
+    let expect = ["stmt 1", "stmt 4", "call twice", "stmt 2", "return twice"];
+    assert_eq!(*events.0.lock().unwrap(), expect);
+
+    lox.remove_observer();
+    lox.eval("twice(2);").expect("should run");
+    assert_eq!(events.0.lock().unwrap().len(), expect.len());
+}
+
+#[test]
+fn observer_inspects_scope() {
+    use crate::{Observer, Scope};
+
+    #[derive(Clone, Default)]
+    struct Inspect(Arc<Mutex<Vec<String>>>);
+
+    impl Observer for Inspect {
+        fn on_statement(&mut self, loc: Location, scope: &Scope<'_>) {
+            if loc.line != 3 {
+                return;
+            }
+            let chain = scope.chain().into_iter().map(|variables| {
+                let variables = variables
+                    .iter()
+                    .map(|(name, value)| format!("{name}={}", scope.display(value)));
+                variables.collect::<Vec<_>>().join(" ")
+            });
+            let mut inspected = chain.collect::<Vec<_>>();
+
+            // evaluated at the paused statement, so the parameter is visible
+            let value = scope.eval("greeting + name").expect("should evaluate");
+            inspected.push(scope.display(&value));
+            let unknown = scope.eval(r#"name + "unknown""#);
+            assert!(matches!(unknown, Err(crate::EvalError::Unknown)));
+
+            *self.0.lock().unwrap() = inspected;
+        }
+    }
+
+    let inspect = Inspect::default();
+    let (mut lox, _) = new_lox();
+    lox.set_observer(inspect.clone());
+
+    let source = indoc::indoc! {r#"
+        var greeting = "hi";
+        fun greet(name) {
+            print greeting + name;
+        }
+        greet("bob");
+    "#};
+    lox.eval(source).expect("should run");
+
+    let chain = inspect.0.lock().unwrap();
+    assert_eq!(chain[0], "name=bob");
+    let globals = &chain[chain.len() - 2];
+    assert!(globals.contains("greeting=hi"));
+    assert!(!globals.contains("clock"));
+    assert_eq!(chain.last().unwrap(), "hibob");
+}
+
+#[test]
+fn tracer_logs_statements_values_and_calls() {
+    use crate::Tracer;
+
+    let output = OutputBuffer::default();
+    let (mut lox, _) = new_lox();
+    lox.set_observer(Tracer::new(output.clone()));
+    lox.eval("fun f(x) { return x; }\nprint f(1 + 2);").unwrap();
+
+    let expected = indoc::indoc! {"
+        [1:1] statement
+        [2:1] statement
+        [2:7] value <fun f>
+        [2:9] value 1
+        [2:13] value 2
+        [2:9] value 3
+        [2:8] call f
+          [1:12] statement
+          [1:19] value 3
+        [2:8] return f
+        [2:7] value 3
+    "};
+    assert_eq!(output.text(), expected);
+}
+
+#[test]
+fn explainer_reduces_one_operation_at_a_time() {
+    use crate::Explainer;
+
+    let source = "fun f(x) { return -x; }\nprint 1 + 2 * f(3) == 0 or !nil;";
+    let output = OutputBuffer::default();
+    let (mut lox, _) = new_lox();
+    lox.set_observer(Explainer::new(output.clone(), source));
+    lox.eval(source).unwrap();
+
+    let expected = indoc::indoc! {"
+        [1:1] fun f(x) { return -x; }
+        [2:1] print 1 + 2 * f(3) == 0 or !nil;
+          [2:16] call f
+            [1:12] fun f(x) { return -x; }
+              [1:19] -(3) = -3
+          [2:16] return f
+          [2:13] 2 * -3 = -6
+          [2:9] 1 + -6 = -5
+          [2:20] -5 == 0 = false
+          [2:28] !(nil) = true
+          [2:25] false or true = true
+    "};
+    assert_eq!(output.text(), expected);
+}
+
+#[test]
+fn profiler_counts_calls_of_each_function() {
+    use crate::Profiler;
+
+    let profiler = Profiler::new();
+    let (mut lox, _) = new_lox();
+    lox.set_observer(profiler.clone());
+    lox.eval("fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }\nprint fib(10);")
+        .unwrap();
+
+    let report = profiler.report();
+    let calls = report
+        .functions
+        .iter()
+        .map(|function| (function.name.as_str(), function.calls));
+    assert_eq!(calls.collect::<Vec<_>>(), vec![("fib", 177)]);
+}
+
+#[test]
+fn coverage_counts_statements_and_branches() {
+    use crate::{Branch, Coverage};
+
+    let coverage = Coverage::new();
+    let (mut lox, _) = new_lox();
+    lox.set_observer(coverage.clone());
+
+    let source = indoc::indoc! {r#"
+        fun check(n) {
+            if (n > 1) {
+                print "big";
+            } else print "small";
+            if (n < 0) return;
+        }
+        check(2);
+        check(3);
+    "#};
+    lox.eval(source).expect("should run");
+
+    let report = coverage.report(source).expect("should parse");
+    let lines = report
+        .statements
+        .iter()
+        .map(|(loc, hits)| (loc.line, *hits));
+    assert_eq!(
+        lines.collect::<Vec<_>>(),
+        vec![
+            (1, 1),
+            (2, 2),
+            (3, 2),
+            (4, 0),
+            (5, 2),
+            (5, 0),
+            (7, 1),
+            (8, 1)
+        ]
+    );
+    assert_eq!(
+        report.branches,
+        vec![
+            Branch {
+                loc: Location::new(2, 5),
+                then: 2,
+                otherwise: 0
+            },
+            Branch {
+                loc: Location::new(5, 5),
+                then: 0,
+                otherwise: 2
+            },
+        ]
+    );
+    assert!(report.lcov("check.lox").contains("DA:4,0\nDA:5,2\n"));
+    assert!(report
+        .annotate(source)
+        .contains("    #####:    4:     } else print \"small\";\n        2:    5:"));
+}
+
+#[test]
+fn lox_runs_registered_tests() {
+    let (mut lox, stdout) = new_lox();
+
+    let source = indoc::indoc! {r#"
+        fun passes() { assertEqual([1, "a"], [1, "a"]); }
+        fun fails() {
+            assertTrue(1 < 2);
+            assertEqual(1 + 1, 3);
+        }
+        test("passes", passes);
+        test("fails", fails);
+    "#};
+    lox.eval(source).expect("should run");
+
+    let summary = lox.run_tests().expect("should run tests");
+    assert_eq!(
+        summary,
+        crate::TestSummary {
+            passed: 1,
+            failed: 1
+        }
+    );
+    assert_eq!(
+        stdout.text(),
+        indoc::indoc! {"
+            test passes ... ok
+            test fails ... FAILED
+                [4:16] RuntimeError: Assertion failed: expected 3, got 2
+            1 passed, 1 failed
+        "}
+    );
+
+    // the tests are forgotten once they ran
+    assert_eq!(lox.run_tests().unwrap(), crate::TestSummary::default());
+}
+
+#[test]
+fn memory_limits_are_runtime_errors() {
+    use crate::{LimitError, Limits};
+
+    let run = |limits: Limits, source: &str| {
+        let (mut lox, _) = new_lox_with(&Options {
+            limits,
+            ..Options::default()
+        });
+        match lox.eval(source) {
+            Err(LoxError::RuntimeError {
+                error: RuntimeError::LimitExceeded(_, err),
+                ..
+            }) => err,
+            result => panic!("should exceed the limit, got {result:?}"),
+        }
+    };
+
+    let doubling = r#"var s = "ab"; while (true) s = s + s;"#;
+    let limits = Limits {
+        max_string_len: Some(1000),
+        ..Limits::default()
+    };
+    assert!(matches!(run(limits, doubling), LimitError::StringLen(1000)));
+
+    let limits = Limits {
+        max_allocation: Some(1 << 16),
+        ..Limits::default()
+    };
+    assert!(matches!(run(limits, doubling), LimitError::Allocation(_)));
+
+    let growing = "var l = []; while (true) l.push(1);";
+    let limits = Limits {
+        max_collection_len: Some(100),
+        ..Limits::default()
+    };
+    assert!(matches!(
+        run(limits, growing),
+        LimitError::CollectionLen(100)
+    ));
+
+    let (mut lox, _) = new_lox();
+    lox.eval(r#"var s = "a" + "b";"#).expect("should run");
+    assert!(lox.allocated() >= 2);
+}
+
+#[test]
+fn lox_instances_share_prelude_without_state() {
+    use crate::Prelude;
+
+    let prelude = Prelude::new(
+        r#"
+        var items = [];
+        fun add(item) { items.push(item); return items.len(); }
+        "#,
+    )
+    .expect("should compile");
+
+    let mut first = Lox::with_prelude(&Options::default(), &prelude).expect("should run");
+    let mut second = Lox::with_prelude(&Options::default(), &prelude).expect("should run");
+
+    first
+        .eval(r#"add("a"); add("b"); var onlyFirst = "new string";"#)
+        .expect("should run");
+    let count = second.eval(r#"add("c");"#).expect("should run");
+    assert!(matches!(count, Value::Int(1)));
+
+    assert!(second.get_global("onlyFirst").is_none());
+    match first.get_global("onlyFirst") {
+        Some(Value::String(str)) => assert_eq!(&*str, "new string"),
+        value => panic!("expect a string, got {value:?}"),
+    }
+
+    // strings interned by each instance after the prelude don't collide
+    let second_value = second
+        .eval(r#"var other = "other"; other + "!";"#)
+        .expect("should run");
+    let first_value = first
+        .eval(r#"var another = "another"; another + "!";"#)
+        .expect("should run");
+    assert_eq!(second.display(&second_value).to_string(), "other!");
+    assert_eq!(first.display(&first_value).to_string(), "another!");
+}
+
+#[cfg(feature = "arc")]
+#[test]
+fn lox_runs_on_another_thread() {
+    let (mut lox, _) = new_lox();
+    lox.eval("var list = [1, 2]; fun sum() { return list[0] + list[1]; }")
+        .expect("should run");
+
+    let result = std::thread::spawn(move || lox.eval("sum();").expect("should run"))
+        .join()
+        .unwrap();
+    assert!(matches!(result, Value::Int(3)));
+}
+
+#[cfg(feature = "arc")]
+#[test]
+fn lox_runs_a_few_statements_per_poll() {
+    use std::future::Future;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::{Context, Poll, Wake, Waker};
+
+    /// Counts how many times the task asked to be polled again.
+    struct Wakes(AtomicUsize);
+
+    impl Wake for Wakes {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let (lox, stdout) = new_lox();
+    let source = "var sum = 0; for (var i = 0; i < 10; i = i + 1) sum = sum + i; print sum;";
+    let mut task = Box::pin(lox.run_async(source, 5));
+
+    let wakes = Arc::new(Wakes(AtomicUsize::new(0)));
+    let waker = Waker::from(Arc::clone(&wakes));
+    let mut cx = Context::from_waker(&waker);
+    let mut polls = 0;
+    let (lox, result) = loop {
+        polls += 1;
+        if let Poll::Ready(output) = task.as_mut().poll(&mut cx) {
+            break output;
+        }
+        while wakes.0.load(Ordering::SeqCst) < polls {
+            std::thread::yield_now();
+        }
+    };
+
+    assert!(matches!(result, Ok(crate::Outcome::Completed)));
+    assert!(polls > 4, "ran in {polls} polls");
+    assert_eq!(stdout.text(), "45\n");
+    assert!(matches!(lox.get_global("sum"), Some(Value::Int(45))));
+}
+
+#[cfg(feature = "arc")]
+#[test]
+fn values_are_shared_between_threads() {
+    let (mut lox, _) = new_lox();
+    let list = lox.eval("[1, 2, 3];").expect("should run");
+
+    std::thread::scope(|scope| {
+        for _ in 0..2 {
+            scope.spawn(|| match &list {
+                Value::List(list) => assert_eq!(list.borrow().len(), 3),
+                _ => panic!("should be a list"),
+            });
+        }
+    });
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn lox_converts_rust_data() {
+    use serde::{Deserialize, Serialize};
+
+    use crate::to_value;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Circle,
+        Rect { w: u32, h: u32 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Config {
+        name: String,
+        scale: f64,
+        tags: Vec<String>,
+        shape: Shape,
+        parent: Option<Box<Config>>,
+    }
+
+    let config = Config {
+        name: "main".to_owned(),
+        scale: 1.5,
+        tags: vec!["a".to_owned()],
+        shape: Shape::Rect { w: 2, h: 3 },
+        parent: None,
+    };
+
+    let (mut lox, _) = new_lox();
+    lox.set_global("config", to_value(&config).unwrap());
+    let source = indoc::indoc! {r#"
+        class Result {}
+        var result = Result();
+        result.name = config["name"] + "!";
+        result.scale = config["scale"] * 2;
+        result.tags = config["tags"];
+        result.tags.push("b");
+        result.shape = "Circle";
+        result.parent = config;
+    "#};
+    lox.eval(source).expect("should run");
+
+    let result: Config = lox.from_value(lox.get_global("result").unwrap()).unwrap();
+    let expected = Config {
+        name: "main!".to_owned(),
+        scale: 3.0,
+        tags: vec!["a".to_owned(), "b".to_owned()],
+        shape: Shape::Circle,
+        parent: Some(Box::new(Config {
+            tags: vec!["a".to_owned(), "b".to_owned()],
+            ..config
+        })),
+    };
+    assert_eq!(result, expected);
+
+    let err = lox.from_value::<Config>(Value::number(1.0)).unwrap_err();
+    assert!(err.to_string().starts_with("ConvertError:"));
+}
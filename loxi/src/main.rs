@@ -1,15 +1,35 @@
-use std::path::PathBuf;
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
 
-use clap::Parser;
-use loxi::{run_file, run_prompt, LoxError, RunMode};
+use clap::{Parser, Subcommand};
+use loxi::{
+    run_file, set_locale, Capabilities, Coverage, Dialect, Explainer, Limits, LintConfig, LintRule,
+    Locale, Lox, LoxError, Message, NumberStyle, Options, Outcome, Profiler, RunMode, Target,
+    Tracer,
+};
+
+mod bench;
+mod debugger;
 
 #[derive(Parser, Debug)]
 #[clap(
     name = "loxi",
-    about = "A Lox interpreter (tree-walk interpreter) written in Rust"
+    about = "A Lox interpreter (tree-walk interpreter) written in Rust",
+    args_conflicts_with_subcommands = true
 )]
 struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     pub source: Option<String>,
 
     #[arg(long, default_value_t = false, requires = "source", group = "dump")]
@@ -17,50 +37,779 @@ struct Args {
 
     #[arg(long, default_value_t = false, requires = "source", group = "dump")]
     pub dump_parse: bool,
+
+    /// Print the syntax tree as an indented tree with the kind and location of each node
+    #[arg(long, default_value_t = false, requires = "source", group = "dump")]
+    pub ast_tree: bool,
+
+    /// Print the syntax tree in another format instead of running the script, `dot` for Graphviz
+    #[arg(long, value_name = "FORMAT", requires = "source", group = "dump")]
+    pub emit: Option<Emit>,
+
+    /// Write the output of --emit to this file instead of stdout
+    #[arg(long, value_name = "FILE", requires = "emit")]
+    pub emit_file: Option<PathBuf>,
+
+    /// Print which statements and branches ran after the script finishes
+    #[arg(
+        long,
+        default_value_t = false,
+        requires = "source",
+        conflicts_with = "dump"
+    )]
+    pub coverage: bool,
+
+    /// Write the coverage in the lcov format to this file
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "source",
+        conflicts_with = "dump"
+    )]
+    pub lcov: Option<PathBuf>,
+
+    /// Write the source annotated with the number of times each line ran to this file
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "source",
+        conflicts_with = "dump"
+    )]
+    pub annotate: Option<PathBuf>,
+
+    /// Log every statement, expression value, and call to stderr while the script runs
+    #[arg(
+        long,
+        default_value_t = false,
+        requires = "source",
+        conflicts_with_all = ["dump", "coverage", "lcov"]
+    )]
+    pub trace: bool,
+
+    /// Write the trace to this file instead, implies --trace
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "source",
+        conflicts_with_all = ["dump", "coverage", "lcov"]
+    )]
+    pub trace_file: Option<PathBuf>,
+
+    /// Show each operation with its operands and result to stderr while the script runs
+    #[arg(
+        long,
+        default_value_t = false,
+        requires = "source",
+        conflicts_with_all = ["dump", "coverage", "lcov", "trace", "trace_file"]
+    )]
+    pub explain: bool,
+
+    /// Print the number of calls and the time spent in each function after the script finishes
+    #[arg(
+        long,
+        default_value_t = false,
+        requires = "source",
+        conflicts_with_all = ["dump", "coverage", "lcov", "trace", "trace_file", "explain"]
+    )]
+    pub profile: bool,
+
+    #[command(flatten)]
+    pub capabilities: CapabilityArgs,
+
+    /// Stop a script after it executes this many statements
+    #[arg(long, global = true, value_name = "STEPS")]
+    pub max_steps: Option<u64>,
+
+    /// Stop a script after it runs for this many milliseconds
+    #[arg(long, global = true, value_name = "MS")]
+    pub timeout: Option<u64>,
+
+    /// Stop a script after it allocates this many bytes of strings, lists, maps, and instances in
+    /// total, even if they are freed again
+    #[arg(long, global = true, value_name = "BYTES")]
+    pub max_allocation: Option<usize>,
+
+    /// The language of the error messages, `LOXI_LOCALE` is used if not set
+    #[arg(long, global = true, value_name = "LOCALE")]
+    pub locale: Option<Locale>,
+
+    /// How numbers are printed, `jlox` like the book's interpreter or `rust` without exponents
+    #[arg(long, global = true, value_name = "STYLE", default_value_t = NumberStyle::Jlox)]
+    pub number_style: NumberStyle,
+
+    /// Fail when dividing by zero instead of giving `inf` or `nan`
+    #[arg(long, global = true)]
+    pub strict_division: bool,
+
+    /// Enable every extension to the book's Lox, e.g. comparing strings with `<`
+    #[arg(long, global = true)]
+    pub extensions: bool,
+
+    /// Run the language of the book without any extension, e.g. lists or `switch`
+    #[arg(long, global = true, conflicts_with = "extensions")]
+    pub book: bool,
+
+    /// Show this many lines of the source before and after the line of each error
+    #[arg(long, global = true, value_name = "LINES", default_value_t = 0)]
+    pub context_lines: usize,
+
+    #[command(flatten)]
+    pub lint: LintArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run a script in an interactive debugger
+    Debug {
+        source: PathBuf,
+
+        #[command(flatten)]
+        capabilities: CapabilityArgs,
+    },
+
+    /// Start the REPL, the same one as running without a script
+    Repl {
+        /// Serve the REPL to the clients connecting to ADDR instead, either a TCP address like
+        /// `127.0.0.1:7070` or `unix:PATH` for a Unix domain socket
+        #[arg(long, value_name = "ADDR")]
+        listen: Option<String>,
+
+        /// Start with the globals saved in FILE, if it exists, and save them there when the REPL
+        /// ends
+        #[arg(long, value_name = "FILE")]
+        session: Option<PathBuf>,
+
+        #[command(flatten)]
+        capabilities: CapabilityArgs,
+    },
+
+    /// Run a script, then the tests it registers using `test(name, fn)`
+    Test {
+        source: PathBuf,
+
+        #[command(flatten)]
+        capabilities: CapabilityArgs,
+    },
+
+    /// Check a script for likely mistakes without running it
+    Lint {
+        source: PathBuf,
+
+        /// Don't check this rule, can be repeated
+        #[arg(long, value_name = "RULE")]
+        disable: Vec<LintRule>,
+
+        /// Only check this rule, can be repeated
+        #[arg(long, value_name = "RULE")]
+        only: Vec<LintRule>,
+    },
+
+    /// Print facts about a script found without running it
+    #[command(group(clap::ArgGroup::new("query").required(true)))]
+    Analyze {
+        source: PathBuf,
+
+        /// Print which functions, methods, and classes reference which, in the DOT format
+        #[arg(long, default_value_t = false, group = "query")]
+        callgraph: bool,
+
+        /// Print the declarations of the variables called NAME and where they are used
+        #[arg(long, value_name = "NAME", group = "query")]
+        refs: Option<String>,
+
+        /// Print the results as JSON instead
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+
+    /// Print the documentation of a script, from its `///` comments, as Markdown
+    Doc { source: PathBuf },
+
+    /// Print a script as an HTML page with syntax highlighting
+    Html { source: PathBuf },
+
+    /// Print a script without comments and unneeded whitespace
+    Minify {
+        source: PathBuf,
+
+        /// Also shorten the names of local variables and parameters
+        #[arg(long, default_value_t = false)]
+        rename: bool,
+    },
+
+    /// Translate a script into another language and print it
+    Transpile {
+        source: PathBuf,
+
+        /// The language to translate to
+        #[arg(long, value_name = "TARGET")]
+        target: Target,
+    },
+
+    /// Measure how fast scripts are lexed, parsed, and run
+    Bench {
+        #[command(subcommand)]
+        suite: BenchSuite,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum BenchSuite {
+    /// Time a few built-in workloads, a quick check for regressions without criterion
+    Internal {
+        /// Run each workload this many times, the fastest run is shown
+        #[arg(long, value_name = "N", default_value_t = 10)]
+        iterations: usize,
+    },
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Emit {
+    Dot,
+}
+
+impl FromStr for Emit {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "dot" => Ok(Emit::Dot),
+            _ => Err(format!("unknown format '{name}', expect one of: dot")),
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct CapabilityArgs {
+    /// Allow scripts to read and write files
+    #[arg(long, default_value_t = false)]
+    pub allow_fs: bool,
+
+    /// Allow scripts to read environment variables
+    #[arg(long, default_value_t = false)]
+    pub allow_env: bool,
+
+    /// Allow scripts to run other programs
+    #[arg(long, default_value_t = false)]
+    pub allow_run: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct LintArgs {
+    /// Check a script for likely mistakes before running it, like `loxi lint` does
+    #[arg(long, global = true)]
+    pub warnings: bool,
+
+    /// Don't warn about this rule, can be repeated. A single line can allow it with a
+    /// `// lint: allow(RULE)` comment on it or on the line before it
+    #[arg(long, global = true, value_name = "RULE")]
+    pub allow: Vec<LintRule>,
+
+    /// Report this rule as an error, which stops the script from running, can be repeated
+    #[arg(long, global = true, value_name = "RULE")]
+    pub deny: Vec<LintRule>,
+
+    /// Report every warning as an error, which stops the script from running
+    #[arg(long, global = true)]
+    pub deny_warnings: bool,
 }
 
+impl LintArgs {
+    /// The levels set by the flags, `None` if a script should run without being checked.
+    fn config(&self) -> Option<LintConfig> {
+        let checked =
+            self.warnings || self.deny_warnings || !self.allow.is_empty() || !self.deny.is_empty();
+        checked.then(|| {
+            let mut config = LintConfig::default();
+            self.apply(&mut config);
+            config
+        })
+    }
+
+    fn apply(&self, config: &mut LintConfig) {
+        for rule in &self.allow {
+            config.disable(*rule);
+        }
+        if self.deny_warnings {
+            config.deny_warnings();
+        }
+        for rule in &self.deny {
+            config.deny(*rule);
+        }
+    }
+}
+
+/// Every Lox call recurses on the native stack, so the program runs on a thread with a stack big
+/// enough for the default call depth limit.
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
 fn main() -> ExitCode {
     coredump::register_panic_handler().unwrap();
 
     let args = Args::parse();
+    thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || run(args))
+        .expect("Unable to spawn the interpreter thread")
+        .join()
+        .unwrap_or(ExitCode::FAILURE)
+}
+
+fn run(args: Args) -> ExitCode {
+    set_locale(args.locale.or_else(Locale::from_env).unwrap_or_default());
+
+    let capabilities = match &args.command {
+        Some(Command::Debug { capabilities, .. }) => capabilities,
+        Some(Command::Repl { capabilities, .. }) => capabilities,
+        Some(Command::Test { capabilities, .. }) => capabilities,
+        _ => &args.capabilities,
+    };
+    let options = Options {
+        capabilities: Capabilities {
+            fs: capabilities.allow_fs,
+            env: capabilities.allow_env,
+            run: capabilities.allow_run,
+        },
+        max_steps: args.max_steps,
+        timeout: args.timeout.map(Duration::from_millis),
+        limits: Limits {
+            max_allocation: args.max_allocation,
+            ..Limits::default()
+        },
+        strict_division: args.strict_division,
+        dialect: match (args.extensions, args.book) {
+            (true, _) => Dialect::extended(),
+            (_, true) => Dialect::book(),
+            _ => Dialect::default(),
+        },
+        number_style: args.number_style,
+        context_lines: args.context_lines,
+        lint: args.lint.config(),
+        ..Options::default()
+    };
+
+    // the first Ctrl-C stops the program gracefully, the second one forcefully in case the program
+    // is stuck somewhere that doesn't check for cancellation
+    let cancel = options.cancel.clone();
+    ctrlc::set_handler(move || {
+        if cancel.is_cancelled() {
+            std::process::exit(130);
+        }
+        cancel.cancel();
+    })
+    .expect("Unable to set Ctrl-C handler");
+
+    match args.command {
+        Some(Command::Debug { source, .. }) => {
+            return match check_file(&source) {
+                true => finish(debugger::run(source, &options)),
+                false => ExitCode::FAILURE,
+            };
+        }
+        Some(Command::Test { source, .. }) => {
+            return match check_file(&source) {
+                true => run_tests(&source, &options),
+                false => ExitCode::FAILURE,
+            };
+        }
+        Some(Command::Lint {
+            source,
+            disable,
+            only,
+        }) => {
+            let mut config = match only.is_empty() {
+                true => LintConfig::default(),
+                false => LintConfig::none(),
+            };
+            for rule in &only {
+                config.enable(*rule);
+            }
+            for rule in &disable {
+                config.disable(*rule);
+            }
+            args.lint.apply(&mut config);
+            return lint(&source, &config, &options);
+        }
+        Some(Command::Analyze {
+            source, refs, json, ..
+        }) => {
+            return match refs {
+                Some(name) => references(&source, &name, json, &options),
+                None => analyze(&source, json, &options),
+            }
+        }
+        Some(Command::Repl {
+            listen, session, ..
+        }) => return repl(listen.as_deref(), session.as_deref(), &options),
+        Some(Command::Doc { source }) => return doc(&source, &options),
+        Some(Command::Html { source }) => return html(&source, &options),
+        Some(Command::Minify { source, rename }) => return minify(&source, rename, &options),
+        Some(Command::Transpile { source, target }) => return transpile(&source, target, &options),
+        Some(Command::Bench {
+            suite: BenchSuite::Internal { iterations },
+        }) => return finish(bench::run(iterations, &options).map(|()| Outcome::Completed)),
+        None => (),
+    }
 
     match args.source {
         Some(source) => {
             let path = PathBuf::from(source);
-
-            if !path.exists() {
-                eprintln!("File not found: {:?}", path);
-                return ExitCode::FAILURE;
-            } else if !path.is_file() {
-                eprintln!("Not a file: {:?}", path);
+            if !check_file(&path) {
                 return ExitCode::FAILURE;
             }
 
-            let mode = match (args.dump_lex, args.dump_parse) {
-                (true, false) => RunMode::DumpLex,
-                (false, true) => RunMode::DumpParse,
+            if let Some(Emit::Dot) = args.emit {
+                return emit(path, RunMode::DumpDot, args.emit_file, &options);
+            }
+
+            let mode = match (args.dump_lex, args.dump_parse, args.ast_tree) {
+                (true, _, _) => RunMode::DumpLex,
+                (_, true, _) => RunMode::DumpParse,
+                (_, _, true) => RunMode::DumpTree,
                 _ => RunMode::Normal,
             };
 
-            if let Err(err) = run_file(path, mode) {
-                eprintln!("{err}");
-                return match err {
-                    LoxError::EmptyError => ExitCode::SUCCESS,
-                    LoxError::IoError(_) => ExitCode::FAILURE,
-                    LoxError::LexError(_) => ExitCode::from(65),
-                    LoxError::ParseError => ExitCode::from(65),
-                    LoxError::ResolveError => ExitCode::from(65),
-                    LoxError::RuntimeError => ExitCode::from(70),
-                };
+            if args.trace || args.trace_file.is_some() {
+                return run_with_trace(path, args.trace_file, &options);
+            }
+            if args.explain {
+                return run_with_explain(path, &options);
+            }
+            if args.profile {
+                return run_with_profile(path, &options);
+            }
+            match args.coverage || args.lcov.is_some() || args.annotate.is_some() {
+                true => run_with_coverage(path, args.coverage, args.lcov, args.annotate, &options),
+                false => finish(run_file(path, mode, &options)),
             }
-            ExitCode::SUCCESS
         }
-        None => {
-            if let Err(err) = run_prompt() {
-                eprintln!("{}", err);
+        None => repl(None, None, &options),
+    }
+}
+
+/// Dump the script in `mode` to `file`, or to stdout if not set.
+fn emit(path: PathBuf, mode: RunMode, file: Option<PathBuf>, options: &Options) -> ExitCode {
+    let mut lox = Lox::new(options);
+    if let Some(file) = file {
+        match fs::File::create(&file) {
+            Ok(out) => lox.set_stdout(out),
+            Err(err) => {
+                let args: [(&str, &dyn Display); 2] = [("path", &file.display()), ("error", &err)];
+                eprintln!("{}", Message::new("cli.write-failed", &args));
                 return ExitCode::FAILURE;
             }
+        }
+    }
+    finish(lox.run_file(path, mode))
+}
+
+/// The coverage is reported even if the script fails, since the statements before the failure did
+/// run.
+fn run_with_coverage(
+    path: PathBuf,
+    summary: bool,
+    lcov: Option<PathBuf>,
+    annotate: Option<PathBuf>,
+    options: &Options,
+) -> ExitCode {
+    let coverage = Coverage::new();
+    let mut lox = Lox::new(options);
+    lox.set_observer(coverage.clone());
+    let result = lox.run_file(&path, RunMode::Normal);
+
+    // the errors of the source were already reported by running it
+    let source = fs::read_to_string(&path).ok();
+    let report = source
+        .as_deref()
+        .and_then(|source| coverage.report(source).ok());
+    if let (Some(source), Some(report)) = (source, report) {
+        if summary {
+            eprint!("{report}");
+        }
+        if let Some(lcov) = lcov {
+            write_report(&lcov, report.lcov(&path.display().to_string()));
+        }
+        if let Some(annotate) = annotate {
+            write_report(&annotate, report.annotate(&source));
+        }
+    }
+
+    finish(result)
+}
+
+fn write_report(path: &Path, contents: String) {
+    if let Err(err) = fs::write(path, contents) {
+        let args: [(&str, &dyn Display); 2] = [("path", &path.display()), ("error", &err)];
+        eprintln!("{}", Message::new("cli.write-failed", &args));
+    }
+}
+
+/// Exit with failure if any test fails.
+fn run_tests(source: &Path, options: &Options) -> ExitCode {
+    let mut lox = Lox::new(options);
+    let result = lox
+        .run_file(source, RunMode::Normal)
+        .and_then(|_| lox.run_tests());
+
+    match result {
+        Ok(summary) if summary.failed == 0 => ExitCode::SUCCESS,
+        Ok(_) => ExitCode::FAILURE,
+        Err(err) => finish(Err(err)),
+    }
+}
+
+/// Exit with failure if there is any warning, so it can be used in scripts.
+fn lint(source: &Path, config: &LintConfig, options: &Options) -> ExitCode {
+    if !check_file(source) {
+        return ExitCode::FAILURE;
+    }
+
+    match Lox::new(options).lint_file(source, config) {
+        Ok(warnings) if warnings.is_empty() => ExitCode::SUCCESS,
+        Ok(_) => ExitCode::FAILURE,
+        Err(err) => finish(Err(err)),
+    }
+}
+
+fn analyze(source: &Path, json: bool, options: &Options) -> ExitCode {
+    if !check_file(source) {
+        return ExitCode::FAILURE;
+    }
+
+    match Lox::new(options).call_graph_file(source) {
+        Ok(graph) if json => {
+            print!("{}", graph.to_json());
             ExitCode::SUCCESS
         }
+        Ok(graph) => {
+            print!("{}", graph.to_dot());
+            ExitCode::SUCCESS
+        }
+        Err(err) => finish(Err(err)),
+    }
+}
+
+fn references(source: &Path, name: &str, json: bool, options: &Options) -> ExitCode {
+    if !check_file(source) {
+        return ExitCode::FAILURE;
+    }
+
+    let bindings = match Lox::new(options).bindings_file(source) {
+        Ok(bindings) => bindings,
+        Err(err) => return finish(Err(err)),
+    };
+    let references = bindings.find_references(name);
+    match json {
+        true => {
+            let references = references.iter().map(|reference| {
+                let (start, end) = (reference.span.start, reference.span.end);
+                format!(
+                    r#"{{"kind":"{}","line":{},"column":{},"end_column":{}}}"#,
+                    reference.kind, start.line, start.column, end.column
+                )
+            });
+            println!("[{}]", references.collect::<Vec<_>>().join(","));
+        }
+        false => references
+            .iter()
+            .for_each(|reference| println!("{reference}")),
+    }
+    ExitCode::SUCCESS
+}
+
+fn doc(source: &Path, options: &Options) -> ExitCode {
+    if !check_file(source) {
+        return ExitCode::FAILURE;
+    }
+
+    match Lox::new(options).doc_file(source) {
+        Ok(doc) => {
+            print!("{doc}");
+            ExitCode::SUCCESS
+        }
+        Err(err) => finish(Err(err)),
+    }
+}
+
+fn html(source: &Path, options: &Options) -> ExitCode {
+    if !check_file(source) {
+        return ExitCode::FAILURE;
+    }
+
+    match Lox::new(options).highlight_file(source) {
+        Ok(page) => {
+            print!("{page}");
+            ExitCode::SUCCESS
+        }
+        Err(err) => finish(Err(err)),
+    }
+}
+
+fn minify(source: &Path, rename: bool, options: &Options) -> ExitCode {
+    if !check_file(source) {
+        return ExitCode::FAILURE;
+    }
+
+    match Lox::new(options).minify_file(source, rename) {
+        Ok(program) => {
+            print!("{program}");
+            ExitCode::SUCCESS
+        }
+        Err(err) => finish(Err(err)),
+    }
+}
+
+fn transpile(source: &Path, target: Target, options: &Options) -> ExitCode {
+    if !check_file(source) {
+        return ExitCode::FAILURE;
+    }
+
+    match Lox::new(options).transpile_file(source, target) {
+        Ok(program) => {
+            print!("{program}");
+            ExitCode::SUCCESS
+        }
+        Err(err) => finish(Err(err)),
+    }
+}
+
+fn run_with_trace(path: PathBuf, file: Option<PathBuf>, options: &Options) -> ExitCode {
+    let tracer = match &file {
+        Some(file) => match fs::File::create(file) {
+            Ok(out) => Tracer::new(io::BufWriter::new(out)),
+            Err(err) => {
+                let args: [(&str, &dyn Display); 2] = [("path", &file.display()), ("error", &err)];
+                eprintln!("{}", Message::new("cli.write-failed", &args));
+                return ExitCode::FAILURE;
+            }
+        },
+        None => Tracer::new(io::stderr()),
+    };
+
+    let mut lox = Lox::new(options);
+    lox.set_observer(tracer);
+    let result = lox.run_file(&path, RunMode::Normal);
+
+    // the buffered trace file is flushed when the tracer is dropped
+    lox.remove_observer();
+    finish(result)
+}
+
+fn run_with_explain(path: PathBuf, options: &Options) -> ExitCode {
+    // failing to read the source is reported by running it
+    let source = fs::read_to_string(&path).unwrap_or_default();
+    let mut lox = Lox::new(options);
+    lox.set_observer(Explainer::new(io::stderr(), &source));
+    finish(lox.run_file(&path, RunMode::Normal))
+}
+
+/// Like coverage, the profile is printed even if the script fails.
+fn run_with_profile(path: PathBuf, options: &Options) -> ExitCode {
+    let profiler = Profiler::new();
+    let mut lox = Lox::new(options);
+    lox.set_observer(profiler.clone());
+    let result = lox.run_file(&path, RunMode::Normal);
+
+    eprint!("{}", profiler.report());
+    finish(result)
+}
+
+/// The session is only saved if it could be restored, so a broken session file is kept as it is.
+fn repl(listen: Option<&str>, session: Option<&Path>, options: &Options) -> ExitCode {
+    let mut lox = Lox::new(options);
+    if let Some(session) = session.filter(|session| session.exists()) {
+        let restored = fs::read(session)
+            .map_err(|err| err.to_string())
+            .and_then(|bytes| lox.restore(&bytes).map_err(|err| err.to_string()));
+        if let Err(err) = restored {
+            let args: [(&str, &dyn Display); 2] = [("path", &session.display()), ("error", &err)];
+            eprintln!("{}", Message::new("cli.restore-failed", &args));
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let result = match listen {
+        None => lox.repl(&mut io::stdout()),
+        Some(addr) => serve(&mut lox, addr),
+    };
+    if let Some(session) = session {
+        if let Err(err) = fs::write(session, lox.snapshot()) {
+            let args: [(&str, &dyn Display); 2] = [("path", &session.display()), ("error", &err)];
+            eprintln!("{}", Message::new("cli.write-failed", &args));
+        }
+    }
+
+    match result {
+        Ok(outcome) => exit_code(outcome),
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn serve(lox: &mut Lox, addr: &str) -> io::Result<Outcome> {
+    match addr.strip_prefix("unix:") {
+        #[cfg(unix)]
+        Some(path) => {
+            let listener = UnixListener::bind(path)?;
+            eprintln!("{}", Message::new("cli.listening", &[("addr", &addr)]));
+            let outcome = lox.serve_repl_unix(listener);
+            fs::remove_file(path)?;
+            outcome
+        }
+        #[cfg(not(unix))]
+        Some(_) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Unix domain sockets are not supported on this platform",
+        )),
+        None => {
+            let listener = TcpListener::bind(addr)?;
+            let addr = listener.local_addr()?;
+            eprintln!("{}", Message::new("cli.listening", &[("addr", &addr)]));
+            lox.serve_repl(listener)
+        }
+    }
+}
+
+fn check_file(path: &Path) -> bool {
+    if !path.exists() {
+        let path = format!("{path:?}");
+        eprintln!("{}", Message::new("cli.file-not-found", &[("path", &path)]));
+        false
+    } else if !path.is_file() {
+        let path = format!("{path:?}");
+        eprintln!("{}", Message::new("cli.not-a-file", &[("path", &path)]));
+        false
+    } else {
+        true
+    }
+}
+
+fn finish(result: Result<Outcome, LoxError>) -> ExitCode {
+    match result {
+        Ok(outcome) => exit_code(outcome),
+        Err(err) => {
+            eprintln!("{err}");
+            match err {
+                LoxError::EmptyError => ExitCode::SUCCESS,
+                LoxError::IoError(_) => ExitCode::FAILURE,
+                LoxError::LexError { .. } => ExitCode::from(65),
+                LoxError::ParseError { .. } => ExitCode::from(65),
+                LoxError::ResolveError { .. } => ExitCode::from(65),
+                LoxError::LintError { .. } => ExitCode::from(65),
+                LoxError::RuntimeError { .. } => ExitCode::from(70),
+                LoxError::Exit(code) => exit_code(Outcome::Exit(code)),
+            }
+        }
+    }
+}
+
+fn exit_code(outcome: Outcome) -> ExitCode {
+    match outcome {
+        Outcome::Completed => ExitCode::SUCCESS,
+
+        // only the lowest 8 bits are used as the exit status on unix
+        Outcome::Exit(code) => ExitCode::from(code as u8),
     }
 }
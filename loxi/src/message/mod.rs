@@ -0,0 +1,157 @@
+//! The text of every error and warning, looked up in the message pack of the current locale by
+//! its code. A message pack is a text file with a `code = message` line for each message, where
+//! `{name}` is replaced by the value of the placeholder called `name`. Messages missing from a pack
+//! fall back to English.
+
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use strum::IntoEnumIterator;
+
+#[cfg(test)]
+mod test;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, strum::EnumIter)]
+pub enum Locale {
+    #[default]
+    English,
+    Indonesian,
+}
+
+/// The index of the current locale in `Locale::iter`, shared by the whole process like the locale
+/// of the environment it comes from.
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+
+impl Locale {
+    pub fn name(self) -> &'static str {
+        match self {
+            Locale::English => "en",
+            Locale::Indonesian => "id",
+        }
+    }
+
+    /// The locale named by the `LOXI_LOCALE` environment variable, if it names a known one.
+    pub fn from_env() -> Option<Self> {
+        std::env::var("LOXI_LOCALE").ok()?.parse().ok()
+    }
+
+    fn pack(self) -> &'static str {
+        match self {
+            Locale::English => include_str!("en.txt"),
+            Locale::Indonesian => include_str!("id.txt"),
+        }
+    }
+
+    /// The message called `code` from the pack of this locale.
+    fn template(self, code: &str) -> Option<&'static str> {
+        self.pack()
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| line.split_once(" = "))
+            .find_map(|(key, template)| (key == code).then_some(template))
+    }
+}
+
+impl Display for Locale {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Accepts the name of a locale alone or with a region and encoding, like `id_ID.UTF-8`.
+impl FromStr for Locale {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let language = name.split(['_', '-', '.']).next().unwrap_or(name);
+        Locale::iter()
+            .find(|locale| locale.name() == language)
+            .ok_or_else(|| {
+                let locales = Locale::iter().map(Locale::name).collect::<Vec<_>>();
+                format!(
+                    "unknown locale '{name}', expect one of: {}",
+                    locales.join(", ")
+                )
+            })
+    }
+}
+
+/// The locale messages are written in, English by default.
+pub fn locale() -> Locale {
+    let current = CURRENT.load(Ordering::Relaxed);
+    Locale::iter().nth(current).unwrap_or_default()
+}
+
+pub fn set_locale(locale: Locale) {
+    let index = Locale::iter().position(|l| l == locale).unwrap_or(0);
+    CURRENT.store(index, Ordering::Relaxed);
+}
+
+/// A message of the current locale with its placeholders filled in.
+pub struct Message<'a> {
+    locale: Locale,
+    code: &'a str,
+    args: &'a [(&'a str, &'a dyn Display)],
+}
+
+impl<'a> Message<'a> {
+    pub fn new(code: &'a str, args: &'a [(&'a str, &'a dyn Display)]) -> Self {
+        Self::with_locale(locale(), code, args)
+    }
+
+    pub fn with_locale(
+        locale: Locale,
+        code: &'a str,
+        args: &'a [(&'a str, &'a dyn Display)],
+    ) -> Self {
+        Self { locale, code, args }
+    }
+}
+
+/// A placeholder without a value is written as is, and so is the code of a message that is in no
+/// pack.
+impl Display for Message<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let template = self.locale.template(self.code);
+        let mut rest = template
+            .or_else(|| Locale::English.template(self.code))
+            .unwrap_or(self.code);
+
+        while let Some(start) = rest.find('{') {
+            f.write_str(&rest[..start])?;
+            rest = &rest[start..];
+
+            let name = rest[1..].split_once('}').map(|(name, _)| name);
+            let value = name.and_then(|name| {
+                let arg = self.args.iter().find(|(arg, _)| *arg == name);
+                arg.map(|(_, value)| (name, value))
+            });
+            match value {
+                Some((name, value)) => {
+                    write!(f, "{value}")?;
+                    rest = &rest[name.len() + 2..];
+                }
+                None => {
+                    f.write_str("{")?;
+                    rest = &rest[1..];
+                }
+            }
+        }
+        f.write_str(rest)
+    }
+}
+
+/// Write the message called `code` in the current locale.
+pub(crate) fn write(
+    f: &mut Formatter<'_>,
+    code: &str,
+    args: &[(&str, &dyn Display)],
+) -> fmt::Result {
+    write!(f, "{}", Message::new(code, args))
+}
+
+/// The message called `code` in the current locale.
+pub(crate) fn format(code: &str, args: &[(&str, &dyn Display)]) -> String {
+    Message::new(code, args).to_string()
+}
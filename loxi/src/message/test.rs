@@ -0,0 +1,44 @@
+use std::collections::BTreeSet;
+use std::fmt::Display;
+
+use pretty_assertions::assert_eq;
+use strum::IntoEnumIterator;
+
+use super::{Locale, Message};
+use crate::lex::LexError;
+use crate::util::Location;
+
+/// The codes of a pack, each with the names of its placeholders.
+fn messages(locale: Locale) -> BTreeSet<(&'static str, BTreeSet<&'static str>)> {
+    let pack = locale.pack().lines().filter(|line| !line.starts_with('#'));
+    let messages = pack.filter_map(|line| line.split_once(" = "));
+    messages
+        .map(|(code, template)| {
+            let placeholders = template.split('{').skip(1);
+            let placeholders = placeholders.filter_map(|s| s.split_once('}').map(|(name, _)| name));
+            (code, placeholders.collect())
+        })
+        .collect()
+}
+
+#[test]
+fn every_pack_has_the_messages_of_the_english_one() {
+    let english = messages(Locale::English);
+    for locale in Locale::iter() {
+        assert_eq!(messages(locale), english, "pack of '{locale}'");
+    }
+}
+
+#[test]
+fn placeholders_are_filled_by_name() {
+    let args: [(&str, &dyn Display); 2] = [("expect", &"';'"), ("got", &"}")];
+    let message = Message::with_locale(Locale::Indonesian, "parse.expect", &args);
+    assert_eq!(message.to_string(), "Seharusnya '';'', ditemukan '}'");
+
+    let message = Message::with_locale(Locale::Indonesian, "no.such-code", &[]);
+    assert_eq!(message.to_string(), "no.such-code");
+
+    let err = LexError::UnknownToken(Location::new(1, 2), '@', '@' as u32);
+    assert_eq!(err.to_string(), "[1:2] Unknown token (@) [0x40]");
+    assert_eq!("id_ID.UTF-8".parse(), Ok(Locale::Indonesian));
+}
@@ -0,0 +1,496 @@
+//! Print a program back as Lox with as few characters as possible. Comments and whitespace are
+//! gone since they are not part of the AST, and the names of locals can be shortened using the
+//! distances found by the resolver. Functions and classes keep their names since they are shown
+//! when printed.
+
+use rustc_hash::{FxHashMap, FxHashSet};
+use strum::IntoEnumIterator;
+
+use crate::interp::interner::{Interner, Key};
+use crate::lex::token::Keyword;
+use crate::parse::expr::{Expr, ExprId, RefExpr, ValExpr};
+use crate::parse::stmt::{Stmt, StmtFunction};
+use crate::parse::{token, Program};
+use crate::resolve::ResolveMap;
+use crate::util::LoxToken;
+
+#[cfg(test)]
+mod test;
+
+/// Print `program` as compact Lox on a single line. With `rename`, locals and parameters get the
+/// shortest names that don't clash with any other variable.
+pub fn minify(
+    program: &Program,
+    resolve_map: &ResolveMap,
+    interner: &Interner,
+    rename: bool,
+) -> String {
+    let mut minifier = Minifier::new(interner, resolve_map, rename);
+    if rename {
+        // a dry run to find the names that are kept, so the shortened ones can avoid them
+        minifier.program(program);
+        minifier.reserved = minifier.kept.clone();
+        minifier
+            .reserved
+            .extend(Keyword::iter().map(|kw| kw.as_str().to_owned()));
+        minifier.out.clear();
+    }
+    minifier.program(program);
+    minifier.out.push('\n');
+    minifier.out
+}
+
+struct Minifier<'a> {
+    interner: &'a Interner,
+    resolve_map: &'a ResolveMap,
+    rename: bool,
+    out: String,
+
+    /// The printed names of the variables in each scope, in the same way as `Resolver` creates
+    /// them so the distance of a variable finds its scope.
+    scopes: Vec<FxHashMap<Key, String>>,
+
+    /// The names printed as they are written: globals, functions, and classes.
+    kept: FxHashSet<String>,
+
+    /// The names a shortened one can't be.
+    reserved: FxHashSet<String>,
+}
+
+impl<'a> Minifier<'a> {
+    fn new(interner: &'a Interner, resolve_map: &'a ResolveMap, rename: bool) -> Self {
+        Self {
+            interner,
+            resolve_map,
+            rename,
+            out: String::new(),
+            scopes: Vec::new(),
+            kept: FxHashSet::default(),
+            reserved: FxHashSet::default(),
+        }
+    }
+
+    fn program(&mut self, program: &Program) {
+        for stmt in program.statements.iter() {
+            self.stmt(stmt, false);
+        }
+    }
+
+    /// The statements of a case, which is a block without braces.
+    fn case_body(&mut self, body: &Stmt) {
+        if let Stmt::Block { statements, .. } = body {
+            self.scoped(|minifier| {
+                statements
+                    .iter()
+                    .for_each(|stmt| minifier.stmt(stmt, false))
+            });
+        }
+    }
+
+    /// A `closed` statement can't end with an `if` without `else`, since it's followed by the
+    /// `else` of an enclosing `if` that would be taken as its own.
+    fn stmt(&mut self, stmt: &Stmt, closed: bool) {
+        match stmt {
+            Stmt::Expr { expr } => {
+                self.expr(expr);
+                self.write(";");
+            }
+            Stmt::Print { expr, .. } => {
+                self.write("print");
+                self.expr(expr);
+                self.write(";");
+            }
+            Stmt::Var { name, init, .. } => {
+                self.write("var");
+                let name = self.declare(*name, self.rename);
+                self.write(&name);
+                if let Some(init) = init {
+                    self.write("=");
+                    self.expr(init);
+                }
+                self.write(";");
+            }
+            Stmt::Block { statements, .. } => match (statements.as_slice(), closed) {
+                // a `for` loop with an initializer is parsed into this
+                (
+                    [init @ (Stmt::Var { .. } | Stmt::Expr { .. }), Stmt::While {
+                        condition, body, ..
+                    }],
+                    false,
+                ) => {
+                    self.scoped(|minifier| {
+                        minifier.write("for(");
+                        minifier.stmt(init, false);
+                        minifier.for_loop(condition, body);
+                    });
+                }
+                _ => {
+                    self.write("{");
+                    self.scoped(|minifier| {
+                        statements
+                            .iter()
+                            .for_each(|stmt| minifier.stmt(stmt, false))
+                    });
+                    self.write("}");
+                }
+            },
+            Stmt::If {
+                condition,
+                then,
+                otherwise,
+                ..
+            } => {
+                self.write("if(");
+                self.expr(condition);
+                self.write(")");
+                match otherwise {
+                    Some(otherwise) => {
+                        self.stmt(then, true);
+                        self.write("else");
+                        self.stmt(otherwise, closed);
+                    }
+                    None => self.stmt(then, closed),
+                }
+            }
+            Stmt::While {
+                condition, body, ..
+            } => match closed {
+                true => {
+                    self.write("while(");
+                    self.expr(condition);
+                    self.write(")");
+                    self.stmt(body, closed);
+                }
+                false => {
+                    self.write("for(;");
+                    self.for_loop(condition, body);
+                }
+            },
+            Stmt::ForIn {
+                name,
+                iterable,
+                body,
+                ..
+            } => {
+                // the iterable is outside of the scope of the variable that precedes it
+                let iterable = self.detached(|minifier| minifier.expr(iterable));
+                self.write("for(var");
+                self.scoped(|minifier| {
+                    let name = minifier.declare(*name, minifier.rename);
+                    minifier.write(&name);
+                    minifier.write("in");
+                    minifier.write(&iterable);
+                    minifier.write(")");
+                    minifier.stmt(body, closed);
+                });
+            }
+            Stmt::Switch {
+                subject,
+                cases,
+                otherwise,
+                ..
+            } => {
+                self.write("switch(");
+                self.expr(subject);
+                self.write("){");
+                for (value, body) in cases {
+                    self.write("case");
+                    self.expr(value);
+                    self.write(":");
+                    self.case_body(body);
+                }
+                if let Some(otherwise) = otherwise {
+                    self.write("else:");
+                    self.case_body(otherwise);
+                }
+                self.write("}");
+            }
+            Stmt::Function { func } => {
+                self.write("fun");
+                let name = self.declare(func.name, false);
+                self.write(&name);
+                self.function(func);
+            }
+            Stmt::Return { value, .. } => {
+                self.write("return");
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+                self.write(";");
+            }
+            Stmt::Class { name, methods, .. } => {
+                self.write("class");
+                let name = self.declare(*name, false);
+                self.write(&name);
+                self.write("{");
+                self.scoped(|minifier| {
+                    minifier.declare(minifier.interner.keyword(Keyword::This), false);
+                    for method in methods.iter() {
+                        minifier.write(minifier.interner.resolve(method.name));
+                        minifier.function(method);
+                    }
+                });
+                self.write("}");
+            }
+        }
+    }
+
+    /// The rest of a `for` loop, after its initializer. The increment is the last statement of
+    /// the body as parsed, unless the body can't be a statement of its own.
+    fn for_loop(&mut self, condition: &Expr, body: &Stmt) {
+        let is_true = matches!(
+            condition,
+            Expr::ValExpr(ValExpr::Literal { value }, _) if value.tok == token::Literal::True
+        );
+        if !is_true {
+            self.expr(condition);
+        }
+        self.write(";");
+
+        match body {
+            Stmt::Block { statements, .. } => match statements.as_slice() {
+                [stmt, Stmt::Expr { expr }] if !is_declaration(stmt) => {
+                    self.scoped(|minifier| {
+                        minifier.expr(expr);
+                        minifier.write(")");
+                        minifier.stmt(stmt, false);
+                    });
+                }
+                _ => {
+                    self.write(")");
+                    self.stmt(body, false);
+                }
+            },
+            body => {
+                self.write(")");
+                self.stmt(body, false);
+            }
+        }
+    }
+
+    fn function(&mut self, func: &StmtFunction) {
+        self.scoped(|minifier| {
+            minifier.write("(");
+            for (i, param) in func.params.iter().enumerate() {
+                if i > 0 {
+                    minifier.write(",");
+                }
+                let param = minifier.declare(*param, minifier.rename);
+                minifier.write(&param);
+            }
+            minifier.write("){");
+            func.body.iter().for_each(|stmt| minifier.stmt(stmt, false));
+            minifier.write("}");
+        });
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::ValExpr(expr, _) => self.val_expr(expr),
+            Expr::RefExpr(expr, id) => self.ref_expr(expr, *id),
+        }
+    }
+
+    fn val_expr(&mut self, expr: &ValExpr) {
+        match expr {
+            ValExpr::Literal { value } => {
+                let literal = value.tok.display(self.interner).to_string();
+                self.write(&literal);
+            }
+            ValExpr::Unary { operator, right } => {
+                self.write(operator.tok.as_str());
+                self.expr(right);
+            }
+            ValExpr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                self.expr(left);
+                self.write(operator.tok.as_str());
+                self.expr(right);
+            }
+            ValExpr::Grouping { expr, .. } => {
+                self.write("(");
+                self.val_expr(expr);
+                self.write(")");
+            }
+            ValExpr::Logical { left, kind, right } => {
+                self.expr(left);
+                self.write(kind.tok.as_str());
+                self.expr(right);
+            }
+            ValExpr::Call { callee, args, .. } => {
+                self.expr(callee);
+                self.list("(", args, ")");
+            }
+            ValExpr::List { elements, .. } => self.list("[", elements, "]"),
+        }
+    }
+
+    /// A grouped reference has the id of the outermost grouping, like in `Resolver`.
+    fn ref_expr(&mut self, expr: &RefExpr, id: ExprId) {
+        match expr {
+            RefExpr::Variable { var } => {
+                let name = self.variable(var.tok.name, id);
+                self.write(&name);
+            }
+            RefExpr::Grouping { expr, .. } => {
+                self.write("(");
+                self.ref_expr(expr, id);
+                self.write(")");
+            }
+            RefExpr::Assignment { var, value } => {
+                let name = self.variable(var.tok.name, id);
+                self.write(&name);
+                self.write("=");
+                self.expr(value);
+            }
+            RefExpr::Get { object, prop } => {
+                self.expr(object);
+                self.write(".");
+                self.write(self.interner.resolve(prop.tok.name));
+            }
+            RefExpr::Set {
+                object,
+                prop,
+                value,
+            } => {
+                self.expr(object);
+                self.write(".");
+                self.write(self.interner.resolve(prop.tok.name));
+                self.write("=");
+                self.expr(value);
+            }
+            RefExpr::This { .. } => self.write("this"),
+            RefExpr::Index { object, index, .. } => {
+                self.expr(object);
+                self.write("[");
+                self.expr(index);
+                self.write("]");
+            }
+            RefExpr::IndexSet {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.expr(object);
+                self.write("[");
+                self.expr(index);
+                self.write("]=");
+                self.expr(value);
+            }
+        }
+    }
+
+    fn list(&mut self, open: &str, exprs: &[Expr], close: &str) {
+        self.write(open);
+        for (i, expr) in exprs.iter().enumerate() {
+            if i > 0 {
+                self.write(",");
+            }
+            self.expr(expr);
+        }
+        self.write(close);
+    }
+
+    /// The printed name of the variable referenced by the expression `id`.
+    fn variable(&mut self, name: Key, id: ExprId) -> String {
+        let scope = self
+            .resolve_map
+            .distance(id)
+            .and_then(|distance| self.scopes.len().checked_sub(distance + 1));
+        match scope.and_then(|scope| self.scopes[scope].get(&name)) {
+            Some(printed) => printed.clone(),
+            None => self.keep(name),
+        }
+    }
+
+    /// Declare `name` in the current scope and return its printed name. Globals are never
+    /// shortened since they can be used before they are declared.
+    fn declare(&mut self, name: Key, shorten: bool) -> String {
+        if self.scopes.is_empty() {
+            return self.keep(name);
+        }
+        let printed = match shorten {
+            true => self.shortest_name(),
+            false => self.keep(name),
+        };
+        let scope = self.scopes.last_mut().expect("scopes should not be empty");
+        scope.insert(name, printed.clone());
+        printed
+    }
+
+    fn keep(&mut self, name: Key) -> String {
+        let name = self.interner.resolve(name).to_owned();
+        self.kept.insert(name.clone());
+        name
+    }
+
+    /// The first name, in the order `a`, ..., `Z`, `aa`, `ab`, ..., that is neither reserved nor
+    /// the name of a visible variable.
+    fn shortest_name(&self) -> String {
+        let visible = self
+            .scopes
+            .iter()
+            .flat_map(|scope| scope.values())
+            .collect::<FxHashSet<_>>();
+        (0..)
+            .map(short_name)
+            .find(|name| !self.reserved.contains(name) && !visible.contains(name))
+            .expect("there are infinitely many names")
+    }
+
+    /// Write what `f` writes into a separate string instead.
+    fn detached(&mut self, f: impl FnOnce(&mut Self)) -> String {
+        let out = std::mem::take(&mut self.out);
+        f(self);
+        std::mem::replace(&mut self.out, out)
+    }
+
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(FxHashMap::default());
+        f(self);
+        self.scopes.pop();
+    }
+
+    /// Write `text`, separated from the previous one only if they would be read as one token.
+    fn write(&mut self, text: &str) {
+        let last = self.out.chars().next_back();
+        let next = text.chars().next();
+        if let (Some(last), Some(next)) = (last, next) {
+            let word = |ch: char| ch.is_alphanumeric() || ch == '_';
+            let merges = (word(last) && word(next))
+                || (last.is_ascii_digit() && next == '.')
+                || (matches!(last, '=' | '!' | '<' | '>') && next == '=')
+                || (last == '/' && next == '/');
+            if merges {
+                self.out.push(' ');
+            }
+        }
+        self.out.push_str(text);
+    }
+}
+
+fn is_declaration(stmt: &Stmt) -> bool {
+    matches!(
+        stmt,
+        Stmt::Var { .. } | Stmt::Function { .. } | Stmt::Class { .. }
+    )
+}
+
+/// The `n`th short name: a letter, then any number of letters and digits.
+fn short_name(mut n: usize) -> String {
+    const FIRST: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    const REST: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+    let mut name = vec![FIRST[n % FIRST.len()]];
+    n /= FIRST.len();
+    while n > 0 {
+        n -= 1;
+        name.push(REST[n % REST.len()]);
+        n /= REST.len();
+    }
+    String::from_utf8(name).expect("names are ascii")
+}
@@ -0,0 +1,43 @@
+use std::io;
+
+use pretty_assertions::assert_eq;
+
+use crate::{Lox, Options};
+
+fn minify(source: &str, rename: bool) -> String {
+    let mut lox = Lox::new(&Options::default());
+    lox.set_stderr(io::sink());
+    lox.minify(source, rename).expect("source should compile")
+}
+
+#[test]
+fn comments_and_whitespace_are_removed() {
+    let source = r#"
+// counts down
+for (var i = 3; i > 0; i = i - 1) {
+    print i and "left"; // inline
+}
+if (true) print 1 - -2; else { print "no"; }
+"#;
+    let expected =
+        "for(var i=3;i>0;i=i-1){print i and\"left\";}if(true)print 1--2;else{print\"no\";}\n";
+    assert_eq!(minify(source, false), expected);
+}
+
+#[test]
+fn locals_are_shortened_without_shadowing_used_names() {
+    let source = r#"
+var b = "global";
+fun outer(first, second) {
+    var total = first + second;
+    fun show() { print total + b; }
+    for (var item in [total]) print item;
+    return show;
+}
+"#;
+    let expected = concat!(
+        "var b=\"global\";fun outer(a,c){var d=a+c;fun show(){print d+b;}",
+        "for(var e in[d])print e;return show;}\n",
+    );
+    assert_eq!(minify(source, true), expected);
+}
@@ -48,6 +48,10 @@ pub enum ValExpr {
         args: Box<[Expr]>,
         loc: Location,
     },
+    List {
+        elements: Box<[Expr]>,
+        loc: Location,
+    },
 }
 
 /// Expression that produces a reference to `Value`
@@ -76,6 +80,17 @@ pub enum RefExpr {
     This {
         loc: Location,
     },
+    Index {
+        object: Box<Expr>,
+        index: Box<Expr>,
+        loc: Location,
+    },
+    IndexSet {
+        object: Box<Expr>,
+        index: Box<Expr>,
+        value: Box<Expr>,
+        loc: Location,
+    },
 }
 
 pub struct DisplayedExpr<'a, 'b> {
@@ -109,6 +124,14 @@ impl Expr {
         }
     }
 
+    /// Location of the start of the expression.
+    pub fn loc(&self) -> Location {
+        match self {
+            Expr::ValExpr(expr, _) => expr.loc(),
+            Expr::RefExpr(expr, _) => expr.loc(),
+        }
+    }
+
     pub fn display<'a, 'b>(&'a self, interner: &'b Interner) -> DisplayedExpr<'a, 'b> {
         DisplayedExpr {
             expr: self,
@@ -145,6 +168,10 @@ impl Expr {
         val_expr!(Call { callee, args, loc })
     }
 
+    pub fn list(elements: Box<[Expr]>, loc: Location) -> Self {
+        val_expr!(List { elements, loc })
+    }
+
     pub fn variable(var: TokLoc<token::Variable>) -> Self {
         ref_expr!(Variable { var })
     }
@@ -176,6 +203,19 @@ impl Expr {
     pub fn this(loc: Location) -> Self {
         Expr::RefExpr(RefExpr::This { loc }, ExprId::new())
     }
+
+    pub fn index(object: Box<Expr>, index: Box<Expr>, loc: Location) -> Self {
+        ref_expr!(Index { object, index, loc })
+    }
+
+    pub fn index_set(object: Box<Expr>, index: Box<Expr>, value: Box<Expr>, loc: Location) -> Self {
+        ref_expr!(IndexSet {
+            object,
+            index,
+            value,
+            loc
+        })
+    }
 }
 
 impl PartialEq for Expr {
@@ -208,6 +248,19 @@ impl ExprId {
 }
 
 impl ValExpr {
+    /// Location of the start of the expression.
+    pub fn loc(&self) -> Location {
+        match self {
+            ValExpr::Literal { value } => value.loc,
+            ValExpr::Unary { operator, .. } => operator.loc,
+            ValExpr::Binary { left, .. } => left.loc(),
+            ValExpr::Grouping { loc, .. } => *loc,
+            ValExpr::Logical { left, .. } => left.loc(),
+            ValExpr::Call { callee, .. } => callee.loc(),
+            ValExpr::List { loc, .. } => *loc,
+        }
+    }
+
     pub fn display<'a, 'b>(&'a self, interner: &'b Interner) -> DisplayedValExpr<'a, 'b> {
         DisplayedValExpr {
             expr: self,
@@ -217,6 +270,20 @@ impl ValExpr {
 }
 
 impl RefExpr {
+    /// Location of the start of the expression.
+    pub fn loc(&self) -> Location {
+        match self {
+            RefExpr::Variable { var } => var.loc,
+            RefExpr::Grouping { loc, .. } => *loc,
+            RefExpr::Assignment { var, .. } => var.loc,
+            RefExpr::Get { object, .. } => object.loc(),
+            RefExpr::Set { object, .. } => object.loc(),
+            RefExpr::This { loc } => *loc,
+            RefExpr::Index { object, .. } => object.loc(),
+            RefExpr::IndexSet { object, .. } => object.loc(),
+        }
+    }
+
     pub fn display<'a, 'b>(&'a self, interner: &'b Interner) -> DisplayedRefExpr<'a, 'b> {
         DisplayedRefExpr {
             expr: self,
@@ -279,6 +346,13 @@ impl Display for DisplayedValExpr<'_, '_> {
                 }
                 write!(f, "))")
             }
+            ValExpr::List { elements, .. } => {
+                write!(f, "(list")?;
+                for expr in elements {
+                    write!(f, " {}", expr.display(interner))?;
+                }
+                write!(f, ")")
+            }
         }
     }
 }
@@ -308,6 +382,24 @@ impl Display for DisplayedRefExpr<'_, '_> {
                 value.display(interner)
             ),
             RefExpr::This { .. } => write!(f, "(this)"),
+            RefExpr::Index { object, index, .. } => write!(
+                f,
+                "(index {} {})",
+                object.display(interner),
+                index.display(interner)
+            ),
+            RefExpr::IndexSet {
+                object,
+                index,
+                value,
+                ..
+            } => write!(
+                f,
+                "(index-set {} {} {})",
+                object.display(interner),
+                index.display(interner),
+                value.display(interner)
+            ),
         }
     }
 }
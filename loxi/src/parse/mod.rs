@@ -22,6 +22,7 @@
 //!                 | if_stmt
 //!                 | print_stmt
 //!                 | return_stmt
+//!                 | switch_stmt
 //!                 | while_stmt
 //!                 | block ;
 //!
@@ -37,13 +38,19 @@
 //!
 //! for_stmt    -> "for" "(" (var_decl | expr_stmt | ";")
 //!                 expression? ";"
-//!                 expression? ")" statement ;
+//!                 expression? ")" statement
+//!                 | "for" "(" "var" IDENTIFIER "in" expression ")" statement ;
 //!
 //! return_stmt -> "return" expression? ";" ;
 //!
+//! switch_stmt -> "switch" "(" expression ")" "{"
+//!                 ( "case" expression ":" declaration* )*
+//!                 ( "else" ":" declaration* )? "}" ;
+//!
 //! expression  -> assignment ;
 //!
 //! assignment  -> (call "." )? IDENTIFIER "=" assignment
+//!                 | call "[" expression "]" "=" assignment
 //!                 | logical_or ;
 //!
 //! logical_or  -> logical_and ( "or" logical_and )* ;
@@ -52,7 +59,15 @@
 //!
 //! equality    -> comparison ( ( "!=" | "==" ) comparison )* ;
 //!
-//! comparison  -> term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
+//! comparison  -> bit_or ( ( ">" | ">=" | "<" | "<=" ) bit_or )* ;
+//!
+//! bit_or      -> bit_xor ( "|" bit_xor )* ;
+//!
+//! bit_xor     -> bit_and ( "^" bit_and )* ;
+//!
+//! bit_and     -> shift ( "&" shift )* ;
+//!
+//! shift       -> term ( ( "<<" | ">>" ) term )* ;
 //!
 //! term        -> factor ( ( "-" | "+" ) factor )* ;
 //!
@@ -60,7 +75,7 @@
 //!
 //! unary       -> ( "!" | "-" ) unary | call ;
 //!
-//! call        -> primary ( "(" arguments? ")" | "." IDENTIFIER )* ;
+//! call        -> primary ( "(" arguments? ")" | "." IDENTIFIER | "[" expression "]" )* ;
 //!
 //! arguments   -> expression ( "," expression )* ;
 //!
@@ -70,16 +85,23 @@
 //!                 | "false"
 //!                 | "nil"
 //!                 | grouping
+//!                 | list
+//!                 | "print"
 //!                 | IDENTIFIER ;
 //!
 //! grouping    -> "(" expression ")"
+//!
+//! list        -> "[" arguments? "]"
 
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 use std::fmt::Display;
 use thiserror::Error;
 
+use crate::analyze::Span;
+use crate::dialect::Dialect;
 use crate::interp::interner::{Interner, Key};
 use crate::lex::{self, token as ltok};
+use crate::message;
 use crate::util::{Location, TokLoc};
 
 use expr::{Expr, RefExpr};
@@ -89,29 +111,32 @@ use macros::{is_tok, missing_delim, peek_no_eof, syntax_error};
 
 use self::stmt::StmtFunction;
 
+pub use self::reparse::TextEdit;
+pub use self::tree::render_tree;
+
 pub mod expr;
 pub mod stmt;
 pub mod token;
 
+mod reparse;
+mod tree;
+
 #[cfg(test)]
 mod test;
 
 #[derive(Debug, Error)]
 pub enum SyntaxError {
-    #[error("{loc} SyntaxError: Expect '{expect}', got '{real}'")]
     Expect {
         expect: &'static str,
         real: &'static str,
         loc: Location,
     },
 
-    #[error("{start} SyntaxError: Missing closing delimiter '{delim}'")]
     MissingDelim {
         delim: &'static str,
         start: Location,
     },
 
-    #[error("{loc} SyntaxError: Number of arguments exceed language limit ({num} exceed {limit})")]
     TooManyArguments {
         num: usize,
         limit: usize,
@@ -127,6 +152,34 @@ impl SyntaxError {
             SyntaxError::TooManyArguments { loc, .. } => *loc,
         }
     }
+
+    /// Syntax errors are only known by where they start, so the span is empty.
+    pub fn span(&self) -> Span {
+        Span::at(self.loc())
+    }
+
+    /// The message without the location, labelled as a `SyntaxError`.
+    pub fn message(&self) -> String {
+        let message = match self {
+            SyntaxError::Expect { expect, real, .. } => {
+                message::format("parse.expect", &[("expect", expect), ("got", real)])
+            }
+            SyntaxError::MissingDelim { delim, .. } => {
+                message::format("parse.missing-delimiter", &[("delimiter", delim)])
+            }
+            SyntaxError::TooManyArguments { num, limit, .. } => message::format(
+                "parse.too-many-arguments",
+                &[("count", num), ("limit", limit)],
+            ),
+        };
+        format!("SyntaxError: {message}")
+    }
+}
+
+impl Display for SyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.loc(), self.message())
+    }
 }
 
 #[derive(Debug)]
@@ -167,8 +220,25 @@ pub struct Parser {
     tokens: VecDeque<lex::Token>,
     errors: Vec<SyntaxError>,
     current: Option<lex::Token>,
+
+    /// Whether a missing `;` at the end of a statement is recorded instead of being an error.
+    recover_semicolons: bool,
+    missing_semicolons: Vec<Location>,
+
+    /// Whether a `;` where a statement starts is recorded and skipped instead of being an error.
+    recover_empty_statements: bool,
+    empty_statements: Vec<Location>,
+
+    /// The `///` comments not attached to a declaration yet, and the text of the attached ones
+    /// by the location of their declaration.
+    doc_comments: VecDeque<lex::DocComment>,
+    docs: BTreeMap<Location, String>,
+
+    /// The extensions to the syntax of the book that are parsed, the others are syntax errors.
+    dialect: Dialect,
 }
 
+#[derive(Clone)]
 pub struct Program {
     pub statements: Vec<Stmt>,
 }
@@ -187,9 +257,61 @@ impl Parser {
             tokens: VecDeque::new(),
             errors: Vec::new(),
             current: None,
+            recover_semicolons: false,
+            missing_semicolons: Vec::new(),
+            recover_empty_statements: false,
+            empty_statements: Vec::new(),
+            doc_comments: VecDeque::new(),
+            docs: BTreeMap::new(),
+            dialect: Dialect::default(),
         }
     }
 
+    /// Parse statements that don't end with `;` as if they do, e.g. for the linter to report all
+    /// of them at once. Their locations are available using `missing_semicolons` after parsing.
+    pub fn with_semicolon_recovery() -> Self {
+        Self {
+            recover_semicolons: true,
+            ..Self::new()
+        }
+    }
+
+    pub fn missing_semicolons(&self) -> &[Location] {
+        &self.missing_semicolons
+    }
+
+    /// Also skip empty statements, i.e. a lone `;`, e.g. for the linter to warn about them. Their
+    /// locations are available using `empty_statements` after parsing.
+    pub fn with_empty_statement_recovery(mut self) -> Self {
+        self.recover_empty_statements = true;
+        self
+    }
+
+    pub fn empty_statements(&self) -> &[Location] {
+        &self.empty_statements
+    }
+
+    /// Only parse the extensions enabled by `dialect`, `Dialect::default()` if not set.
+    pub fn with_dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Attach the `///` comments on the lines right before a `fun`, `class`, `var`, or method to
+    /// it as documentation. It's available using `docs` after parsing.
+    pub fn with_docs(comments: Vec<lex::DocComment>) -> Self {
+        Self {
+            doc_comments: VecDeque::from(comments),
+            ..Self::new()
+        }
+    }
+
+    /// The documentation by the location of the declaration, which is the location of the
+    /// statement for `fun`, `class`, and `var`, and of the function for a method.
+    pub fn docs(&self) -> &BTreeMap<Location, String> {
+        &self.docs
+    }
+
     pub fn parse(&mut self, tokens: Vec<lex::Token>) -> Result<Program, Vec<SyntaxError>> {
         self.tokens = VecDeque::from(tokens);
 
@@ -219,6 +341,7 @@ impl Parser {
                 is_tok!(Keyword::Else) => break,
                 is_tok!(Keyword::For) => break,
                 is_tok!(Keyword::While) => break,
+                is_tok!(Keyword::Switch) => break,
                 is_tok!(Keyword::Fun) => break,
                 is_tok!(Keyword::Print) => break,
                 is_tok!(Keyword::Return) => break,
@@ -231,6 +354,15 @@ impl Parser {
 
     /// this function only returns Err when it reaches EOF
     fn declaration(&mut self) -> Option<Stmt> {
+        self.skip_empty_statements();
+        let line = match self.peek() {
+            Ok(tok @ (is_tok!(Keyword::Var) | is_tok!(Keyword::Fun) | is_tok!(Keyword::Class))) => {
+                Some(tok.loc().line)
+            }
+            _ => None,
+        };
+        let doc = line.and_then(|line| self.take_doc(line));
+
         if let Ok(tok) = self.peek() {
             let stmt = match tok {
                 is_tok!(Keyword::Var) => {
@@ -249,7 +381,17 @@ impl Parser {
                 _ => self.statement(),
             };
             match stmt {
-                Ok(stmt) => Some(stmt),
+                Ok(stmt) => {
+                    let loc = match &stmt {
+                        Stmt::Var { loc, .. } | Stmt::Class { loc, .. } => Some(*loc),
+                        Stmt::Function { func } => Some(func.loc),
+                        _ => None,
+                    };
+                    if let (Some(loc), Some(doc)) = (loc, doc) {
+                        self.docs.insert(loc, doc);
+                    }
+                    Some(stmt)
+                }
                 Err(err) => {
                     match err {
                         ParseError::SyntaxError(err) => self.errors.push(err),
@@ -264,9 +406,35 @@ impl Parser {
         }
     }
 
+    /// The text of the `///` comments on the lines right before `line`. The ones before them are
+    /// dropped since they are not followed by a declaration.
+    fn take_doc(&mut self, line: usize) -> Option<String> {
+        let mut block = Vec::<lex::DocComment>::new();
+        while let Some(comment) = self.doc_comments.front() {
+            if comment.loc.line >= line {
+                break;
+            }
+            if block
+                .last()
+                .is_some_and(|last| last.loc.line + 1 != comment.loc.line)
+            {
+                block.clear();
+            }
+            block.extend(self.doc_comments.pop_front());
+        }
+
+        match block.last() {
+            Some(last) if last.loc.line + 1 == line => {
+                let lines = block.iter().map(|comment| comment.text.as_str());
+                Some(lines.collect::<Vec<_>>().join("\n"))
+            }
+            _ => None,
+        }
+    }
+
     fn class_declaration(&mut self, loc: Location) -> StmtResult {
-        let name = peek_no_eof! { self as ["<identifier>"]
-            if is_tok!(Literal::Identifier(str, _)) => *str,
+        let (name, name_loc) = peek_no_eof! { self as ["<identifier>"]
+            if is_tok!(Literal::Identifier(str, loc)) => (*str, *loc),
         }?;
         self.advance();
         peek_no_eof! { self as ["{"] if is_tok!(Punctuation::BraceLeft) => self.advance(), }?;
@@ -278,7 +446,11 @@ impl Parser {
                 Ok(is_tok!(Punctuation::BraceRight)) => break,
                 Ok(tok) => {
                     let loc = tok.loc();
+                    let doc = self.take_doc(loc.line);
                     methods.push(self.function_declaration(loc)?);
+                    if let Some(doc) = doc {
+                        self.docs.insert(loc, doc);
+                    }
                 }
             };
         }
@@ -286,18 +458,23 @@ impl Parser {
         peek_no_eof! { self as ["}"] if is_tok!(Punctuation::BraceRight) => self.advance(), }?;
 
         let methods = methods.into_boxed_slice();
-        Ok(Stmt::Class { loc, name, methods })
+        Ok(Stmt::Class {
+            loc,
+            name,
+            name_loc,
+            methods,
+        })
     }
 
     fn function_declaration(&mut self, loc: Location) -> Result<StmtFunction, ParseError> {
         let name = peek_no_eof! { self as ["<identifier>"]
-            if is_tok!(Literal::Identifier(name, _)) => *name,
+            if is_tok!(Literal::Identifier(name, loc)) => (*name, *loc),
         }?;
         self.advance();
 
         peek_no_eof! { self as ["("] if is_tok!(Punctuation::ParenLeft) => self.advance(), }?;
 
-        let mut params = Vec::<Key>::new();
+        let mut params = Vec::<(Key, Location)>::new();
 
         match self.peek() {
             Ok(is_tok!(Punctuation::ParenRight)) => {
@@ -305,8 +482,8 @@ impl Parser {
             }
             Ok(is_tok!(Literal::Identifier(_, _))) => loop {
                 match self.peek() {
-                    Ok(is_tok!(Literal::Identifier(name, _))) => {
-                        params.push(*name);
+                    Ok(is_tok!(Literal::Identifier(name, loc))) => {
+                        params.push((*name, *loc));
                         self.advance();
                     }
                     Ok(tok) => Err(syntax_error!(
@@ -349,16 +526,11 @@ impl Parser {
         }?;
 
         let body = match body {
-            Stmt::Block { statements } => statements.into_boxed_slice(),
+            Stmt::Block { statements, .. } => statements.into_boxed_slice(),
             _ => unreachable!(),
         };
 
-        Ok(StmtFunction::new(
-            name,
-            params.into_boxed_slice(),
-            body,
-            loc,
-        ))
+        Ok(StmtFunction::new(name, params, body, loc))
     }
 
     fn var_declaration(&mut self) -> StmtResult {
@@ -378,14 +550,13 @@ impl Parser {
             },
         }?;
 
-        let _ = peek_no_eof! { self as [";"]
-            if is_tok!(Punctuation::Semicolon) => self.advance(),
-        }?;
+        self.end_statement()?;
 
         Ok(Stmt::Var { loc, name, init })
     }
 
     fn statement(&mut self) -> StmtResult {
+        let dialect = self.dialect;
         match self.peek()? {
             is_tok!(Keyword::Print) => {
                 let loc = self.advance().unwrap().loc();
@@ -407,6 +578,10 @@ impl Parser {
                 let loc = self.advance().unwrap().loc();
                 self.for_statement(loc)
             }
+            is_tok!(Keyword::Switch) if dialect.switch => {
+                let loc = self.advance().unwrap().loc();
+                self.switch_statement(loc)
+            }
             is_tok!(Keyword::Return) => {
                 let loc = self.advance().unwrap().loc();
                 self.return_statement(loc)
@@ -439,6 +614,19 @@ impl Parser {
     fn for_statement(&mut self, loc: Location) -> StmtResult {
         peek_no_eof! { self as ["("] if is_tok!(Punctuation::ParenLeft) => self.advance(), }?;
 
+        // `var <identifier> in` can't be the start of a regular for loop
+        let is_in = |tok: Option<&lex::Token>| {
+            matches!(tok, Some(is_tok!(Literal::Identifier(name, _)))
+                if *name == Interner::special_key(ltok::Special::In))
+        };
+        if let (true, Some(is_tok!(Keyword::Var)), true) = (
+            self.dialect.for_in,
+            self.tokens.front(),
+            is_in(self.tokens.get(2)),
+        ) {
+            return self.for_in_statement(loc);
+        }
+
         let init = match self
             .peek()
             .map_err(|err| err.syntax_err("<var_stmt> or <expr_stmt"))?
@@ -476,6 +664,7 @@ impl Parser {
         let body = match increment {
             None => self.statement()?,
             Some(expr) => Stmt::Block {
+                loc,
                 statements: vec![self.statement()?, Stmt::Expr { expr }],
             },
         };
@@ -488,12 +677,34 @@ impl Parser {
 
         match init {
             Some(stmt) => Ok(Stmt::Block {
+                loc,
                 statements: vec![stmt, while_stmt],
             }),
             None => Ok(while_stmt),
         }
     }
 
+    fn for_in_statement(&mut self, loc: Location) -> StmtResult {
+        self.advance();
+        let (name, name_loc) = peek_no_eof! { self as ["<identifier>"]
+            if is_tok!(Literal::Identifier(name, loc)) => (*name, *loc),
+        }?;
+        self.advance();
+        self.advance();
+
+        let iterable = self.expression()?;
+        peek_no_eof! { self as [")"] if is_tok!(Punctuation::ParenRight) => self.advance(), }?;
+        let body = self.statement()?;
+
+        Ok(Stmt::ForIn {
+            loc,
+            name,
+            name_loc,
+            iterable,
+            body: body.boxed(),
+        })
+    }
+
     fn return_statement(&mut self, loc: Location) -> StmtResult {
         match self.peek() {
             Ok(tok) => {
@@ -501,9 +712,7 @@ impl Parser {
                     is_tok!(Punctuation::Semicolon) => None,
                     _ => Some(self.expression()?),
                 };
-                let _ = peek_no_eof! { self as [";"]
-                    if is_tok!(Punctuation::Semicolon) => self.advance(),
-                }?;
+                self.end_statement()?;
                 Ok(Stmt::Return { loc, value })
             }
             _ => Err(syntax_error!("<expression> or ;", "<eof>", loc)),
@@ -512,11 +721,13 @@ impl Parser {
 
     fn block(&mut self, start: Location) -> StmtResult {
         let mut statements = Vec::new();
+        let recover = self.recover_empty_statements;
 
         // this loop can only stop if EndOfFile or BraceRight encountered
         while let Ok(tok) = self.peek() {
             match tok {
                 is_tok!(Punctuation::BraceRight) => break,
+                is_tok!(Punctuation::Semicolon) if recover => self.skip_empty_statements(),
                 _ => match self.declaration() {
                     Some(decl) => statements.push(decl),
                     None => continue,
@@ -526,7 +737,10 @@ impl Parser {
 
         if let Ok(is_tok!(Punctuation::BraceRight)) = self.peek() {
             self.advance();
-            Ok(Stmt::Block { statements })
+            Ok(Stmt::Block {
+                loc: start,
+                statements,
+            })
         } else {
             Err(missing_delim!("}", start))
         }
@@ -534,20 +748,52 @@ impl Parser {
 
     fn expression_statement(&mut self) -> StmtResult {
         let expr = self.expression().map_err(|e| e.syntax_err(";"))?;
-        let _ = peek_no_eof! { self as [";"]
-            if is_tok!(Punctuation::Semicolon) => self.advance(),
-        }?;
+        self.end_statement()?;
         Ok(Stmt::Expr { expr })
     }
 
+    /// Consume the `;` that ends a statement.
+    fn skip_empty_statements(&mut self) {
+        while self.recover_empty_statements {
+            let Ok(tok @ is_tok!(Punctuation::Semicolon)) = self.peek() else {
+                break;
+            };
+            let loc = tok.loc();
+            self.empty_statements.push(loc);
+            self.advance();
+        }
+    }
+
+    fn end_statement(&mut self) -> Result<(), ParseError> {
+        let result = peek_no_eof! { self as [";"]
+            if is_tok!(Punctuation::Semicolon) => {
+                self.advance();
+            },
+        };
+        match result {
+            Ok(()) => Ok(()),
+            Err(ParseError::SyntaxError(SyntaxError::Expect {
+                expect: ";", loc, ..
+            })) if self.recover_semicolons => {
+                self.missing_semicolons.push(loc);
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
     fn if_statement(&mut self, loc: Location) -> StmtResult {
         peek_no_eof! { self as ["("] if is_tok!(Punctuation::ParenLeft) => self.advance(), }?;
         let condition = self.expression()?;
         peek_no_eof! { self as [")"] if is_tok!(Punctuation::ParenRight) => self.advance(), }?;
 
         let then = self.statement()?;
-        let otherwise = match self.peek() {
-            Ok(is_tok!(Keyword::Else)) => {
+
+        // `else:` is the else case of an enclosing switch
+        let otherwise = match (self.tokens.front(), self.tokens.get(1)) {
+            (Some(is_tok!(Keyword::Else)), next)
+                if !matches!(next, Some(is_tok!(Punctuation::Colon))) =>
+            {
                 self.advance();
                 Some(self.statement()?)
             }
@@ -562,6 +808,73 @@ impl Parser {
         })
     }
 
+    fn switch_statement(&mut self, loc: Location) -> StmtResult {
+        peek_no_eof! { self as ["("] if is_tok!(Punctuation::ParenLeft) => self.advance(), }?;
+        let subject = self.expression()?;
+        peek_no_eof! { self as [")"] if is_tok!(Punctuation::ParenRight) => self.advance(), }?;
+        let start = peek_no_eof! { self as ["{"]
+            if is_tok!(Punctuation::BraceLeft) => self.advance().unwrap().loc(),
+        }?;
+
+        let mut cases = Vec::new();
+        let mut otherwise = None;
+
+        // the else case, if any, is the last one
+        loop {
+            let expected = match otherwise {
+                None => "case, else, or }",
+                Some(_) => "}",
+            };
+            match self.peek() {
+                Ok(is_tok!(Punctuation::BraceRight)) => {
+                    self.advance();
+                    break;
+                }
+                Ok(is_tok!(Keyword::Case)) if otherwise.is_none() => {
+                    let case_loc = self.advance().unwrap().loc();
+                    let value = self.expression()?;
+                    cases.push((value, self.case_body(case_loc)?));
+                }
+                Ok(is_tok!(Keyword::Else)) if otherwise.is_none() => {
+                    let else_loc = self.advance().unwrap().loc();
+                    otherwise = Some(self.case_body(else_loc)?.boxed());
+                }
+                Ok(tok) => Err(syntax_error!(expected, tok.static_str(), tok.loc()))?,
+                Err(_) => Err(missing_delim!("}", start))?,
+            }
+        }
+
+        Ok(Stmt::Switch {
+            loc,
+            subject,
+            cases: cases.into_boxed_slice(),
+            otherwise,
+        })
+    }
+
+    /// The statements after the `:` of a case up to the next case or the end of the switch.
+    fn case_body(&mut self, start: Location) -> StmtResult {
+        peek_no_eof! { self as [":"] if is_tok!(Punctuation::Colon) => self.advance(), }?;
+
+        let mut statements = Vec::new();
+        while let Ok(tok) = self.peek() {
+            match tok {
+                is_tok!(Keyword::Case)
+                | is_tok!(Keyword::Else)
+                | is_tok!(Punctuation::BraceRight) => break,
+                _ => match self.declaration() {
+                    Some(decl) => statements.push(decl),
+                    None => continue,
+                },
+            }
+        }
+
+        Ok(Stmt::Block {
+            loc: start,
+            statements,
+        })
+    }
+
     fn expression(&mut self) -> ExprResult {
         self.assignment()
     }
@@ -578,14 +891,20 @@ impl Parser {
                     Expr::RefExpr(lvalue, _) => match lvalue {
                         RefExpr::Variable { var } => Ok(Expr::assignment(var, value).boxed()),
                         RefExpr::Get { object, prop } => Ok(Expr::set(object, prop, value).boxed()),
+                        RefExpr::Index { object, index, loc } => {
+                            Ok(Expr::index_set(object, index, value, loc).boxed())
+                        }
                         RefExpr::Grouping { .. } => Err(syntax_error!("<lvalue>", "<group>", loc)),
 
                         // TODO: use better error message
-                        RefExpr::This { loc } => Err(syntax_error!("<lvalue>", "<this keyword>", loc)),
+                        RefExpr::This { loc } => {
+                            Err(syntax_error!("<lvalue>", "<this keyword>", loc))
+                        }
 
                         // RefExpr::Grouping should protect these cases
                         RefExpr::Assignment { .. } => unreachable!(),
                         RefExpr::Set { .. } => unreachable!(),
+                        RefExpr::IndexSet { .. } => unreachable!(),
                     },
                     Expr::ValExpr(_, _) => Err(syntax_error!("<lvalue>", "<rvalue>", loc)),
                 }
@@ -613,7 +932,26 @@ impl Parser {
     }
 
     fn comparison(&mut self) -> ExprResult {
-        self.binary(conv::to_comparison, Self::term)
+        match self.dialect.bitwise {
+            true => self.binary(conv::to_comparison, Self::bit_or),
+            false => self.binary(conv::to_comparison, Self::term),
+        }
+    }
+
+    fn bit_or(&mut self) -> ExprResult {
+        self.binary(conv::to_bit_or, Self::bit_xor)
+    }
+
+    fn bit_xor(&mut self) -> ExprResult {
+        self.binary(conv::to_bit_xor, Self::bit_and)
+    }
+
+    fn bit_and(&mut self) -> ExprResult {
+        self.binary(conv::to_bit_and, Self::shift)
+    }
+
+    fn shift(&mut self) -> ExprResult {
+        self.binary(conv::to_shift, Self::term)
     }
 
     fn term(&mut self) -> ExprResult {
@@ -634,6 +972,7 @@ impl Parser {
     }
 
     fn call(&mut self) -> ExprResult {
+        let dialect = self.dialect;
         let mut expr = self.primary()?;
 
         loop {
@@ -653,6 +992,16 @@ impl Parser {
                     expr = Expr::get(expr, tok).boxed();
                     Ok(())
                 }
+                is_tok!(Punctuation::BracketLeft) if dialect.lists => {
+                    let loc = self.advance().unwrap().loc();
+                    let index = self.expression().map_err(|e| e.missing_delim("]", loc))?;
+                    match self.peek().map_err(|e| e.missing_delim("]", loc))? {
+                        is_tok!(Punctuation::BracketRight) => self.advance(),
+                        tok => Err(syntax_error!("]", tok.static_str(), tok.loc()))?,
+                    };
+                    expr = Expr::index(expr, index, loc).boxed();
+                    Ok(())
+                }
                 _ => break,
             }?;
         }
@@ -691,6 +1040,7 @@ impl Parser {
     }
 
     fn primary(&mut self) -> ExprResult {
+        let dialect = self.dialect;
         let curr = self.advance().expect("Unexpected end of file");
         let loc = curr.loc();
 
@@ -705,8 +1055,12 @@ impl Parser {
             is_tok!(Keyword::Nil) => lit(Lit::Nil),
             is_tok!(Keyword::This) => Expr::this(loc),
 
+            // `print` is a statement, but also a native so it can be used in expressions
+            is_tok!(Keyword::Print) => var(Interner::keyword_key(ltok::Keyword::Print)),
+
             is_tok!(Literal::String(str, _)) => lit(Lit::String(*str)),
             is_tok!(Literal::Number(num, _)) => lit(Lit::Number(*num)),
+            is_tok!(Literal::Int(int, _)) => lit(Lit::Int(*int)),
             is_tok!(Literal::Identifier(name, _)) => var(*name),
 
             is_tok!(Punctuation::ParenLeft) => {
@@ -721,6 +1075,11 @@ impl Parser {
                 }
             }
 
+            is_tok!(Punctuation::BracketLeft) if dialect.lists => {
+                let elements = self.list_elements(loc)?;
+                Expr::list(elements, loc)
+            }
+
             lex::Token::Eof(_) => return Err(ParseError::EndOfFile(loc)),
             _ => return Err(syntax_error!("<expression>", curr.static_str(), loc)),
         };
@@ -728,6 +1087,23 @@ impl Parser {
         Ok(expr.boxed())
     }
 
+    fn list_elements(&mut self, start: Location) -> Result<Box<[Expr]>, ParseError> {
+        let mut elements = Vec::new();
+        loop {
+            match self.peek().map_err(|e| e.missing_delim("]", start))? {
+                is_tok!(Punctuation::BracketRight) => break,
+                _ => elements.push(*self.expression().map_err(|e| e.missing_delim("]", start))?),
+            }
+            match self.peek().map_err(|e| e.missing_delim("]", start))? {
+                is_tok!(Punctuation::Comma) => self.advance(),
+                is_tok!(Punctuation::BracketRight) => break,
+                tok => Err(syntax_error!(", or ]", tok.static_str(), tok.loc()))?,
+            };
+        }
+        self.advance();
+        Ok(elements.into_boxed_slice())
+    }
+
     fn binary<F1, F2>(&mut self, curr: F1, inner: F2) -> ExprResult
     where
         F1: Fn(&lex::Token) -> Option<TokLoc<token::BinaryOp>>,
@@ -801,6 +1177,51 @@ mod conv {
         })
     }
 
+    pub fn to_bit_or(tok: &lex::Token) -> Option<TokLoc<token::BinaryOp>> {
+        let new_tok = match tok {
+            is_tok!(Operator::Pipe) => token::BinaryOp::BitOr,
+            _ => return None,
+        };
+        Some(TokLoc {
+            tok: new_tok,
+            loc: tok.loc(),
+        })
+    }
+
+    pub fn to_bit_xor(tok: &lex::Token) -> Option<TokLoc<token::BinaryOp>> {
+        let new_tok = match tok {
+            is_tok!(Operator::Caret) => token::BinaryOp::BitXor,
+            _ => return None,
+        };
+        Some(TokLoc {
+            tok: new_tok,
+            loc: tok.loc(),
+        })
+    }
+
+    pub fn to_bit_and(tok: &lex::Token) -> Option<TokLoc<token::BinaryOp>> {
+        let new_tok = match tok {
+            is_tok!(Operator::Ampersand) => token::BinaryOp::BitAnd,
+            _ => return None,
+        };
+        Some(TokLoc {
+            tok: new_tok,
+            loc: tok.loc(),
+        })
+    }
+
+    pub fn to_shift(tok: &lex::Token) -> Option<TokLoc<token::BinaryOp>> {
+        let new_tok = match tok {
+            is_tok!(Operator::LessLess) => token::BinaryOp::ShiftLeft,
+            is_tok!(Operator::GreaterGreater) => token::BinaryOp::ShiftRight,
+            _ => return None,
+        };
+        Some(TokLoc {
+            tok: new_tok,
+            loc: tok.loc(),
+        })
+    }
+
     pub fn to_term(tok: &lex::Token) -> Option<TokLoc<token::BinaryOp>> {
         let new_tok = match tok {
             is_tok!(Operator::Plus) => token::BinaryOp::Add,
@@ -0,0 +1,338 @@
+use unicode_width::UnicodeWidthChar;
+
+use crate::interp::interner::Interner;
+use crate::lex::{Lexer, ScanResult};
+use crate::util::Location;
+
+use super::expr::{Expr, RefExpr, ValExpr};
+use super::stmt::{Stmt, StmtFunction};
+use super::{Parser, Program};
+
+/// A change to a source: the text from `start` up to `end` is replaced with `text`. The locations
+/// are the same as the ones reported for errors, counted from 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextEdit {
+    pub start: Location,
+    pub end: Location,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub(crate) fn apply(&self, source: &mut String) {
+        let start = offset(source, self.start);
+        let end = offset(source, self.end).max(start);
+        source.replace_range(start..end, &self.text);
+    }
+}
+
+impl Program {
+    /// Parse again only the top-level statements touched by `edit`, keeping the others. `source`
+    /// is the source before the edit.
+    ///
+    /// Returns false, leaving the program as it was, when the touched statements don't parse on
+    /// their own, e.g. when the edit opens a block that a later statement closes. The whole
+    /// source has to be parsed again then.
+    pub fn reparse(&mut self, source: &str, edit: &TextEdit, interner: &mut Interner) -> bool {
+        let starts = self.statements.iter().map(start).collect::<Vec<_>>();
+        if starts.is_empty() {
+            return false;
+        }
+        let clean = |i: usize| starts_line(source, starts[i]);
+
+        // the statements before `first` end before the edit, the source is lexed again from a
+        // line that starts with a statement
+        let mut first = starts
+            .iter()
+            .skip(1)
+            .take_while(|s| **s <= edit.start)
+            .count();
+        while first > 0 && !clean(first) {
+            first -= 1;
+        }
+
+        // the edit may be before the first statement, e.g. in a comment
+        let line = match first {
+            0 => 1,
+            _ => starts[first].line,
+        };
+
+        // statements on the lines after the edit are kept, only their lines change
+        let last = (first + 1..starts.len())
+            .find(|i| starts[*i].line > edit.end.line && clean(*i))
+            .unwrap_or(starts.len());
+
+        let old_lines = edit.end.line - edit.start.line;
+        let new_lines = edit.text.matches('\n').count();
+        let lines = new_lines as isize - old_lines as isize;
+
+        let region_start = line_offset(source, line);
+        let region_end = match starts.get(last) {
+            Some(start) => line_offset(source, start.line),
+            None => source.len(),
+        };
+        let mut region = source[region_start..region_end].to_owned();
+        let region_edit = TextEdit {
+            start: shift_up(edit.start, line),
+            end: shift_up(edit.end, line),
+            text: edit.text.clone(),
+        };
+        region_edit.apply(&mut region);
+
+        let ScanResult { tokens, errors, .. } =
+            Lexer::new(&region, interner).starting_at(line).scan();
+        if !errors.is_empty() {
+            return false;
+        }
+        let Ok(program) = Parser::new().parse(tokens) else {
+            return false;
+        };
+
+        let mut kept = self.statements.split_off(last);
+        kept.iter_mut().for_each(|stmt| shift_stmt(stmt, lines));
+        self.statements.truncate(first);
+        self.statements.extend(program.statements);
+        self.statements.extend(kept);
+        true
+    }
+}
+
+/// Where the first token of a top-level statement is.
+fn start(stmt: &Stmt) -> Location {
+    match stmt {
+        Stmt::Block { loc, .. } => *loc,
+        stmt => stmt.loc().expect("only blocks have no location"),
+    }
+}
+
+/// Whether `loc` is the first thing on its line, so the line doesn't start inside a string.
+fn starts_line(source: &str, loc: Location) -> bool {
+    let line = &source[line_offset(source, loc.line)..];
+    let indent = line.chars().take_while(|c| *c != '\n' && c.is_whitespace());
+    let column = indent.map(|c| c.width().unwrap_or(0)).sum::<usize>() + 1;
+    column == loc.column
+}
+
+/// The location relative to the line `line`, which becomes the first line.
+fn shift_up(loc: Location, line: usize) -> Location {
+    Location {
+        line: loc.line + 1 - line,
+        ..loc
+    }
+}
+
+fn line_offset(source: &str, line: usize) -> usize {
+    match line {
+        0 | 1 => 0,
+        _ => source
+            .match_indices('\n')
+            .nth(line - 2)
+            .map_or(source.len(), |(i, _)| i + 1),
+    }
+}
+
+/// The byte offset of `loc`, clamped to the end of its line.
+fn offset(source: &str, loc: Location) -> usize {
+    let start = line_offset(source, loc.line);
+    let mut column = 1;
+    for (i, c) in source[start..].char_indices() {
+        if c == '\n' || column >= loc.column {
+            return start + i;
+        }
+        column += c.width().unwrap_or(0);
+    }
+    source.len()
+}
+
+fn shift(loc: &mut Location, lines: isize) {
+    loc.line = loc.line.saturating_add_signed(lines);
+}
+
+fn shift_stmt(stmt: &mut Stmt, lines: isize) {
+    match stmt {
+        Stmt::Expr { expr } => shift_expr(expr, lines),
+        Stmt::Print { loc, expr } => {
+            shift(loc, lines);
+            shift_expr(expr, lines);
+        }
+        Stmt::Var { loc, init, .. } => {
+            shift(loc, lines);
+            if let Some(init) = init {
+                shift_expr(init, lines);
+            }
+        }
+        Stmt::Block { loc, statements } => {
+            shift(loc, lines);
+            statements
+                .iter_mut()
+                .for_each(|stmt| shift_stmt(stmt, lines));
+        }
+        Stmt::If {
+            loc,
+            condition,
+            then,
+            otherwise,
+        } => {
+            shift(loc, lines);
+            shift_expr(condition, lines);
+            shift_stmt(then, lines);
+            if let Some(otherwise) = otherwise {
+                shift_stmt(otherwise, lines);
+            }
+        }
+        Stmt::While {
+            loc,
+            condition,
+            body,
+        } => {
+            shift(loc, lines);
+            shift_expr(condition, lines);
+            shift_stmt(body, lines);
+        }
+        Stmt::ForIn {
+            loc,
+            name_loc,
+            iterable,
+            body,
+            ..
+        } => {
+            shift(loc, lines);
+            shift(name_loc, lines);
+            shift_expr(iterable, lines);
+            shift_stmt(body, lines);
+        }
+        Stmt::Switch {
+            loc,
+            subject,
+            cases,
+            otherwise,
+        } => {
+            shift(loc, lines);
+            shift_expr(subject, lines);
+            for (value, body) in cases.iter_mut() {
+                shift_expr(value, lines);
+                shift_stmt(body, lines);
+            }
+            if let Some(otherwise) = otherwise {
+                shift_stmt(otherwise, lines);
+            }
+        }
+        Stmt::Function { func } => shift_function(func, lines),
+        Stmt::Return { loc, value } => {
+            shift(loc, lines);
+            if let Some(value) = value {
+                shift_expr(value, lines);
+            }
+        }
+        Stmt::Class {
+            loc,
+            name_loc,
+            methods,
+            ..
+        } => {
+            shift(loc, lines);
+            shift(name_loc, lines);
+            methods
+                .iter_mut()
+                .for_each(|method| shift_function(method, lines));
+        }
+    }
+}
+
+fn shift_function(func: &mut StmtFunction, lines: isize) {
+    shift(&mut func.loc, lines);
+    shift(&mut func.name_loc, lines);
+    func.param_locs.iter_mut().for_each(|loc| shift(loc, lines));
+    func.body
+        .iter_mut()
+        .for_each(|stmt| shift_stmt(stmt, lines));
+}
+
+fn shift_expr(expr: &mut Expr, lines: isize) {
+    match expr {
+        Expr::ValExpr(expr, _) => shift_val(expr, lines),
+        Expr::RefExpr(expr, _) => shift_ref(expr, lines),
+    }
+}
+
+fn shift_val(expr: &mut ValExpr, lines: isize) {
+    match expr {
+        ValExpr::Literal { value } => shift(&mut value.loc, lines),
+        ValExpr::Unary { operator, right } => {
+            shift(&mut operator.loc, lines);
+            shift_expr(right, lines);
+        }
+        ValExpr::Binary {
+            left,
+            operator,
+            right,
+        } => {
+            shift_expr(left, lines);
+            shift(&mut operator.loc, lines);
+            shift_expr(right, lines);
+        }
+        ValExpr::Grouping { expr, loc } => {
+            shift(loc, lines);
+            shift_val(expr, lines);
+        }
+        ValExpr::Logical { left, kind, right } => {
+            shift_expr(left, lines);
+            shift(&mut kind.loc, lines);
+            shift_expr(right, lines);
+        }
+        ValExpr::Call { callee, args, loc } => {
+            shift(loc, lines);
+            shift_expr(callee, lines);
+            args.iter_mut().for_each(|arg| shift_expr(arg, lines));
+        }
+        ValExpr::List { elements, loc } => {
+            shift(loc, lines);
+            elements
+                .iter_mut()
+                .for_each(|element| shift_expr(element, lines));
+        }
+    }
+}
+
+fn shift_ref(expr: &mut RefExpr, lines: isize) {
+    match expr {
+        RefExpr::Variable { var } => shift(&mut var.loc, lines),
+        RefExpr::Grouping { expr, loc } => {
+            shift(loc, lines);
+            shift_ref(expr, lines);
+        }
+        RefExpr::Assignment { var, value } => {
+            shift(&mut var.loc, lines);
+            shift_expr(value, lines);
+        }
+        RefExpr::Get { object, prop } => {
+            shift_expr(object, lines);
+            shift(&mut prop.loc, lines);
+        }
+        RefExpr::Set {
+            object,
+            prop,
+            value,
+        } => {
+            shift_expr(object, lines);
+            shift(&mut prop.loc, lines);
+            shift_expr(value, lines);
+        }
+        RefExpr::This { loc } => shift(loc, lines),
+        RefExpr::Index { object, index, loc } => {
+            shift(loc, lines);
+            shift_expr(object, lines);
+            shift_expr(index, lines);
+        }
+        RefExpr::IndexSet {
+            object,
+            index,
+            value,
+            loc,
+        } => {
+            shift(loc, lines);
+            shift_expr(object, lines);
+            shift_expr(index, lines);
+            shift_expr(value, lines);
+        }
+    }
+}
@@ -21,6 +21,7 @@ pub enum Stmt {
         init: Option<Box<Expr>>,
     },
     Block {
+        loc: Location,
         statements: Vec<Stmt>,
     },
     If {
@@ -34,6 +35,21 @@ pub enum Stmt {
         condition: Box<Expr>,
         body: Box<Stmt>,
     },
+    ForIn {
+        loc: Location,
+        name: Key,
+        name_loc: Location,
+        iterable: Box<Expr>,
+        body: Box<Stmt>,
+    },
+    /// Runs the body of the first case whose value is equal to the subject, or `otherwise` if
+    /// none is. The bodies are blocks.
+    Switch {
+        loc: Location,
+        subject: Box<Expr>,
+        cases: Box<[(Box<Expr>, Stmt)]>,
+        otherwise: Option<Box<Stmt>>,
+    },
     Function {
         func: StmtFunction,
     },
@@ -44,6 +60,7 @@ pub enum Stmt {
     Class {
         loc: Location,
         name: Key,
+        name_loc: Location,
         methods: Box<[StmtFunction]>,
     },
 }
@@ -66,6 +83,10 @@ pub struct StmtFunction {
     pub params: Box<[Key]>,
     pub body: Box<[Stmt]>,
     pub loc: Location,
+
+    /// Locations of the name and of each parameter, where they are declared.
+    pub name_loc: Location,
+    pub param_locs: Box<[Location]>,
 }
 
 impl Stmt {
@@ -73,6 +94,23 @@ impl Stmt {
         Box::new(self)
     }
 
+    /// Location of the start of the statement, `None` for blocks.
+    pub fn loc(&self) -> Option<Location> {
+        match self {
+            Stmt::Expr { expr } => Some(expr.loc()),
+            Stmt::Block { .. } => None,
+            Stmt::Function { func } => Some(func.loc),
+            Stmt::Print { loc, .. }
+            | Stmt::Var { loc, .. }
+            | Stmt::If { loc, .. }
+            | Stmt::While { loc, .. }
+            | Stmt::ForIn { loc, .. }
+            | Stmt::Switch { loc, .. }
+            | Stmt::Return { loc, .. }
+            | Stmt::Class { loc, .. } => Some(*loc),
+        }
+    }
+
     pub fn display<'a, 'b>(&'a self, interner: &'b Interner) -> DisplayedStmt<'a, 'b> {
         DisplayedStmt {
             stmt: self,
@@ -94,7 +132,7 @@ impl Display for DisplayedStmt<'_, '_> {
                     None => write!(f, "(var {} nil)", name),
                 }
             }
-            Stmt::Block { statements } => {
+            Stmt::Block { statements, .. } => {
                 write!(f, "(block")?;
                 for stmt in statements {
                     write!(f, " {}", stmt.display(interner))?;
@@ -126,6 +164,33 @@ impl Display for DisplayedStmt<'_, '_> {
                 let body = body.display(interner);
                 write!(f, "(while {condition} {body})")
             }
+            Stmt::ForIn {
+                name,
+                iterable,
+                body,
+                ..
+            } => {
+                let name = interner.resolve(*name);
+                let iterable = iterable.display(interner);
+                let body = body.display(interner);
+                write!(f, "(for-in {name} {iterable} {body})")
+            }
+            Stmt::Switch {
+                subject,
+                cases,
+                otherwise,
+                ..
+            } => {
+                write!(f, "(switch {}", subject.display(interner))?;
+                for (value, body) in cases {
+                    let value = value.display(interner);
+                    write!(f, " (case {value} {})", body.display(interner))?;
+                }
+                if let Some(otherwise) = otherwise {
+                    write!(f, " (else {})", otherwise.display(interner))?;
+                }
+                write!(f, ")")
+            }
             Stmt::Function { func, .. } => {
                 write!(f, "(fun {} (", interner.resolve(func.name))?;
                 for param in &func.params {
@@ -151,12 +216,20 @@ impl Display for DisplayedStmt<'_, '_> {
 }
 
 impl StmtFunction {
-    pub fn new(name: Key, params: Box<[Key]>, body: Box<[Stmt]>, loc: Location) -> Self {
+    pub fn new(
+        (name, name_loc): (Key, Location),
+        params: Vec<(Key, Location)>,
+        body: Box<[Stmt]>,
+        loc: Location,
+    ) -> Self {
+        let (params, param_locs): (Vec<_>, Vec<_>) = params.into_iter().unzip();
         Self {
             name,
-            params,
+            params: params.into_boxed_slice(),
             body,
             loc,
+            name_loc,
+            param_locs: param_locs.into_boxed_slice(),
         }
     }
 }
@@ -1,9 +1,10 @@
 use indoc::indoc;
 use pretty_assertions::assert_eq;
 
+use crate::dialect::Dialect;
 use crate::interp::interner::Interner;
 use crate::lex::Lexer;
-use crate::parse::Parser;
+use crate::parse::{render_tree, Parser, TextEdit};
 use crate::util::{Location, TokLoc};
 
 use super::{expr::*, stmt::*, token};
@@ -21,10 +22,10 @@ fn get_reference_ast() -> Expr {
 
     let loc = Location::new;
 
-    let lit1 = Expr::literal(TokLoc::new(token::Literal::Number(1.0), loc(1, 1)));
-    let lit2 = Expr::literal(TokLoc::new(token::Literal::Number(2.0), loc(1, 6)));
-    let lit3 = Expr::literal(TokLoc::new(token::Literal::Number(3.0), loc(1, 10)));
-    let lit4 = Expr::literal(TokLoc::new(token::Literal::Number(4.0), loc(1, 15)));
+    let lit1 = Expr::literal(TokLoc::new(token::Literal::Int(1), loc(1, 1)));
+    let lit2 = Expr::literal(TokLoc::new(token::Literal::Int(2), loc(1, 6)));
+    let lit3 = Expr::literal(TokLoc::new(token::Literal::Int(3), loc(1, 10)));
+    let lit4 = Expr::literal(TokLoc::new(token::Literal::Int(4), loc(1, 15)));
     let litf = Expr::literal(TokLoc::new(token::Literal::False, loc(1, 20)));
 
     let eqeq = TokLoc {
@@ -93,3 +94,554 @@ fn parse_to_a_correct_ast() {
         _ => unreachable!(),
     };
 }
+
+#[test]
+fn parse_list_and_index() {
+    let mut interner = Interner::new();
+    let lexer = Lexer::new("xs[0] = [1, xs[1][2],];", &mut interner);
+    let result = lexer.scan();
+
+    assert!(result.errors.is_empty());
+
+    let mut parser = Parser::new();
+    let program = parser.parse(result.tokens).unwrap();
+
+    match program.statements.first().unwrap() {
+        Stmt::Expr { expr } => assert_eq!(
+            format!("{}", expr.display(&interner)),
+            "(index-set (var xs) 0 (list 1 (index (index (var xs) 1) 2)))"
+        ),
+        _ => unreachable!(),
+    };
+}
+
+#[test]
+fn parse_switch() {
+    let mut interner = Interner::new();
+    let source = "switch (x) { case 1: case 2: print a; if (b) c; else: d; }";
+    let result = Lexer::new(source, &mut interner).scan();
+
+    assert!(result.errors.is_empty());
+
+    let mut parser = Parser::new();
+    let program = parser.parse(result.tokens).unwrap();
+
+    // the else of the switch isn't taken by the `if` before it
+    assert_eq!(
+        format!("{}", program.statements[0].display(&interner)),
+        "(switch (var x) (case 1 (block)) (case 2 (block (print (var a)) (if (var b) (var c)))) \
+         (else (block (var d))))"
+    );
+}
+
+#[test]
+fn extensions_are_syntax_errors_in_the_book_dialect() {
+    let sources = [
+        "var xs = [1, 2];",
+        "print xs[0];",
+        "for (var x in xs) print x;",
+        "switch (x) { case 1: print x; }",
+        "print 1 | 2;",
+    ];
+    for source in sources {
+        let mut interner = Interner::new();
+        let tokens = Lexer::new(source, &mut interner).scan().tokens;
+        assert!(Parser::new().parse(tokens.clone()).is_ok(), "{source}");
+
+        let mut parser = Parser::new().with_dialect(Dialect::book());
+        assert!(parser.parse(tokens).is_err(), "{source}");
+    }
+}
+
+#[test]
+fn in_is_a_name_outside_of_for_loops() {
+    let source = "var in = [3]; print in; for (var in in in) print in;";
+    let mut interner = Interner::new();
+    let tokens = Lexer::new(source, &mut interner).scan().tokens;
+    let program = Parser::new().parse(tokens).unwrap();
+    assert!(matches!(program.statements[2], Stmt::ForIn { .. }));
+
+    let tokens = Lexer::new("var in = 3; print in;", &mut interner)
+        .scan()
+        .tokens;
+    let mut parser = Parser::new().with_dialect(Dialect::book());
+    assert!(parser.parse(tokens).is_ok());
+}
+
+#[test]
+fn render_program_as_tree() {
+    let mut interner = Interner::new();
+    let source = indoc! { r#"
+        var x = -1 + 2;
+        if (x) print "x"; else { x.y = f(x); }
+    "# };
+    let result = Lexer::new(source, &mut interner).scan();
+
+    assert!(result.errors.is_empty());
+
+    let program = Parser::new().parse(result.tokens).unwrap();
+    let expect = indoc! { r#"
+        Program
+        ├── Var x [1:5]
+        │   └── Binary + [1:9]
+        │       ├── Unary - [1:9]
+        │       │   └── Literal 1 [1:10]
+        │       └── Literal 2 [1:14]
+        └── If [2:1]
+            ├── Variable x [2:5]
+            ├── Print [2:8]
+            │   └── Literal "x" [2:14]
+            └── Else
+                └── Block
+                    └── Expression [2:26]
+                        └── Set y [2:26]
+                            ├── Variable x [2:26]
+                            └── Call [2:32]
+                                ├── Variable f [2:32]
+                                └── Variable x [2:34]
+    "# };
+    assert_eq!(render_tree(&program, &interner), expect);
+}
+
+#[test]
+fn render_program_as_dot() {
+    let mut interner = Interner::new();
+    let result = Lexer::new("print -x;", &mut interner).scan();
+
+    assert!(result.errors.is_empty());
+
+    let program = Parser::new().parse(result.tokens).unwrap();
+    let expect = indoc! { r#"
+        digraph ast {
+            n0 [label="Program"];
+            n1 [label="Print [1:1]"];
+            n2 [label="Unary - [1:7]"];
+            n3 [label="Variable x [1:8]"];
+            n2 -> n3;
+            n1 -> n2;
+            n0 -> n1;
+        }
+    "# };
+    assert_eq!(program.to_dot(&interner), expect);
+}
+
+// property tests
+// --------------
+// random ASTs are printed as Lox source with the least parentheses needed, then parsed back and
+// compared with the AST using their s-expression form
+
+mod property {
+    use proptest::prelude::*;
+
+    use crate::interp::interner::Interner;
+    use crate::lex::Lexer;
+    use crate::parse::Parser;
+
+    const NAMES: [&str; 4] = ["a", "b", "foo", "bar_"];
+    const BINARY: [&str; 17] = [
+        "or", "and", "==", "!=", "<", "<=", ">", ">=", "|", "^", "&", "<<", ">>", "+", "-", "*",
+        "/",
+    ];
+    const TOKENS: [&str; 50] = [
+        "(", ")", "{", "}", "[", "]", ",", ".", ";", ":", "switch", "case", "-", "+", "*", "/",
+        "&", "|", "^", "<<", ">>", "!", "!=", "=", "==", "<", "<=", ">", ">=", "and", "or", "if",
+        "else", "while", "for", "in", "fun", "class", "return", "var", "print", "this", "super",
+        "nil", "true", "false", "a", "b", "1", "\"s\"",
+    ];
+
+    /// Binding power of each level of the grammar, from `assignment` to `primary`.
+    const ASSIGNMENT: u8 = 1;
+    const UNARY: u8 = 12;
+    const CALL: u8 = 13;
+    const PRIMARY: u8 = 14;
+
+    #[derive(Clone, Debug)]
+    enum Ast {
+        Number(u16),
+        String(String),
+        Keyword(&'static str),
+        Var(&'static str),
+        This,
+        Unary(&'static str, Box<Ast>),
+        Binary(&'static str, Box<Ast>, Box<Ast>),
+        Call(Box<Ast>, Vec<Ast>),
+        Get(Box<Ast>, &'static str),
+        Index(Box<Ast>, Box<Ast>),
+        List(Vec<Ast>),
+        Assign(Box<Ast>, Box<Ast>),
+    }
+
+    #[derive(Clone, Debug)]
+    enum Statement {
+        Expr(Ast),
+        Print(Ast),
+        Var(&'static str, Option<Ast>),
+        Block(Vec<Statement>),
+        If(Ast, Box<Statement>, Option<Box<Statement>>),
+        While(Ast, Box<Statement>),
+    }
+
+    impl Ast {
+        fn precedence(&self) -> u8 {
+            match self {
+                Ast::Assign(..) => ASSIGNMENT,
+                Ast::Binary(op, ..) => binary_precedence(op),
+                Ast::Unary(..) => UNARY,
+                Ast::Call(..) | Ast::Get(..) | Ast::Index(..) => CALL,
+                _ => PRIMARY,
+            }
+        }
+
+        /// Whether the parser produces a `RefExpr`, whose grouping is not part of its s-expression.
+        fn is_ref(&self) -> bool {
+            matches!(
+                self,
+                Ast::Var(_) | Ast::This | Ast::Get(..) | Ast::Index(..) | Ast::Assign(..)
+            )
+        }
+
+        /// The source and the s-expression of `self`, grouped if it binds looser than `min`.
+        fn render_at(&self, min: u8) -> (String, String) {
+            let (source, sexpr) = self.render();
+            match self.precedence() < min {
+                true if self.is_ref() => (format!("({source})"), sexpr),
+                true => (format!("({source})"), format!("(group {sexpr})")),
+                false => (source, sexpr),
+            }
+        }
+
+        fn render(&self) -> (String, String) {
+            match self {
+                Ast::Number(num) => (num.to_string(), num.to_string()),
+                Ast::String(str) => (format!("\"{str}\""), format!("\"{str}\"")),
+                Ast::Keyword(kw) => (kw.to_string(), kw.to_string()),
+                Ast::Var(name) => (name.to_string(), format!("(var {name})")),
+                Ast::This => ("this".to_owned(), "(this)".to_owned()),
+                Ast::Unary(op, right) => {
+                    let (source, sexpr) = right.render_at(UNARY);
+                    (format!("{op} {source}"), format!("({op} {sexpr})"))
+                }
+                Ast::Binary(op, left, right) => {
+                    // every binary operator is left associative
+                    let precedence = binary_precedence(op);
+                    let (l_source, l_sexpr) = left.render_at(precedence);
+                    let (r_source, r_sexpr) = right.render_at(precedence + 1);
+                    (
+                        format!("{l_source} {op} {r_source}"),
+                        format!("({op} {l_sexpr} {r_sexpr})"),
+                    )
+                }
+                Ast::Call(callee, args) => {
+                    let (source, sexpr) = callee.render_at(CALL);
+                    let (args_source, args_sexpr) = render_list(args);
+                    (
+                        format!("{source}({})", args_source.join(", ")),
+                        format!("(call {sexpr} (args{}))", args_sexpr.concat()),
+                    )
+                }
+                Ast::Get(object, name) => {
+                    let (source, sexpr) = object.render_at(CALL);
+                    (format!("{source}.{name}"), format!("(get {sexpr} {name})"))
+                }
+                Ast::Index(object, index) => {
+                    let (source, sexpr) = object.render_at(CALL);
+                    let (i_source, i_sexpr) = index.render_at(ASSIGNMENT);
+                    (
+                        format!("{source}[{i_source}]"),
+                        format!("(index {sexpr} {i_sexpr})"),
+                    )
+                }
+                Ast::List(elements) => {
+                    let (source, sexpr) = render_list(elements);
+                    (
+                        format!("[{}]", source.join(", ")),
+                        format!("(list{})", sexpr.concat()),
+                    )
+                }
+                Ast::Assign(target, value) => {
+                    // assignment is right associative
+                    let (v_source, v_sexpr) = value.render_at(ASSIGNMENT);
+                    match target.as_ref() {
+                        Ast::Var(name) => (
+                            format!("{name} = {v_source}"),
+                            format!("(= {name} {v_sexpr})"),
+                        ),
+                        Ast::Get(object, name) => {
+                            let (source, sexpr) = object.render_at(CALL);
+                            (
+                                format!("{source}.{name} = {v_source}"),
+                                format!("(set {sexpr} {v_sexpr})"),
+                            )
+                        }
+                        Ast::Index(object, index) => {
+                            let (source, sexpr) = object.render_at(CALL);
+                            let (i_source, i_sexpr) = index.render_at(ASSIGNMENT);
+                            (
+                                format!("{source}[{i_source}] = {v_source}"),
+                                format!("(index-set {sexpr} {i_sexpr} {v_sexpr})"),
+                            )
+                        }
+                        _ => unreachable!("assignment target should be an lvalue"),
+                    }
+                }
+            }
+        }
+    }
+
+    impl Statement {
+        fn render(&self) -> (String, String) {
+            match self {
+                Statement::Expr(expr) => {
+                    let (source, sexpr) = expr.render_at(ASSIGNMENT);
+                    (format!("{source};"), sexpr)
+                }
+                Statement::Print(expr) => {
+                    let (source, sexpr) = expr.render_at(ASSIGNMENT);
+                    (format!("print {source};"), format!("(print {sexpr})"))
+                }
+                Statement::Var(name, None) => (format!("var {name};"), format!("(var {name} nil)")),
+                Statement::Var(name, Some(init)) => {
+                    let (source, sexpr) = init.render_at(ASSIGNMENT);
+                    (
+                        format!("var {name} = {source};"),
+                        format!("(var {name} {sexpr})"),
+                    )
+                }
+                Statement::Block(statements) => {
+                    let (source, sexpr): (Vec<_>, Vec<_>) =
+                        statements.iter().map(Statement::render).unzip();
+                    let sexpr = sexpr.iter().map(|s| format!(" {s}")).collect::<String>();
+                    (
+                        format!("{{ {} }}", source.join(" ")),
+                        format!("(block{sexpr})"),
+                    )
+                }
+                Statement::If(condition, then, otherwise) => {
+                    let (c_source, c_sexpr) = condition.render_at(ASSIGNMENT);
+                    let (t_source, t_sexpr) = then.render();
+                    match otherwise {
+                        None => (
+                            format!("if ({c_source}) {t_source}"),
+                            format!("(if {c_sexpr} {t_sexpr})"),
+                        ),
+                        Some(otherwise) => {
+                            let (o_source, o_sexpr) = otherwise.render();
+                            (
+                                format!("if ({c_source}) {t_source} else {o_source}"),
+                                format!("(if-else {c_sexpr} {t_sexpr} {o_sexpr})"),
+                            )
+                        }
+                    }
+                }
+                Statement::While(condition, body) => {
+                    let (c_source, c_sexpr) = condition.render_at(ASSIGNMENT);
+                    let (b_source, b_sexpr) = body.render();
+                    (
+                        format!("while ({c_source}) {b_source}"),
+                        format!("(while {c_sexpr} {b_sexpr})"),
+                    )
+                }
+            }
+        }
+    }
+
+    fn binary_precedence(op: &str) -> u8 {
+        match op {
+            "or" => 2,
+            "and" => 3,
+            "==" | "!=" => 4,
+            "<" | "<=" | ">" | ">=" => 5,
+            "|" => 6,
+            "^" => 7,
+            "&" => 8,
+            "<<" | ">>" => 9,
+            "+" | "-" => 10,
+            "*" | "/" => 11,
+            _ => unreachable!("unknown binary operator {op}"),
+        }
+    }
+
+    fn render_list(exprs: &[Ast]) -> (Vec<String>, Vec<String>) {
+        exprs
+            .iter()
+            .map(|expr| {
+                let (source, sexpr) = expr.render_at(ASSIGNMENT);
+                (source, format!(" {sexpr}"))
+            })
+            .unzip()
+    }
+
+    fn name() -> impl Strategy<Value = &'static str> {
+        prop::sample::select(&NAMES[..])
+    }
+
+    fn expr() -> impl Strategy<Value = Ast> {
+        let leaf = prop_oneof![
+            any::<u16>().prop_map(Ast::Number),
+            "[a-z ]{0,4}".prop_map(Ast::String),
+            prop::sample::select(&["true", "false", "nil"][..]).prop_map(Ast::Keyword),
+            name().prop_map(Ast::Var),
+            Just(Ast::This),
+        ];
+
+        leaf.prop_recursive(5, 48, 3, |inner| {
+            let target = prop_oneof![
+                name().prop_map(Ast::Var),
+                (inner.clone(), name()).prop_map(|(object, name)| Ast::Get(object.into(), name)),
+                (inner.clone(), inner.clone())
+                    .prop_map(|(object, index)| Ast::Index(object.into(), index.into())),
+            ];
+
+            prop_oneof![
+                (prop::sample::select(&["-", "!"][..]), inner.clone())
+                    .prop_map(|(op, right)| Ast::Unary(op, right.into())),
+                (
+                    prop::sample::select(&BINARY[..]),
+                    inner.clone(),
+                    inner.clone()
+                )
+                    .prop_map(|(op, left, right)| Ast::Binary(
+                        op,
+                        left.into(),
+                        right.into()
+                    )),
+                (inner.clone(), prop::collection::vec(inner.clone(), 0..3))
+                    .prop_map(|(callee, args)| Ast::Call(callee.into(), args)),
+                (inner.clone(), name()).prop_map(|(object, name)| Ast::Get(object.into(), name)),
+                (inner.clone(), inner.clone())
+                    .prop_map(|(object, index)| Ast::Index(object.into(), index.into())),
+                prop::collection::vec(inner.clone(), 0..3).prop_map(Ast::List),
+                (target, inner)
+                    .prop_map(|(target, value)| Ast::Assign(target.into(), value.into())),
+            ]
+        })
+    }
+
+    fn statement() -> impl Strategy<Value = Statement> {
+        let leaf = prop_oneof![
+            expr().prop_map(Statement::Expr),
+            expr().prop_map(Statement::Print),
+            (name(), prop::option::of(expr())).prop_map(|(name, init)| Statement::Var(name, init)),
+        ];
+
+        leaf.prop_recursive(3, 16, 3, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..3).prop_map(Statement::Block),
+                (expr(), inner.clone(), prop::option::of(inner.clone())).prop_map(
+                    |(condition, then, otherwise)| {
+                        // a dangling `else` belongs to the innermost `if`, so `then` is put in a
+                        // block to keep `otherwise` attached to this one
+                        let then = match (then, &otherwise) {
+                            (then @ Statement::Block(_), _) => then,
+                            (then, None) => body(then),
+                            (then, Some(_)) => Statement::Block(vec![then]),
+                        };
+                        let otherwise = otherwise.map(|otherwise| body(otherwise).into());
+                        Statement::If(condition, then.into(), otherwise)
+                    }
+                ),
+                (expr(), inner)
+                    .prop_map(|(condition, stmt)| Statement::While(condition, body(stmt).into())),
+            ]
+        })
+    }
+
+    /// Declarations are not statements, so they are put in a block to be the body of a statement.
+    fn body(stmt: Statement) -> Statement {
+        match stmt {
+            Statement::Var(..) => Statement::Block(vec![stmt]),
+            stmt => stmt,
+        }
+    }
+
+    /// The s-expression of each statement of `source`, or the syntax errors.
+    fn parse(source: &str) -> Result<Vec<String>, String> {
+        let mut interner = Interner::new();
+        let result = Lexer::new(source, &mut interner).scan();
+        if !result.errors.is_empty() {
+            return Err(format!("{:?}", result.errors));
+        }
+
+        let program = Parser::new()
+            .parse(result.tokens)
+            .map_err(|errors| format!("{errors:?}"))?;
+        let statements = program.statements.iter();
+        Ok(statements
+            .map(|stmt| stmt.display(&interner).to_string())
+            .collect())
+    }
+
+    proptest! {
+        #[test]
+        fn printed_expressions_parse_back(ast in expr()) {
+            let (source, sexpr) = ast.render_at(ASSIGNMENT);
+            prop_assert_eq!(parse(&format!("{source};")), Ok(vec![sexpr]), "source: {}", source);
+        }
+
+        #[test]
+        fn printed_statements_parse_back(statements in prop::collection::vec(statement(), 1..4)) {
+            let (source, sexpr): (Vec<_>, Vec<_>) =
+                statements.iter().map(Statement::render).unzip();
+            let source = source.join("\n");
+            prop_assert_eq!(parse(&source), Ok(sexpr), "source: {}", source);
+        }
+
+        #[test]
+        fn parser_never_panics(tokens in prop::collection::vec(token(), 0..48)) {
+            let source = tokens.join(" ");
+            let _ = parse(&source);
+
+            let mut interner = Interner::new();
+            let result = Lexer::new(&source, &mut interner).scan();
+            let _ = Parser::with_semicolon_recovery().parse(result.tokens);
+        }
+    }
+
+    fn token() -> impl Strategy<Value = &'static str> {
+        prop::sample::select(&TOKENS[..])
+    }
+}
+
+#[test]
+fn reparse_matches_parsing_the_edited_source() {
+    let source = indoc! { r#"
+        // the sum
+        fun add(a, b) {
+            return a + b;
+        }
+        var x = add(1, 2);
+        print "x is
+        " + x;
+        print x;
+    "# };
+
+    let parse = |source: &str, interner: &mut Interner| {
+        let tokens = Lexer::new(source, interner).scan().tokens;
+        Parser::new().parse(tokens).expect("source should parse")
+    };
+
+    // the edit, whether only the touched statements are parsed again
+    let edits = [
+        ((5, 9), (5, 10), "y", true),
+        ((2, 1), (2, 1), "\n\nvar z;\n", true),
+        ((1, 1), (1, 11), "", true),
+        ((3, 12), (3, 17), "\n        a * b", true),
+        ((8, 7), (8, 8), "x + 1", true),
+        ((5, 1), (5, 1), "{ ", false),
+    ];
+    for (start, end, text, incremental) in edits {
+        let edit = TextEdit {
+            start: Location::new(start.0, start.1),
+            end: Location::new(end.0, end.1),
+            text: text.to_owned(),
+        };
+        let mut interner = Interner::new();
+        let mut program = parse(source, &mut interner);
+        assert_eq!(program.reparse(source, &edit, &mut interner), incremental);
+        if incremental {
+            let mut edited = source.to_owned();
+            edit.apply(&mut edited);
+            assert_eq!(program.statements, parse(&edited, &mut interner).statements);
+        }
+    }
+}
@@ -8,6 +8,7 @@ use self::macros::impl_token;
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
 pub enum Literal {
     Number(f64),
+    Int(i64),
     String(Key),
     True,
     False,
@@ -32,6 +33,11 @@ pub enum BinaryOp {
     Sub,
     Mul,
     Div,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
@@ -83,7 +89,7 @@ impl Variable {
 impl From<&Literal> for &str {
     fn from(val: &Literal) -> Self {
         match val {
-            Literal::Number(_) => "<number>",
+            Literal::Number(_) | Literal::Int(_) => "<number>",
             Literal::String(_) => "<string>",
             Literal::True => "true",
             Literal::False => "false",
@@ -114,6 +120,11 @@ impl From<&BinaryOp> for &str {
             BinaryOp::Sub => "-",
             BinaryOp::Mul => "*",
             BinaryOp::Div => "/",
+            BinaryOp::BitAnd => "&",
+            BinaryOp::BitOr => "|",
+            BinaryOp::BitXor => "^",
+            BinaryOp::ShiftLeft => "<<",
+            BinaryOp::ShiftRight => ">>",
         }
     }
 }
@@ -168,6 +179,7 @@ impl Display for DisplayedLiteral<'_, '_> {
         let interner = self.interner;
         match self.literal {
             Literal::Number(num) => write!(f, "{num}"),
+            Literal::Int(int) => write!(f, "{int}"),
             Literal::String(str) => write!(f, r#""{}""#, interner.resolve(*str)),
             Literal::True => write!(f, "true"),
             Literal::False => write!(f, "false"),
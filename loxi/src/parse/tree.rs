@@ -0,0 +1,270 @@
+//! Rendering a program as a tree of its statements and expressions, like `cargo tree` does for
+//! dependencies, or as a graph for Graphviz. It shows the same structure as the s-expressions of
+//! `Program::display` but with the kind and location of every node, which is easier to follow when
+//! learning how parsing works.
+
+use crate::interp::interner::Interner;
+use crate::util::{Location, LoxToken};
+
+use super::expr::{Expr, RefExpr, ValExpr};
+use super::stmt::{Stmt, StmtFunction};
+use super::token::Literal;
+use super::Program;
+
+/// Render `program` as an indented tree drawn with box-drawing characters. Each node shows its
+/// kind, its details like the name or operator, and its location.
+pub fn render_tree(program: &Program, interner: &Interner) -> String {
+    let mut out = String::new();
+    let root = Node::program(program, interner);
+    out.push_str(&root.label);
+    out.push('\n');
+    for (i, child) in root.children.iter().enumerate() {
+        render(child, "", i + 1 == root.children.len(), &mut out);
+    }
+    out
+}
+
+impl Program {
+    /// The syntax tree in the DOT format of Graphviz, with the same nodes as `render_tree`.
+    pub fn to_dot(&self, interner: &Interner) -> String {
+        let mut out = String::from("digraph ast {\n");
+        let mut next = 0;
+        dot_node(&Node::program(self, interner), &mut next, &mut out);
+        out.push_str("}\n");
+        out
+    }
+}
+
+fn render(node: &Node, prefix: &str, last: bool, out: &mut String) {
+    let (branch, indent) = match last {
+        true => ("└── ", "    "),
+        false => ("├── ", "│   "),
+    };
+    out.push_str(prefix);
+    out.push_str(branch);
+    out.push_str(&node.label);
+    out.push('\n');
+
+    let prefix = format!("{prefix}{indent}");
+    for (i, child) in node.children.iter().enumerate() {
+        render(child, &prefix, i + 1 == node.children.len(), out);
+    }
+}
+
+/// Write `node` and its children, numbered from `next` in preorder, and return the number of
+/// `node`.
+fn dot_node(node: &Node, next: &mut usize, out: &mut String) -> usize {
+    let id = *next;
+    *next += 1;
+    out.push_str(&format!("    n{id} [label={:?}];\n", node.label));
+    for child in &node.children {
+        let child = dot_node(child, next, out);
+        out.push_str(&format!("    n{id} -> n{child};\n"));
+    }
+    id
+}
+
+/// A node of the syntax tree reduced to what is shown of it.
+pub(super) struct Node {
+    pub label: String,
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    fn new(label: String, children: Vec<Node>) -> Self {
+        Self { label, children }
+    }
+
+    fn at(kind: &str, loc: Location, children: Vec<Node>) -> Self {
+        Self::new(format!("{kind} {loc}"), children)
+    }
+
+    pub fn program(program: &Program, interner: &Interner) -> Self {
+        let children = Self::stmts(&program.statements, interner);
+        Self::new("Program".to_owned(), children)
+    }
+
+    fn stmts(stmts: &[Stmt], interner: &Interner) -> Vec<Self> {
+        stmts
+            .iter()
+            .map(|stmt| Self::stmt(stmt, interner))
+            .collect()
+    }
+
+    fn stmt(stmt: &Stmt, interner: &Interner) -> Self {
+        let expr = |expr: &Expr| Self::expr(expr, interner);
+        let stmt_node = |stmt: &Stmt| Self::stmt(stmt, interner);
+
+        match stmt {
+            Stmt::Expr { expr: inner } => Self::at("Expression", inner.loc(), vec![expr(inner)]),
+            Stmt::Print { loc, expr: inner } => Self::at("Print", *loc, vec![expr(inner)]),
+            Stmt::Var { loc, name, init } => {
+                let kind = format!("Var {}", interner.resolve(*name));
+                Self::at(&kind, *loc, init.iter().map(|init| expr(init)).collect())
+            }
+            Stmt::Block { statements, .. } => {
+                Self::new("Block".to_owned(), Self::stmts(statements, interner))
+            }
+            Stmt::If {
+                loc,
+                condition,
+                then,
+                otherwise,
+            } => {
+                let mut children = vec![expr(condition), stmt_node(then)];
+                if let Some(otherwise) = otherwise {
+                    children.push(Self::new("Else".to_owned(), vec![stmt_node(otherwise)]));
+                }
+                Self::at("If", *loc, children)
+            }
+            Stmt::While {
+                loc,
+                condition,
+                body,
+            } => Self::at("While", *loc, vec![expr(condition), stmt_node(body)]),
+            Stmt::ForIn {
+                loc,
+                name,
+                iterable,
+                body,
+                ..
+            } => {
+                let kind = format!("ForIn {}", interner.resolve(*name));
+                Self::at(&kind, *loc, vec![expr(iterable), stmt_node(body)])
+            }
+            Stmt::Switch {
+                loc,
+                subject,
+                cases,
+                otherwise,
+            } => {
+                let mut children = vec![expr(subject)];
+                for (value, body) in cases {
+                    let case = Self::at("Case", value.loc(), vec![expr(value), stmt_node(body)]);
+                    children.push(case);
+                }
+                if let Some(otherwise) = otherwise {
+                    children.push(Self::new("Else".to_owned(), vec![stmt_node(otherwise)]));
+                }
+                Self::at("Switch", *loc, children)
+            }
+            Stmt::Function { func } => Self::function("Function", func, interner),
+            Stmt::Return { loc, value } => Self::at(
+                "Return",
+                *loc,
+                value.iter().map(|value| expr(value)).collect(),
+            ),
+            Stmt::Class {
+                loc, name, methods, ..
+            } => {
+                let kind = format!("Class {}", interner.resolve(*name));
+                let methods = methods
+                    .iter()
+                    .map(|method| Self::function("Method", method, interner))
+                    .collect();
+                Self::at(&kind, *loc, methods)
+            }
+        }
+    }
+
+    fn function(kind: &str, func: &StmtFunction, interner: &Interner) -> Self {
+        let params = func.params.iter().map(|param| interner.resolve(*param));
+        let params = params.collect::<Vec<_>>().join(", ");
+        let kind = format!("{kind} {}({params})", interner.resolve(func.name));
+        Self::at(&kind, func.loc, Self::stmts(&func.body, interner))
+    }
+
+    fn expr(expr: &Expr, interner: &Interner) -> Self {
+        match expr {
+            Expr::ValExpr(expr, _) => Self::val_expr(expr, interner),
+            Expr::RefExpr(expr, _) => Self::ref_expr(expr, interner),
+        }
+    }
+
+    fn val_expr(expr: &ValExpr, interner: &Interner) -> Self {
+        let node = |expr: &Expr| Self::expr(expr, interner);
+        let loc = expr.loc();
+
+        match expr {
+            ValExpr::Literal { value } => {
+                let value = match &value.tok {
+                    Literal::String(key) => format!("{:?}", interner.resolve(*key)),
+                    literal => literal.display(interner).to_string(),
+                };
+                Self::at(&format!("Literal {value}"), loc, Vec::new())
+            }
+            ValExpr::Unary { operator, right } => {
+                let kind = format!("Unary {}", operator.tok.as_str());
+                Self::at(&kind, loc, vec![node(right)])
+            }
+            ValExpr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let kind = format!("Binary {}", operator.tok.as_str());
+                Self::at(&kind, loc, vec![node(left), node(right)])
+            }
+            ValExpr::Grouping { expr, .. } => {
+                Self::at("Grouping", loc, vec![Self::val_expr(expr, interner)])
+            }
+            ValExpr::Logical { left, kind, right } => {
+                let kind = format!("Logical {}", kind.tok.as_str());
+                Self::at(&kind, loc, vec![node(left), node(right)])
+            }
+            ValExpr::Call { callee, args, .. } => {
+                let mut children = vec![node(callee)];
+                children.extend(args.iter().map(node));
+                Self::at("Call", loc, children)
+            }
+            ValExpr::List { elements, .. } => {
+                Self::at("List", loc, elements.iter().map(node).collect())
+            }
+        }
+    }
+
+    fn ref_expr(expr: &RefExpr, interner: &Interner) -> Self {
+        let node = |expr: &Expr| Self::expr(expr, interner);
+        let loc = expr.loc();
+
+        match expr {
+            RefExpr::Variable { var } => {
+                let kind = format!("Variable {}", interner.resolve(var.tok.name));
+                Self::at(&kind, loc, Vec::new())
+            }
+            RefExpr::Grouping { expr, .. } => {
+                Self::at("Grouping", loc, vec![Self::ref_expr(expr, interner)])
+            }
+            RefExpr::Assignment { var, value } => {
+                let kind = format!("Assign {}", interner.resolve(var.tok.name));
+                Self::at(&kind, loc, vec![node(value)])
+            }
+            RefExpr::Get { object, prop } => {
+                let kind = format!("Get {}", interner.resolve(prop.tok.name));
+                Self::at(&kind, loc, vec![node(object)])
+            }
+            RefExpr::Set {
+                object,
+                prop,
+                value,
+            } => {
+                let kind = format!("Set {}", interner.resolve(prop.tok.name));
+                Self::at(&kind, loc, vec![node(object), node(value)])
+            }
+            RefExpr::This { .. } => Self::at("This", loc, Vec::new()),
+            RefExpr::Index { object, index, .. } => {
+                Self::at("Index", loc, vec![node(object), node(index)])
+            }
+            RefExpr::IndexSet {
+                object,
+                index,
+                value,
+                ..
+            } => Self::at(
+                "IndexSet",
+                loc,
+                vec![node(object), node(index), node(value)],
+            ),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Running many scripts at the same time, e.g. a test suite or a batch of files.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::ErrorKind;
+use crate::interp::output::OutputBuffer;
+use crate::lox::{Lox, Options, Outcome};
+use crate::RunMode;
+
+/// Every Lox call recurses on the native stack, so the scripts run on threads with a stack big
+/// enough for the default call depth limit, like the `loxi` binary.
+pub(crate) const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+/// Runs scripts on a few threads at once. Each script runs in its own `Lox`, so scripts share
+/// neither their globals nor their interned strings, and its output is captured in its result.
+#[derive(Clone, Debug)]
+pub struct InterpreterPool {
+    options: Options,
+    threads: usize,
+}
+
+/// How a script of `InterpreterPool` ran.
+#[derive(Clone, Debug)]
+pub struct ScriptResult {
+    pub path: PathBuf,
+
+    /// The kind of the error that stopped the script, if any.
+    pub outcome: Result<Outcome, ErrorKind>,
+    pub stdout: String,
+
+    /// The errors of the script as they are reported, along with what it printed to stderr.
+    pub stderr: String,
+    pub duration: Duration,
+}
+
+impl InterpreterPool {
+    /// A pool running `threads` scripts at a time, at least one, each with `options`.
+    pub fn new(options: &Options, threads: usize) -> Self {
+        Self {
+            options: options.clone(),
+            threads: threads.max(1),
+        }
+    }
+
+    /// Run every file in `paths`, returning their results in the same order.
+    pub fn run_files(&self, paths: &[impl AsRef<Path> + Sync]) -> Vec<ScriptResult> {
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(Vec::with_capacity(paths.len()));
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads.min(paths.len()) {
+                let worker = || loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(path) = paths.get(index) else {
+                        break;
+                    };
+                    let result = self.run_file(path.as_ref());
+                    results.lock().unwrap().push((index, result));
+                };
+                thread::Builder::new()
+                    .stack_size(STACK_SIZE)
+                    .spawn_scoped(scope, worker)
+                    .expect("should spawn a worker thread");
+            }
+        });
+
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+
+    fn run_file(&self, path: &Path) -> ScriptResult {
+        let (stdout, stderr) = (OutputBuffer::default(), OutputBuffer::default());
+        let mut lox = Lox::new(&self.options);
+        lox.set_stdout(stdout.clone());
+        lox.set_stderr(stderr.clone());
+
+        let start = Instant::now();
+        let outcome = lox.run_file(path, RunMode::Normal).map_err(|err| {
+            // the errors found in the program are already reported, unlike e.g. a missing file
+            if err.diagnostics().is_empty() {
+                let _ = writeln!(stderr.clone(), "{err}");
+            }
+            err.kind()
+        });
+
+        ScriptResult {
+            path: path.to_owned(),
+            outcome,
+            stdout: stdout.text(),
+            stderr: stderr.text(),
+            duration: start.elapsed(),
+        }
+    }
+}
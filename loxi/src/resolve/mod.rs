@@ -4,8 +4,10 @@ use std::mem;
 use rustc_hash::FxHashMap;
 use thiserror::Error;
 
+use crate::analyze::Span;
 use crate::interp::interner::{Interner, Key};
 use crate::lex::token::{Keyword, Special};
+use crate::message;
 use crate::parse::expr::{ExprId, RefExpr, ValExpr};
 use crate::parse::{expr::Expr, stmt::Stmt, Program};
 use crate::util::Location;
@@ -16,7 +18,7 @@ mod scope;
 
 pub struct Resolver<'a> {
     scope: Scope,
-    resolved_expr: FxHashMap<ExprId, usize>,
+    resolved_expr: FxHashMap<ExprId, Slot>,
     interner: &'a Interner,
     func_context: FunctionContext, // track if we are in a function or not, acts like a stack
     class_context: ClassContext,
@@ -36,25 +38,30 @@ enum ClassContext {
 
 #[derive(Debug, Error)]
 pub enum ResolveError {
-    #[error("{0} SyntaxError: Variable is used in its own initializer")]
     VariableInInitializer(Location),
-
-    #[error("{0} SyntaxError: Variable with the same name already defined at {1}")]
     DuplicateDeclaration(Location, Location),
-
-    #[error("{0} SyntaxError: Stray return statement outside of function")]
     StrayReturn(Location),
-
-    #[error("{0} SyntaxError: Stray this keyword outside of a class")]
     StrayThis(Location),
-
-    #[error("{0} SyntaxError: Can't return a value from initializer")]
     FobiddenReturn(Location),
 }
 
-#[derive(Default)]
+impl Display for ResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.loc(), self.message())
+    }
+}
+
+/// Where a local variable is stored: the number of scopes between its use and its declaration, and
+/// its index among the variables of the scope it's declared in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Slot {
+    pub distance: usize,
+    pub index: usize,
+}
+
+#[derive(Clone, Default)]
 pub struct ResolveMap {
-    resolved_expr: FxHashMap<ExprId, usize>,
+    resolved_expr: FxHashMap<ExprId, Slot>,
 }
 
 impl Resolver<'_> {
@@ -91,7 +98,7 @@ impl Resolver<'_> {
                 self.define_var(*name, *loc);
                 Ok(())
             }
-            Stmt::Block { statements } => {
+            Stmt::Block { statements, .. } => {
                 self.scope.create_scope();
                 for stmt in statements.iter() {
                     self.resolve_stmt(stmt)?
@@ -118,6 +125,36 @@ impl Resolver<'_> {
                 self.resolve_expr(condition)?;
                 self.resolve_stmt(body)
             }
+            Stmt::ForIn {
+                loc,
+                name,
+                iterable,
+                body,
+                ..
+            } => {
+                self.resolve_expr(iterable)?;
+                self.scope.create_scope();
+                self.declare_and_define_var(*name, *loc)?;
+                self.resolve_stmt(body)?;
+                self.scope.drop_scope();
+                Ok(())
+            }
+            Stmt::Switch {
+                subject,
+                cases,
+                otherwise,
+                ..
+            } => {
+                self.resolve_expr(subject)?;
+                for (value, body) in cases {
+                    self.resolve_expr(value)?;
+                    self.resolve_stmt(body)?;
+                }
+                match otherwise {
+                    Some(otherwise) => self.resolve_stmt(otherwise),
+                    _ => Ok(()),
+                }
+            }
             Stmt::Function { func } => {
                 self.declare_and_define_var(func.name, func.loc)?;
                 self.resolve_function(
@@ -141,7 +178,9 @@ impl Resolver<'_> {
                     None => Ok(()),
                 }
             }
-            Stmt::Class { loc, name, methods } => {
+            Stmt::Class {
+                loc, name, methods, ..
+            } => {
                 let mut prev_context = mem::replace(&mut self.class_context, ClassContext::Class);
                 self.declare_and_define_var(*name, *loc)?;
                 self.scope.create_scope();
@@ -191,6 +230,12 @@ impl Resolver<'_> {
                 }
                 Ok(())
             }
+            ValExpr::List { elements, .. } => {
+                for element in elements.iter() {
+                    self.resolve_expr(element)?;
+                }
+                Ok(())
+            }
         }
     }
 
@@ -225,6 +270,20 @@ impl Resolver<'_> {
                 self.resolve_expr(value)?;
                 self.resolve_expr(object)
             }
+            RefExpr::Index { object, index, .. } => {
+                self.resolve_expr(object)?;
+                self.resolve_expr(index)
+            }
+            RefExpr::IndexSet {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.resolve_expr(value)?;
+                self.resolve_expr(object)?;
+                self.resolve_expr(index)
+            }
             RefExpr::This { loc } => match self.class_context {
                 ClassContext::None => Err(ResolveError::StrayThis(*loc)),
                 ClassContext::Class => {
@@ -242,8 +301,8 @@ impl Resolver<'_> {
 
         let result = self.scope.get(name);
         match result {
-            Some((_, distance)) => {
-                self.resolved_expr.insert(expr_id, distance);
+            Some(slot) => {
+                self.resolved_expr.insert(expr_id, slot);
             }
             None => {
                 // if we can't find the variable we assume it's global
@@ -314,23 +373,52 @@ impl ResolveError {
             ResolveError::FobiddenReturn(loc) => *loc,
         }
     }
+
+    /// Resolve errors are only known by where they start, so the span is empty.
+    pub fn span(&self) -> Span {
+        Span::at(self.loc())
+    }
+
+    /// The message without the location, labelled as a `SyntaxError` like the errors of the
+    /// parser.
+    pub fn message(&self) -> String {
+        let message = match self {
+            ResolveError::VariableInInitializer(_) => {
+                message::format("resolve.variable-in-initializer", &[])
+            }
+            ResolveError::DuplicateDeclaration(_, previous) => {
+                message::format("resolve.duplicate-declaration", &[("previous", previous)])
+            }
+            ResolveError::StrayReturn(_) => message::format("resolve.stray-return", &[]),
+            ResolveError::StrayThis(_) => message::format("resolve.stray-this", &[]),
+            ResolveError::FobiddenReturn(_) => {
+                message::format("resolve.return-from-initializer", &[])
+            }
+        };
+        format!("SyntaxError: {message}")
+    }
 }
 
 impl ResolveMap {
+    pub fn extend(&mut self, other: ResolveMap) {
+        self.resolved_expr.extend(other.resolved_expr);
+    }
+
+    /// The slot of the local variable used by the expression, `None` if it's a global.
+    pub fn slot(&self, expr_id: ExprId) -> Option<Slot> {
+        self.resolved_expr.get(&expr_id).copied()
+    }
+
     pub fn distance(&self, expr_id: ExprId) -> Option<usize> {
-        self.resolved_expr
-            .iter()
-            .find(|v| *v.0 == expr_id)
-            .map(|v| v.1)
-            .copied()
+        self.slot(expr_id).map(|slot| slot.distance)
     }
 }
 
 impl Display for ResolveMap {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Resolved expressions:")?;
-        for (expr_id, distance) in self.resolved_expr.iter() {
-            writeln!(f, "  {:?} -> {}", expr_id, distance)?;
+        for (expr_id, slot) in self.resolved_expr.iter() {
+            writeln!(f, "  {:?} -> {}:{}", expr_id, slot.distance, slot.index)?;
         }
         Ok(())
     }
@@ -5,6 +5,8 @@ use rustc_hash::FxHashMap;
 use crate::interp::interner::Key;
 use crate::util::Location;
 
+use super::Slot;
+
 #[derive(Debug)]
 pub enum VarBind {
     Decl(Location),
@@ -15,9 +17,11 @@ pub enum ScopeError {
     DuplicateDefine(Location),
 }
 
+/// The variables of every local scope, each with the index of its slot in the scope, which is
+/// the order they are declared in.
 #[derive(Debug)]
 pub struct Scope {
-    stack: RefCell<Vec<FxHashMap<Key, VarBind>>>,
+    stack: RefCell<Vec<FxHashMap<Key, (VarBind, usize)>>>,
 }
 
 impl VarBind {
@@ -54,8 +58,9 @@ impl Scope {
 
     pub fn define(&self, key: Key, value: VarBind) -> Result<(), ScopeError> {
         let mut map = self.get_map(0);
-        match map.insert(key, value) {
-            Some(kind) => Err(ScopeError::DuplicateDefine(kind.loc())),
+        let index = map.len();
+        match map.insert(key, (value, index)) {
+            Some((kind, _)) => Err(ScopeError::DuplicateDefine(kind.loc())),
             None => Ok(()),
         }
     }
@@ -69,7 +74,7 @@ impl Scope {
             );
         }
         let map = self.get_map(distance);
-        RefMut::filter_map(map, |m| m.get_mut(&key)).ok()
+        RefMut::filter_map(map, |m| m.get_mut(&key).map(|(bind, _)| bind)).ok()
     }
 
     pub fn get_current(&self, key: Key) -> Option<RefMut<'_, VarBind>> {
@@ -79,17 +84,20 @@ impl Scope {
         self.get_at(key, 0)
     }
 
-    // get the value of the key and the distance from the current scope
-    pub fn get(&self, key: Key) -> Option<(RefMut<'_, VarBind>, usize)> {
-        for i in 0..=self.index() {
-            if let Some(value) = self.get_at(key, i) {
-                return Some((value, i));
+    // get the slot of the key, counting the distance from the current scope
+    pub fn get(&self, key: Key) -> Option<Slot> {
+        for distance in 0..=self.index() {
+            if let Some((_, index)) = self.get_map(distance).get(&key) {
+                return Some(Slot {
+                    distance,
+                    index: *index,
+                });
             }
         }
         None
     }
 
-    fn get_map(&self, distance: usize) -> RefMut<'_, FxHashMap<Key, VarBind>> {
+    fn get_map(&self, distance: usize) -> RefMut<'_, FxHashMap<Key, (VarBind, usize)>> {
         let index = self.index() - distance;
         let stack = self.stack.borrow_mut();
         RefMut::map(stack, |stack| {
@@ -0,0 +1,149 @@
+//! Running a program as a future, for hosts built on async code, e.g. a server.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread::{self, JoinHandle};
+
+use crate::interp::cancel::CancelToken;
+use crate::interp::observer::{Observer, Scope};
+use crate::lox::{Lox, Outcome};
+use crate::pool::STACK_SIZE;
+use crate::util::Location;
+use crate::{LoxError, RunMode};
+
+/// A program running as a future, see `Lox::run_async`. Each poll lets it run a few more
+/// statements, so it makes progress only as fast as the executor polls it. Dropping it cancels the
+/// program.
+///
+/// The interpreter can't pause in the middle of a call and resume later, so the program doesn't
+/// run inside `poll`: every task spawns an OS thread that runs the program and waits between the
+/// polls. It keeps a long program from blocking the executor, but it costs a thread per task, so
+/// it suits a few long programs better than many short ones.
+pub struct RunTask {
+    shared: Arc<Shared>,
+    statements: usize,
+    cancel: CancelToken,
+    thread: Option<JoinHandle<(Lox, Result<Outcome, LoxError>)>>,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    resumed: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    /// The number of statements the program can run before waiting for the next poll.
+    budget: usize,
+
+    /// Whether the program used its statements and waits for the next poll to get more.
+    waiting: bool,
+    finished: bool,
+    waker: Option<Waker>,
+}
+
+/// Makes the program wait whenever it runs out of statements.
+struct Yielder {
+    shared: Arc<Shared>,
+
+    /// The statements taken from the shared budget that are left, counted without the lock.
+    remaining: usize,
+}
+
+impl RunTask {
+    /// `cancel` is the token of `lox`.
+    pub(crate) fn spawn(
+        mut lox: Lox,
+        source: String,
+        statements: usize,
+        cancel: CancelToken,
+    ) -> Self {
+        let shared = Arc::new(Shared::default());
+        lox.set_observer(Yielder {
+            shared: Arc::clone(&shared),
+            remaining: 0,
+        });
+
+        let thread_shared = Arc::clone(&shared);
+        let thread = thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(move || {
+                let result = lox.run(&source, RunMode::Normal);
+                lox.remove_observer();
+
+                let mut state = thread_shared.state.lock().unwrap();
+                state.finished = true;
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+                (lox, result)
+            })
+            .expect("should spawn the program thread");
+
+        Self {
+            shared,
+            statements: statements.max(1),
+            cancel,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Future for RunTask {
+    /// The interpreter is given back along with the result, e.g. to read its globals.
+    type Output = (Lox, Result<Outcome, LoxError>);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.finished {
+            drop(state);
+            let thread = self
+                .thread
+                .take()
+                .expect("should not be polled after completion");
+            return Poll::Ready(thread.join().expect("the program thread should not panic"));
+        }
+
+        state.waker = Some(cx.waker().clone());
+        if state.waiting && state.budget == 0 {
+            state.budget = self.statements;
+            self.shared.resumed.notify_one();
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for RunTask {
+    fn drop(&mut self) {
+        if self.thread.is_some() {
+            self.cancel.cancel();
+            self.shared.state.lock().unwrap().budget = usize::MAX;
+            self.shared.resumed.notify_one();
+        }
+    }
+}
+
+impl Observer for Yielder {
+    fn on_statement(&mut self, _: Location, _: &Scope<'_>) {
+        if self.remaining == 0 {
+            let mut state = self.shared.state.lock().unwrap();
+            if state.budget == 0 {
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+                state.waiting = true;
+                state = self
+                    .shared
+                    .resumed
+                    .wait_while(state, |state| state.budget == 0)
+                    .unwrap();
+                state.waiting = false;
+            }
+            self.remaining = std::mem::take(&mut state.budget);
+        }
+        self.remaining -= 1;
+    }
+}
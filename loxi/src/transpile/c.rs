@@ -0,0 +1,657 @@
+//! Lox to C. The runtime in `runtime.h` is prepended to the program, so the result is a single
+//! file that a C11 compiler can build on its own.
+//!
+//! Expressions are lowered into a sequence of assignments to temporaries, `t[0]`, `t[1]`, ..., so
+//! operands are evaluated from left to right like in Lox, which C doesn't guarantee for the
+//! arguments of a call. Every local lives in a heap allocated cell, `v_name`, that closures keep a
+//! pointer to. A function gets the cells it uses from enclosing functions in `self->captures`,
+//! aliased as `c_name`. Globals are static variables, `G_name`, that are undefined until assigned.
+
+use std::collections::BTreeSet;
+
+use rustc_hash::FxHashSet;
+
+use crate::dialect::Dialect;
+use crate::interp::interner::{Interner, Key};
+use crate::interp::value::NumberStyle;
+use crate::parse::expr::{Expr, RefExpr, ValExpr};
+use crate::parse::stmt::{Stmt, StmtFunction};
+use crate::parse::{token, Program};
+
+const RUNTIME: &str = include_str!("runtime.h");
+
+/// The natives of the runtime with their minimum and maximum arity, defined when a program uses
+/// them. `deepEquals` is only defined if the dialect enables it.
+const NATIVES: &[(&str, usize, usize, &str)] = &[
+    ("clock", 0, 0, "lox_clock_"),
+    ("deepEquals", 2, 2, "lox_deep_equals_"),
+    ("print", 1, 1, "lox_print_"),
+    ("println", 1, 1, "lox_print_"),
+    ("write", 1, 1, "lox_write_"),
+    ("eprint", 1, 1, "lox_eprint_"),
+    ("Map", 0, 0, "lox_map_new_"),
+    ("exit", 0, 1, "lox_exit_"),
+    ("assert", 1, 2, "lox_assert_"),
+    ("panic", 1, 1, "lox_panic_"),
+];
+
+const INDENT: &str = "    ";
+
+const THIS: &str = "this";
+
+pub struct Emitter<'a> {
+    interner: &'a Interner,
+    style: NumberStyle,
+    dialect: Dialect,
+
+    /// The C functions translated so far. A function is finished before the one it's declared
+    /// in, so each one only refers to those before it.
+    functions: Vec<String>,
+
+    /// The names of every global the program declares or uses.
+    globals: BTreeSet<String>,
+
+    /// The functions being translated, from `main` to the innermost one.
+    stack: Vec<Function>,
+
+    /// Number of C functions and loops so far, to make their names unique.
+    count: usize,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum Kind {
+    Main,
+    Function,
+    Method,
+    Initializer,
+}
+
+struct Function {
+    kind: Kind,
+    out: String,
+    indent: usize,
+
+    /// Number of temporaries used by the function.
+    temps: usize,
+
+    /// The locals declared in each scope. The scope of the parameters is the first one, `main`
+    /// starts without any since its variables are globals.
+    scopes: Vec<FxHashSet<String>>,
+
+    /// The variables of the enclosing functions this one uses, in the order of `self->captures`.
+    captures: Vec<String>,
+}
+
+impl Function {
+    fn new(kind: Kind, indent: usize) -> Self {
+        let scopes = match kind {
+            Kind::Main => Vec::new(),
+            _ => vec![FxHashSet::default()],
+        };
+        Self {
+            kind,
+            out: String::new(),
+            indent,
+            temps: 0,
+            scopes,
+            captures: Vec::new(),
+        }
+    }
+}
+
+impl<'a> Emitter<'a> {
+    pub fn new(interner: &'a Interner, style: NumberStyle, dialect: Dialect) -> Self {
+        Self {
+            interner,
+            style,
+            dialect,
+            functions: Vec::new(),
+            globals: BTreeSet::new(),
+            stack: vec![Function::new(Kind::Main, 1)],
+            count: 0,
+        }
+    }
+
+    pub fn program(mut self, program: &Program) -> String {
+        for stmt in program.statements.iter() {
+            self.stmt(stmt);
+        }
+        let main = self.stack.pop().expect("main should be the last function");
+
+        let mut out = String::from(RUNTIME);
+        out.push('\n');
+        if !self.globals.is_empty() {
+            // zero initialized, which is the undefined value
+            for global in self.globals.iter() {
+                out.push_str(&format!("static LoxValue G_{global};\n"));
+            }
+            out.push('\n');
+        }
+        for function in self.functions.iter() {
+            out.push_str(function);
+            out.push('\n');
+        }
+
+        out.push_str("int main(void) {\n");
+        if self.style == NumberStyle::Rust {
+            out.push_str(&format!("{INDENT}lox_rust_numbers = true;\n"));
+        }
+        if main.temps > 0 {
+            out.push_str(&format!("{INDENT}LoxValue t[{}];\n", main.temps));
+        }
+        for (name, min, max, func) in NATIVES {
+            if *name == "deepEquals" && !self.dialect.deep_equals {
+                continue;
+            }
+            if self.globals.contains(*name) {
+                let define = format!("lox_native(\"{name}\", {min}, {max}, {func})");
+                out.push_str(&format!("{INDENT}G_{name} = {define};\n"));
+            }
+        }
+        for (name, value) in [("nan", "NAN"), ("inf", "INFINITY")] {
+            if self.globals.contains(name) {
+                out.push_str(&format!("{INDENT}G_{name} = lox_number({value});\n"));
+            }
+        }
+        out.push_str(&main.out);
+        out.push_str(&format!("{INDENT}return 0;\n}}\n"));
+        out
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expr { expr } => {
+                self.expr(expr);
+            }
+            Stmt::Print { expr, .. } => {
+                let value = self.expr(expr);
+                self.line(&format!("lox_print({value});"));
+            }
+            Stmt::Var { name, init, .. } => {
+                let value = match init {
+                    Some(init) => self.expr(init),
+                    None => "lox_nil()".to_owned(),
+                };
+                self.define(*name, &value);
+            }
+            Stmt::Block { statements, .. } => {
+                self.line("{");
+                self.scoped(|emitter| statements.iter().for_each(|stmt| emitter.stmt(stmt)));
+                self.line("}");
+            }
+            Stmt::If {
+                condition,
+                then,
+                otherwise,
+                ..
+            } => {
+                let condition = self.expr(condition);
+                self.line(&format!("if (lox_truthy({condition})) {{"));
+                self.body(then);
+                if let Some(otherwise) = otherwise {
+                    self.line("} else {");
+                    self.body(otherwise);
+                }
+                self.line("}");
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                // the condition may need statements of its own, so it's checked inside the loop
+                self.line("for (;;) {");
+                self.indent(1);
+                let condition = self.expr(condition);
+                self.line(&format!("if (!lox_truthy({condition})) break;"));
+                self.indent(-1);
+                self.body(body);
+                self.line("}");
+            }
+            Stmt::ForIn {
+                name,
+                iterable,
+                body,
+                ..
+            } => {
+                let iterable = self.expr(iterable);
+                self.count += 1;
+                let (iter, item) = (
+                    format!("iter_{}", self.count),
+                    format!("item_{}", self.count),
+                );
+                self.line(&format!("LoxIter {iter} = lox_iter({iterable});"));
+                self.line(&format!("LoxValue {item};"));
+                self.line(&format!("while (lox_iter_next(&{iter}, &{item})) {{"));
+
+                // the body is a scope of its own inside the one of the variable
+                self.scoped(|emitter| {
+                    emitter.define(*name, &item);
+                    emitter.stmt(body);
+                });
+                self.line("}");
+            }
+            Stmt::Switch {
+                subject,
+                cases,
+                otherwise,
+                ..
+            } => {
+                let subject = self.expr(subject);
+                let subject = self.temp(&subject);
+
+                // each case is checked in the else of the previous one, since evaluating its value
+                // may take statements
+                for (value, body) in cases.iter() {
+                    let value = self.expr(value);
+                    self.line(&format!("if (lox_equal({subject}, {value})) {{"));
+                    self.body(body);
+                    self.line("} else {");
+                    self.indent(1);
+                }
+                if let Some(otherwise) = otherwise {
+                    self.line("{");
+                    self.body(otherwise);
+                    self.line("}");
+                }
+                for _ in cases.iter() {
+                    self.indent(-1);
+                    self.line("}");
+                }
+            }
+            Stmt::Function { func } => {
+                let name = self.interner.resolve(func.name).to_owned();
+                let global = self.is_global();
+                if !global {
+                    self.define(func.name, "lox_nil()");
+                }
+                let value = self.function(func, &name, Kind::Function);
+                self.assign(&name, &value);
+            }
+            Stmt::Return { value, .. } => match (value, self.current().kind) {
+                (_, Kind::Initializer) => self.line("return *v_this;"),
+                (Some(value), _) => {
+                    let value = self.expr(value);
+                    self.line(&format!("return {value};"));
+                }
+                (None, _) => self.line("return lox_nil();"),
+            },
+            Stmt::Class { name, methods, .. } => {
+                let lox_name = self.interner.resolve(*name).to_owned();
+                let class = format!("lox_class(\"{lox_name}\")");
+                let class = match self.is_global() {
+                    true => {
+                        self.assign(&lox_name, &class);
+                        format!("G_{lox_name}")
+                    }
+                    false => {
+                        self.define(*name, &class);
+                        format!("*v_{lox_name}")
+                    }
+                };
+
+                for method in methods.iter() {
+                    let method_name = self.interner.resolve(method.name);
+                    let kind = match method_name {
+                        "init" => Kind::Initializer,
+                        _ => Kind::Method,
+                    };
+                    let c_name = format!("{lox_name}_{method_name}");
+                    let method = self.function(method, &c_name, kind);
+                    self.line(&format!("lox_method({class}, {method});"));
+                }
+            }
+        }
+    }
+
+    /// The body of a statement, whose braces are written by the caller.
+    fn body(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block { statements, .. } => {
+                self.scoped(|emitter| statements.iter().for_each(|stmt| emitter.stmt(stmt)))
+            }
+            stmt => self.scoped(|emitter| emitter.stmt(stmt)),
+        }
+    }
+
+    /// Translate `func` into a C function and return the expression that creates it.
+    fn function(&mut self, func: &StmtFunction, c_name: &str, kind: Kind) -> String {
+        self.count += 1;
+        let c_name = format!("fn_{}_{c_name}", self.count);
+        let name = self.interner.resolve(func.name);
+        let arity = func.params.len();
+
+        self.stack.push(Function::new(kind, 1));
+        if kind != Kind::Function {
+            self.declare(THIS);
+            self.line("LoxValue *v_this = lox_cell(this_);");
+        }
+        for (i, param) in func.params.iter().enumerate() {
+            self.define(*param, &format!("argv[{i}]"));
+        }
+        for stmt in func.body.iter() {
+            self.stmt(stmt);
+        }
+        match kind {
+            Kind::Initializer => self.line("return *v_this;"),
+            _ => self.line("return lox_nil();"),
+        }
+        let function = self
+            .stack
+            .pop()
+            .expect("the function should be on the stack");
+
+        let params = "LoxFunction *self, LoxValue this_, int argc, LoxValue *argv";
+        let mut out = format!("static LoxValue {c_name}({params}) {{\n");
+        out.push_str(&format!("{INDENT}LOX_UNUSED;\n"));
+        for (i, capture) in function.captures.iter().enumerate() {
+            out.push_str(&format!(
+                "{INDENT}LoxValue *c_{capture} = self->captures[{i}];\n"
+            ));
+        }
+        if function.temps > 0 {
+            out.push_str(&format!("{INDENT}LoxValue t[{}];\n", function.temps));
+        }
+        out.push_str(&function.out);
+        out.push_str("}\n");
+        self.functions.push(out);
+
+        // the captured variables as seen from where the function is declared
+        let captures = function
+            .captures
+            .iter()
+            .map(|capture| self.lookup(self.stack.len() - 1, capture))
+            .collect::<Option<Vec<_>>>()
+            .expect("captured variables should be visible where the function is declared");
+        match captures.is_empty() {
+            true => format!("lox_function(\"{name}\", {arity}, {c_name}, 0, NULL)"),
+            false => format!(
+                "lox_function(\"{name}\", {arity}, {c_name}, {}, (LoxValue *[]){{{}}})",
+                captures.len(),
+                captures.join(", ")
+            ),
+        }
+    }
+
+    /// Evaluate `expr` and return an operand holding its value: a temporary or a constant.
+    fn expr(&mut self, expr: &Expr) -> String {
+        match expr {
+            Expr::ValExpr(expr, _) => self.val_expr(expr),
+            Expr::RefExpr(expr, _) => self.ref_expr(expr),
+        }
+    }
+
+    fn val_expr(&mut self, expr: &ValExpr) -> String {
+        match expr {
+            ValExpr::Literal { value } => match &value.tok {
+                token::Literal::Number(num) => format!("lox_number({})", number(*num)),
+                token::Literal::Int(int) => format!("lox_number({})", number(*int as f64)),
+                token::Literal::String(str) => {
+                    let str = self.interner.resolve(*str);
+                    format!("lox_str({}, {})", string(str), str.len())
+                }
+                token::Literal::True => "lox_bool(true)".to_owned(),
+                token::Literal::False => "lox_bool(false)".to_owned(),
+                token::Literal::Nil => "lox_nil()".to_owned(),
+            },
+            ValExpr::Unary { operator, right } => {
+                let right = self.expr(right);
+                match operator.tok {
+                    token::UnaryOp::Minus => self.temp(&format!("lox_neg({right})")),
+                    token::UnaryOp::Not => self.temp(&format!("lox_not({right})")),
+                }
+            }
+            ValExpr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let func = match operator.tok {
+                    token::BinaryOp::Equal => "eq",
+                    token::BinaryOp::NotEqual => "ne",
+                    token::BinaryOp::Less => "lt",
+                    token::BinaryOp::LessEq => "le",
+                    token::BinaryOp::Greater => "gt",
+                    token::BinaryOp::GreaterEq => "ge",
+                    token::BinaryOp::Add => "add",
+                    token::BinaryOp::Sub => "sub",
+                    token::BinaryOp::Mul => "mul",
+                    token::BinaryOp::Div => "div",
+                    token::BinaryOp::BitAnd => "band",
+                    token::BinaryOp::BitOr => "bor",
+                    token::BinaryOp::BitXor => "bxor",
+                    token::BinaryOp::ShiftLeft => "shl",
+                    token::BinaryOp::ShiftRight => "shr",
+                };
+                let left = self.expr(left);
+                let right = self.expr(right);
+                self.temp(&format!("lox_{func}({left}, {right})"))
+            }
+            ValExpr::Grouping { expr, .. } => self.val_expr(expr),
+            ValExpr::Logical { left, kind, right } => {
+                let left = self.expr(left);
+                let result = self.temp(&left);
+                match kind.tok {
+                    token::LogicalOp::And => self.line(&format!("if (lox_truthy({result})) {{")),
+                    token::LogicalOp::Or => self.line(&format!("if (!lox_truthy({result})) {{")),
+                }
+                self.indent(1);
+                let right = self.expr(right);
+                self.line(&format!("{result} = {right};"));
+                self.indent(-1);
+                self.line("}");
+                result
+            }
+            ValExpr::Call { callee, args, .. } => {
+                let callee = self.expr(callee);
+                let args = self.list(args);
+                self.temp(&format!("lox_call({callee}, {args})"))
+            }
+            ValExpr::List { elements, .. } => {
+                let elements = self.list(elements);
+                self.temp(&format!("lox_list({elements})"))
+            }
+        }
+    }
+
+    fn ref_expr(&mut self, expr: &RefExpr) -> String {
+        match expr {
+            RefExpr::Variable { var } => {
+                let name = self.interner.resolve(var.tok.name).to_owned();
+                match self.resolve(&name) {
+                    Some(cell) => self.temp(&format!("*{cell}")),
+                    None => self.temp(&format!("lox_global(G_{name}, \"{name}\")")),
+                }
+            }
+            RefExpr::Grouping { expr, .. } => self.ref_expr(expr),
+            RefExpr::Assignment { var, value } => {
+                let value = self.expr(value);
+                let name = self.interner.resolve(var.tok.name).to_owned();
+                match self.resolve(&name) {
+                    Some(cell) => self.line(&format!("*{cell} = {value};")),
+                    None => self.line(&format!(
+                        "lox_assign_global(&G_{name}, \"{name}\", {value});"
+                    )),
+                }
+                value
+            }
+            RefExpr::Get { object, prop } => {
+                let object = self.expr(object);
+                let name = self.interner.resolve(prop.tok.name);
+                self.temp(&format!("lox_get({object}, \"{name}\")"))
+            }
+            RefExpr::Set {
+                object,
+                prop,
+                value,
+            } => {
+                let object = self.expr(object);
+                let value = self.expr(value);
+                let name = self.interner.resolve(prop.tok.name);
+                self.line(&format!("lox_set({object}, \"{name}\", {value});"));
+                value
+            }
+            RefExpr::This { .. } => {
+                let cell = self
+                    .resolve(THIS)
+                    .expect("this outside of a method should have been rejected by Resolver");
+                self.temp(&format!("*{cell}"))
+            }
+            RefExpr::Index { object, index, .. } => {
+                let object = self.expr(object);
+                let index = self.expr(index);
+                self.temp(&format!("lox_index({object}, {index})"))
+            }
+            RefExpr::IndexSet {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                let object = self.expr(object);
+                let index = self.expr(index);
+                let value = self.expr(value);
+                self.line(&format!("lox_set_index({object}, {index}, {value});"));
+                value
+            }
+        }
+    }
+
+    /// The number of `exprs` and an array of their values, as taken by `lox_call` and `lox_list`.
+    fn list(&mut self, exprs: &[Expr]) -> String {
+        let values = exprs.iter().map(|expr| self.expr(expr)).collect::<Vec<_>>();
+        match values.is_empty() {
+            true => "0, NULL".to_owned(),
+            false => format!("{}, (LoxValue[]){{{}}}", values.len(), values.join(", ")),
+        }
+    }
+
+    /// Store `value` in a new temporary.
+    fn temp(&mut self, value: &str) -> String {
+        let function = self.current();
+        let temp = format!("t[{}]", function.temps);
+        function.temps += 1;
+        self.line(&format!("{temp} = {value};"));
+        temp
+    }
+
+    /// Declare the variable `name` with `value`, as a global in `main` or a local otherwise.
+    fn define(&mut self, name: Key, value: &str) {
+        let name = self.interner.resolve(name).to_owned();
+        match self.is_global() {
+            true => self.assign(&name, value),
+            false => {
+                self.declare(&name);
+                self.line(&format!("LoxValue *v_{name} = lox_cell({value});"));
+            }
+        }
+    }
+
+    /// Assign to a variable that is already declared, or a global.
+    fn assign(&mut self, name: &str, value: &str) {
+        match self.resolve(name) {
+            Some(cell) => self.line(&format!("*{cell} = {value};")),
+            None => {
+                self.globals.insert(name.to_owned());
+                self.line(&format!("G_{name} = {value};"));
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &str) {
+        let scope = self.current().scopes.last_mut();
+        scope
+            .expect("globals are not declared")
+            .insert(name.to_owned());
+    }
+
+    /// The cell of the local `name` as seen from the current function, `None` for a global.
+    fn resolve(&mut self, name: &str) -> Option<String> {
+        let cell = self.lookup(self.stack.len() - 1, name);
+        if cell.is_none() {
+            self.globals.insert(name.to_owned());
+        }
+        cell
+    }
+
+    /// The cell of the local `name` in the function at `level` of the stack. A local of an
+    /// enclosing function is captured by every function in between.
+    fn lookup(&mut self, level: usize, name: &str) -> Option<String> {
+        let function = &self.stack[level];
+        if function.scopes.iter().any(|scope| scope.contains(name)) {
+            return Some(format!("v_{name}"));
+        } else if function.captures.iter().any(|capture| capture == name) {
+            return Some(format!("c_{name}"));
+        } else if level == 0 {
+            return None;
+        }
+
+        self.lookup(level - 1, name)?;
+        self.stack[level].captures.push(name.to_owned());
+        Some(format!("c_{name}"))
+    }
+
+    fn is_global(&self) -> bool {
+        let function = self
+            .stack
+            .last()
+            .expect("main should always be on the stack");
+        function.kind == Kind::Main && function.scopes.is_empty()
+    }
+
+    fn current(&mut self) -> &mut Function {
+        self.stack
+            .last_mut()
+            .expect("main should always be on the stack")
+    }
+
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.current().scopes.push(FxHashSet::default());
+        self.indent(1);
+        f(self);
+        self.indent(-1);
+        self.current().scopes.pop();
+    }
+
+    fn indent(&mut self, by: isize) {
+        let function = self.current();
+        function.indent = function.indent.saturating_add_signed(by);
+    }
+
+    fn line(&mut self, text: &str) {
+        let function = self.current();
+        for _ in 0..function.indent {
+            function.out.push_str(INDENT);
+        }
+        function.out.push_str(text);
+        function.out.push('\n');
+    }
+}
+
+/// A C double literal of `num`, which is always finite and positive in a Lox literal.
+fn number(num: f64) -> String {
+    let num = format!("{num:?}");
+    match num.contains(['.', 'e']) {
+        true => num,
+        false => format!("{num}.0"),
+    }
+}
+
+/// A C string literal of `str`. Bytes outside of printable ASCII are written in octal, which is
+/// always three digits so a digit that follows isn't taken as part of the escape.
+fn string(str: &str) -> String {
+    let mut out = String::with_capacity(str.len() + 2);
+    out.push('"');
+    for byte in str.bytes() {
+        match byte {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            b'\n' => out.push_str("\\n"),
+            b'\t' => out.push_str("\\t"),
+            // `??` starts a trigraph in older C standards
+            b'?' => out.push_str("\\?"),
+            b' '..=b'~' => out.push(byte as char),
+            byte => out.push_str(&format!("\\{byte:03o}")),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,537 @@
+//! Lox to JavaScript. Values map to their JavaScript counterparts, `nil` to `null`, lists to
+//! arrays, and maps to `Map`. Operations whose semantics differ, e.g. `+` that doesn't convert its
+//! operands or truthiness, go through the runtime in `runtime.js`, whose functions all live in `$`.
+//!
+//! Globals are declared with `var` so they can be declared again like in Lox, locals with `let`.
+//! Local functions are arrow functions, so they capture `this` of the method they're declared in.
+//!
+//! Lox resolves a local variable to the declarations before it, while `let` covers its whole block
+//! in JavaScript. A local that is declared after a variable of the same name from an outer scope is
+//! used in its block is renamed, e.g. to `a$1`, so the uses before it still see the outer one.
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::dialect::Dialect;
+use crate::interp::interner::{Interner, Key};
+use crate::interp::value::NumberStyle;
+use crate::parse::expr::{Expr, RefExpr, ValExpr};
+use crate::parse::stmt::{Stmt, StmtFunction};
+use crate::parse::{token, Program};
+
+const RUNTIME: &str = include_str!("runtime.js");
+
+/// Names that are valid in Lox but can't be used as is in JavaScript: keywords, and globals the
+/// runtime relies on. They are suffixed with `$`, which can't appear in a Lox name.
+const RESERVED: &[&str] = &[
+    "__proto__",
+    "arguments",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "const",
+    "constructor",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "enum",
+    "eval",
+    "export",
+    "extends",
+    "finally",
+    "function",
+    "implements",
+    "import",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "static",
+    "switch",
+    "throw",
+    "try",
+    "typeof",
+    "undefined",
+    "void",
+    "with",
+    "yield",
+    "Array",
+    "Date",
+    "Error",
+    "Infinity",
+    "Map",
+    "NaN",
+    "Number",
+    "Object",
+    "String",
+    "WeakMap",
+    "WeakSet",
+    "console",
+    "process",
+];
+
+const INDENT: &str = "  ";
+
+pub struct Emitter<'a> {
+    interner: &'a Interner,
+    style: NumberStyle,
+    dialect: Dialect,
+    out: String,
+    indent: usize,
+
+    /// The local scopes around the current statement, empty for globals.
+    scopes: Vec<Scope>,
+
+    /// Number of locals renamed so far, to make the new names unique.
+    renamed: usize,
+
+    /// Whether the current function is an initializer, which always returns `this`.
+    in_init: bool,
+}
+
+#[derive(Default)]
+struct Scope {
+    /// The JavaScript name of each local declared in the scope.
+    names: FxHashMap<Key, String>,
+
+    /// Variables used in the scope that are declared outside of it.
+    used: FxHashSet<Key>,
+}
+
+impl<'a> Emitter<'a> {
+    pub fn new(interner: &'a Interner, style: NumberStyle, dialect: Dialect) -> Self {
+        Self {
+            interner,
+            style,
+            dialect,
+            out: String::new(),
+            indent: 0,
+            scopes: Vec::new(),
+            renamed: 0,
+            in_init: false,
+        }
+    }
+
+    pub fn program(mut self, program: &Program) -> String {
+        self.out.push_str(RUNTIME);
+        self.out.push('\n');
+        if self.dialect.deep_equals {
+            self.out
+                .push_str("var deepEquals = $.natives.deepEquals;\n\n");
+        }
+        if self.style == NumberStyle::Rust {
+            self.out.push_str("$.rustNumbers();\n\n");
+        }
+        for stmt in program.statements.iter() {
+            self.stmt(stmt);
+        }
+        self.out
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expr { expr } => {
+                let expr = match expr.as_ref() {
+                    // no need for the parentheses of an assignment used as a statement
+                    Expr::RefExpr(RefExpr::Assignment { var, value }, _) => {
+                        let value = self.expr(value);
+                        format!("{} = {value}", self.var(var.tok.name))
+                    }
+                    Expr::RefExpr(
+                        RefExpr::Set {
+                            object,
+                            prop,
+                            value,
+                        },
+                        _,
+                    ) if is_this(object) => {
+                        format!("this.{} = {}", self.name(prop.tok.name), self.expr(value))
+                    }
+                    expr => self.expr(expr),
+                };
+                self.line(&format!("{expr};"));
+            }
+            Stmt::Print { expr, .. } => {
+                let expr = self.expr(expr);
+                self.line(&format!("$.print({expr});"));
+            }
+            Stmt::Var { name, init, .. } => {
+                let init = match init {
+                    Some(init) => self.expr(init),
+                    None => "null".to_owned(),
+                };
+                let declare = self.declare();
+                let name = self.define(*name);
+                self.line(&format!("{declare} {name} = {init};"));
+            }
+            Stmt::Block { statements, .. } => {
+                self.line("{");
+                self.scoped(|emitter| statements.iter().for_each(|stmt| emitter.stmt(stmt)));
+                self.line("}");
+            }
+            Stmt::If {
+                condition,
+                then,
+                otherwise,
+                ..
+            } => {
+                let condition = self.expr(condition);
+                self.line(&format!("if ($.truthy({condition})) {{"));
+                self.body(then);
+                if let Some(otherwise) = otherwise {
+                    self.line("} else {");
+                    self.body(otherwise);
+                }
+                self.line("}");
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                let condition = self.expr(condition);
+                self.line(&format!("while ($.truthy({condition})) {{"));
+                self.body(body);
+                self.line("}");
+            }
+            Stmt::ForIn {
+                name,
+                iterable,
+                body,
+                ..
+            } => {
+                let iterable = self.expr(iterable);
+                self.scopes.push(Scope::default());
+                let name = self.define(*name);
+                self.line(&format!("for (let {name} of $.iter({iterable})) {{"));
+                self.body(body);
+                self.scopes.pop();
+                self.line("}");
+            }
+            Stmt::Switch {
+                subject,
+                cases,
+                otherwise,
+                ..
+            } => {
+                // the subject is evaluated once, the values in order until one is equal to it
+                let subject = self.expr(subject);
+                self.line("{");
+                self.indent += 1;
+                self.line(&format!("const $subject = {subject};"));
+                for (i, (value, body)) in cases.iter().enumerate() {
+                    let value = self.expr(value);
+                    let keyword = if i == 0 { "if" } else { "} else if" };
+                    self.line(&format!("{keyword} ($.eq($subject, {value})) {{"));
+                    self.body(body);
+                }
+                match (otherwise, cases.is_empty()) {
+                    (Some(otherwise), empty) => {
+                        self.line(if empty { "{" } else { "} else {" });
+                        self.body(otherwise);
+                        self.line("}");
+                    }
+                    (None, false) => self.line("}"),
+                    (None, true) => (),
+                }
+                self.indent -= 1;
+                self.line("}");
+            }
+            Stmt::Function { func } => self.function(func),
+            Stmt::Return { value, .. } => match (value, self.in_init) {
+                (_, true) => self.line("return this;"),
+                (Some(value), false) => {
+                    let value = self.expr(value);
+                    self.line(&format!("return {value};"));
+                }
+                (None, false) => self.line("return null;"),
+            },
+            Stmt::Class { name, methods, .. } => {
+                let declare = self.declare();
+                let js_name = self.define(*name);
+                let lox_name = self.interner.resolve(*name);
+                self.line(&format!(
+                    "{declare} {js_name} = $.class({lox_name:?}, class {{"
+                ));
+                self.indent += 1;
+                for method in methods.iter() {
+                    self.method(method);
+                }
+                self.indent -= 1;
+                self.line("});");
+            }
+        }
+    }
+
+    /// The body of a statement, always enclosed in braces by the caller.
+    fn body(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block { statements, .. } => {
+                self.scoped(|emitter| statements.iter().for_each(|stmt| emitter.stmt(stmt)))
+            }
+            stmt => self.scoped(|emitter| emitter.stmt(stmt)),
+        }
+    }
+
+    fn function(&mut self, func: &StmtFunction) {
+        // declared before its body so it can call itself
+        let global = self.scopes.is_empty();
+        let name = self.define(func.name);
+
+        self.scopes.push(Scope::default());
+        let params = self.params(func);
+        match global {
+            true => self.line(&format!("function {name}({params}) {{")),
+            false => self.line(&format!("let {name} = ({params}) => {{")),
+        }
+        self.function_body(func, false);
+        self.scopes.pop();
+
+        match global {
+            true => self.line("}"),
+            false => self.line("};"),
+        }
+    }
+
+    fn method(&mut self, method: &StmtFunction) {
+        self.scopes.push(Scope::default());
+        let name = self.name(method.name);
+        let params = self.params(method);
+        self.line(&format!("{name}({params}) {{"));
+
+        let is_init = self.interner.resolve(method.name) == "init";
+        self.function_body(method, is_init);
+        self.scopes.pop();
+        self.line("}");
+    }
+
+    /// The statements of `func`, in the scope of its parameters.
+    fn function_body(&mut self, func: &StmtFunction, is_init: bool) {
+        let in_init = std::mem::replace(&mut self.in_init, is_init);
+        self.indent += 1;
+        func.body.iter().for_each(|stmt| self.stmt(stmt));
+        if is_init {
+            self.line("return this;");
+        }
+        self.indent -= 1;
+        self.in_init = in_init;
+    }
+
+    fn params(&mut self, func: &StmtFunction) -> String {
+        let params = func.params.iter().map(|param| self.define(*param));
+        params.collect::<Vec<_>>().join(", ")
+    }
+
+    fn expr(&mut self, expr: &Expr) -> String {
+        match expr {
+            Expr::ValExpr(expr, _) => self.val_expr(expr),
+            Expr::RefExpr(expr, _) => self.ref_expr(expr),
+        }
+    }
+
+    fn val_expr(&mut self, expr: &ValExpr) -> String {
+        match expr {
+            ValExpr::Literal { value } => match &value.tok {
+                token::Literal::Number(num) => num.to_string(),
+                token::Literal::Int(int) => int.to_string(),
+                token::Literal::String(str) => string(self.interner.resolve(*str)),
+                token::Literal::True => "true".to_owned(),
+                token::Literal::False => "false".to_owned(),
+                token::Literal::Nil => "null".to_owned(),
+            },
+            ValExpr::Unary { operator, right } => {
+                let right = self.expr(right);
+                match operator.tok {
+                    token::UnaryOp::Minus => format!("$.neg({right})"),
+                    token::UnaryOp::Not => format!("!$.truthy({right})"),
+                }
+            }
+            ValExpr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let func = match operator.tok {
+                    token::BinaryOp::Equal => "eq",
+                    token::BinaryOp::NotEqual => "ne",
+                    token::BinaryOp::Less => "lt",
+                    token::BinaryOp::LessEq => "le",
+                    token::BinaryOp::Greater => "gt",
+                    token::BinaryOp::GreaterEq => "ge",
+                    token::BinaryOp::Add => "add",
+                    token::BinaryOp::Sub => "sub",
+                    token::BinaryOp::Mul => "mul",
+                    token::BinaryOp::Div => "div",
+                    token::BinaryOp::BitAnd => "band",
+                    token::BinaryOp::BitOr => "bor",
+                    token::BinaryOp::BitXor => "bxor",
+                    token::BinaryOp::ShiftLeft => "shl",
+                    token::BinaryOp::ShiftRight => "shr",
+                };
+                let left = self.expr(left);
+                format!("$.{func}({left}, {})", self.expr(right))
+            }
+            ValExpr::Grouping { expr, .. } => self.val_expr(expr),
+            ValExpr::Logical { left, kind, right } => {
+                // the right operand is only evaluated when needed
+                let func = match kind.tok {
+                    token::LogicalOp::And => "and",
+                    token::LogicalOp::Or => "or",
+                };
+                let left = self.expr(left);
+                format!("$.{func}({left}, () => {})", self.expr(right))
+            }
+            ValExpr::Call { callee, args, .. } => {
+                let callee = match callee.as_ref() {
+                    // `this` is always an instance, so its methods can be called directly
+                    Expr::RefExpr(RefExpr::Get { object, prop }, _) if is_this(object) => {
+                        format!("this.{}", self.name(prop.tok.name))
+                    }
+                    callee => self.expr(callee),
+                };
+                format!("{callee}({})", self.list(args))
+            }
+            ValExpr::List { elements, .. } => format!("[{}]", self.list(elements)),
+        }
+    }
+
+    fn ref_expr(&mut self, expr: &RefExpr) -> String {
+        match expr {
+            RefExpr::Variable { var } => self.var(var.tok.name),
+            RefExpr::Grouping { expr, .. } => self.ref_expr(expr),
+            RefExpr::Assignment { var, value } => {
+                let value = self.expr(value);
+                format!("({} = {value})", self.var(var.tok.name))
+            }
+            RefExpr::Get { object, prop } => {
+                let name = self.name(prop.tok.name);
+                format!("$.get({}, {})", self.expr(object), string(&name))
+            }
+            RefExpr::Set {
+                object,
+                prop,
+                value,
+            } => {
+                let name = self.name(prop.tok.name);
+                match is_this(object) {
+                    true => format!("(this.{name} = {})", self.expr(value)),
+                    false => {
+                        let object = self.expr(object);
+                        format!("$.set({object}, {}, {})", string(&name), self.expr(value))
+                    }
+                }
+            }
+            RefExpr::This { .. } => "this".to_owned(),
+            RefExpr::Index { object, index, .. } => {
+                let object = self.expr(object);
+                format!("$.index({object}, {})", self.expr(index))
+            }
+            RefExpr::IndexSet {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                let object = self.expr(object);
+                let index = self.expr(index);
+                format!("$.setIndex({object}, {index}, {})", self.expr(value))
+            }
+        }
+    }
+
+    fn list(&mut self, exprs: &[Expr]) -> String {
+        let exprs = exprs.iter().map(|expr| self.expr(expr)).collect::<Vec<_>>();
+        exprs.join(", ")
+    }
+
+    /// The JavaScript name of a use of the variable `name`, marking it as used in the scopes
+    /// between here and its declaration.
+    fn var(&mut self, name: Key) -> String {
+        for scope in self.scopes.iter_mut().rev() {
+            match scope.names.get(&name) {
+                Some(js_name) => return js_name.clone(),
+                None => scope.used.insert(name),
+            };
+        }
+        self.name(name)
+    }
+
+    /// Declare `name` in the current scope and return its JavaScript name.
+    fn define(&mut self, name: Key) -> String {
+        let js_name = self.name(name);
+        let Some(scope) = self.scopes.last_mut() else {
+            return js_name;
+        };
+
+        let js_name = match scope.used.contains(&name) {
+            true => {
+                self.renamed += 1;
+                format!("{js_name}${}", self.renamed)
+            }
+            false => js_name,
+        };
+        scope.names.insert(name, js_name.clone());
+        js_name
+    }
+
+    /// A name that is valid in JavaScript, used as is for globals and properties.
+    fn name(&self, name: Key) -> String {
+        let name = self.interner.resolve(name);
+        match RESERVED.contains(&name) {
+            true => format!("{name}$"),
+            false => name.to_owned(),
+        }
+    }
+
+    fn declare(&self) -> &'static str {
+        match self.scopes.is_empty() {
+            true => "var",
+            false => "let",
+        }
+    }
+
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(Scope::default());
+        self.indent += 1;
+        f(self);
+        self.indent -= 1;
+        self.scopes.pop();
+    }
+
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+}
+
+fn is_this(expr: &Expr) -> bool {
+    matches!(expr, Expr::RefExpr(RefExpr::This { .. }, _))
+}
+
+/// A JavaScript string literal of `str`.
+fn string(str: &str) -> String {
+    let mut out = String::with_capacity(str.len() + 2);
+    out.push('"');
+    for c in str.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() || c == '\u{2028}' || c == '\u{2029}' => {
+                out.push_str(&format!("\\u{{{:x}}}", c as u32))
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
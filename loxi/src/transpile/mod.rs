@@ -0,0 +1,73 @@
+//! Backends that translate a Lox program into another language instead of running it. They share
+//! the lexer, parser, and resolver with the interpreter, so only valid programs are translated.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use strum::IntoEnumIterator;
+
+use crate::dialect::Dialect;
+use crate::interp::interner::Interner;
+use crate::interp::value::NumberStyle;
+use crate::parse::Program;
+
+mod c;
+mod js;
+
+#[cfg(test)]
+mod test;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, strum::EnumIter)]
+pub enum Target {
+    /// A script for any JavaScript engine, with a small runtime prepended to it.
+    Js,
+
+    /// A C11 source file with its runtime included, built with e.g. `cc -std=c11 out.c -lm`.
+    C,
+}
+
+impl Target {
+    pub fn name(self) -> &'static str {
+        match self {
+            Target::Js => "js",
+            Target::C => "c",
+        }
+    }
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Target::iter()
+            .find(|target| target.name() == name)
+            .ok_or_else(|| {
+                let targets = Target::iter().map(Target::name).collect::<Vec<_>>();
+                format!(
+                    "unknown target '{name}', expect one of: {}",
+                    targets.join(", ")
+                )
+            })
+    }
+}
+
+/// Translate `program` into the language of `target`. The result is a complete program that can
+/// be run on its own, printing numbers in `style` and with the natives enabled by `dialect`.
+pub fn transpile(
+    program: &Program,
+    interner: &Interner,
+    target: Target,
+    style: NumberStyle,
+    dialect: Dialect,
+) -> String {
+    match target {
+        Target::Js => js::Emitter::new(interner, style, dialect).program(program),
+        Target::C => c::Emitter::new(interner, style, dialect).program(program),
+    }
+}
@@ -0,0 +1,1279 @@
+/* Runtime of Lox programs translated to C by loxi. It only needs a C11 compiler and the standard
+ * library, e.g. `cc -std=c11 -O2 program.c -lm`. Objects are never freed. */
+
+#include <ctype.h>
+#include <math.h>
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <time.h>
+
+#if defined(__GNUC__)
+#define LOX_API static __attribute__((unused))
+#define LOX_NORETURN __attribute__((noreturn))
+#else
+#define LOX_API static
+#define LOX_NORETURN
+#endif
+
+#define LOX_MAX_CALL_DEPTH 1024
+
+typedef enum { LOX_UNDEF, LOX_NIL, LOX_BOOL, LOX_NUMBER, LOX_OBJ } LoxTag;
+
+typedef enum { LOX_STRING, LOX_FUNCTION, LOX_CLASS, LOX_INSTANCE, LOX_LIST, LOX_MAP } LoxKind;
+
+typedef struct LoxObj {
+    LoxKind kind;
+} LoxObj;
+
+typedef struct LoxValue {
+    LoxTag tag;
+    union {
+        bool boolean;
+        double number;
+        LoxObj *obj;
+    } as;
+} LoxValue;
+
+typedef struct LoxFunction LoxFunction;
+
+typedef LoxValue (*LoxFn)(LoxFunction *self, LoxValue this_, int argc, LoxValue *argv);
+
+struct LoxFunction {
+    LoxObj obj;
+    const char *name;
+    int min_arity, max_arity;
+    bool native;
+    LoxFn fn;
+    /* the instance of a bound method, or the receiver of a method of a builtin type */
+    LoxValue this_;
+    /* the variables of the enclosing functions used by this one */
+    LoxValue **captures;
+};
+
+typedef struct {
+    LoxObj obj;
+    size_t len;
+    char chars[];
+} LoxString;
+
+typedef struct {
+    LoxObj obj;
+    const char *name;
+    size_t counter;
+    LoxFunction **methods;
+    size_t len;
+} LoxClass;
+
+typedef struct {
+    const char *name;
+    LoxValue value;
+} LoxField;
+
+typedef struct {
+    LoxObj obj;
+    LoxClass *cls;
+    size_t id;
+    LoxField *fields;
+    size_t len, cap;
+} LoxInstance;
+
+typedef struct {
+    LoxObj obj;
+    LoxValue *items;
+    size_t len, cap;
+} LoxList;
+
+/* entries are kept in insertion order */
+typedef struct {
+    LoxObj obj;
+    LoxValue *keys, *values;
+    size_t len, cap;
+} LoxMap;
+
+static const LoxValue LOX_UNDEFINED = {LOX_UNDEF, {false}};
+static const LoxValue LOX_NIL_VALUE = {LOX_NIL, {false}};
+
+static int lox_depth = 0;
+
+/* How numbers are printed, see NumberStyle in value.rs. */
+static bool lox_rust_numbers = false;
+
+/* ---- values ---- */
+
+LOX_API LoxValue lox_nil(void) { return LOX_NIL_VALUE; }
+
+LOX_API LoxValue lox_bool(bool boolean) {
+    LoxValue value = {LOX_BOOL, {false}};
+    value.as.boolean = boolean;
+    return value;
+}
+
+LOX_API LoxValue lox_number(double number) {
+    LoxValue value = {LOX_NUMBER, {false}};
+    value.as.number = number;
+    return value;
+}
+
+LOX_API LoxValue lox_obj(void *obj) {
+    LoxValue value = {LOX_OBJ, {false}};
+    value.as.obj = (LoxObj *)obj;
+    return value;
+}
+
+LOX_API bool lox_is(LoxValue value, LoxKind kind) {
+    return value.tag == LOX_OBJ && value.as.obj->kind == kind;
+}
+
+#define LOX_AS(type, value) ((type *)(value).as.obj)
+
+LOX_API void *lox_alloc(size_t size) {
+    void *ptr = calloc(1, size);
+    if (!ptr) {
+        fputs("Out of memory\n", stderr);
+        exit(70);
+    }
+    return ptr;
+}
+
+LOX_API void *lox_grow(void *ptr, size_t *cap, size_t len, size_t size) {
+    if (len < *cap) return ptr;
+    *cap = *cap ? *cap * 2 : 8;
+    ptr = realloc(ptr, *cap * size);
+    if (!ptr) {
+        fputs("Out of memory\n", stderr);
+        exit(70);
+    }
+    return ptr;
+}
+
+LOX_API LoxValue lox_str(const char *chars, size_t len) {
+    LoxString *str = lox_alloc(sizeof(LoxString) + len + 1);
+    str->obj.kind = LOX_STRING;
+    str->len = len;
+    memcpy(str->chars, chars, len);
+    return lox_obj(str);
+}
+
+LOX_API LoxValue lox_cstr(const char *chars) { return lox_str(chars, strlen(chars)); }
+
+LOX_API LoxValue lox_list(size_t len, LoxValue *items) {
+    LoxList *list = lox_alloc(sizeof(LoxList));
+    list->obj.kind = LOX_LIST;
+    for (size_t i = 0; i < len; i++) {
+        list->items = lox_grow(list->items, &list->cap, list->len, sizeof(LoxValue));
+        list->items[list->len++] = items[i];
+    }
+    return lox_obj(list);
+}
+
+LOX_API LoxValue *lox_cell(LoxValue value) {
+    LoxValue *cell = lox_alloc(sizeof(LoxValue));
+    *cell = value;
+    return cell;
+}
+
+LOX_API const char *lox_type(LoxValue value) {
+    switch (value.tag) {
+    case LOX_UNDEF:
+    case LOX_NIL: return "<nil>";
+    case LOX_BOOL: return "<bool>";
+    case LOX_NUMBER: return "<number>";
+    case LOX_OBJ: break;
+    }
+    switch (value.as.obj->kind) {
+    case LOX_STRING: return "<string>";
+    case LOX_FUNCTION: return "<function>";
+    case LOX_CLASS: return "<class>";
+    case LOX_INSTANCE: return "<instance>";
+    case LOX_LIST: return "<list>";
+    case LOX_MAP: return "<map>";
+    }
+    return "<unknown>";
+}
+
+/* ---- errors ---- */
+
+LOX_API LOX_NORETURN void lox_error(const char *format, ...) {
+    va_list args;
+    va_start(args, format);
+    fflush(stdout);
+    fputs("RuntimeError: ", stderr);
+    vfprintf(stderr, format, args);
+    fputc('\n', stderr);
+    va_end(args);
+    exit(70);
+}
+
+/* ---- display ---- */
+
+typedef struct {
+    char *chars;
+    size_t len, cap;
+} LoxBuf;
+
+LOX_API void lox_buf_push(LoxBuf *buf, const char *chars, size_t len) {
+    while (buf->len + len + 1 > buf->cap) {
+        buf->cap = buf->cap ? buf->cap * 2 : 64;
+        buf->chars = realloc(buf->chars, buf->cap);
+        if (!buf->chars) lox_error("Out of memory");
+    }
+    memcpy(buf->chars + buf->len, chars, len);
+    buf->len += len;
+    buf->chars[buf->len] = '\0';
+}
+
+LOX_API void lox_buf_str(LoxBuf *buf, const char *chars) { lox_buf_push(buf, chars, strlen(chars)); }
+
+/* The shortest digits of a finite number that read back as the same number, in the scientific
+ * notation of printf, e.g. `1.5e+10`. */
+LOX_API void lox_shortest_sci(char sci[32], double num) {
+    for (int precision = 1; precision <= 17; precision++) {
+        snprintf(sci, 32, "%.*e", precision - 1, num);
+        if (strtod(sci, NULL) == num) break;
+    }
+}
+
+/* Numbers are formatted like Rust does: the shortest digits that read back as the same number,
+ * without an exponent, e.g. 1e21 is 1000000000000000000000. */
+LOX_API void lox_buf_rust_number(LoxBuf *buf, double num) {
+    const char *special = NULL;
+    if (isnan(num)) special = "nan";
+    else if (isinf(num)) special = num > 0 ? "inf" : "-inf";
+    else if (num == 0) special = signbit(num) ? "-0" : "0";
+    if (special) {
+        lox_buf_str(buf, special);
+        return;
+    }
+
+    char sci[32];
+    lox_shortest_sci(sci, num);
+
+    char digits[32];
+    size_t len = 0;
+    const char *c = sci;
+    if (*c == '-') {
+        lox_buf_str(buf, "-");
+        c++;
+    }
+    for (; *c != 'e'; c++) {
+        if (isdigit((unsigned char)*c)) digits[len++] = *c;
+    }
+    int exp = atoi(c + 1);
+    while (len > 1 && digits[len - 1] == '0') len--;
+
+    if (exp < 0) {
+        lox_buf_str(buf, "0.");
+        for (int i = 0; i < -exp - 1; i++) lox_buf_str(buf, "0");
+        lox_buf_push(buf, digits, len);
+    } else if ((size_t)exp + 1 >= len) {
+        lox_buf_push(buf, digits, len);
+        for (size_t i = len; i < (size_t)exp + 1; i++) lox_buf_str(buf, "0");
+    } else {
+        lox_buf_push(buf, digits, (size_t)exp + 1);
+        lox_buf_str(buf, ".");
+        lox_buf_push(buf, digits + exp + 1, len - exp - 1);
+    }
+}
+
+/* Numbers are formatted like jlox does with Java's Double.toString, e.g. 1e21 is 1.0E21. The
+ * interpreter prints integers in full, which are the integral numbers that fit in 64 bits here. */
+LOX_API void lox_buf_jlox_number(LoxBuf *buf, double num) {
+    double abs = fabs(num);
+    if (isnan(num)) {
+        lox_buf_str(buf, "NaN");
+        return;
+    }
+    if (isinf(num)) {
+        lox_buf_str(buf, num > 0 ? "Infinity" : "-Infinity");
+        return;
+    }
+    if (num == 0 || (abs >= 1e-3 && abs < 1e7) || (num == trunc(num) && abs < 0x1p63)) {
+        lox_buf_rust_number(buf, num);
+        return;
+    }
+
+    char sci[32];
+    lox_shortest_sci(sci, num);
+    char *e = strchr(sci, 'e');
+    lox_buf_push(buf, sci, (size_t)(e - sci));
+    if (!memchr(sci, '.', (size_t)(e - sci))) lox_buf_str(buf, ".0");
+
+    char exp[16];
+    snprintf(exp, sizeof(exp), "E%d", atoi(e + 1));
+    lox_buf_str(buf, exp);
+}
+
+LOX_API void lox_buf_number(LoxBuf *buf, double num) {
+    if (lox_rust_numbers) lox_buf_rust_number(buf, num);
+    else lox_buf_jlox_number(buf, num);
+}
+
+LOX_API void lox_buf_nested(LoxBuf *buf, LoxObj *obj, LoxObj **parents, size_t depth);
+
+LOX_API void lox_buf_value(LoxBuf *buf, LoxValue value, LoxObj **parents, size_t depth) {
+    char chars[64];
+    switch (value.tag) {
+    case LOX_UNDEF:
+    case LOX_NIL: lox_buf_str(buf, "nil"); return;
+    case LOX_BOOL: lox_buf_str(buf, value.as.boolean ? "true" : "false"); return;
+    case LOX_NUMBER: lox_buf_number(buf, value.as.number); return;
+    case LOX_OBJ: break;
+    }
+
+    LoxObj *obj = value.as.obj;
+    switch (obj->kind) {
+    case LOX_STRING:
+        lox_buf_push(buf, ((LoxString *)obj)->chars, ((LoxString *)obj)->len);
+        break;
+    case LOX_FUNCTION:
+        lox_buf_str(buf, ((LoxFunction *)obj)->native ? "<native_fun " : "<fun ");
+        lox_buf_str(buf, ((LoxFunction *)obj)->name);
+        lox_buf_str(buf, ">");
+        break;
+    case LOX_CLASS:
+        lox_buf_str(buf, "<class ");
+        lox_buf_str(buf, ((LoxClass *)obj)->name);
+        lox_buf_str(buf, ">");
+        break;
+    case LOX_INSTANCE:
+        lox_buf_str(buf, "<instance of:");
+        lox_buf_str(buf, ((LoxInstance *)obj)->cls->name);
+        snprintf(chars, sizeof(chars), " no:%zu>", ((LoxInstance *)obj)->id);
+        lox_buf_str(buf, chars);
+        break;
+    case LOX_LIST:
+    case LOX_MAP: lox_buf_nested(buf, obj, parents, depth); break;
+    }
+}
+
+/* A list or a map, printing [...] or {...} for one that contains itself instead of recursing
+ * forever. */
+LOX_API void lox_buf_nested(LoxBuf *buf, LoxObj *obj, LoxObj **parents, size_t depth) {
+    bool list = obj->kind == LOX_LIST;
+    for (size_t i = 0; i < depth; i++) {
+        if (parents[i] == obj) {
+            lox_buf_str(buf, list ? "[...]" : "{...}");
+            return;
+        }
+    }
+    LoxObj **nested = lox_alloc((depth + 1) * sizeof(LoxObj *));
+    if (depth > 0) memcpy(nested, parents, depth * sizeof(LoxObj *));
+    nested[depth] = obj;
+
+    if (list) {
+        LoxList *items = (LoxList *)obj;
+        lox_buf_str(buf, "[");
+        for (size_t i = 0; i < items->len; i++) {
+            if (i > 0) lox_buf_str(buf, ", ");
+            lox_buf_value(buf, items->items[i], nested, depth + 1);
+        }
+        lox_buf_str(buf, "]");
+    } else {
+        LoxMap *map = (LoxMap *)obj;
+        lox_buf_str(buf, "{");
+        for (size_t i = 0; i < map->len; i++) {
+            if (i > 0) lox_buf_str(buf, ", ");
+            lox_buf_value(buf, map->keys[i], nested, depth + 1);
+            lox_buf_str(buf, ": ");
+            lox_buf_value(buf, map->values[i], nested, depth + 1);
+        }
+        lox_buf_str(buf, "}");
+    }
+    free(nested);
+}
+
+/* The value as it's printed, the result is never freed. */
+LOX_API const char *lox_display(LoxValue value) {
+    LoxBuf buf = {NULL, 0, 0};
+    lox_buf_str(&buf, "");
+    lox_buf_value(&buf, value, NULL, 0);
+    return buf.chars;
+}
+
+LOX_API void lox_print(LoxValue value) {
+    fputs(lox_display(value), stdout);
+    fputc('\n', stdout);
+}
+
+/* ---- operators ---- */
+
+LOX_API bool lox_truthy(LoxValue value) {
+    switch (value.tag) {
+    case LOX_UNDEF:
+    case LOX_NIL: return false;
+    case LOX_BOOL: return value.as.boolean;
+    default: return true;
+    }
+}
+
+LOX_API bool lox_equal(LoxValue lhs, LoxValue rhs) {
+    if (lhs.tag == LOX_UNDEF) lhs = LOX_NIL_VALUE;
+    if (rhs.tag == LOX_UNDEF) rhs = LOX_NIL_VALUE;
+    if (lhs.tag != rhs.tag) return false;
+
+    switch (lhs.tag) {
+    case LOX_UNDEF:
+    case LOX_NIL: return true;
+    case LOX_BOOL: return lhs.as.boolean == rhs.as.boolean;
+    case LOX_NUMBER: return lhs.as.number == rhs.as.number;
+    case LOX_OBJ: break;
+    }
+    if (lox_is(lhs, LOX_STRING) && lox_is(rhs, LOX_STRING)) {
+        LoxString *a = LOX_AS(LoxString, lhs), *b = LOX_AS(LoxString, rhs);
+        return a->len == b->len && memcmp(a->chars, b->chars, a->len) == 0;
+    }
+    return lhs.as.obj == rhs.as.obj;
+}
+
+LOX_API LoxValue lox_eq(LoxValue lhs, LoxValue rhs) { return lox_bool(lox_equal(lhs, rhs)); }
+
+LOX_API LoxValue lox_ne(LoxValue lhs, LoxValue rhs) { return lox_bool(!lox_equal(lhs, rhs)); }
+
+LOX_API LoxValue lox_not(LoxValue value) { return lox_bool(!lox_truthy(value)); }
+
+LOX_API LoxValue lox_neg(LoxValue value) {
+    if (value.tag != LOX_NUMBER) {
+        lox_error("Invalid unary operation '-' on '%s'", lox_type(value));
+    }
+    return lox_number(-value.as.number);
+}
+
+LOX_API void lox_numbers(const char *op, LoxValue lhs, LoxValue rhs) {
+    if (lhs.tag != LOX_NUMBER || rhs.tag != LOX_NUMBER) {
+        lox_error("Invalid binary operation '%s' between '%s' and '%s'", op, lox_type(lhs),
+                  lox_type(rhs));
+    }
+}
+
+LOX_API LoxValue lox_add(LoxValue lhs, LoxValue rhs) {
+    if (lox_is(lhs, LOX_STRING) && lox_is(rhs, LOX_STRING)) {
+        LoxString *a = LOX_AS(LoxString, lhs), *b = LOX_AS(LoxString, rhs);
+        LoxValue result = lox_str(a->chars, a->len + b->len);
+        memcpy(LOX_AS(LoxString, result)->chars + a->len, b->chars, b->len);
+        return result;
+    }
+    lox_numbers("+", lhs, rhs);
+    return lox_number(lhs.as.number + rhs.as.number);
+}
+
+LOX_API LoxValue lox_sub(LoxValue lhs, LoxValue rhs) {
+    lox_numbers("-", lhs, rhs);
+    return lox_number(lhs.as.number - rhs.as.number);
+}
+
+LOX_API LoxValue lox_mul(LoxValue lhs, LoxValue rhs) {
+    lox_numbers("*", lhs, rhs);
+    return lox_number(lhs.as.number * rhs.as.number);
+}
+
+LOX_API LoxValue lox_div(LoxValue lhs, LoxValue rhs) {
+    lox_numbers("/", lhs, rhs);
+    return lox_number(lhs.as.number / rhs.as.number);
+}
+
+/* The number truncated to an integer, saturating at the bounds of `int64_t` and with nan as 0. */
+LOX_API int64_t lox_int(double num) {
+    if (isnan(num)) return 0;
+    if (num >= 9223372036854775807.0) return INT64_MAX;
+    if (num <= -9223372036854775808.0) return INT64_MIN;
+    return (int64_t)num;
+}
+
+LOX_API LoxValue lox_band(LoxValue lhs, LoxValue rhs) {
+    lox_numbers("&", lhs, rhs);
+    return lox_number((double)(lox_int(lhs.as.number) & lox_int(rhs.as.number)));
+}
+
+LOX_API LoxValue lox_bor(LoxValue lhs, LoxValue rhs) {
+    lox_numbers("|", lhs, rhs);
+    return lox_number((double)(lox_int(lhs.as.number) | lox_int(rhs.as.number)));
+}
+
+LOX_API LoxValue lox_bxor(LoxValue lhs, LoxValue rhs) {
+    lox_numbers("^", lhs, rhs);
+    return lox_number((double)(lox_int(lhs.as.number) ^ lox_int(rhs.as.number)));
+}
+
+/* Only the low 6 bits of the shift amount are used, and shifting left wraps around. */
+LOX_API LoxValue lox_shl(LoxValue lhs, LoxValue rhs) {
+    lox_numbers("<<", lhs, rhs);
+    uint64_t shifted = (uint64_t)lox_int(lhs.as.number) << (lox_int(rhs.as.number) & 63);
+    return lox_number((double)(int64_t)shifted);
+}
+
+LOX_API LoxValue lox_shr(LoxValue lhs, LoxValue rhs) {
+    lox_numbers(">>", lhs, rhs);
+    return lox_number((double)(lox_int(lhs.as.number) >> (lox_int(rhs.as.number) & 63)));
+}
+
+LOX_API LoxValue lox_lt(LoxValue lhs, LoxValue rhs) {
+    lox_numbers("<", lhs, rhs);
+    return lox_bool(lhs.as.number < rhs.as.number);
+}
+
+LOX_API LoxValue lox_le(LoxValue lhs, LoxValue rhs) {
+    lox_numbers("<=", lhs, rhs);
+    return lox_bool(lhs.as.number <= rhs.as.number);
+}
+
+LOX_API LoxValue lox_gt(LoxValue lhs, LoxValue rhs) {
+    lox_numbers(">", lhs, rhs);
+    return lox_bool(lhs.as.number > rhs.as.number);
+}
+
+LOX_API LoxValue lox_ge(LoxValue lhs, LoxValue rhs) {
+    lox_numbers(">=", lhs, rhs);
+    return lox_bool(lhs.as.number >= rhs.as.number);
+}
+
+/* ---- variables ---- */
+
+LOX_API LoxValue lox_global(LoxValue value, const char *name) {
+    if (value.tag == LOX_UNDEF) lox_error("Trying to access undefined variable: '%s'", name);
+    return value;
+}
+
+LOX_API LoxValue lox_assign_global(LoxValue *global, const char *name, LoxValue value) {
+    lox_global(*global, name);
+    return *global = value;
+}
+
+/* ---- functions and classes ---- */
+
+LOX_API LoxValue lox_function(const char *name, int arity, LoxFn fn, size_t len, LoxValue **captures) {
+    LoxFunction *func = lox_alloc(sizeof(LoxFunction));
+    func->obj.kind = LOX_FUNCTION;
+    func->name = name;
+    func->min_arity = func->max_arity = arity;
+    func->fn = fn;
+    func->this_ = LOX_NIL_VALUE;
+    func->captures = lox_alloc((len ? len : 1) * sizeof(LoxValue *));
+    memcpy(func->captures, captures, len * sizeof(LoxValue *));
+    return lox_obj(func);
+}
+
+LOX_API LoxValue lox_native(const char *name, int min_arity, int max_arity, LoxFn fn) {
+    LoxValue value = lox_function(name, min_arity, fn, 0, NULL);
+    LoxFunction *func = LOX_AS(LoxFunction, value);
+    func->max_arity = max_arity;
+    func->native = true;
+    return value;
+}
+
+LOX_API LoxValue lox_bind(LoxFunction *method, LoxValue this_) {
+    LoxFunction *bound = lox_alloc(sizeof(LoxFunction));
+    *bound = *method;
+    bound->this_ = this_;
+    return lox_obj(bound);
+}
+
+LOX_API LoxValue lox_class(const char *name) {
+    LoxClass *cls = lox_alloc(sizeof(LoxClass));
+    cls->obj.kind = LOX_CLASS;
+    cls->name = name;
+    cls->counter = 1;
+    return lox_obj(cls);
+}
+
+LOX_API void lox_method(LoxValue cls, LoxValue method) {
+    LoxClass *class_ = LOX_AS(LoxClass, cls);
+    class_->methods = realloc(class_->methods, (class_->len + 1) * sizeof(LoxFunction *));
+    class_->methods[class_->len++] = LOX_AS(LoxFunction, method);
+}
+
+LOX_API LoxFunction *lox_find_method(LoxClass *cls, const char *name) {
+    for (size_t i = cls->len; i > 0; i--) {
+        if (strcmp(cls->methods[i - 1]->name, name) == 0) return cls->methods[i - 1];
+    }
+    return NULL;
+}
+
+LOX_API LoxValue lox_call(LoxValue callee, int argc, LoxValue *argv) {
+    LoxFunction *func;
+    LoxValue this_;
+    if (lox_is(callee, LOX_FUNCTION)) {
+        func = LOX_AS(LoxFunction, callee);
+        this_ = func->this_;
+    } else if (lox_is(callee, LOX_CLASS)) {
+        LoxClass *cls = LOX_AS(LoxClass, callee);
+        LoxInstance *instance = lox_alloc(sizeof(LoxInstance));
+        instance->obj.kind = LOX_INSTANCE;
+        instance->cls = cls;
+        instance->id = cls->counter++;
+        this_ = lox_obj(instance);
+
+        func = lox_find_method(cls, "init");
+        if (!func) {
+            if (argc != 0) {
+                lox_error("Mismatched number of arguments. Expected 0 argument(s) got %d instead",
+                          argc);
+            }
+            return this_;
+        }
+    } else {
+        lox_error("Not a function or a callable object");
+    }
+
+    if (argc < func->min_arity || argc > func->max_arity) {
+        if (func->min_arity == func->max_arity) {
+            lox_error("Mismatched number of arguments. Expected %d argument(s) got %d instead",
+                      func->min_arity, argc);
+        }
+        lox_error("Mismatched number of arguments. Expected %d..=%d argument(s) got %d instead",
+                  func->min_arity, func->max_arity, argc);
+    }
+    if (++lox_depth > LOX_MAX_CALL_DEPTH) lox_error("Stack overflow.");
+    LoxValue result = func->fn(func, this_, argc, argv);
+    lox_depth--;
+    return result;
+}
+
+/* ---- builtin methods ---- */
+
+LOX_API size_t lox_index_arg(LoxValue value) {
+    double index = value.tag == LOX_NUMBER ? value.as.number : -1;
+    if (index < 0 || index != floor(index)) {
+        lox_error("Index must be a non-negative integer, got '%s'", lox_display(value));
+    }
+    return (size_t)index;
+}
+
+LOX_API LoxValue lox_key(LoxValue value) {
+    switch (value.tag) {
+    case LOX_UNDEF: return LOX_NIL_VALUE;
+    case LOX_NIL:
+    case LOX_BOOL: return value;
+    case LOX_NUMBER:
+        if (isnan(value.as.number)) break;
+        return value.as.number == 0 ? lox_number(0) : value;
+    case LOX_OBJ:
+        if (lox_is(value, LOX_STRING)) return value;
+        break;
+    }
+    lox_error("Invalid map key of type '%s', expect nil, bool, number (except nan), or string",
+              lox_type(value));
+}
+
+/* The position of `key` in `map`, or its length if it's not in the map. */
+LOX_API size_t lox_map_find(LoxMap *map, LoxValue key) {
+    key = lox_key(key);
+    for (size_t i = 0; i < map->len; i++) {
+        if (lox_equal(map->keys[i], key)) return i;
+    }
+    return map->len;
+}
+
+LOX_API void lox_map_insert(LoxMap *map, LoxValue key, LoxValue value) {
+    size_t i = lox_map_find(map, key);
+    if (i == map->len) {
+        size_t cap = map->cap;
+        map->keys = lox_grow(map->keys, &cap, map->len, sizeof(LoxValue));
+        map->values = lox_grow(map->values, &map->cap, map->len, sizeof(LoxValue));
+        map->keys[map->len++] = lox_key(key);
+    }
+    map->values[i] = value;
+}
+
+LOX_API void lox_list_push(LoxList *list, LoxValue value) {
+    list->items = lox_grow(list->items, &list->cap, list->len, sizeof(LoxValue));
+    list->items[list->len++] = value;
+}
+
+LOX_API LoxFunction *lox_callback(LoxValue value) {
+    if (!lox_is(value, LOX_FUNCTION) && !lox_is(value, LOX_CLASS)) {
+        lox_error("Invalid argument: expect '<function>', got '%s'", lox_type(value));
+    }
+    return (LoxFunction *)value.as.obj;
+}
+
+LOX_API LoxString *lox_string_arg(LoxValue value) {
+    if (!lox_is(value, LOX_STRING)) {
+        lox_error("Invalid argument: expect '<string>', got '%s'", lox_type(value));
+    }
+    return LOX_AS(LoxString, value);
+}
+
+/* Byte offset of the character at `index`, or `len` if there are not that many characters. */
+LOX_API size_t lox_char_offset(LoxString *str, size_t index) {
+    size_t offset = 0;
+    for (; offset < str->len; offset++) {
+        if (((unsigned char)str->chars[offset] & 0xC0) == 0x80) continue;
+        if (index-- == 0) return offset;
+    }
+    return str->len;
+}
+
+LOX_API size_t lox_char_count(LoxString *str) {
+    size_t count = 0;
+    for (size_t i = 0; i < str->len; i++) {
+        count += ((unsigned char)str->chars[i] & 0xC0) != 0x80;
+    }
+    return count;
+}
+
+LOX_API LoxValue lox_chars(LoxString *str) {
+    LoxValue list = lox_list(0, NULL);
+    for (size_t i = 0; i < str->len;) {
+        size_t end = i + 1;
+        while (end < str->len && ((unsigned char)str->chars[end] & 0xC0) == 0x80) end++;
+        lox_list_push(LOX_AS(LoxList, list), lox_str(str->chars + i, end - i));
+        i = end;
+    }
+    return list;
+}
+
+#define LOX_METHOD(name) LOX_API LoxValue name(LoxFunction *self, LoxValue this_, int argc, LoxValue *argv)
+#define LOX_UNUSED (void)self, (void)this_, (void)argc, (void)argv
+
+LOX_METHOD(lox_list_push_) {
+    LOX_UNUSED;
+    lox_list_push(LOX_AS(LoxList, this_), argv[0]);
+    return LOX_NIL_VALUE;
+}
+
+LOX_METHOD(lox_list_pop_) {
+    LOX_UNUSED;
+    LoxList *list = LOX_AS(LoxList, this_);
+    if (list->len == 0) lox_error("Can't pop from an empty list");
+    return list->items[--list->len];
+}
+
+LOX_METHOD(lox_list_insert_) {
+    LOX_UNUSED;
+    LoxList *list = LOX_AS(LoxList, this_);
+    size_t index = lox_index_arg(argv[0]);
+    if (index > list->len) lox_error("Index %zu is out of range for length %zu", index, list->len);
+    lox_list_push(list, LOX_NIL_VALUE);
+    memmove(list->items + index + 1, list->items + index, (list->len - 1 - index) * sizeof(LoxValue));
+    list->items[index] = argv[1];
+    return LOX_NIL_VALUE;
+}
+
+LOX_METHOD(lox_list_remove_) {
+    LOX_UNUSED;
+    LoxList *list = LOX_AS(LoxList, this_);
+    size_t index = lox_index_arg(argv[0]);
+    if (index >= list->len) lox_error("Index %zu is out of range for length %zu", index, list->len);
+    LoxValue removed = list->items[index];
+    memmove(list->items + index, list->items + index + 1, (list->len - 1 - index) * sizeof(LoxValue));
+    list->len--;
+    return removed;
+}
+
+LOX_METHOD(lox_list_len_) {
+    LOX_UNUSED;
+    return lox_number((double)LOX_AS(LoxList, this_)->len);
+}
+
+LOX_METHOD(lox_list_index_of_) {
+    LOX_UNUSED;
+    LoxList *list = LOX_AS(LoxList, this_);
+    for (size_t i = 0; i < list->len; i++) {
+        if (lox_equal(list->items[i], argv[0])) return lox_number((double)i);
+    }
+    return lox_number(-1);
+}
+
+LOX_METHOD(lox_list_contains_) {
+    return lox_bool(lox_list_index_of_(self, this_, argc, argv).as.number >= 0);
+}
+
+LOX_API int lox_order(LoxValue cmp, LoxValue lhs, LoxValue rhs) {
+    if (cmp.tag != LOX_UNDEF) {
+        LoxValue args[2] = {lhs, rhs};
+        LoxValue order = lox_call(cmp, 2, args);
+        if (order.tag != LOX_NUMBER) {
+            lox_error("Invalid argument: expect '<number>', got '%s'", lox_type(order));
+        }
+        return order.as.number < 0 ? -1 : order.as.number > 0;
+    }
+    if (lhs.tag == LOX_NUMBER && rhs.tag == LOX_NUMBER) {
+        return lhs.as.number < rhs.as.number ? -1 : lhs.as.number > rhs.as.number;
+    }
+    if (lox_is(lhs, LOX_STRING) && lox_is(rhs, LOX_STRING)) {
+        LoxString *a = LOX_AS(LoxString, lhs), *b = LOX_AS(LoxString, rhs);
+        int order = memcmp(a->chars, b->chars, a->len < b->len ? a->len : b->len);
+        if (order == 0) return a->len < b->len ? -1 : a->len > b->len;
+        return order;
+    }
+    lox_error("Invalid argument: expect '<number> or <string>', got '%s'",
+              lox_type(lhs.tag == LOX_NUMBER ? rhs : lhs));
+}
+
+/* stable merge sort, since the comparator may not be a total order */
+LOX_API void lox_sort(LoxValue cmp, LoxValue *items, LoxValue *scratch, size_t len) {
+    if (len <= 1) return;
+    size_t mid = len / 2;
+    lox_sort(cmp, items, scratch, mid);
+    lox_sort(cmp, items + mid, scratch, len - mid);
+
+    size_t left = 0, right = mid, out = 0;
+    while (left < mid && right < len) {
+        if (lox_order(cmp, items[right], items[left]) < 0) scratch[out++] = items[right++];
+        else scratch[out++] = items[left++];
+    }
+    while (left < mid) scratch[out++] = items[left++];
+    while (right < len) scratch[out++] = items[right++];
+    memcpy(items, scratch, len * sizeof(LoxValue));
+}
+
+LOX_METHOD(lox_list_sort_) {
+    LOX_UNUSED;
+    LoxList *list = LOX_AS(LoxList, this_);
+    LoxValue cmp = argc > 0 ? argv[0] : LOX_UNDEFINED;
+    if (argc > 0) lox_callback(cmp);
+
+    LoxValue *items = lox_alloc((list->len + 1) * sizeof(LoxValue));
+    LoxValue *scratch = lox_alloc((list->len + 1) * sizeof(LoxValue));
+    memcpy(items, list->items, list->len * sizeof(LoxValue));
+    lox_sort(cmp, items, scratch, list->len);
+    memcpy(list->items, items, list->len * sizeof(LoxValue));
+    free(items);
+    free(scratch);
+    return LOX_NIL_VALUE;
+}
+
+LOX_METHOD(lox_list_map_) {
+    LOX_UNUSED;
+    LoxList *list = LOX_AS(LoxList, this_);
+    lox_callback(argv[0]);
+    LoxValue result = lox_list(0, NULL);
+    for (size_t i = 0; i < list->len; i++) {
+        lox_list_push(LOX_AS(LoxList, result), lox_call(argv[0], 1, &list->items[i]));
+    }
+    return result;
+}
+
+LOX_METHOD(lox_list_filter_) {
+    LOX_UNUSED;
+    LoxList *list = LOX_AS(LoxList, this_);
+    lox_callback(argv[0]);
+    LoxValue result = lox_list(0, NULL);
+    for (size_t i = 0; i < list->len; i++) {
+        LoxValue item = list->items[i];
+        if (lox_truthy(lox_call(argv[0], 1, &item))) lox_list_push(LOX_AS(LoxList, result), item);
+    }
+    return result;
+}
+
+LOX_METHOD(lox_map_get_) {
+    LOX_UNUSED;
+    LoxMap *map = LOX_AS(LoxMap, this_);
+    size_t i = lox_map_find(map, argv[0]);
+    if (i < map->len) return map->values[i];
+    return argc > 1 ? argv[1] : LOX_NIL_VALUE;
+}
+
+LOX_METHOD(lox_map_set_) {
+    LOX_UNUSED;
+    lox_map_insert(LOX_AS(LoxMap, this_), argv[0], argv[1]);
+    return LOX_NIL_VALUE;
+}
+
+LOX_METHOD(lox_map_remove_) {
+    LOX_UNUSED;
+    LoxMap *map = LOX_AS(LoxMap, this_);
+    size_t i = lox_map_find(map, argv[0]);
+    if (i == map->len) return LOX_NIL_VALUE;
+
+    LoxValue removed = map->values[i];
+    memmove(map->keys + i, map->keys + i + 1, (map->len - 1 - i) * sizeof(LoxValue));
+    memmove(map->values + i, map->values + i + 1, (map->len - 1 - i) * sizeof(LoxValue));
+    map->len--;
+    return removed;
+}
+
+LOX_METHOD(lox_map_has_) {
+    LOX_UNUSED;
+    LoxMap *map = LOX_AS(LoxMap, this_);
+    return lox_bool(lox_map_find(map, argv[0]) < map->len);
+}
+
+LOX_METHOD(lox_map_keys_) {
+    LOX_UNUSED;
+    LoxMap *map = LOX_AS(LoxMap, this_);
+    return lox_list(map->len, map->keys);
+}
+
+LOX_METHOD(lox_map_values_) {
+    LOX_UNUSED;
+    LoxMap *map = LOX_AS(LoxMap, this_);
+    return lox_list(map->len, map->values);
+}
+
+LOX_METHOD(lox_map_len_) {
+    LOX_UNUSED;
+    return lox_number((double)LOX_AS(LoxMap, this_)->len);
+}
+
+LOX_METHOD(lox_string_len_) {
+    LOX_UNUSED;
+    return lox_number((double)lox_char_count(LOX_AS(LoxString, this_)));
+}
+
+LOX_METHOD(lox_string_substring_) {
+    LOX_UNUSED;
+    LoxString *str = LOX_AS(LoxString, this_);
+    size_t start = lox_index_arg(argv[0]), end = lox_index_arg(argv[1]), len = lox_char_count(str);
+    if (end > len) lox_error("Index %zu is out of range for length %zu", end, len);
+    if (start > end) lox_error("Range start %zu is after its end %zu", start, end);
+
+    size_t from = lox_char_offset(str, start), to = lox_char_offset(str, end);
+    return lox_str(str->chars + from, to - from);
+}
+
+/* Byte offset of the first occurrence of `pattern` in `str` from `from`, or `str->len`. */
+LOX_API size_t lox_find(LoxString *str, LoxString *pattern, size_t from) {
+    for (size_t i = from; i + pattern->len <= str->len; i++) {
+        if (memcmp(str->chars + i, pattern->chars, pattern->len) == 0) return i;
+    }
+    return str->len;
+}
+
+LOX_METHOD(lox_string_index_of_) {
+    LOX_UNUSED;
+    LoxString *str = LOX_AS(LoxString, this_), *pattern = lox_string_arg(argv[0]);
+    size_t offset = lox_find(str, pattern, 0);
+    if (offset == str->len && pattern->len > 0) return lox_number(-1);
+
+    LoxString *prefix = LOX_AS(LoxString, lox_str(str->chars, offset));
+    return lox_number((double)lox_char_count(prefix));
+}
+
+LOX_METHOD(lox_string_split_) {
+    LOX_UNUSED;
+    LoxString *str = LOX_AS(LoxString, this_), *sep = lox_string_arg(argv[0]);
+    if (sep->len == 0) return lox_chars(str);
+
+    LoxValue parts = lox_list(0, NULL);
+    size_t start = 0;
+    for (;;) {
+        size_t end = lox_find(str, sep, start);
+        if (end == str->len) break;
+        lox_list_push(LOX_AS(LoxList, parts), lox_str(str->chars + start, end - start));
+        start = end + sep->len;
+    }
+    lox_list_push(LOX_AS(LoxList, parts), lox_str(str->chars + start, str->len - start));
+    return parts;
+}
+
+LOX_METHOD(lox_string_trim_) {
+    LOX_UNUSED;
+    LoxString *str = LOX_AS(LoxString, this_);
+    size_t start = 0, end = str->len;
+    while (start < end && isspace((unsigned char)str->chars[start])) start++;
+    while (end > start && isspace((unsigned char)str->chars[end - 1])) end--;
+    return lox_str(str->chars + start, end - start);
+}
+
+LOX_API LoxValue lox_map_chars(LoxValue value, int (*convert)(int)) {
+    LoxString *str = LOX_AS(LoxString, value);
+    LoxValue result = lox_str(str->chars, str->len);
+    char *chars = LOX_AS(LoxString, result)->chars;
+    for (size_t i = 0; i < str->len; i++) chars[i] = (char)convert((unsigned char)chars[i]);
+    return result;
+}
+
+LOX_METHOD(lox_string_to_upper_) {
+    LOX_UNUSED;
+    return lox_map_chars(this_, toupper);
+}
+
+LOX_METHOD(lox_string_to_lower_) {
+    LOX_UNUSED;
+    return lox_map_chars(this_, tolower);
+}
+
+LOX_METHOD(lox_string_replace_) {
+    LOX_UNUSED;
+    LoxString *from = lox_string_arg(argv[0]), *to = lox_string_arg(argv[1]);
+    LoxValue sep = lox_obj(from);
+    LoxList *parts = LOX_AS(LoxList, lox_string_split_(self, this_, 1, &sep));
+    if (from->len == 0) {
+        /* like Rust, an empty pattern matches around every character */
+        LoxValue empty = lox_str("", 0);
+        lox_list_push(parts, empty);
+        memmove(parts->items + 1, parts->items, (parts->len - 1) * sizeof(LoxValue));
+        parts->items[0] = empty;
+    }
+
+    LoxBuf buf = {NULL, 0, 0};
+    lox_buf_str(&buf, "");
+    for (size_t i = 0; i < parts->len; i++) {
+        if (i > 0) lox_buf_push(&buf, to->chars, to->len);
+        LoxString *part = LOX_AS(LoxString, parts->items[i]);
+        lox_buf_push(&buf, part->chars, part->len);
+    }
+    return lox_str(buf.chars, buf.len);
+}
+
+LOX_METHOD(lox_string_char_at_) {
+    LOX_UNUSED;
+    LoxString *str = LOX_AS(LoxString, this_);
+    size_t index = lox_index_arg(argv[0]), len = lox_char_count(str);
+    if (index >= len) lox_error("Index %zu is out of range for length %zu", index, len);
+    LoxList *chars = LOX_AS(LoxList, lox_chars(str));
+    return chars->items[index];
+}
+
+typedef struct {
+    LoxKind kind;
+    const char *name;
+    int min_arity, max_arity;
+    LoxFn fn;
+} LoxBuiltin;
+
+static const LoxBuiltin lox_builtins[] = {
+    {LOX_LIST, "push", 1, 1, lox_list_push_},
+    {LOX_LIST, "pop", 0, 0, lox_list_pop_},
+    {LOX_LIST, "insert", 2, 2, lox_list_insert_},
+    {LOX_LIST, "remove", 1, 1, lox_list_remove_},
+    {LOX_LIST, "len", 0, 0, lox_list_len_},
+    {LOX_LIST, "contains", 1, 1, lox_list_contains_},
+    {LOX_LIST, "indexOf", 1, 1, lox_list_index_of_},
+    {LOX_LIST, "sort", 0, 1, lox_list_sort_},
+    {LOX_LIST, "map", 1, 1, lox_list_map_},
+    {LOX_LIST, "filter", 1, 1, lox_list_filter_},
+    {LOX_MAP, "get", 1, 2, lox_map_get_},
+    {LOX_MAP, "set", 2, 2, lox_map_set_},
+    {LOX_MAP, "remove", 1, 1, lox_map_remove_},
+    {LOX_MAP, "has", 1, 1, lox_map_has_},
+    {LOX_MAP, "keys", 0, 0, lox_map_keys_},
+    {LOX_MAP, "values", 0, 0, lox_map_values_},
+    {LOX_MAP, "len", 0, 0, lox_map_len_},
+    {LOX_STRING, "len", 0, 0, lox_string_len_},
+    {LOX_STRING, "substring", 2, 2, lox_string_substring_},
+    {LOX_STRING, "indexOf", 1, 1, lox_string_index_of_},
+    {LOX_STRING, "split", 1, 1, lox_string_split_},
+    {LOX_STRING, "trim", 0, 0, lox_string_trim_},
+    {LOX_STRING, "toUpper", 0, 0, lox_string_to_upper_},
+    {LOX_STRING, "toLower", 0, 0, lox_string_to_lower_},
+    {LOX_STRING, "replace", 2, 2, lox_string_replace_},
+    {LOX_STRING, "charAt", 1, 1, lox_string_char_at_},
+};
+
+/* ---- properties and indexing ---- */
+
+LOX_API LoxValue lox_get(LoxValue object, const char *name) {
+    if (lox_is(object, LOX_LIST) || lox_is(object, LOX_MAP) || lox_is(object, LOX_STRING)) {
+        for (size_t i = 0; i < sizeof(lox_builtins) / sizeof(lox_builtins[0]); i++) {
+            const LoxBuiltin *builtin = &lox_builtins[i];
+            if (builtin->kind == object.as.obj->kind && strcmp(builtin->name, name) == 0) {
+                LoxValue method = lox_native(name, builtin->min_arity, builtin->max_arity, builtin->fn);
+                LOX_AS(LoxFunction, method)->this_ = object;
+                return method;
+            }
+        }
+        lox_error("Trying to access an undefined property");
+    }
+    if (!lox_is(object, LOX_INSTANCE)) lox_error("Trying to access a property on a non-instance object");
+
+    LoxInstance *instance = LOX_AS(LoxInstance, object);
+    for (size_t i = 0; i < instance->len; i++) {
+        if (strcmp(instance->fields[i].name, name) == 0) return instance->fields[i].value;
+    }
+    LoxFunction *method = lox_find_method(instance->cls, name);
+    if (!method) lox_error("Trying to access an undefined property");
+    return lox_bind(method, object);
+}
+
+LOX_API LoxValue lox_set(LoxValue object, const char *name, LoxValue value) {
+    if (!lox_is(object, LOX_INSTANCE)) lox_error("Trying to access a property on a non-instance object");
+
+    LoxInstance *instance = LOX_AS(LoxInstance, object);
+    for (size_t i = 0; i < instance->len; i++) {
+        if (strcmp(instance->fields[i].name, name) == 0) return instance->fields[i].value = value;
+    }
+    instance->fields = lox_grow(instance->fields, &instance->cap, instance->len, sizeof(LoxField));
+    instance->fields[instance->len].name = name;
+    instance->fields[instance->len++].value = value;
+    return value;
+}
+
+LOX_API LoxValue lox_index(LoxValue object, LoxValue index) {
+    if (lox_is(object, LOX_LIST)) {
+        LoxList *list = LOX_AS(LoxList, object);
+        size_t i = lox_index_arg(index);
+        if (i >= list->len) lox_error("Index %zu is out of range for length %zu", i, list->len);
+        return list->items[i];
+    }
+    if (lox_is(object, LOX_MAP)) {
+        LoxMap *map = LOX_AS(LoxMap, object);
+        size_t i = lox_map_find(map, index);
+        if (i == map->len) lox_error("Key '%s' is not found in the map", lox_display(index));
+        return map->values[i];
+    }
+    lox_error("Trying to index into a non-indexable '%s'", lox_type(object));
+}
+
+LOX_API LoxValue lox_set_index(LoxValue object, LoxValue index, LoxValue value) {
+    if (lox_is(object, LOX_LIST)) {
+        LoxList *list = LOX_AS(LoxList, object);
+        size_t i = lox_index_arg(index);
+        if (i >= list->len) lox_error("Index %zu is out of range for length %zu", i, list->len);
+        return list->items[i] = value;
+    }
+    if (lox_is(object, LOX_MAP)) {
+        lox_map_insert(LOX_AS(LoxMap, object), index, value);
+        return value;
+    }
+    lox_error("Trying to index into a non-indexable '%s'", lox_type(object));
+}
+
+/* Either a snapshot of the elements of a collection, so the body can freely modify it, or the
+ * `next` method of an iterator. */
+typedef struct {
+    LoxList *items;
+    size_t i;
+    LoxValue next;
+} LoxIter;
+
+/* The property `name` of `value` if it's an instance that has one, otherwise nil. */
+LOX_API LoxValue lox_protocol(LoxValue value, const char *name) {
+    if (!lox_is(value, LOX_INSTANCE)) return lox_nil();
+
+    LoxInstance *instance = LOX_AS(LoxInstance, value);
+    for (size_t i = 0; i < instance->len; i++) {
+        if (strcmp(instance->fields[i].name, name) == 0) return instance->fields[i].value;
+    }
+    LoxFunction *method = lox_find_method(instance->cls, name);
+    return method ? lox_bind(method, value) : lox_nil();
+}
+
+LOX_API LoxIter lox_iter(LoxValue value) {
+    LoxIter iter = {NULL, 0, LOX_NIL_VALUE};
+    if (lox_is(value, LOX_LIST)) {
+        LoxList *list = LOX_AS(LoxList, value);
+        iter.items = LOX_AS(LoxList, lox_list(list->len, list->items));
+    } else if (lox_is(value, LOX_MAP)) {
+        LoxMap *map = LOX_AS(LoxMap, value);
+        iter.items = LOX_AS(LoxList, lox_list(map->len, map->keys));
+    } else if (lox_is(value, LOX_STRING)) {
+        iter.items = LOX_AS(LoxList, lox_chars(LOX_AS(LoxString, value)));
+    } else {
+        /* the iteration protocol: `iter()` returns the iterator, or the instance is the iterator */
+        LoxValue method = lox_protocol(value, "iter");
+        LoxValue iterator = method.tag == LOX_NIL ? value : lox_call(method, 0, NULL);
+        iter.next = lox_protocol(iterator, "next");
+        if (iter.next.tag == LOX_NIL) lox_error("Can't iterate over a value of type '%s'", lox_type(iterator));
+    }
+    return iter;
+}
+
+/* Sets `item` to the next item, returns false once there are no more. */
+LOX_API bool lox_iter_next(LoxIter *iter, LoxValue *item) {
+    if (iter->items) {
+        if (iter->i == iter->items->len) return false;
+        *item = iter->items->items[iter->i++];
+        return true;
+    }
+    *item = lox_call(iter->next, 0, NULL);
+    return item->tag != LOX_NIL;
+}
+
+/* ---- natives ---- */
+
+LOX_METHOD(lox_clock_) {
+    LOX_UNUSED;
+    struct timespec now;
+    timespec_get(&now, TIME_UTC);
+    return lox_number((double)now.tv_sec + (double)now.tv_nsec / 1e9);
+}
+
+LOX_API bool lox_deep_equal(LoxValue lhs, LoxValue rhs, LoxObj **seen, size_t depth) {
+    if (lox_equal(lhs, rhs)) return true;
+    if (lhs.tag != LOX_OBJ || rhs.tag != LOX_OBJ || lhs.as.obj->kind != rhs.as.obj->kind) {
+        return false;
+    }
+    /* a pair that is already being compared is assumed to be equal */
+    for (size_t i = 0; i < depth; i++) {
+        if (seen[2 * i] == lhs.as.obj && seen[2 * i + 1] == rhs.as.obj) return true;
+    }
+    LoxObj **nested = lox_alloc((depth + 1) * 2 * sizeof(LoxObj *));
+    memcpy(nested, seen, depth * 2 * sizeof(LoxObj *));
+    nested[2 * depth] = lhs.as.obj;
+    nested[2 * depth + 1] = rhs.as.obj;
+
+    bool equal = false;
+    if (lox_is(lhs, LOX_LIST)) {
+        LoxList *a = LOX_AS(LoxList, lhs), *b = LOX_AS(LoxList, rhs);
+        equal = a->len == b->len;
+        for (size_t i = 0; equal && i < a->len; i++) {
+            equal = lox_deep_equal(a->items[i], b->items[i], nested, depth + 1);
+        }
+    } else if (lox_is(lhs, LOX_MAP)) {
+        LoxMap *a = LOX_AS(LoxMap, lhs), *b = LOX_AS(LoxMap, rhs);
+        equal = a->len == b->len;
+        for (size_t i = 0; equal && i < a->len; i++) {
+            size_t j = lox_map_find(b, a->keys[i]);
+            equal = j < b->len && lox_deep_equal(a->values[i], b->values[j], nested, depth + 1);
+        }
+    } else if (lox_is(lhs, LOX_INSTANCE)) {
+        LoxInstance *a = LOX_AS(LoxInstance, lhs), *b = LOX_AS(LoxInstance, rhs);
+        equal = a->cls == b->cls && a->len == b->len;
+        for (size_t i = 0; equal && i < a->len; i++) {
+            size_t j = 0;
+            while (j < b->len && strcmp(a->fields[i].name, b->fields[j].name) != 0) j++;
+            equal = j < b->len &&
+                    lox_deep_equal(a->fields[i].value, b->fields[j].value, nested, depth + 1);
+        }
+    }
+    free(nested);
+    return equal;
+}
+
+LOX_METHOD(lox_deep_equals_) {
+    LOX_UNUSED;
+    return lox_bool(lox_deep_equal(argv[0], argv[1], NULL, 0));
+}
+
+LOX_METHOD(lox_print_) {
+    LOX_UNUSED;
+    lox_print(argv[0]);
+    return LOX_NIL_VALUE;
+}
+
+LOX_METHOD(lox_write_) {
+    LOX_UNUSED;
+    fputs(lox_display(argv[0]), stdout);
+    return LOX_NIL_VALUE;
+}
+
+LOX_METHOD(lox_eprint_) {
+    LOX_UNUSED;
+    fflush(stdout);
+    fprintf(stderr, "%s\n", lox_display(argv[0]));
+    return LOX_NIL_VALUE;
+}
+
+LOX_METHOD(lox_map_new_) {
+    LOX_UNUSED;
+    LoxMap *map = lox_alloc(sizeof(LoxMap));
+    map->obj.kind = LOX_MAP;
+    return lox_obj(map);
+}
+
+LOX_METHOD(lox_exit_) {
+    LOX_UNUSED;
+    if (argc > 0 && (argv[0].tag != LOX_NUMBER || argv[0].as.number != floor(argv[0].as.number) ||
+                     fabs(argv[0].as.number) > INT32_MAX)) {
+        lox_error("Invalid argument: expect '<integer>', got '%s'", lox_type(argv[0]));
+    }
+    fflush(stdout);
+    exit(argc > 0 ? (int)argv[0].as.number : 0);
+}
+
+LOX_METHOD(lox_assert_) {
+    LOX_UNUSED;
+    if (lox_truthy(argv[0])) return LOX_NIL_VALUE;
+    lox_error("Assertion failed: %s", argc > 1 ? lox_display(argv[1]) : "expected a truthy value");
+}
+
+LOX_METHOD(lox_panic_) {
+    LOX_UNUSED;
+    lox_error("Panic: %s", lox_display(argv[0]));
+}
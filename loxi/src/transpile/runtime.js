@@ -0,0 +1,347 @@
+// Runtime of Lox programs translated to JavaScript by loxi.
+"use strict";
+
+const $ = (() => {
+  class RuntimeError extends Error {
+    constructor(message) {
+      super(message);
+      this.name = "RuntimeError";
+    }
+  }
+
+  const classes = new WeakMap();
+  const ids = new WeakMap();
+  const names = new WeakMap();
+  const natives = new WeakSet();
+
+  const fail = (message) => {
+    throw new RuntimeError(message);
+  };
+
+  const typeName = (value) => {
+    if (value === null || value === undefined) return "<nil>";
+    if (typeof value === "boolean") return "<bool>";
+    if (typeof value === "number") return "<number>";
+    if (typeof value === "string") return "<string>";
+    if (Array.isArray(value)) return "<list>";
+    if (value instanceof Map) return "<map>";
+    if (classes.has(value)) return "<class>";
+    if (typeof value === "function") return "<function>";
+    return "<instance>";
+  };
+
+  // how numbers are printed, see NumberStyle in value.rs
+  let rustNumbers = false;
+
+  // Rust formats numbers without an exponent, e.g. 1e21 is 1000000000000000000000
+  const rustNumber = (num) => {
+    if (Number.isNaN(num)) return "nan";
+    if (num === Infinity) return "inf";
+    if (num === -Infinity) return "-inf";
+    if (Object.is(num, -0)) return "-0";
+
+    const str = String(num);
+    const match = /^(-?)(\d)(?:\.(\d+))?e([+-]\d+)$/.exec(str);
+    if (!match) return str;
+
+    const [, sign, int, frac = "", exp] = match;
+    const digits = int + frac;
+    const point = 1 + Number(exp);
+    if (point <= 0) return `${sign}0.${"0".repeat(-point)}${digits}`;
+    return sign + digits.padEnd(point, "0");
+  };
+
+  // jlox formats numbers like Java's Double.toString, e.g. 1e21 is 1.0E21. The interpreter prints
+  // integers in full, which are the integral numbers that fit in 64 bits here.
+  const jloxNumber = (num) => {
+    if (Number.isNaN(num)) return "NaN";
+    if (num === Infinity) return "Infinity";
+    if (num === -Infinity) return "-Infinity";
+
+    const abs = Math.abs(num);
+    if (num === 0 || (abs >= 1e-3 && abs < 1e7) || (Number.isInteger(num) && abs < 2 ** 63)) {
+      return rustNumber(num);
+    }
+    const [mantissa, exp] = num.toExponential().split("e");
+    return `${mantissa.includes(".") ? mantissa : `${mantissa}.0`}E${Number(exp)}`;
+  };
+
+  const number = (num) => (rustNumbers ? rustNumber(num) : jloxNumber(num));
+
+  const str = (value, parents = []) => {
+    if (value === null || value === undefined) return "nil";
+    if (typeof value === "number") return number(value);
+    if (typeof value !== "object" && typeof value !== "function") return String(value);
+    if (classes.has(value)) return `<class ${classes.get(value)}>`;
+    if (natives.has(value)) return `<native_fun ${value.name}>`;
+    // the suffix of renamed variables, see js.rs
+    if (typeof value === "function") return `<fun ${names.get(value) ?? value.name.replace(/\$\d*$/, "")}>`;
+
+    if (Array.isArray(value)) {
+      if (parents.includes(value)) return "[...]";
+      const elements = value.map((element) => str(element, [...parents, value]));
+      return `[${elements.join(", ")}]`;
+    }
+    if (value instanceof Map) {
+      if (parents.includes(value)) return "{...}";
+      const entries = [...value].map(([key, val]) => `${str(key)}: ${str(val, [...parents, value])}`);
+      return `{${entries.join(", ")}}`;
+    }
+    const [name, id] = ids.get(value);
+    return `<instance of:${name} no:${id}>`;
+  };
+
+  const truthy = (value) => value !== null && value !== undefined && value !== false;
+
+  const nil = (value) => (value === undefined ? null : value);
+
+  const isString = (value) => typeof value === "string";
+
+  const isNumber = (value) => typeof value === "number";
+
+  const numbers = (op, lhs, rhs) => {
+    if (isNumber(lhs) && isNumber(rhs)) return;
+    fail(`Invalid binary operation '${op}' between '${typeName(lhs)}' and '${typeName(rhs)}'`);
+  };
+
+  // the numbers truncated to 64-bit integers, saturating at their bounds and with nan as 0
+  const integers = (op, lhs, rhs, f) => {
+    numbers(op, lhs, rhs);
+    const int = (num) => {
+      if (Number.isNaN(num)) return 0n;
+      if (num >= 2 ** 63) return 2n ** 63n - 1n;
+      if (num <= -(2 ** 63)) return -(2n ** 63n);
+      return BigInt(Math.trunc(num));
+    };
+    return Number(BigInt.asIntN(64, f(int(lhs), int(rhs))));
+  };
+
+  const index = (len, i) => {
+    if (!Number.isInteger(i) || i < 0) fail(`Index must be a non-negative integer, got '${str(i)}'`);
+    if (i >= len) fail(`Index ${i} is out of range for length ${len}`);
+    return i;
+  };
+
+  const key = (value) => {
+    if (value === null || value === undefined) return null;
+    if (isNumber(value) && !Number.isNaN(value)) return Object.is(value, -0) ? 0 : value;
+    if (isString(value) || typeof value === "boolean") return value;
+    return fail(`Invalid map key of type '${typeName(value)}', expect nil, bool, number (except nan), or string`);
+  };
+
+  const callback = (func) => {
+    if (typeof func !== "function") fail(`Invalid argument, expect <function> got ${typeName(func)}`);
+    return func;
+  };
+
+  const order = (a, b) => {
+    if (isNumber(a) && isNumber(b)) return a < b ? -1 : a > b ? 1 : 0;
+    if (isString(a) && isString(b)) return a < b ? -1 : a > b ? 1 : 0;
+    fail(`Invalid argument, expect <number> or <string> got ${typeName(isNumber(a) ? b : a)}`);
+  };
+
+  // the methods of lists, maps, and strings, called with the value as `this`
+  const methods = {
+    list: {
+      push(value) { this.push(value); return null; },
+      pop() { return this.length ? this.pop() : fail("List is empty"); },
+      insert(i, value) { this.splice(index(this.length + 1, i), 0, value); return null; },
+      remove(i) { return this.splice(index(this.length, i), 1)[0]; },
+      len() { return this.length; },
+      contains(value) { return this.some((element) => eq(element, value)); },
+      indexOf(value) { return this.findIndex((element) => eq(element, value)); },
+      sort(cmp) {
+        const sorted = cmp === undefined ? [...this].sort(order) : [...this].sort((a, b) => callback(cmp)(a, b));
+        this.splice(0, this.length, ...sorted);
+        return null;
+      },
+      map(func) { return this.map((element) => nil(callback(func)(element))); },
+      filter(func) { return this.filter((element) => truthy(callback(func)(element))); },
+    },
+    map: {
+      get(k, fallback = null) { k = key(k); return this.has(k) ? this.get(k) : fallback; },
+      set(k, value) { this.set(key(k), value); return null; },
+      remove(k) { k = key(k); const value = this.has(k) ? this.get(k) : null; this.delete(k); return value; },
+      has(k) { return this.has(key(k)); },
+      keys() { return [...this.keys()]; },
+      values() { return [...this.values()]; },
+      len() { return this.size; },
+    },
+    string: {
+      len() { return [...this].length; },
+      substring(start, end) {
+        const chars = [...this];
+        index(chars.length + 1, end);
+        index(Infinity, start);
+        if (start > end) fail(`Range start ${start} is after its end ${end}`);
+        return chars.slice(start, end).join("");
+      },
+      indexOf(pattern) { const i = this.indexOf(pattern); return i < 0 ? -1 : [...this.slice(0, i)].length; },
+      split(sep) { return sep === "" ? [...this] : this.split(sep); },
+      trim() { return this.trim(); },
+      toUpper() { return this.toUpperCase(); },
+      toLower() { return this.toLowerCase(); },
+      replace(from, to) { return this.split(from).join(to); },
+      charAt(i) { const chars = [...this]; return chars[index(chars.length, i)]; },
+    },
+  };
+
+  const eq = (lhs, rhs) => nil(lhs) === nil(rhs);
+
+  const deepEq = (lhs, rhs, seen = []) => {
+    if (eq(lhs, rhs)) return true;
+    if (typeof lhs !== "object" || typeof rhs !== "object" || lhs === null || rhs === null) return false;
+    if (seen.some(([a, b]) => a === lhs && b === rhs)) return true;
+    seen = [...seen, [lhs, rhs]];
+
+    if (Array.isArray(lhs) && Array.isArray(rhs)) {
+      return lhs.length === rhs.length && lhs.every((element, i) => deepEq(element, rhs[i], seen));
+    }
+    if (lhs instanceof Map && rhs instanceof Map) {
+      return lhs.size === rhs.size && [...lhs].every(([k, v]) => rhs.has(k) && deepEq(v, rhs.get(k), seen));
+    }
+    if (ids.has(lhs) && ids.has(rhs) && ids.get(lhs)[0] === ids.get(rhs)[0]) {
+      const fields = Object.keys(lhs);
+      return fields.length === Object.keys(rhs).length && fields.every((f) => Object.hasOwn(rhs, f) && deepEq(lhs[f], rhs[f], seen));
+    }
+    return false;
+  };
+
+  const write = (text) => {
+    if (typeof process !== "undefined") process.stdout.write(text);
+    else console.log(text);
+  };
+
+  // the iteration protocol of instances: `iter()` returns the iterator, or the instance is the
+  // iterator itself, and its `next()` returns the items until nil
+  function* protocol(value) {
+    const has = (object, name) =>
+      ids.has(object) && (Object.hasOwn(object, name) || Object.hasOwn(Object.getPrototypeOf(object), name));
+    const iterator = has(value, "iter") ? $.get(value, "iter")() : value;
+    if (!has(iterator, "next")) fail(`Can only iterate over lists, maps, strings, and iterators, got ${typeName(iterator)}`);
+    const next = $.get(iterator, "next");
+    for (let item = nil(next()); item !== null; item = nil(next())) yield item;
+  }
+
+  return {
+    RuntimeError,
+
+    rustNumbers: () => {
+      rustNumbers = true;
+    },
+
+    truthy,
+    str,
+
+    print: (value) => console.log(str(value)),
+
+    neg: (value) => (isNumber(value) ? -value : fail(`Invalid unary operation '-' on '${typeName(value)}'`)),
+    add: (lhs, rhs) => {
+      if (isString(lhs) && isString(rhs)) return lhs + rhs;
+      numbers("+", lhs, rhs);
+      return lhs + rhs;
+    },
+    sub: (lhs, rhs) => (numbers("-", lhs, rhs), lhs - rhs),
+    mul: (lhs, rhs) => (numbers("*", lhs, rhs), lhs * rhs),
+    div: (lhs, rhs) => (numbers("/", lhs, rhs), lhs / rhs),
+    band: (lhs, rhs) => integers("&", lhs, rhs, (a, b) => a & b),
+    bor: (lhs, rhs) => integers("|", lhs, rhs, (a, b) => a | b),
+    bxor: (lhs, rhs) => integers("^", lhs, rhs, (a, b) => a ^ b),
+    shl: (lhs, rhs) => integers("<<", lhs, rhs, (a, b) => a << (b & 63n)),
+    shr: (lhs, rhs) => integers(">>", lhs, rhs, (a, b) => a >> (b & 63n)),
+    lt: (lhs, rhs) => (numbers("<", lhs, rhs), lhs < rhs),
+    le: (lhs, rhs) => (numbers("<=", lhs, rhs), lhs <= rhs),
+    gt: (lhs, rhs) => (numbers(">", lhs, rhs), lhs > rhs),
+    ge: (lhs, rhs) => (numbers(">=", lhs, rhs), lhs >= rhs),
+    eq,
+    ne: (lhs, rhs) => !eq(lhs, rhs),
+
+    and: (lhs, rhs) => (truthy(lhs) ? rhs() : lhs),
+    or: (lhs, rhs) => (truthy(lhs) ? lhs : rhs()),
+
+    // `init` is called by the class itself, which also returns the instance
+    class: (name, Class) => {
+      let counter = 1;
+      const construct = (...args) => {
+        const instance = new Class();
+        ids.set(instance, [name, counter++]);
+        if (Object.hasOwn(Class.prototype, "init")) instance.init(...args);
+        return instance;
+      };
+      classes.set(construct, name);
+      for (const method of Object.getOwnPropertyNames(Class.prototype)) {
+        if (method !== "constructor") names.set(Class.prototype[method], method);
+      }
+      return construct;
+    },
+
+    get: (object, name) => {
+      if (isString(object) || Array.isArray(object) || object instanceof Map) {
+        const kind = isString(object) ? "string" : Array.isArray(object) ? "list" : "map";
+        if (Object.hasOwn(methods[kind], name)) return methods[kind][name].bind(object);
+        return fail(`Undefined property '${name}' of ${typeName(object)}`);
+      }
+      if (!ids.has(object)) return fail(`Only instances have properties, got ${typeName(object)}`);
+      if (Object.hasOwn(object, name)) return object[name];
+
+      const proto = Object.getPrototypeOf(object);
+      if (Object.hasOwn(proto, name) && name !== "constructor") {
+        const method = proto[name].bind(object);
+        names.set(method, name);
+        return method;
+      }
+      return fail(`Undefined property '${name}'`);
+    },
+    set: (object, name, value) => {
+      if (!ids.has(object)) return fail(`Only instances have fields, got ${typeName(object)}`);
+      return (object[name] = value);
+    },
+
+    index: (object, i) => {
+      if (Array.isArray(object)) return object[index(object.length, i)];
+      if (object instanceof Map) {
+        const k = key(i);
+        return object.has(k) ? object.get(k) : fail(`Undefined key '${str(i)}'`);
+      }
+      return fail(`Can only index lists and maps, got ${typeName(object)}`);
+    },
+    setIndex: (object, i, value) => {
+      if (Array.isArray(object)) return (object[index(object.length, i)] = value);
+      if (object instanceof Map) return (object.set(key(i), value), value);
+      return fail(`Can only index lists and maps, got ${typeName(object)}`);
+    },
+
+    // iterates over a snapshot so the body can freely modify the collection
+    iter: (value) => {
+      if (Array.isArray(value)) return [...value];
+      if (value instanceof Map) return [...value.keys()];
+      if (isString(value)) return [...value];
+      if (ids.has(value)) return protocol(value);
+      return fail(`Can only iterate over lists, maps, strings, and iterators, got ${typeName(value)}`);
+    },
+
+    natives: Object.fromEntries(Object.entries({
+      clock: () => Date.now() / 1000,
+      deepEquals: (lhs, rhs) => deepEq(lhs, rhs),
+      print: (value) => (console.log(str(value)), null),
+      println: (value) => (console.log(str(value)), null),
+      write: (value) => (write(str(value)), null),
+      eprint: (value) => (console.error(str(value)), null),
+      Map: () => new Map(),
+      exit: (code = 0) => {
+        if (typeof process !== "undefined") process.exit(code);
+        throw new RuntimeError(`exit ${code}`);
+      },
+      assert: (value, message) => (truthy(value) ? null : fail(`Assertion failed${message === undefined ? "" : `: ${str(message)}`}`)),
+      panic: (message) => fail(`Panic: ${str(message)}`),
+    }).map(([name, func]) => {
+      Object.defineProperty(func, "name", { value: name });
+      natives.add(func);
+      return [name, func];
+    })),
+  };
+})();
+
+var { clock, print, println, write, eprint, Map: Map$, exit, assert, panic } = $.natives;
+var nan = NaN;
+var inf = Infinity;
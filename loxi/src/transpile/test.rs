@@ -0,0 +1,191 @@
+use std::io;
+
+use pretty_assertions::assert_eq;
+
+use crate::interp::value::NumberStyle;
+use crate::{Dialect, Lox, Options};
+
+use super::Target;
+
+/// The translation of `source` without the runtime.
+fn js(source: &str) -> String {
+    let mut lox = Lox::new(&Options::default());
+    lox.set_stderr(io::sink());
+
+    let program = lox
+        .transpile(source, Target::Js)
+        .expect("source should compile");
+    let (_, program) = program
+        .split_once("var inf = Infinity;\n\n")
+        .expect("program should start with the runtime");
+    program.to_owned()
+}
+
+/// The translation of `source` without the runtime.
+fn c(source: &str) -> String {
+    let mut lox = Lox::new(&Options::default());
+    lox.set_stderr(io::sink());
+
+    let program = lox
+        .transpile(source, Target::C)
+        .expect("source should compile");
+    let program = program
+        .strip_prefix(include_str!("runtime.h"))
+        .expect("program should start with the runtime");
+    program.trim_start().to_owned()
+}
+
+#[test]
+fn closures_and_classes_are_translated() {
+    let source = r#"
+class Counter {
+    init(step) { this.step = step; this.count = 0; }
+    next() {
+        fun add() { this.count = this.count + this.step; }
+        add();
+        return this.count;
+    }
+}
+var counter = Counter(2);
+if (counter.next() > 1 and true) print "ok"; else print nil;
+"#;
+    let expected = r#"var Counter = $.class("Counter", class {
+  init(step) {
+    this.step = step;
+    this.count = 0;
+    return this;
+  }
+  next() {
+    let add = () => {
+      this.count = $.add($.get(this, "count"), $.get(this, "step"));
+    };
+    add();
+    return $.get(this, "count");
+  }
+});
+var counter = Counter(2);
+if ($.truthy($.and($.gt($.get(counter, "next")(), 1), () => true))) {
+  $.print("ok");
+} else {
+  $.print(null);
+}
+"#;
+    assert_eq!(js(source), expected);
+}
+
+#[test]
+fn locals_declared_after_a_use_of_an_outer_one_are_renamed() {
+    let source = r#"
+var a = "global";
+{
+    fun show() { print a; }
+    var a = "local";
+    for (var let in [a]) print let;
+}
+"#;
+    let expected = r#"var a = "global";
+{
+  let show = () => {
+    $.print(a);
+  };
+  let a$1 = "local";
+  for (let let$ of $.iter([a$1])) {
+    $.print(let$);
+  }
+}
+"#;
+    assert_eq!(js(source), expected);
+}
+
+#[test]
+fn captured_locals_are_shared_cells_in_c() {
+    let source = r#"
+var a = 1;
+fun outer() {
+  var b = a + 1;
+  fun inner() { b = b * 2; return b; }
+  return inner;
+}
+print outer()() or nil;
+"#;
+    let expected = r#"static LoxValue G_a;
+static LoxValue G_outer;
+
+static LoxValue fn_2_inner(LoxFunction *self, LoxValue this_, int argc, LoxValue *argv) {
+    LOX_UNUSED;
+    LoxValue *c_b = self->captures[0];
+    LoxValue t[3];
+    t[0] = *c_b;
+    t[1] = lox_mul(t[0], lox_number(2.0));
+    *c_b = t[1];
+    t[2] = *c_b;
+    return t[2];
+    return lox_nil();
+}
+
+static LoxValue fn_1_outer(LoxFunction *self, LoxValue this_, int argc, LoxValue *argv) {
+    LOX_UNUSED;
+    LoxValue t[3];
+    t[0] = lox_global(G_a, "a");
+    t[1] = lox_add(t[0], lox_number(1.0));
+    LoxValue *v_b = lox_cell(t[1]);
+    LoxValue *v_inner = lox_cell(lox_nil());
+    *v_inner = lox_function("inner", 0, fn_2_inner, 1, (LoxValue *[]){v_b});
+    t[2] = *v_inner;
+    return t[2];
+    return lox_nil();
+}
+
+int main(void) {
+    LoxValue t[4];
+    G_a = lox_number(1.0);
+    G_outer = lox_function("outer", 0, fn_1_outer, 0, NULL);
+    t[0] = lox_global(G_outer, "outer");
+    t[1] = lox_call(t[0], 0, NULL);
+    t[2] = lox_call(t[1], 0, NULL);
+    t[3] = t[2];
+    if (!lox_truthy(t[3])) {
+        t[3] = lox_nil();
+    }
+    lox_print(t[3]);
+    return 0;
+}
+"#;
+    assert_eq!(c(source), expected);
+}
+
+#[test]
+fn the_number_style_is_kept_in_the_translation() {
+    let options = Options {
+        number_style: NumberStyle::Rust,
+        ..Options::default()
+    };
+    let mut lox = Lox::new(&options);
+    lox.set_stderr(io::sink());
+
+    let js = lox.transpile("print 1e21;", Target::Js).unwrap();
+    assert!(js.contains("var inf = Infinity;\n\n$.rustNumbers();\n"));
+    let c = lox.transpile("print 1e21;", Target::C).unwrap();
+    assert!(c.contains("int main(void) {\n    lox_rust_numbers = true;\n"));
+
+    let mut lox = Lox::new(&Options::default());
+    let js = lox.transpile("print 1e21;", Target::Js).unwrap();
+    assert!(!js.contains("$.rustNumbers();"));
+}
+
+#[test]
+fn deep_equals_is_only_defined_if_the_dialect_enables_it() {
+    let source = "print deepEquals([1], [1]);";
+    assert!(!c(source).contains("G_deepEquals = lox_native"));
+    assert!(!js(source).contains("var deepEquals"));
+
+    let options = Options {
+        dialect: Dialect::extended(),
+        ..Options::default()
+    };
+    let mut lox = Lox::new(&options);
+    let c = lox.transpile(source, Target::C).unwrap();
+    assert!(c.contains("G_deepEquals = lox_native(\"deepEquals\", 2, 2, lox_deep_equals_);"));
+    let js = lox.transpile(source, Target::Js).unwrap();
+    assert!(js.contains("var deepEquals = $.natives.deepEquals;"));
+}
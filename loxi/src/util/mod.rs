@@ -1,5 +1,13 @@
 use std::fmt::{Debug, Display, Formatter};
 
+pub mod shared;
+pub mod source;
+
+pub use self::source::{SourceId, SourceMap};
+
+#[cfg(test)]
+mod test;
+
 pub trait LoxToken {
     fn as_str(&self) -> &'static str;
 }
@@ -10,11 +18,13 @@ pub struct TokLoc<T: LoxToken> {
     pub loc: Location,
 }
 
-// TODO: add other information like filename and column
-#[derive(Copy, Clone, Default, Eq, PartialEq, PartialOrd, Ord)]
+/// A position in a source, shown as `[line:column]`. Locations in different sources are ordered
+/// by their line and column first, the source only breaks the ties.
+#[derive(Copy, Clone, Default, Eq, PartialEq, PartialOrd, Ord, Hash)]
 pub struct Location {
     pub line: usize,
     pub column: usize,
+    pub source: SourceId,
 }
 
 impl<T: LoxToken> TokLoc<T> {
@@ -24,8 +34,13 @@ impl<T: LoxToken> TokLoc<T> {
 }
 
 impl Location {
-    pub(crate) fn new(line: usize, column: usize) -> Self {
-        Self { line, column }
+    /// A location in the first source, see `SourceId`.
+    pub fn new(line: usize, column: usize) -> Self {
+        Self {
+            line,
+            column,
+            source: SourceId::default(),
+        }
     }
 }
 
@@ -0,0 +1,81 @@
+//! Shared ownership types used by runtime values.
+//!
+//! By default values are shared using `Rc` and `RefCell`, which makes the interpreter bound to the
+//! thread that created it. With the `arc` feature they are shared using `Arc` and `RwLock` instead,
+//! so the interpreter is `Send` and can run on another thread. That comes at a cost: every clone is
+//! an atomic operation and every access to a list, map, instance, or environment takes a lock.
+
+pub use self::imp::*;
+
+#[cfg(not(feature = "arc"))]
+mod imp {
+    pub use std::cell::RefCell as Lock;
+    pub use std::rc::Rc as Shared;
+
+    /// Implemented by every type, see the `arc` feature variant.
+    pub trait ThreadSafe {}
+
+    impl<T: ?Sized> ThreadSafe for T {}
+}
+
+#[cfg(feature = "arc")]
+mod imp {
+    use std::cmp::Ordering;
+    use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+    pub use std::sync::Arc as Shared;
+
+    /// Bound for anything stored in the interpreter that is given by the host, e.g. output sinks
+    /// and native functions, so the interpreter can be sent to another thread.
+    pub trait ThreadSafe: Send + Sync {}
+
+    impl<T: ?Sized + Send + Sync> ThreadSafe for T {}
+
+    /// `RwLock` with the interface of `RefCell`. Unlike `RefCell`, a borrow that conflicts with
+    /// another one waits for it to end instead of panicking. That's what a borrow from another
+    /// thread needs, but a conflicting borrow on the same thread waits forever, e.g. a native of the
+    /// host that mutates a list while it still reads it deadlocks where the default build panics.
+    /// The interpreter itself doesn't hold a borrow while it calls back into the program.
+    #[derive(Debug, Default)]
+    pub struct Lock<T>(RwLock<T>);
+
+    impl<T> Lock<T> {
+        pub fn new(value: T) -> Self {
+            Self(RwLock::new(value))
+        }
+
+        pub fn borrow(&self) -> RwLockReadGuard<'_, T> {
+            self.0.read().expect("lock should not be poisoned")
+        }
+
+        pub fn borrow_mut(&self) -> RwLockWriteGuard<'_, T> {
+            self.0.write().expect("lock should not be poisoned")
+        }
+    }
+
+    // the same trait implementations as `RefCell`
+
+    impl<T: Clone> Clone for Lock<T> {
+        fn clone(&self) -> Self {
+            Self::new(self.borrow().clone())
+        }
+    }
+
+    impl<T> From<T> for Lock<T> {
+        fn from(value: T) -> Self {
+            Self::new(value)
+        }
+    }
+
+    impl<T: PartialEq> PartialEq for Lock<T> {
+        fn eq(&self, other: &Self) -> bool {
+            *self.borrow() == *other.borrow()
+        }
+    }
+
+    impl<T: PartialOrd> PartialOrd for Lock<T> {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            self.borrow().partial_cmp(&*other.borrow())
+        }
+    }
+}
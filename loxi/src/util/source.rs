@@ -0,0 +1,138 @@
+//! The sources a program is made of, so a location can be traced back to the file and the line it
+//! is in even after the source that is being run changes, e.g. when a function defined by a file
+//! fails while it's called from the REPL.
+
+use std::fmt::Write;
+
+use unicode_width::UnicodeWidthChar;
+
+use super::Location;
+use crate::analyze::Span;
+
+/// The columns a tab takes in a rendered line. The lexer counts a tab as no column at all, so the
+/// line is shown with its tabs expanded to keep the marks under the right characters.
+const TAB_WIDTH: usize = 4;
+
+/// Identifies a source in a `SourceMap`. Locations that are made without one, e.g. by lexing a
+/// source on its own, are in the first source of the map.
+#[derive(Copy, Clone, Default, Debug, Eq, PartialEq, PartialOrd, Ord, Hash)]
+pub struct SourceId(u32);
+
+struct SourceFile {
+    name: Option<String>,
+    text: String,
+}
+
+/// Owns the text of every source added to it. Sources are never removed, so their ids stay valid.
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a source named `name`, usually its path, `None` for a source that isn't a file.
+    pub fn add(&mut self, name: Option<String>, text: &str) -> SourceId {
+        let id = u32::try_from(self.files.len()).expect("there should be less than 2^32 sources");
+        let text = text.to_owned();
+        self.files.push(SourceFile { name, text });
+        SourceId(id)
+    }
+
+    /// The name of the source, `None` if it has none or `id` is not of this map.
+    pub fn name(&self, id: SourceId) -> Option<&str> {
+        self.file(id)?.name.as_deref()
+    }
+
+    pub fn text(&self, id: SourceId) -> Option<&str> {
+        Some(&self.file(id)?.text)
+    }
+
+    /// The line `loc` is at, without its line break. `None` if it's outside of its source, e.g.
+    /// the end of the input at `[0:0]`.
+    pub fn line(&self, loc: Location) -> Option<&str> {
+        let index = loc.line.checked_sub(1)?;
+        self.text(loc.source)?.lines().nth(index)
+    }
+
+    /// Render the line `span` starts at with a gutter of line numbers, `context` lines before and
+    /// after it, and marks under `span`, or a single mark if it ends on another line. The name of
+    /// the source and the location are written first if the source has a name. `color` is the
+    /// ANSI escape sequence the marks are written in, e.g. `"\x1b[1;31m"`, uncolored if `None`.
+    pub fn render(&self, span: Span, context: usize, color: Option<&str>) -> String {
+        let loc = span.start;
+        let first = loc.line.saturating_sub(context).max(1).min(loc.line);
+        let last = loc.line + context;
+        let width = last.to_string().len().max(4);
+
+        let mut out = String::new();
+        if let Some(name) = self.name(loc.source) {
+            writeln!(out, "{:>width$}--> {name}:{}:{}", "", loc.line, loc.column).unwrap();
+        }
+        writeln!(out, "{:>width$} |", "").unwrap();
+
+        for number in first..=last {
+            let line = match self.line(Location {
+                line: number,
+                ..loc
+            }) {
+                Some(line) => line,
+                // errors at the end of the input may be outside of the source, e.g. at [0:0]
+                None if number == loc.line => "",
+                None => continue,
+            };
+            writeln!(
+                out,
+                "{number:>width$} | {}",
+                line.replace('\t', &" ".repeat(TAB_WIDTH))
+            )
+            .unwrap();
+            if number != loc.line {
+                continue;
+            }
+
+            let start = offset(line, loc.column);
+            let marks = match span.end.line == loc.line {
+                true => offset(line, span.end.column).saturating_sub(start).max(1),
+                false => 1,
+            };
+            let marks = "^".repeat(marks);
+            match color {
+                Some(color) => writeln!(
+                    out,
+                    "{:>width$} | \x1b[1m{:start$}{color}{marks}\x1b[00m",
+                    "", ""
+                ),
+                None => writeln!(out, "{:>width$} | {:start$}{marks}", "", ""),
+            }
+            .unwrap();
+        }
+        out
+    }
+
+    fn file(&self, id: SourceId) -> Option<&SourceFile> {
+        self.files.get(id.0 as usize)
+    }
+}
+
+/// The rendered width of `line` before `column` as the lexer counts it, which starts at 1. Columns
+/// past the end of the line are one column each.
+fn offset(line: &str, column: usize) -> usize {
+    let (mut lexed, mut shown) = (1, 0);
+    for ch in line.chars() {
+        let width = ch.width().unwrap_or(0);
+        // characters without a width, e.g. tabs, are at the column of the next one
+        if lexed >= column && width > 0 {
+            break;
+        }
+        lexed += width;
+        shown += match ch {
+            '\t' => TAB_WIDTH,
+            _ => width,
+        };
+    }
+    shown + column.saturating_sub(lexed)
+}
@@ -0,0 +1,49 @@
+use pretty_assertions::assert_eq;
+
+use super::{Location, SourceMap};
+use crate::analyze::Span;
+
+#[test]
+fn render_lines_around_a_span() {
+    let mut sources = SourceMap::new();
+    let first = sources.add(None, "var a = 1;\n\tprint a + nil;\nprint a;\n");
+    let file = sources.add(Some("main.lox".to_owned()), "print 1;\n");
+
+    // the tab counts as no column, like the lexer counts it
+    let start = Location {
+        source: first,
+        ..Location::new(2, 9)
+    };
+    let span = Span {
+        start,
+        end: Location {
+            column: 12,
+            ..start
+        },
+    };
+    let expected = [
+        "     |",
+        "   1 | var a = 1;",
+        "   2 |     print a + nil;",
+        "     |             ^^^",
+        "   3 | print a;",
+        "",
+    ];
+    assert_eq!(sources.render(span, 1, None), expected.join("\n"));
+
+    // column 0 and a line past the end of the source
+    let start = Location {
+        source: file,
+        ..Location::new(2, 0)
+    };
+    let span = Span { start, end: start };
+    let expected = [
+        "    --> main.lox:2:0",
+        "     |",
+        "   1 | print 1;",
+        "   2 | ",
+        "     | ^",
+        "",
+    ];
+    assert_eq!(sources.render(span, 2, None), expected.join("\n"));
+}
@@ -0,0 +1,179 @@
+//! Runs the test suite of the book, vendored in `test/` at the root of the repository, and checks
+//! each file against its expectation comments:
+//!
+//! - `// expect: <output>` is a line the program prints, in order.
+//! - `// expect runtime error: <message>` means the program stops with a runtime error.
+//! - `// Error ...` and `// [line N] Error ...` mean the program doesn't compile. Lines prefixed
+//!   with `[c line N]` are only for the bytecode interpreter.
+//!
+//! The messages of errors are not compared since they differ from the ones of jlox. Files listed in
+//! `EXPECTED_FAILURES` are known to fail, the test fails if any of them starts passing so the list
+//! stays accurate. Run with `--nocapture` to see the progress, e.g.
+//!
+//! ```sh
+//! cargo test -p loxi --test book -- --nocapture
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use loxi::{Dialect, ErrorKind, InterpreterPool, Options, ScriptResult};
+
+/// Files that fail, relative to `test/`, mostly because of extensions to the language or error
+/// behavior that differs from jlox.
+const EXPECTED_FAILURES: &[&str] = &[
+    // instances are displayed with their id
+    "constructor/call_init_early_return.lox",
+    "constructor/call_init_explicitly.lox",
+    "constructor/return_in_nested_function.lox",
+    // inheritance is not implemented yet
+    "class/inherited_method.lox",
+    "class/local_inherit_other.lox",
+    "inheritance/constructor.lox",
+    "inheritance/inherit_from_function.lox",
+    "inheritance/inherit_from_nil.lox",
+    "inheritance/inherit_from_number.lox",
+    "inheritance/inherit_methods.lox",
+    "inheritance/set_fields_from_base_class.lox",
+    "regression/394.lox",
+    "super/bound_method.lox",
+    "super/call_other_method.lox",
+    "super/call_same_method.lox",
+    "super/closure.lox",
+    "super/constructor.lox",
+    "super/extra_arguments.lox",
+    "super/indirectly_inherited.lox",
+    "super/missing_arguments.lox",
+    "super/no_superclass_method.lox",
+    "super/reassign_superclass.lox",
+    "super/super_in_closure_in_inherited_method.lox",
+    "super/super_in_inherited_method.lox",
+    "super/this_in_superclass_method.lox",
+];
+
+/// Directories that are not run at all.
+const SKIPPED: &[&str] = &[
+    // too slow without optimizations, and they only print timings
+    "benchmark",
+    // the scanning and expressions chapters use a different entry point in the book
+    "scanning",
+    "expressions",
+    // limits of the bytecode interpreter
+    "limit",
+];
+
+#[derive(Default)]
+struct Expect {
+    output: Vec<String>,
+    compile_error: bool,
+    runtime_error: bool,
+}
+
+#[test]
+fn book_test_suite() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../test");
+    let mut files = Vec::new();
+    collect(&root, &mut files);
+    files.sort();
+
+    let tests = files.into_iter().filter_map(|path| {
+        let source = fs::read_to_string(&path).expect("should read the test file");
+        parse_expect(&source).map(|expect| (path, expect))
+    });
+    let (paths, expects): (Vec<_>, Vec<_>) = tests.unzip();
+
+    // the suite is for the language of the book, without the extensions of the default dialect
+    let options = Options {
+        dialect: Dialect::book(),
+        ..Options::default()
+    };
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let results = InterpreterPool::new(&options, threads).run_files(&paths);
+
+    let (mut passed, mut failed) = (0, 0);
+    let mut unexpected = Vec::new();
+    for (result, expect) in results.iter().zip(expects) {
+        let name = result
+            .path
+            .strip_prefix(&root)
+            .unwrap()
+            .to_string_lossy()
+            .into_owned();
+
+        let result = check(result, &expect);
+        let expected_failure = EXPECTED_FAILURES.contains(&name.as_str());
+        match (&result, expected_failure) {
+            (Ok(()), false) => passed += 1,
+            (Ok(()), true) => unexpected.push(format!("{name}: passes, remove it from the list")),
+            (Err(_), true) => failed += 1,
+            (Err(reason), false) => unexpected.push(format!("{name}: {reason}")),
+        }
+    }
+
+    println!("book tests: {passed} passed, {failed} expected failures");
+    assert!(
+        unexpected.is_empty(),
+        "unexpected results:\n{}",
+        unexpected.join("\n")
+    );
+}
+
+fn collect(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).expect("should read the test directory") {
+        let path = entry.unwrap().path();
+        let skipped = path
+            .file_name()
+            .is_some_and(|name| SKIPPED.iter().any(|skipped| name == *skipped));
+
+        if skipped {
+            continue;
+        } else if path.is_dir() {
+            collect(&path, files);
+        } else if path.extension().is_some_and(|ext| ext == "lox") {
+            files.push(path);
+        }
+    }
+}
+
+/// `None` if the file is not a test, i.e. marked with `// nontest`.
+fn parse_expect(source: &str) -> Option<Expect> {
+    let mut expect = Expect::default();
+    for line in source.lines() {
+        let comment = |marker: &str| line.find(marker).map(|start| &line[start + marker.len()..]);
+
+        if comment("// nontest").is_some() {
+            return None;
+        } else if let Some(output) = comment("// expect: ") {
+            expect.output.push(output.to_owned());
+        } else if comment("// expect runtime error: ").is_some() {
+            expect.runtime_error = true;
+        } else if ["// Error", "// [line ", "// [java line "]
+            .iter()
+            .any(|marker| comment(marker).is_some())
+        {
+            expect.compile_error = true;
+        }
+    }
+    Some(expect)
+}
+
+fn check(result: &ScriptResult, expect: &Expect) -> Result<(), String> {
+    let kind = result.outcome.err();
+    let output = result.stdout.lines().collect::<Vec<_>>();
+    if output != expect.output {
+        return Err(format!(
+            "expected output {:?}, got {output:?}",
+            expect.output
+        ));
+    }
+
+    match kind {
+        None | Some(ErrorKind::Empty) if !expect.compile_error && !expect.runtime_error => Ok(()),
+        Some(ErrorKind::Lex | ErrorKind::Parse | ErrorKind::Resolve) if expect.compile_error => {
+            Ok(())
+        }
+        Some(ErrorKind::Runtime) if expect.runtime_error => Ok(()),
+        kind => Err(format!("unexpected outcome {kind:?}")),
+    }
+}